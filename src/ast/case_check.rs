@@ -0,0 +1,266 @@
+//! finds the clauses never reached and the values not covered by a pattern match.
+//! this is the usefulness check of Maranget, "Warnings for pattern matching".
+use crate::ast::*;
+
+/// how many examples of missing patterns to report
+const MAX_MISSING: usize = 3;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Ctor {
+    Constructor(Symbol),
+    Tuple(usize),
+    Int(i64),
+    Word(u64),
+    Char(u32),
+}
+
+#[derive(Debug, Clone)]
+enum Pat {
+    Any,
+    Ctor(Ctor, Vec<Pat>),
+}
+
+type Row = Vec<Pat>;
+
+#[derive(Debug)]
+pub struct CaseCheck<'a> {
+    symbol_table: &'a SymbolTable,
+}
+
+#[derive(Debug, Default)]
+pub struct Report {
+    /// 1-origin indices of the clauses never reached
+    pub redundant: Vec<usize>,
+    /// examples of the values no clause matches
+    pub missing: Vec<String>,
+}
+
+impl<'a> CaseCheck<'a> {
+    pub fn new(symbol_table: &'a SymbolTable) -> Self {
+        Self { symbol_table }
+    }
+
+    pub fn check<'b>(
+        &self,
+        ty: &Type,
+        patterns: impl IntoIterator<Item = &'b TypedPattern>,
+    ) -> Report {
+        let rows = patterns
+            .into_iter()
+            .map(|pat| vec![Self::simplify(pat)])
+            .collect::<Vec<_>>();
+        let tys = [ty.clone()];
+        let redundant = (0..rows.len())
+            .filter(|&i| !self.useful(&rows[..i], &rows[i], &tys))
+            .map(|i| i + 1)
+            .collect();
+        let missing = self
+            .missing(&rows, &tys)
+            .iter()
+            .map(|row| Self::show(&row[0]))
+            .collect();
+        Report { redundant, missing }
+    }
+
+    fn simplify(pat: &TypedPattern) -> Pat {
+        use PatternKind::*;
+        match &pat.inner {
+            Variable { .. } | Wildcard { .. } => Pat::Any,
+            // rejected by typing
+            Real { .. } => Pat::Any,
+            Constant { value } => Pat::Ctor(Ctor::Int(*value), vec![]),
+            Word { value } => Pat::Ctor(Ctor::Word(*value), vec![]),
+            Char { value } => Pat::Ctor(Ctor::Char(*value), vec![]),
+            Constructor { name, arg } => Pat::Ctor(
+                Ctor::Constructor(name.clone()),
+                arg.iter().map(|arg| Self::simplify(arg)).collect(),
+            ),
+            Tuple { tuple } => Pat::Ctor(
+                Ctor::Tuple(tuple.len()),
+                tuple.iter().map(Self::simplify).collect(),
+            ),
+        }
+    }
+
+    /// every constructor of the type, or None if there are too many to enumerate
+    fn all_ctors(&self, ty: &Type) -> Option<Vec<Ctor>> {
+        match ty {
+            Type::Datatype(name) => Some(
+                self.type_info(name)
+                    .constructors
+                    .iter()
+                    .map(|(cname, _)| Ctor::Constructor(cname.clone()))
+                    .collect(),
+            ),
+            Type::Tuple(tys) => Some(vec![Ctor::Tuple(tys.len())]),
+            _ => None,
+        }
+    }
+
+    fn arg_tys(&self, ctor: &Ctor, ty: &Type) -> Vec<Type> {
+        match (ctor, ty) {
+            (Ctor::Constructor(cname), Type::Datatype(name)) => self
+                .type_info(name)
+                .constructors
+                .iter()
+                .find(|(name, _)| name == cname)
+                .and_then(|(_, arg)| arg.clone())
+                .into_iter()
+                .collect(),
+            (Ctor::Tuple(_), Type::Tuple(tys)) => tys.clone(),
+            _ => vec![],
+        }
+    }
+
+    fn type_info(&self, name: &Symbol) -> &TypeInfo {
+        self.symbol_table
+            .get_type(name)
+            .expect("internal error: type not found")
+    }
+
+    fn used_ctors(rows: &[Row]) -> Vec<Ctor> {
+        let mut used = Vec::new();
+        for row in rows {
+            if let Pat::Ctor(c, _) = &row[0] {
+                if !used.contains(c) {
+                    used.push(c.clone())
+                }
+            }
+        }
+        used
+    }
+
+    /// the rows matching `ctor` at the head, with the arguments of it expanded
+    fn specialize(rows: &[Row], ctor: &Ctor, arity: usize) -> Vec<Row> {
+        rows.iter()
+            .filter_map(|row| {
+                let mut args = match &row[0] {
+                    Pat::Ctor(c, args) if c == ctor => args.clone(),
+                    Pat::Ctor(..) => return None,
+                    Pat::Any => vec![Pat::Any; arity],
+                };
+                args.extend(row[1..].iter().cloned());
+                Some(args)
+            })
+            .collect()
+    }
+
+    /// the rows matching any constructor not at the heads
+    fn default(rows: &[Row]) -> Vec<Row> {
+        rows.iter()
+            .filter(|row| match row[0] {
+                Pat::Any => true,
+                Pat::Ctor(..) => false,
+            })
+            .map(|row| row[1..].to_vec())
+            .collect()
+    }
+
+    /// whether some value matches `row` but none of `rows`
+    fn useful(&self, rows: &[Row], row: &[Pat], tys: &[Type]) -> bool {
+        let (head, rest) = match row.split_first() {
+            None => return rows.is_empty(),
+            Some(split) => split,
+        };
+        let (ty, rest_tys) = tys.split_first().unwrap();
+        let specialized = |ctor: &Ctor, args: Vec<Pat>| {
+            let mut tys = self.arg_tys(ctor, ty);
+            tys.extend(rest_tys.iter().cloned());
+            let rows = Self::specialize(rows, ctor, args.len());
+            let mut row = args;
+            row.extend(rest.iter().cloned());
+            self.useful(&rows, &row, &tys)
+        };
+        match head {
+            Pat::Ctor(ctor, args) => specialized(ctor, args.clone()),
+            Pat::Any => {
+                let used = Self::used_ctors(rows);
+                match self.all_ctors(ty) {
+                    Some(all) if all.iter().all(|c| used.contains(c)) => all.iter().any(|c| {
+                        let arity = self.arg_tys(c, ty).len();
+                        specialized(c, vec![Pat::Any; arity])
+                    }),
+                    _ => self.useful(&Self::default(rows), rest, rest_tys),
+                }
+            }
+        }
+    }
+
+    /// examples of the values none of `rows` match
+    fn missing(&self, rows: &[Row], tys: &[Type]) -> Vec<Row> {
+        let (ty, rest_tys) = match tys.split_first() {
+            None if rows.is_empty() => return vec![vec![]],
+            None => return vec![],
+            Some(split) => split,
+        };
+        let used = Self::used_ctors(rows);
+        let mut ret = Vec::new();
+        match self.all_ctors(ty) {
+            Some(all) if all.iter().all(|c| used.contains(c)) => {
+                for c in all {
+                    let mut tys = self.arg_tys(&c, ty);
+                    let arity = tys.len();
+                    tys.extend(rest_tys.iter().cloned());
+                    for mut args in self.missing(&Self::specialize(rows, &c, arity), &tys) {
+                        let rest = args.split_off(arity);
+                        let mut row = vec![Pat::Ctor(c.clone(), args)];
+                        row.extend(rest);
+                        ret.push(row);
+                        if ret.len() == MAX_MISSING {
+                            return ret;
+                        }
+                    }
+                }
+            }
+            all => {
+                let rests = self.missing(&Self::default(rows), rest_tys);
+                let heads = match all {
+                    // show the constructors not mentioned rather than `_`
+                    Some(all) if !used.is_empty() => all
+                        .into_iter()
+                        .filter(|c| !used.contains(c))
+                        .map(|c| {
+                            let arity = self.arg_tys(&c, ty).len();
+                            Pat::Ctor(c, vec![Pat::Any; arity])
+                        })
+                        .collect(),
+                    _ => vec![Pat::Any],
+                };
+                for head in heads {
+                    for rest in &rests {
+                        let mut row = vec![head.clone()];
+                        row.extend(rest.iter().cloned());
+                        ret.push(row);
+                        if ret.len() == MAX_MISSING {
+                            return ret;
+                        }
+                    }
+                }
+            }
+        }
+        ret
+    }
+
+    fn show(pat: &Pat) -> String {
+        match pat {
+            Pat::Any => "_".to_string(),
+            Pat::Ctor(Ctor::Constructor(name), args) => match args.first() {
+                None => name.0.clone(),
+                Some(arg @ Pat::Ctor(Ctor::Constructor(_), args)) if !args.is_empty() => {
+                    format!("{} ({})", name.0, Self::show(arg))
+                }
+                Some(arg) => format!("{} {}", name.0, Self::show(arg)),
+            },
+            Pat::Ctor(Ctor::Tuple(_), args) => format!(
+                "({})",
+                args.iter().map(Self::show).collect::<Vec<_>>().join(", ")
+            ),
+            Pat::Ctor(Ctor::Int(n), _) => n.to_string().replace('-', "~"),
+            Pat::Ctor(Ctor::Word(w), _) => format!("0w{}", w),
+            Pat::Ctor(Ctor::Char(c), _) => format!(
+                r##"#"{}""##,
+                std::char::from_u32(*c).unwrap_or(std::char::REPLACEMENT_CHARACTER)
+            ),
+        }
+    }
+}
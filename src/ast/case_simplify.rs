@@ -1,18 +1,24 @@
-use super::util::Transform;
+use super::case_check::CaseCheck;
+use super::util::{NamesSpan, Transform};
 use crate::ast::*;
 use crate::config::Config;
 use crate::id::Id;
+use crate::prim::{BuiltinExn, BIF};
 use std::collections::{HashMap, HashSet};
 
 #[derive(Debug)]
 pub struct CaseSimplify {
     id: Id,
+    warnings: Vec<Warning>,
 }
 
 #[derive(Debug)]
 pub struct CaseSimplifyPass {
     symbol_table: SymbolTable,
     id: Id,
+    // the exception raised when no clause matches
+    failure: BuiltinExn,
+    warnings: Vec<Warning>,
 }
 
 #[derive(Debug)]
@@ -24,7 +30,15 @@ type Stack<T> = Vec<T>;
 
 impl CaseSimplify {
     pub fn new(id: Id) -> Self {
-        Self { id }
+        Self {
+            id,
+            warnings: Vec::new(),
+        }
+    }
+
+    /// the warnings found in the programs simplified so far
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
     }
 
     fn generate_pass(&mut self, symbol_table: SymbolTable) -> CaseSimplifyPass {
@@ -34,18 +48,23 @@ impl CaseSimplify {
 
 impl CaseSimplifyPass {
     fn new(symbol_table: SymbolTable, id: Id) -> Self {
-        Self { symbol_table, id }
+        Self {
+            symbol_table,
+            id,
+            failure: BuiltinExn::Match,
+            warnings: Vec::new(),
+        }
     }
     fn symbol_table(&self) -> &SymbolTable {
         &self.symbol_table
     }
-    fn into_inner(self) -> (SymbolTable, Id) {
-        (self.symbol_table, self.id)
+    fn into_inner(self) -> (SymbolTable, Id, Vec<Warning>) {
+        (self.symbol_table, self.id, self.warnings)
     }
 
     fn gensym(&mut self, name: &str) -> Symbol {
         let id = self.id.next();
-        Symbol(format!("#{}", name), id)
+        Symbol(format!("#{}", name), id, None)
     }
 
     fn wildcard_to_variable(&mut self, ast: TypedCore) -> TypedCore {
@@ -87,6 +106,8 @@ impl CaseSimplifyPass {
             self.match_compile_constant(cond, ty, clauses)
         } else if clauses[0].0.iter().any(|p| p.is_char()) {
             self.match_compile_char(cond, ty, clauses)
+        } else if clauses[0].0.iter().any(|p| p.is_word()) {
+            self.match_compile_word(cond, ty, clauses)
         } else {
             self.match_compile_mixture(cond, ty, clauses)
         }
@@ -95,10 +116,17 @@ impl CaseSimplifyPass {
     fn match_compile_empty(
         &mut self,
         _: Stack<(Type, Symbol)>,
-        _: Type,
+        ty: Type,
         _: Vec<(Stack<TypedPattern>, TypedCoreExpr)>,
     ) -> TypedCoreExpr {
-        panic!("non-exhausitive pattern");
+        // reported by `simplify_case`. fails at runtime
+        Expr {
+            ty,
+            inner: ExprKind::BuiltinCall {
+                fun: BIF::Raise(self.failure),
+                args: vec![],
+            },
+        }
     }
 
     fn match_compile_variable(
@@ -352,6 +380,75 @@ impl CaseSimplifyPass {
         }
     }
 
+    fn match_compile_word(
+        &mut self,
+        mut cond: Stack<(Type, Symbol)>,
+        ret_ty: Type,
+        clauses: Vec<(Stack<TypedPattern>, TypedCoreExpr)>,
+    ) -> TypedCoreExpr {
+        let pos = self.find_word(&clauses);
+
+        let (cty, c) = cond.swap_remove(pos);
+        let clause_with_heads = clauses
+            .into_iter()
+            .map(|mut clause| {
+                let head = clause.0.swap_remove(pos);
+                (head, clause)
+            })
+            .collect::<Vec<_>>();
+        let words = clause_with_heads
+            .iter()
+            .filter_map(|(head, _)| match head {
+                Pattern {
+                    ty,
+                    inner: PatternKind::Word { value },
+                } => Some((*value, ty.clone())),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let mut clauses = words
+            .iter()
+            .map(|(value, ty)| {
+                let clauses = self.specialized_patterns_for_word(
+                    (cty.clone(), c.clone()),
+                    *value,
+                    clause_with_heads.iter(),
+                );
+                (
+                    Pattern {
+                        ty: ty.clone(),
+                        inner: PatternKind::Word { value: *value },
+                    },
+                    self.match_compile(cond.clone(), ret_ty.clone(), clauses),
+                )
+            })
+            .collect::<Vec<_>>();
+
+        // no check for exhausitiveness
+        let default =
+            self.default_patterns(c.clone(), cond, ret_ty.clone(), clause_with_heads.iter());
+        clauses.push((
+            Pattern {
+                ty: cty.clone(),
+                inner: PatternKind::Variable {
+                    name: self.gensym("_"),
+                },
+            },
+            default,
+        ));
+        Expr {
+            ty: ret_ty,
+            inner: ExprKind::Case {
+                cond: Expr {
+                    ty: cty,
+                    inner: ExprKind::Symbol { name: c },
+                }
+                .boxed(),
+                clauses,
+            },
+        }
+    }
+
     fn match_compile_mixture(
         &mut self,
         mut cond: Stack<(Type, Symbol)>,
@@ -469,6 +566,10 @@ impl CaseSimplifyPass {
         clauses[0].0.iter().rposition(|p| p.is_char()).unwrap()
     }
 
+    fn find_word(&mut self, clauses: &[(Stack<TypedPattern>, TypedCoreExpr)]) -> usize {
+        clauses[0].0.iter().rposition(|p| p.is_word()).unwrap()
+    }
+
     fn find_constructor(&mut self, clauses: &[(Stack<TypedPattern>, TypedCoreExpr)]) -> usize {
         clauses[0]
             .0
@@ -607,6 +708,43 @@ impl CaseSimplifyPass {
             .collect()
     }
 
+    fn specialized_patterns_for_word<'a, 'b>(
+        &'a mut self,
+        (cty, cond): (Type, Symbol),
+        value: u64,
+        clause_with_heads: impl Iterator<
+            Item = &'b (TypedPattern, (Stack<TypedPattern>, TypedCoreExpr)),
+        >,
+    ) -> Vec<(Stack<TypedPattern>, TypedCoreExpr)> {
+        clause_with_heads
+            .filter_map(|(head, clause)| match &head.inner {
+                PatternKind::Word { value: value1, .. } if value == *value1 => Some(clause.clone()),
+                v @ PatternKind::Variable { .. } => {
+                    let (pat, arm) = clause.clone();
+                    let arm = Expr {
+                        ty: arm.ty(),
+                        inner: ExprKind::Binds {
+                            binds: vec![Declaration::Val {
+                                rec: false,
+                                pattern: Pattern {
+                                    ty: head.ty.clone(),
+                                    inner: v.clone(),
+                                },
+                                expr: Expr {
+                                    ty: cty.clone(),
+                                    inner: ExprKind::Symbol { name: cond.clone() },
+                                },
+                            }],
+                            ret: arm.boxed(),
+                        },
+                    };
+                    Some((pat, arm))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
     fn default_patterns<'a, 'b>(
         &'a mut self,
         c: Symbol,
@@ -649,8 +787,13 @@ impl CaseSimplifyPass {
     ) -> bool {
         use Type::*;
         match ty {
-            Real | Variable(_) | Fun(_, _) => panic!("no way to pattern match against this type"),
-            Char | Int => false,
+            // real constant patterns are rejected by typing
+            Real => unreachable!("internal error: real pattern remains after typing"),
+            String => unreachable!("internal error: string constant pattern"),
+            // constant patterns are typed int
+            IntInf => unreachable!("internal error: IntInf constant pattern"),
+            Variable(_) | Fun(_, _) => panic!("no way to pattern match against this type"),
+            Char | Int | Word(_) => false,
             Tuple(_) => {
                 // unlikely reachable, but writing incase it reaches.
                 true
@@ -724,7 +867,7 @@ impl Transform<Type> for CaseSimplifyPass {
                     pattern: tuple_pat,
                     expr: Expr {
                         ty,
-                        inner: self.transform_case(cond.boxed(), vec![(pattern, tuple)]),
+                        inner: self.simplify_case(cond, vec![(pattern, tuple)], BuiltinExn::Bind),
                     },
                 }
             }
@@ -735,6 +878,17 @@ impl Transform<Type> for CaseSimplifyPass {
         &mut self,
         cond: Box<TypedCoreExpr>,
         clauses: Vec<(TypedPattern, TypedCoreExpr)>,
+    ) -> TypedCoreExprKind {
+        self.simplify_case(*cond, clauses, BuiltinExn::Match)
+    }
+}
+
+impl CaseSimplifyPass {
+    fn simplify_case(
+        &mut self,
+        cond: TypedCoreExpr,
+        clauses: Vec<(TypedPattern, TypedCoreExpr)>,
+        failure: BuiltinExn,
     ) -> TypedCoreExprKind {
         let condsym = self.gensym("cond");
         let condty = cond.ty();
@@ -743,10 +897,13 @@ impl Transform<Type> for CaseSimplifyPass {
             .map(|(_, expr)| expr.ty())
             .next()
             .expect("case should have at least 1 clause");
+        self.check_case(&cond, &clauses, failure);
         let clauses = clauses
             .into_iter()
             .map(|(pat, arm)| (vec![pat], self.transform_expr(arm)))
             .collect();
+        // arms may contain cases of their own
+        self.failure = failure;
         ExprKind::Binds {
             binds: vec![Declaration::Val {
                 pattern: Pattern {
@@ -756,13 +913,44 @@ impl Transform<Type> for CaseSimplifyPass {
                     },
                 },
                 rec: false,
-                expr: *cond,
+                expr: cond,
             }],
             ret: self
                 .match_compile(vec![(condty, condsym)], ty, clauses)
                 .boxed(),
         }
     }
+
+    fn check_case(
+        &mut self,
+        cond: &TypedCoreExpr,
+        clauses: &[(TypedPattern, TypedCoreExpr)],
+        failure: BuiltinExn,
+    ) {
+        let report =
+            CaseCheck::new(&self.symbol_table).check(&cond.ty, clauses.iter().map(|(pat, _)| pat));
+        // the spans are of the names written in the match as literals have no spans
+        let mut case = NamesSpan::default();
+        case.expr(cond);
+        for (pat, arm) in clauses {
+            case.pattern(pat);
+            case.expr(arm);
+        }
+        if !report.missing.is_empty() {
+            self.warnings.push(match failure {
+                BuiltinExn::Bind => Warning::NonExhaustiveBinding(report.missing, case.0),
+                _ => Warning::NonExhaustiveMatch(report.missing, case.0),
+            })
+        }
+        for clause in report.redundant {
+            let (pat, arm) = &clauses[clause - 1];
+            let mut names = NamesSpan::default();
+            names.pattern(pat);
+            names.expr(arm);
+            self.warnings
+                .push(Warning::RedundantClause(clause, names.0.or(case.0)))
+        }
+    }
 }
 
 impl WildcardToVariable {
@@ -772,7 +960,7 @@ impl WildcardToVariable {
 
     fn gensym(&mut self, name: &str) -> Symbol {
         let id = self.id.next();
-        Symbol(format!("#{}", name), id)
+        Symbol(format!("#{}", name), id, None)
     }
 }
 
@@ -796,7 +984,8 @@ impl<'a> Pass<(SymbolTable, TypedCore), TypeError<'a>> for CaseSimplify {
         let mut pass = self.generate_pass(symbol_table);
         let ast = pass.wildcard_to_variable(ast);
         let ast = pass.transform_ast(ast);
-        let (symbol_table, _) = pass.into_inner();
+        let (symbol_table, _, warnings) = pass.into_inner();
+        self.warnings.extend(warnings);
         Ok((symbol_table, ast))
     }
 }
@@ -15,7 +15,7 @@ impl Desugar {
 
     pub fn gensym(&mut self) -> Symbol {
         let id = self.id.next();
-        Symbol("#arg".into(), id)
+        Symbol("#arg".into(), id, None)
     }
 }
 
@@ -32,7 +32,11 @@ impl Desugar {
         use Declaration::*;
         match decl {
             Datatype { name, constructors } => Some(self.transform_datatype(name, constructors)),
+            Type { name, ty } => Some(Type { name, ty }),
             Val { rec, pattern, expr } => Some(self.transform_val(rec, pattern, expr)),
+            Abstype { datatype, decls } => Some(self.transform_abstype(*datatype, decls)),
+            Local { binds, body } => Some(self.transform_local(binds, body)),
+            And(decls) => Some(self.transform_and(decls)),
             D(DerivedDeclaration::Fun { name, clauses }) => Some(self.transform_fun(name, clauses)),
             D(DerivedDeclaration::Infix { .. }) => None,
         }
@@ -59,6 +63,49 @@ impl Desugar {
         }
     }
 
+    fn transform_abstype(
+        &mut self,
+        datatype: UntypedDeclaration,
+        decls: Vec<UntypedDeclaration>,
+    ) -> UntypedCoreDeclaration {
+        Declaration::Abstype {
+            datatype: Box::new(
+                self.transform_statement(datatype)
+                    .expect("internal error: abstype without datatype"),
+            ),
+            decls: decls
+                .into_iter()
+                .filter_map(|decl| self.transform_statement(decl))
+                .collect(),
+        }
+    }
+
+    fn transform_local(
+        &mut self,
+        binds: Vec<UntypedDeclaration>,
+        body: Vec<UntypedDeclaration>,
+    ) -> UntypedCoreDeclaration {
+        Declaration::Local {
+            binds: binds
+                .into_iter()
+                .filter_map(|decl| self.transform_statement(decl))
+                .collect(),
+            body: body
+                .into_iter()
+                .filter_map(|decl| self.transform_statement(decl))
+                .collect(),
+        }
+    }
+
+    fn transform_and(&mut self, decls: Vec<UntypedDeclaration>) -> UntypedCoreDeclaration {
+        Declaration::And(
+            decls
+                .into_iter()
+                .filter_map(|decl| self.transform_statement(decl))
+                .collect(),
+        )
+    }
+
     fn transform_fun(
         &mut self,
         name: Symbol,
@@ -1,6 +1,8 @@
+mod case_check;
 mod case_simplify;
 mod desugar;
 mod pp;
+mod printer;
 mod rename;
 mod typing;
 mod util;
@@ -8,12 +10,13 @@ mod var2constructor;
 
 pub use self::case_simplify::CaseSimplify;
 pub use self::desugar::Desugar;
+pub use self::printer::Printer;
 pub use self::rename::Rename;
 pub use self::typing::Typer;
 pub use self::var2constructor::VarToConstructor;
 use crate::ast;
+use crate::parser::ParseError;
 use crate::prim::*;
-use nom;
 pub use std::collections::HashMap;
 use std::error::Error;
 use std::fmt;
@@ -39,11 +42,30 @@ pub enum Declaration<Ty, DE = DerivedExprKind<Ty>, DS = DerivedDeclaration<Ty>>
         name: Symbol,
         constructors: Vec<(Symbol, Option<Type>)>,
     },
+    /// type abbreviation `type name = ty`
+    Type {
+        name: Symbol,
+        ty: Type,
+    },
     Val {
         rec: bool,
         pattern: Pattern<Ty>,
         expr: Expr<Ty, DE, DS>,
     },
+    /// `abstype datatype with decls end`.
+    /// the constructors of `datatype` are visible only in `decls`.
+    Abstype {
+        datatype: Box<Declaration<Ty, DE, DS>>,
+        decls: Vec<Declaration<Ty, DE, DS>>,
+    },
+    /// `local binds in body end`. only the bindings of `body` are visible outside.
+    Local {
+        binds: Vec<Declaration<Ty, DE, DS>>,
+        body: Vec<Declaration<Ty, DE, DS>>,
+    },
+    /// declarations joined with `and`, e.g. `fun even ... and odd ...`.
+    /// the names bound in them are visible to each other.
+    And(Vec<Declaration<Ty, DE, DS>>),
     D(DS),
 }
 
@@ -146,10 +168,19 @@ pub enum PatternKind<Ty> {
         // same type as Literal::Int
         value: i64,
     },
+    Word {
+        // same type as Literal::Word
+        value: u64,
+    },
     Char {
         // same type as Literal::Char
         value: u32,
     },
+    /// only to report an error. real is not an equality type.
+    Real {
+        // same type as Literal::Real
+        value: f64,
+    },
     Constructor {
         name: Symbol,
         arg: Option<Box<Pattern<Ty>>>,
@@ -167,6 +198,7 @@ pub enum PatternKind<Ty> {
 pub struct SymbolTable {
     pub types: HashMap<Symbol, TypeInfo>,
     pub constructors: HashMap<Symbol, Symbol>,
+    pub type_aliases: HashMap<Symbol, Type>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -174,7 +206,10 @@ pub enum Type {
     Variable(u64),
     Char,
     Int,
+    IntInf,
+    Word(WordSize),
     Real,
+    String,
     Fun(Box<Type>, Box<Type>),
     Tuple(Vec<Type>),
     Datatype(Symbol),
@@ -208,12 +243,22 @@ impl<Ty> CoreDeclaration<Ty> {
         use Declaration::*;
         match self {
             Datatype { name, constructors } => Datatype { name, constructors },
+            Type { name, ty } => Type { name, ty },
 
             Val { pattern, expr, rec } => Val {
                 rec,
                 pattern: pattern.map_ty(&mut *f),
                 expr: expr.map_ty(f),
             },
+            Abstype { datatype, decls } => Abstype {
+                datatype: Box::new(datatype.map_ty(&mut *f)),
+                decls: decls.into_iter().map(|decl| decl.map_ty(f)).collect(),
+            },
+            Local { binds, body } => Local {
+                binds: binds.into_iter().map(|decl| decl.map_ty(&mut *f)).collect(),
+                body: body.into_iter().map(|decl| decl.map_ty(f)).collect(),
+            },
+            And(decls) => And(decls.into_iter().map(|decl| decl.map_ty(f)).collect()),
             D(d) => match d {},
         }
     }
@@ -282,7 +327,9 @@ impl<Ty> Pattern<Ty> {
         let ty = f(self.ty);
         let inner = match self.inner {
             Constant { value } => Constant { value },
+            Word { value } => Word { value },
             Char { value } => Char { value },
+            Real { value } => Real { value },
             Constructor { name, arg } => Constructor {
                 name,
                 arg: arg.map(|pat| Box::new(pat.map_ty(f))),
@@ -299,7 +346,7 @@ impl<Ty> Pattern<Ty> {
     pub fn binds(&self) -> Vec<(&Symbol, &Ty)> {
         use self::PatternKind::*;
         match &self.inner {
-            Constant { .. } | Char { .. } | Wildcard { .. } => vec![],
+            Constant { .. } | Word { .. } | Char { .. } | Real { .. } | Wildcard { .. } => vec![],
             Variable { name } => vec![(name, &self.ty)],
             Tuple { tuple, .. } => tuple.iter().flat_map(|pat| pat.binds()).collect(),
             Constructor { arg, .. } => arg.iter().flat_map(|pat| pat.binds()).collect(),
@@ -330,6 +377,10 @@ impl<Ty> Pattern<Ty> {
         }
     }
 
+    pub fn is_word(&self) -> bool {
+        matches!(self.inner, PatternKind::Word { .. })
+    }
+
     pub fn is_char(&self) -> bool {
         use self::PatternKind::*;
         match &self.inner {
@@ -361,6 +412,7 @@ impl SymbolTable {
         Self {
             types: HashMap::new(),
             constructors: HashMap::new(),
+            type_aliases: HashMap::new(),
         }
     }
 
@@ -371,6 +423,37 @@ impl SymbolTable {
         self.types.insert(name, info);
     }
 
+    pub fn register_type_alias(&mut self, name: Symbol, ty: Type) {
+        self.type_aliases.insert(name, ty);
+    }
+
+    /// replace type abbreviations in `ty` with their definitions
+    pub fn expand_type_aliases(&self, ty: Type) -> Type {
+        use self::Type::*;
+        match ty {
+            Datatype(name) => match self.type_aliases.get(&name) {
+                Some(ty) => self.expand_type_aliases(ty.clone()),
+                None => Datatype(name),
+            },
+            Fun(param, body) => Type::fun(
+                self.expand_type_aliases(*param),
+                self.expand_type_aliases(*body),
+            ),
+            Tuple(tys) => Tuple(
+                tys.into_iter()
+                    .map(|ty| self.expand_type_aliases(ty))
+                    .collect(),
+            ),
+            ty @ Variable(_)
+            | ty @ Char
+            | ty @ Int
+            | ty @ IntInf
+            | ty @ Word(_)
+            | ty @ Real
+            | ty @ String => ty,
+        }
+    }
+
     pub fn get_type(&self, name: &Symbol) -> Option<&TypeInfo> {
         self.types.get(&name)
     }
@@ -407,49 +490,267 @@ impl SymbolTable {
 
 #[derive(Debug)]
 pub enum TypeError<'a> {
-    MisMatch { expected: Type, actual: Type },
+    /// `expected` and `actual` are the conflicting parts of the types being unified.
+    /// `enclosing` holds the whole types if they differ from the parts and
+    /// `expr` the innermost expression being typed
+    MisMatch {
+        expected: Type,
+        actual: Type,
+        enclosing: Option<Box<(Type, Type)>>,
+        expr: Option<Box<TypedCoreExpr>>,
+    },
+    /// the type variable `var` occurs in `ty` it is unified with
+    Circularity {
+        var: Type,
+        ty: Type,
+        expr: Option<Box<TypedCoreExpr>>,
+    },
+    /// `name` is not in scope. `location` is the binding it is used in
+    /// and `span` where it is written
+    Unbound {
+        kind: NameKind,
+        name: String,
+        location: Option<String>,
+        span: Option<Span>,
+        suggestions: Vec<String>,
+    },
     CannotInfer,
     FreeVar,
-    NotFunction(ast::Expr<Type>),
-    ParseError(nom::Err<(&'a str, nom::error::ErrorKind)>),
+    RealPattern,
+    IntOutOfRange(i64),
+    /// the literal as written and the type it is out of the range of
+    WordOutOfRange(String, WordSize),
+    NotFunction(TypedCoreExpr),
+    /// the top level binding of the unit cannot be exported to the other units as its type,
+    /// the second, has functions in it other than the parameters and the result of a function
+    NotExported(String, Type, Option<Span>),
+    ParseError(Vec<ParseError<'a>>),
+}
+
+/// the namespaces of identifiers
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NameKind {
+    Variable,
+    Constructor,
+    Type,
+}
+
+impl fmt::Display for NameKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NameKind::Variable => write!(f, "variable"),
+            NameKind::Constructor => write!(f, "constructor"),
+            NameKind::Type => write!(f, "type"),
+        }
+    }
 }
 
 impl<'a> fmt::Display for TypeError<'a> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        fmt::Debug::fmt(self, f)
+        use self::TypeError::*;
+        let mut printer = Printer::new();
+        match self {
+            MisMatch {
+                expected,
+                actual,
+                enclosing,
+                expr,
+            } => {
+                write!(f, "type mismatch")?;
+                if let Some(expr) = expr {
+                    write!(f, "\n  in:       {}", printer.expr(expr))?;
+                }
+                match enclosing {
+                    Some(enclosing) => {
+                        let (whole_expected, whole_actual) = &**enclosing;
+                        write!(f, "\n  expected: {}", printer.ty(whole_expected))?;
+                        write!(f, "\n  found:    {}", printer.ty(whole_actual))?;
+                        write!(
+                            f,
+                            "\n  because {} conflicts with {}",
+                            printer.ty(expected),
+                            printer.ty(actual)
+                        )
+                    }
+                    None => {
+                        write!(f, "\n  expected: {}", printer.ty(expected))?;
+                        write!(f, "\n  found:    {}", printer.ty(actual))
+                    }
+                }
+            }
+            Circularity { var, ty, expr } => {
+                let var = printer.ty(var);
+                write!(f, "circular type: {} occurs in {}", var, printer.ty(ty))?;
+                if let Some(expr) = expr {
+                    write!(f, "\n  in:       {}", printer.expr(expr))?;
+                }
+                Ok(())
+            }
+            Unbound {
+                kind,
+                name,
+                location,
+                suggestions,
+                ..
+            } => {
+                write!(f, "unbound {} `{}`", kind, name)?;
+                if let Some(location) = location {
+                    write!(f, " in `{}`", location)?;
+                }
+                if let Some((last, init)) = suggestions.split_last() {
+                    let init = init.iter().map(|s| format!("`{}`", s)).collect::<Vec<_>>();
+                    write!(f, "\n  did you mean ")?;
+                    if !init.is_empty() {
+                        write!(f, "{} or ", init.join(", "))?;
+                    }
+                    write!(f, "`{}`?", last)?;
+                }
+                Ok(())
+            }
+            IntOutOfRange(n) => write!(f, "integer constant {} is out of the range of int", n),
+            WordOutOfRange(w, size) => write!(
+                f,
+                "word constant {} is out of the range of {}",
+                w,
+                printer.ty(&Type::Word(*size))
+            ),
+            NotFunction(expr) => write!(f, "not a function: {}", printer.expr(expr)),
+            NotExported(name, ty, _) => write!(
+                f,
+                "`{}: {}` cannot be exported: functions cannot be passed between units",
+                name,
+                printer.ty(ty)
+            ),
+            CannotInfer => write!(f, "cannot infer the type"),
+            FreeVar => write!(f, "free variable is found"),
+            RealPattern => write!(f, "real constants cannot be used in patterns"),
+            ParseError(errors) => {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                write!(f, "{}", errors.join("\n"))
+            }
+        }
+    }
+}
+
+impl<'a> TypeError<'a> {
+    /// the name of the kind of the error, for the tools
+    pub fn code(&self) -> &'static str {
+        use self::TypeError::*;
+        match self {
+            MisMatch { .. } => "type-mismatch",
+            Circularity { .. } => "circular-type",
+            Unbound { .. } => "unbound-name",
+            CannotInfer => "cannot-infer",
+            FreeVar => "free-variable",
+            RealPattern => "real-pattern",
+            IntOutOfRange(_) => "int-out-of-range",
+            WordOutOfRange(..) => "word-out-of-range",
+            NotFunction(_) => "not-a-function",
+            NotExported(..) => "not-exported",
+            ParseError(_) => "syntax-error",
+        }
+    }
+
+    /// the range of the source the error is found in, if known.
+    /// for the errors on an expression, it covers the names in the expression
+    pub fn span(&self) -> Option<Span> {
+        use self::TypeError::*;
+        match self {
+            MisMatch {
+                expr: Some(expr), ..
+            }
+            | Circularity {
+                expr: Some(expr), ..
+            } => names_span(expr),
+            NotFunction(expr) => names_span(expr),
+            Unbound { span, .. } | NotExported(_, _, span) => *span,
+            ParseError(errors) => errors.first().map(|e| e.span),
+            _ => None,
+        }
     }
 }
 
+fn names_span(expr: &TypedCoreExpr) -> Option<Span> {
+    let mut names = util::NamesSpan::default();
+    names.expr(expr);
+    names.0
+}
+
 impl<'a> Error for TypeError<'a> {
     fn description(&self) -> &str {
         use self::TypeError::*;
         match self {
             &MisMatch { .. } => "type mismatches against expected type",
+            &Circularity { .. } => "type variable occurs in the type it is unified with",
+            &Unbound { .. } => "unbound identifier",
             &CannotInfer => "cannot infer the type",
             &FreeVar => "free variable is found",
+            &RealPattern => "real constants cannot be used in patterns",
+            &IntOutOfRange(_) => "integer constant is out of the range of int",
+            &WordOutOfRange(..) => "word constant is out of the range of its type",
             &NotFunction(_) => "not a function",
+            &NotExported(..) => "binding cannot be exported",
             &ParseError(_) => "parse error",
         }
     }
 }
 
-impl<'a> From<nom::Err<(&'a str, nom::error::ErrorKind)>> for TypeError<'a> {
-    fn from(e: nom::Err<(&'a str, nom::error::ErrorKind)>) -> Self {
-        // fn conv<'b>(e: nom::Err<&'b [u8]>) -> nom::Err<&'b str> {
-        //     use std::str::from_utf8;
-        //     use nom::Err::*;
-        //     match e {
-        //         Code(e) => Code(e),
-        //         Node(kind, box_err) => Node(kind, Box::new(conv(*box_err))),
-        //         Position(kind, slice) => Position(kind, from_utf8(slice).unwrap()),
-        //         NodePosition(kind, slice, box_err) => {
-        //             NodePosition(kind, from_utf8(slice).unwrap(), Box::new(conv(*box_err)))
-        //         }
-        //     }
-        // }
-
-        TypeError::ParseError(e)
+impl<'a> From<Vec<ParseError<'a>>> for TypeError<'a> {
+    fn from(errors: Vec<ParseError<'a>>) -> Self {
+        TypeError::ParseError(errors)
     }
 }
 
 pub type Result<'a, T> = ::std::result::Result<T, TypeError<'a>>;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Warning {
+    /// the patterns of `case`, `fn` or `fun` don't cover every value.
+    /// holds examples of the values not covered and where the match is
+    NonExhaustiveMatch(Vec<String>, Option<Span>),
+    /// the pattern of `val` doesn't cover every value
+    NonExhaustiveBinding(Vec<String>, Option<Span>),
+    /// the clause, 1-origin, is covered by the clauses before it
+    RedundantClause(usize, Option<Span>),
+}
+
+impl Warning {
+    /// the range of the source the warning is found in, if known
+    pub fn span(&self) -> Option<Span> {
+        use self::Warning::*;
+        match self {
+            NonExhaustiveMatch(_, span)
+            | NonExhaustiveBinding(_, span)
+            | RedundantClause(_, span) => *span,
+        }
+    }
+
+    /// the name of the kind of the warning, for the tools
+    pub fn code(&self) -> &'static str {
+        use self::Warning::*;
+        match self {
+            NonExhaustiveMatch(..) => "non-exhaustive-match",
+            NonExhaustiveBinding(..) => "non-exhaustive-binding",
+            RedundantClause(..) => "redundant-clause",
+        }
+    }
+}
+
+impl fmt::Display for Warning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use self::Warning::*;
+        match self {
+            NonExhaustiveMatch(missing, _) => {
+                write!(f, "match nonexhaustive, missing: {}", missing.join(" | "))
+            }
+            NonExhaustiveBinding(missing, _) => {
+                write!(
+                    f,
+                    "binding not exhaustive, missing: {}",
+                    missing.join(" | ")
+                )
+            }
+            RedundantClause(n, _) => write!(f, "redundant clause {} of the match", n),
+        }
+    }
+}
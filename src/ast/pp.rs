@@ -20,10 +20,22 @@ impl<Ty: PP, DE: PP, DS: PP> PP for AST<Ty, DE, DS> {
 
 impl<Ty: PP, DE: PP, DS: PP> PP for Declaration<Ty, DE, DS> {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        self.pp_with_keyword(w, indent, None)
+    }
+}
+
+impl<Ty: PP, DE: PP, DS: PP> Declaration<Ty, DE, DS> {
+    // `keyword` overrides the leading keyword, used to print `and` in groups.
+    fn pp_with_keyword<W: io::Write>(
+        &self,
+        w: &mut W,
+        indent: usize,
+        keyword: Option<&str>,
+    ) -> io::Result<()> {
         use Declaration::*;
         match self {
             Datatype { name, constructors } => {
-                write!(w, "datatype ")?;
+                write!(w, "{} ", keyword.unwrap_or("datatype"))?;
                 name.pp(w, indent)?;
                 write!(w, " =")?;
                 inter_iter!(constructors, write!(w, " |")?, |(name, param)| =>{
@@ -36,11 +48,48 @@ impl<Ty: PP, DE: PP, DS: PP> PP for Declaration<Ty, DE, DS> {
                 });
                 Ok(())
             }
+            Type { name, ty } => {
+                write!(w, "{} ", keyword.unwrap_or("type"))?;
+                name.pp(w, indent)?;
+                write!(w, " = ")?;
+                ty.pp(w, indent)?;
+                Ok(())
+            }
+            Abstype { datatype, decls } => {
+                write!(w, "{}abs", Self::nspaces(indent))?;
+                datatype.pp(w, indent)?;
+                writeln!(w, " with")?;
+                for decl in decls {
+                    decl.pp(w, indent + 4)?;
+                    writeln!(w)?;
+                }
+                write!(w, "{}end", Self::nspaces(indent))?;
+                Ok(())
+            }
+            Local { binds, body } => {
+                writeln!(w, "{}local", Self::nspaces(indent))?;
+                for decl in binds {
+                    decl.pp(w, indent + 4)?;
+                    writeln!(w)?;
+                }
+                writeln!(w, "{}in", Self::nspaces(indent))?;
+                for decl in body {
+                    decl.pp(w, indent + 4)?;
+                    writeln!(w)?;
+                }
+                write!(w, "{}end", Self::nspaces(indent))?;
+                Ok(())
+            }
             Val { pattern, expr, rec } => {
                 write!(w, "{}", Self::nspaces(indent))?;
-                write!(w, "val ")?;
-                if *rec {
-                    write!(w, "rec ")?;
+                match keyword {
+                    Some(keyword) => write!(w, "{} ", keyword)?,
+                    None => {
+                        write!(w, "val ")?;
+                        if *rec {
+                            write!(w, "rec ")?;
+                        }
+                    }
                 }
                 pattern.pp(w, indent)?;
                 // write!(w, ": ")?;
@@ -49,7 +98,23 @@ impl<Ty: PP, DE: PP, DS: PP> PP for Declaration<Ty, DE, DS> {
                 expr.pp(w, indent + 4)?;
                 Ok(())
             }
-            D(d) => d.pp(w, indent),
+            And(decls) => {
+                inter_iter!(decls.iter().enumerate(), writeln!(w)?, |(i, decl)| => {
+                    let keyword = match (i, decl) {
+                        (0, _) => None,
+                        (_, Type { .. }) if matches!(decls[0], Datatype { .. }) => Some("withtype"),
+                        _ => Some("and"),
+                    };
+                    decl.pp_with_keyword(w, indent, keyword)?;
+                });
+                Ok(())
+            }
+            D(d) => {
+                if keyword.is_some() {
+                    writeln!(w, "{}and", Self::nspaces(indent))?;
+                }
+                d.pp(w, indent)
+            }
         }
     }
 }
@@ -236,7 +301,9 @@ impl<Ty> PP for Pattern<Ty> {
         use PatternKind::*;
         match &self.inner {
             Constant { value, .. } => write!(w, "{}", value),
+            Word { value } => write!(w, "0w{}", value),
             Char { value } => write!(w, r##"#"{}""##, value),
+            Real { value } => write!(w, "{}", value),
             Constructor { name, arg, .. } => {
                 name.pp(w, indent)?;
                 if let Some(arg) = arg {
@@ -270,8 +337,11 @@ impl PP for Type {
         match self {
             Variable(id) => write!(w, "'{}", id)?,
             Char => write!(w, "char")?,
+            Word(size) => size.pp(w, indent)?,
             Int => write!(w, "int")?,
+            IntInf => write!(w, "IntInf.int")?,
             Real => write!(w, "float")?,
+            String => write!(w, "string")?,
             Fun(t1, t2) => {
                 t1.pp(w, indent)?;
                 write!(w, " -> ")?;
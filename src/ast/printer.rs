@@ -0,0 +1,321 @@
+//! prints types and expressions as they would be written in the source, for messages to users.
+//! unlike `PP`, the renamed symbols are shown by their original names.
+use super::rename::BUILTIN_FUNCTIONS;
+use crate::ast::*;
+use crate::prim;
+
+/// subexpressions deeper than this are shown as `...`
+const MAX_DEPTH: usize = 4;
+
+/// names the type variables `'a`, `'b`, ... in the order they are printed.
+/// use one printer for all the types in a message to keep the names consistent.
+#[derive(Debug, Default)]
+pub struct Printer {
+    tyvars: HashMap<u64, String>,
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn ty(&mut self, ty: &Type) -> String {
+        let mut s = String::new();
+        self.write_ty(&mut s, ty, 0);
+        s
+    }
+
+    /// `prec` is 0 at the top, 1 on the left of `->` and 2 in a tuple
+    fn write_ty(&mut self, s: &mut String, ty: &Type, prec: u8) {
+        use Type::*;
+        match ty {
+            Variable(id) => {
+                let n = self.tyvars.len();
+                let name = self.tyvars.entry(*id).or_insert_with(|| {
+                    let letter = (b'a' + (n % 26) as u8) as char;
+                    match n / 26 {
+                        0 => format!("'{}", letter),
+                        suffix => format!("'{}{}", letter, suffix),
+                    }
+                });
+                s.push_str(name)
+            }
+            Char => s.push_str("char"),
+            Int => s.push_str("int"),
+            IntInf => s.push_str("IntInf.int"),
+            Word(WordSize::W8) => s.push_str("Word8.word"),
+            Word(WordSize::W32) => s.push_str("word"),
+            Word(WordSize::W64) => s.push_str("Word64.word"),
+            Real => s.push_str("real"),
+            String => s.push_str("string"),
+            Datatype(name) => s.push_str(&name.0),
+            Tuple(tys) if tys.is_empty() => s.push_str("unit"),
+            Tuple(tys) => self.paren(s, prec >= 2, |this, s| {
+                for (i, ty) in tys.iter().enumerate() {
+                    if i != 0 {
+                        s.push_str(" * ")
+                    }
+                    this.write_ty(s, ty, 2)
+                }
+            }),
+            Fun(param, body) => self.paren(s, prec >= 1, |this, s| {
+                this.write_ty(s, param, 1);
+                s.push_str(" -> ");
+                this.write_ty(s, body, 0)
+            }),
+        }
+    }
+
+    pub fn expr<Ty>(&mut self, expr: &CoreExpr<Ty>) -> String {
+        let mut s = String::new();
+        self.write_expr(&mut s, expr, 0);
+        s
+    }
+
+    fn write_expr<Ty>(&mut self, s: &mut String, expr: &CoreExpr<Ty>, depth: usize) {
+        use ExprKind::*;
+        if depth > MAX_DEPTH {
+            s.push_str("...");
+            return;
+        }
+        let depth = depth + 1;
+        match &expr.inner {
+            Binds { binds, ret } => {
+                s.push_str("let ");
+                for decl in binds {
+                    self.write_decl(s, decl, depth);
+                    s.push(' ');
+                }
+                s.push_str("in ");
+                self.write_expr(s, ret, depth);
+                s.push_str(" end")
+            }
+            BuiltinCall { fun, args } => {
+                s.push_str(&format!("op {} (", bif_name(fun)));
+                self.write_list(s, args, depth);
+                s.push(')')
+            }
+            ExternCall {
+                module, fun, args, ..
+            } => {
+                s.push_str(&format!(r#"_externcall("{}"."{}")("#, module, fun));
+                self.write_list(s, args, depth);
+                s.push(')')
+            }
+            Fn { param, body } => match builtin_wrapper(expr) {
+                Some(bif) => s.push_str(&format!("op {}", bif_name(&bif))),
+                None => {
+                    s.push_str(&format!("fn {} => ", param.0));
+                    self.write_expr(s, body, depth)
+                }
+            },
+            App { fun, arg } => match (builtin_wrapper(fun), &arg.inner) {
+                (Some(bif), Tuple { tuple }) if tuple.len() == 2 && is_infix(&bif) => {
+                    self.write_arg(s, &tuple[0], depth);
+                    s.push_str(&format!(" {} ", bif_name(&bif)));
+                    self.write_arg(s, &tuple[1], depth)
+                }
+                _ => {
+                    match fun.inner {
+                        App { .. } => self.write_expr(s, fun, depth),
+                        _ => self.write_arg(s, fun, depth),
+                    }
+                    s.push(' ');
+                    self.write_arg(s, arg, depth)
+                }
+            },
+            Case { cond, clauses } => {
+                s.push_str("case ");
+                self.write_expr(s, cond, depth);
+                s.push_str(" of ");
+                for (i, (pat, arm)) in clauses.iter().enumerate() {
+                    if i != 0 {
+                        s.push_str(" | ")
+                    }
+                    self.write_pat(s, pat);
+                    s.push_str(" => ");
+                    self.write_expr(s, arm, depth)
+                }
+            }
+            Tuple { tuple } => {
+                s.push('(');
+                self.write_list(s, tuple, depth);
+                s.push(')')
+            }
+            Symbol { name } => s.push_str(&name.0),
+            Constructor { name, arg } => {
+                s.push_str(&name.0);
+                if let Some(arg) = arg {
+                    s.push(' ');
+                    self.write_arg(s, arg, depth)
+                }
+            }
+            Literal { value } => s.push_str(&literal(value)),
+            D(d) => match *d {},
+        }
+    }
+
+    /// an operand of an application, parenthesized unless it is atomic
+    fn write_arg<Ty>(&mut self, s: &mut String, expr: &CoreExpr<Ty>, depth: usize) {
+        use ExprKind::*;
+        let atomic = match &expr.inner {
+            Tuple { .. } | Symbol { .. } | Literal { .. } | BuiltinCall { .. } => true,
+            Constructor { arg, .. } => arg.is_none(),
+            Fn { .. } => builtin_wrapper(expr).is_some(),
+            _ => false,
+        };
+        if atomic {
+            self.write_expr(s, expr, depth)
+        } else {
+            self.paren(s, true, |this, s| this.write_expr(s, expr, depth))
+        }
+    }
+
+    fn write_list<Ty>(&mut self, s: &mut String, exprs: &[CoreExpr<Ty>], depth: usize) {
+        for (i, expr) in exprs.iter().enumerate() {
+            if i != 0 {
+                s.push_str(", ")
+            }
+            self.write_expr(s, expr, depth)
+        }
+    }
+
+    fn write_decl<Ty>(&mut self, s: &mut String, decl: &CoreDeclaration<Ty>, depth: usize) {
+        use Declaration::*;
+        match decl {
+            Datatype { name, .. } => s.push_str(&format!("datatype {} = ...", name.0)),
+            Type { name, ty } => {
+                s.push_str(&format!("type {} = ", name.0));
+                self.write_ty(s, ty, 0)
+            }
+            Val { rec, pattern, expr } => {
+                s.push_str(if *rec { "val rec " } else { "val " });
+                self.write_pat(s, pattern);
+                s.push_str(" = ");
+                self.write_expr(s, expr, depth)
+            }
+            Abstype { .. } => s.push_str("abstype ... end"),
+            Local { .. } => s.push_str("local ... end"),
+            And(decls) => {
+                for (i, decl) in decls.iter().enumerate() {
+                    if i != 0 {
+                        s.push_str(" and ")
+                    }
+                    self.write_decl(s, decl, depth)
+                }
+            }
+            D(d) => match *d {},
+        }
+    }
+
+    pub fn pat<Ty>(&mut self, pat: &Pattern<Ty>) -> String {
+        let mut s = String::new();
+        self.write_pat(&mut s, pat);
+        s
+    }
+
+    fn write_pat<Ty>(&mut self, s: &mut String, pat: &Pattern<Ty>) {
+        use PatternKind::*;
+        match &pat.inner {
+            Constant { value } => s.push_str(&literal(&prim::Literal::Int(*value))),
+            Word { value } => s.push_str(&literal(&prim::Literal::Word(*value))),
+            Char { value } => s.push_str(&literal(&prim::Literal::Char(*value))),
+            Real { value } => s.push_str(&literal(&prim::Literal::Real(*value))),
+            Constructor { name, arg } => {
+                s.push_str(&name.0);
+                if let Some(arg) = arg {
+                    s.push(' ');
+                    let atomic = match &arg.inner {
+                        Constructor { arg, .. } => arg.is_none(),
+                        _ => true,
+                    };
+                    self.paren(s, !atomic, |this, s| this.write_pat(s, arg))
+                }
+            }
+            Tuple { tuple } => {
+                s.push('(');
+                for (i, pat) in tuple.iter().enumerate() {
+                    if i != 0 {
+                        s.push_str(", ")
+                    }
+                    self.write_pat(s, pat)
+                }
+                s.push(')')
+            }
+            Variable { name } => s.push_str(&name.0),
+            Wildcard {} => s.push('_'),
+        }
+    }
+
+    fn paren(&mut self, s: &mut String, paren: bool, body: impl FnOnce(&mut Self, &mut String)) {
+        if paren {
+            s.push('(')
+        }
+        body(self, s);
+        if paren {
+            s.push(')')
+        }
+    }
+}
+
+/// the builtin function wrapped by `Rename`, which is
+/// `fn x => _builtincall "f"(x)` or `fn t => case t of (x, y) => _builtincall "f"(x, y)`
+fn builtin_wrapper<Ty>(expr: &CoreExpr<Ty>) -> Option<BIF> {
+    use ExprKind::*;
+    let body = match &expr.inner {
+        Fn { body, .. } => body,
+        _ => return None,
+    };
+    let body = match &body.inner {
+        Case { clauses, .. } if clauses.len() == 1 => &clauses[0].1,
+        _ => body,
+    };
+    match &body.inner {
+        BuiltinCall { fun, .. } => Some(*fun),
+        _ => None,
+    }
+}
+
+fn bif_name(bif: &BIF) -> &'static str {
+    BUILTIN_FUNCTIONS
+        .iter()
+        .find(|(_, b)| b == bif)
+        .map(|(name, _)| *name)
+        .unwrap_or("_builtin")
+}
+
+fn is_infix(bif: &BIF) -> bool {
+    use BIF::*;
+    matches!(
+        bif,
+        Add | Sub
+            | Mul
+            | Div
+            | Divf
+            | Mod
+            | Eq
+            | Neq
+            | Gt
+            | Ge
+            | Lt
+            | Le
+            | Lshift
+            | Rshift
+            | Arshift
+    )
+}
+
+fn literal(lit: &prim::Literal) -> String {
+    use prim::Literal::*;
+    match lit {
+        Int(v) => v.to_string().replace('-', "~"),
+        LargeInt(v) => v.replace('-', "~"),
+        Word(v) => format!("0w{}", v),
+        LargeWord(v) => v.clone(),
+        Real(v) => format!("{:?}", v).replace('-', "~"),
+        Char(c) => format!(
+            r##"#"{}""##,
+            std::char::from_u32(*c).unwrap_or(std::char::REPLACEMENT_CHARACTER)
+        ),
+    }
+}
@@ -5,6 +5,7 @@ use crate::id::Id;
 use crate::pass::Pass;
 use crate::prim::*;
 use std::collections::HashMap;
+use std::mem;
 use std::ops::{Deref, DerefMut, Drop};
 
 pub struct Rename {
@@ -14,6 +15,10 @@ pub struct Rename {
     constructor_tables: Vec<HashMap<Symbol, u64>>,
     pos: usize,
     id: Id,
+    /// the innermost binding being renamed, to tell where an unbound name is used
+    location: Option<String>,
+    /// the first unbound name found
+    error: Option<TypeError<'static>>,
 }
 
 struct Scope<'a>(&'a mut Rename);
@@ -98,6 +103,12 @@ impl<'a> Scope<'a> {
                 None => {}
             }
         }
+        let candidates = self.variable_tables[0..pos]
+            .iter()
+            .chain(&self.constructor_tables[0..pos])
+            .flat_map(|table| table.keys().map(|name| name.0.clone()))
+            .collect::<Vec<_>>();
+        self.unbound(NameKind::Variable, symbol, candidates)
     }
 
     fn rename_constructor(&mut self, symbol: &mut Symbol) {
@@ -111,13 +122,68 @@ impl<'a> Scope<'a> {
                 None => {}
             }
         }
+        let candidates = self.constructor_tables[0..pos]
+            .iter()
+            .flat_map(|table| table.keys().map(|name| name.0.clone()))
+            .collect::<Vec<_>>();
+        self.unbound(NameKind::Constructor, symbol, candidates)
+    }
+
+    /// record the first unbound name with the candidates close to it
+    fn unbound(&mut self, kind: NameKind, symbol: &Symbol, candidates: Vec<String>) {
+        if self.error.is_some() {
+            return;
+        }
+        let suggestions = suggest(&symbol.0, candidates);
+        self.error = Some(TypeError::Unbound {
+            kind,
+            name: symbol.0.clone(),
+            location: self.location.clone(),
+            span: symbol.2,
+            suggestions,
+        })
+    }
+
+    fn new_datatype_names(
+        &mut self,
+        name: &mut Symbol,
+        constructors: &mut [(Symbol, Option<Type>)],
+    ) {
+        self.new_type(name);
+        for (cname, _) in constructors.iter_mut() {
+            self.new_constructor(cname);
+        }
+    }
+
+    fn register_datatype(
+        &mut self,
+        name: &mut Symbol,
+        constructors: &mut [(Symbol, Option<Type>)],
+    ) {
+        for (_, argty) in constructors.iter_mut() {
+            if let Some(argty) = argty {
+                self.rename_type(argty);
+                *argty = self.symbol_table().expand_type_aliases(argty.clone());
+            }
+        }
+
+        let constructor_info = TypeInfo {
+            constructors: constructors.to_vec(),
+        };
+        self.symbol_table()
+            .register_type(name.clone(), constructor_info);
+    }
+
+    fn register_type_alias(&mut self, name: &Symbol, ty: &Type) {
+        self.symbol_table()
+            .register_type_alias(name.clone(), ty.clone());
     }
 
     fn rename_type(&mut self, ty: &mut Type) {
         use Type::*;
 
         match ty {
-            Variable(_) | Char | Int | Real => {
+            Variable(_) | Char | Int | IntInf | Word(_) | Real | String => {
                 // noop
                 ()
             }
@@ -141,6 +207,12 @@ impl<'a> Scope<'a> {
                         None => {}
                     }
                 }
+                let candidates = self.type_tables[0..pos]
+                    .iter()
+                    .flat_map(|table| table.keys().map(|name| name.0.clone()))
+                    .chain(PRIMITIVE_TYPES.iter().map(|name| name.to_string()))
+                    .collect::<Vec<_>>();
+                self.unbound(NameKind::Type, name, candidates)
             }
         }
     }
@@ -153,20 +225,154 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
         constructors: &mut Vec<(Symbol, Option<Type>)>,
     ) {
         let scope = self;
-        scope.new_type(name);
-        for (cname, argty) in constructors.iter_mut() {
-            scope.new_constructor(cname);
-            if let Some(argty) = argty {
-                scope.rename_type(argty);
-            }
+        scope.new_datatype_names(name, constructors);
+        scope.register_datatype(name, constructors);
+    }
+
+    fn traverse_type(&mut self, name: &mut Symbol, ty: &mut Type) {
+        // type abbreviations are not recursive
+        self.rename_type(ty);
+        self.new_type(name);
+        self.register_type_alias(name, ty);
+    }
+
+    fn traverse_externcall(
+        &mut self,
+        _module: &mut String,
+        _fun: &mut String,
+        args: &mut Vec<CoreExpr<Ty>>,
+        argty: &mut Vec<Type>,
+        retty: &mut Type,
+    ) {
+        for arg in args {
+            self.traverse_expr(arg)
+        }
+        for ty in argty {
+            self.rename_type(ty)
         }
+        self.rename_type(retty)
+    }
 
-        let constructor_info = TypeInfo {
-            constructors: constructors.clone(),
+    fn traverse_abstype(
+        &mut self,
+        datatype: &mut CoreDeclaration<Ty>,
+        decls: &mut Vec<CoreDeclaration<Ty>>,
+    ) {
+        use Declaration::*;
+        let cnames = match &*datatype {
+            Datatype { constructors, .. } => constructors.iter().map(|(c, _)| c.clone()).collect(),
+            And(group) => group
+                .iter()
+                .flat_map(|decl| match decl {
+                    Datatype { constructors, .. } => {
+                        constructors.iter().map(|(c, _)| c.clone()).collect()
+                    }
+                    _ => Vec::new(),
+                })
+                .collect(),
+            _ => Vec::new(),
         };
-        scope
-            .symbol_table()
-            .register_type(name.clone(), constructor_info);
+        let pos = self.pos - 1;
+        let saved = cnames
+            .into_iter()
+            .map(|cname| {
+                let id = self.constructor_tables[pos].get(&cname).cloned();
+                (cname, id)
+            })
+            .collect::<Vec<_>>();
+
+        self.traverse_statement(datatype);
+        for decl in decls.iter_mut() {
+            self.traverse_statement(decl);
+        }
+
+        // the constructors are visible only inside of `with ... end`
+        for (cname, id) in saved {
+            match id {
+                Some(id) => self.constructor_tables[pos].insert(cname, id),
+                None => self.constructor_tables[pos].remove(&cname),
+            };
+        }
+    }
+
+    fn traverse_local(
+        &mut self,
+        binds: &mut Vec<CoreDeclaration<Ty>>,
+        body: &mut Vec<CoreDeclaration<Ty>>,
+    ) {
+        let outer = self.pos - 1;
+        let inner = {
+            let mut scope = self.new_scope();
+            for decl in binds.iter_mut() {
+                scope.traverse_statement(decl);
+            }
+            let mut scope = scope.new_scope();
+            for decl in body.iter_mut() {
+                scope.traverse_statement(decl);
+            }
+            scope.pos - 1
+        };
+        // export only the names bound in `body`
+        let rename = &mut *self.0;
+        for tables in &mut [
+            &mut rename.variable_tables,
+            &mut rename.type_tables,
+            &mut rename.constructor_tables,
+        ] {
+            let exported = mem::take(&mut tables[inner]);
+            tables[outer].extend(exported);
+        }
+    }
+
+    fn traverse_and(&mut self, decls: &mut Vec<CoreDeclaration<Ty>>) {
+        use Declaration::*;
+        let scope = self;
+        // datatypes in the group can refer each other,
+        // so bring all the names into scope before renaming the argument types
+        for decl in decls.iter_mut() {
+            if let Datatype { name, constructors } = decl {
+                scope.new_datatype_names(name, constructors);
+            }
+        }
+        // `withtype` abbreviations can refer the datatypes of the group and vice versa
+        for decl in decls.iter_mut() {
+            if let Type { ty, .. } = decl {
+                scope.rename_type(ty);
+            }
+        }
+        for decl in decls.iter_mut() {
+            if let Type { name, ty } = decl {
+                scope.new_type(name);
+                scope.register_type_alias(name, ty);
+            }
+        }
+        for decl in decls.iter_mut() {
+            if let Datatype { name, constructors } = decl {
+                scope.register_datatype(name, constructors);
+            }
+        }
+        // expressions of `val` refer the outer scope and ones of `val rec` refer the group.
+        for decl in decls.iter_mut() {
+            if let Val {
+                rec: false, expr, ..
+            } = decl
+            {
+                scope.traverse_expr(expr);
+            }
+        }
+        for decl in decls.iter_mut() {
+            if let Val { pattern, .. } = decl {
+                scope.traverse_pattern(pattern);
+            }
+        }
+        for decl in decls.iter_mut() {
+            if let Val {
+                rec: true, expr, ..
+            } = decl
+            {
+                scope.traverse_expr(expr);
+            }
+        }
     }
 
     fn traverse_val<'b, 'c>(
@@ -176,6 +382,10 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
         expr: &mut CoreExpr<Ty>,
     ) {
         let scope = self;
+        let outer = scope.location.clone();
+        if let Some((name, _)) = pattern.binds().first() {
+            scope.location = Some(name.0.clone());
+        }
         if *rec {
             scope.traverse_pattern(pattern);
             scope.traverse_expr(expr);
@@ -183,6 +393,7 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
             scope.traverse_expr(expr);
             scope.traverse_pattern(pattern);
         }
+        scope.location = outer;
     }
 
     fn traverse_binds(
@@ -246,7 +457,63 @@ impl<'a, Ty: Clone> util::Traverse<Ty> for Scope<'a> {
     }
 }
 
-static BUILTIN_FUNCTIONS: &[(&str, BIF)] = &[
+/// the names of the types the parser turns into the primitive types
+static PRIMITIVE_TYPES: &[&str] = &[
+    "unit",
+    "real",
+    "string",
+    "int",
+    "word",
+    "Word8.word",
+    "Word32.word",
+    "Word64.word",
+    "IntInf.int",
+    "LargeInt.int",
+];
+
+/// how many names to suggest for an unbound one
+const MAX_SUGGESTIONS: usize = 3;
+
+/// the candidates within the edit distance of about a third of the length of `name`, closest first
+fn suggest(name: &str, candidates: Vec<String>) -> Vec<String> {
+    let limit = std::cmp::max(1, name.chars().count() / 3);
+    let mut close = candidates
+        .into_iter()
+        // skip the names generated by the compiler
+        .filter(|c| !c.starts_with('#'))
+        .map(|c| (edit_distance(name, &c), c))
+        .filter(|&(d, _)| d <= limit)
+        .collect::<Vec<_>>();
+    close.sort();
+    close.dedup();
+    close
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, c)| c)
+        .collect()
+}
+
+/// Levenshtein distance
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut cur = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = prev[j] + if ca == *cb { 0 } else { 1 };
+            cur.push(
+                *[prev[j + 1] + 1, cur[j] + 1, substitute]
+                    .iter()
+                    .min()
+                    .unwrap(),
+            );
+        }
+        prev = cur;
+    }
+    prev[b.len()]
+}
+
+pub(super) static BUILTIN_FUNCTIONS: &[(&str, BIF)] = &[
     ("+", BIF::Add),
     ("-", BIF::Sub),
     ("*", BIF::Mul),
@@ -259,6 +526,61 @@ static BUILTIN_FUNCTIONS: &[(&str, BIF)] = &[
     (">=", BIF::Ge),
     ("<", BIF::Lt),
     ("<=", BIF::Le),
+    ("andb", BIF::Andb),
+    ("orb", BIF::Orb),
+    ("xorb", BIF::Xorb),
+    ("<<", BIF::Lshift),
+    (">>", BIF::Rshift),
+    ("~>>", BIF::Arshift),
+    ("~", BIF::Neg),
+    ("abs", BIF::Abs),
+    ("real", BIF::Real),
+    ("floor", BIF::Floor),
+    ("ceil", BIF::Ceil),
+    ("round", BIF::Round),
+    ("trunc", BIF::Trunc),
+    ("Math.sqrt", BIF::Sqrt),
+    ("IntInf.fromInt", BIF::IntInfFromInt),
+    ("IntInf.toInt", BIF::IntInfToInt),
+];
+
+// functions of the host or the runtime: the name, the module and the function imported,
+// the parameter and the result types
+type ExternFunction = (&'static str, &'static str, &'static str, Type, Type);
+
+static EXTERN_FUNCTIONS: &[ExternFunction] = &[
+    ("Math.sin", "js-ffi", "sin", Type::Real, Type::Real),
+    ("Math.cos", "js-ffi", "cos", Type::Real, Type::Real),
+    ("Math.exp", "js-ffi", "exp", Type::Real, Type::Real),
+    ("Math.ln", "js-ffi", "ln", Type::Real, Type::Real),
+    (
+        "Real.toString",
+        "webml-rt",
+        "real_to_string",
+        Type::Real,
+        Type::String,
+    ),
+    (
+        "String.print",
+        "webml-rt",
+        "string_print",
+        Type::String,
+        Type::Tuple(Vec::new()),
+    ),
+    (
+        "IntInf.toString",
+        "webml-rt",
+        "intinf_to_string",
+        Type::IntInf,
+        Type::String,
+    ),
+    (
+        "IntInf.print",
+        "webml-rt",
+        "intinf_print",
+        Type::IntInf,
+        Type::Tuple(Vec::new()),
+    ),
 ];
 
 impl Rename {
@@ -266,7 +588,9 @@ impl Rename {
         // leave built in functions as non_renamed
         let functions = BUILTIN_FUNCTIONS
             .iter()
-            .map(|(s, _)| (Symbol::new(*s), 0))
+            .map(|(s, _)| *s)
+            .chain(EXTERN_FUNCTIONS.iter().map(|(s, ..)| *s))
+            .map(|s| (Symbol::new(s), 0))
             .collect();
         let datatypes = ["bool"].iter().map(|s| (Symbol::new(*s), 0)).collect();
         let constructors = ["false", "true"]
@@ -287,8 +611,11 @@ impl Rename {
             variable_tables: vec![functions],
             type_tables: vec![datatypes],
             constructor_tables: vec![constructors],
-            pos: 0,
+            // keep the tables of the builtins from being cleared by the toplevel scope
+            pos: 1,
             id,
+            location: None,
+            error: None,
         }
     }
 
@@ -306,8 +633,10 @@ impl Rename {
 }
 
 // bif -> fn x => _builtincall "bif"(x)
+// extern -> fn x => _externcall("module"."fun": (param) -> ret)(x)
 struct WrapBIF {
     bif_table: HashMap<String, BIF>,
+    extern_table: HashMap<String, &'static ExternFunction>,
     id: Id,
 }
 impl WrapBIF {
@@ -317,13 +646,17 @@ impl WrapBIF {
                 .iter()
                 .map(|(s, bif)| (s.to_string(), *bif))
                 .collect(),
+            extern_table: EXTERN_FUNCTIONS
+                .iter()
+                .map(|ext| (ext.0.to_string(), ext))
+                .collect(),
             id,
         }
     }
 
     fn gensym(&mut self, name: impl Into<String>) -> Symbol {
         let id = self.id.next();
-        Symbol(name.into(), id)
+        Symbol(name.into(), id, None)
     }
 }
 
@@ -333,7 +666,8 @@ impl Transform<()> for WrapBIF {
             if let Some(bif) = self.bif_table.get(&name.0).cloned() {
                 use BIF::*;
                 return match bif {
-                    Add | Sub | Mul | Div | Divf | Mod | Eq | Neq | Gt | Ge | Lt | Le => {
+                    Add | Sub | Mul | Div | Divf | Mod | Eq | Neq | Gt | Ge | Lt | Le | Andb
+                    | Orb | Xorb | Lshift | Rshift | Arshift => {
                         let tuple = self.gensym("tuple");
                         let l = self.gensym("x");
                         let r = self.gensym("y");
@@ -390,6 +724,46 @@ impl Transform<()> for WrapBIF {
                             .boxed(),
                         }
                     }
+                    Neg | Abs | Real | Floor | Ceil | Round | Trunc | Sqrt | IntInfFromInt
+                    | IntInfToInt => {
+                        let x = self.gensym("x");
+                        // fn x => _builtincall "op"(x)
+                        ExprKind::Fn {
+                            param: x.clone(),
+                            body: Expr {
+                                ty: (),
+                                inner: ExprKind::BuiltinCall {
+                                    fun: bif,
+                                    args: vec![Expr {
+                                        ty: (),
+                                        inner: ExprKind::Symbol { name: x },
+                                    }],
+                                },
+                            }
+                            .boxed(),
+                        }
+                    }
+                    Raise(_) => unreachable!("internal error: raise is not in the table"),
+                };
+            }
+            if let Some((_, module, fun, param, ret)) = self.extern_table.get(&name.0).cloned() {
+                let x = self.gensym("x");
+                return ExprKind::Fn {
+                    param: x.clone(),
+                    body: Expr {
+                        ty: (),
+                        inner: ExprKind::ExternCall {
+                            module: module.to_string(),
+                            fun: fun.to_string(),
+                            args: vec![Expr {
+                                ty: (),
+                                inner: ExprKind::Symbol { name: x },
+                            }],
+                            argty: vec![param.clone()],
+                            retty: ret.clone(),
+                        },
+                    }
+                    .boxed(),
                 };
             }
         }
@@ -397,18 +771,51 @@ impl Transform<()> for WrapBIF {
     }
 }
 
-impl<E> Pass<UntypedCore, E> for Rename {
+impl<'a> Pass<UntypedCore, TypeError<'a>> for Rename {
     type Target = (SymbolTable, UntypedCore);
 
-    fn trans(
-        &mut self,
-        mut ast: UntypedCore,
-        _: &Config,
-    ) -> ::std::result::Result<Self::Target, E> {
+    fn trans(&mut self, mut ast: UntypedCore, _: &Config) -> Result<'a, Self::Target> {
         self.scope().traverse_ast(&mut ast);
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
         let mut wrap_bif = WrapBIF::new(self.id.clone());
         let ast = wrap_bif.transform_ast(ast);
         let symbol_table = self.generate_symbol_table();
         Ok((symbol_table, ast))
     }
 }
+
+#[test]
+fn test_edit_distance() {
+    assert_eq!(edit_distance("length", "length"), 0);
+    assert_eq!(edit_distance("lenght", "length"), 2);
+    assert_eq!(edit_distance("prnt", "print"), 1);
+    assert_eq!(edit_distance("print", "prnt"), 1);
+    assert_eq!(edit_distance("", "abc"), 3);
+    assert_eq!(edit_distance("kitten", "sitting"), 3);
+}
+
+#[test]
+fn test_suggest_within_the_limit() {
+    let candidates = vec!["length", "len", "map", "#lenght"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    // 6 characters allow the distance of 2
+    assert_eq!(suggest("lenght", candidates), vec!["length"]);
+    // the short names allow the distance of 1
+    let candidates = vec!["map".to_string(), "mod".to_string()];
+    assert_eq!(suggest("mop", candidates), vec!["map", "mod"]);
+    let candidates = vec!["arg".to_string(), "#arg".to_string()];
+    assert_eq!(suggest("ag", candidates), vec!["arg"]);
+}
+
+#[test]
+fn test_suggest_closest_first() {
+    let candidates = vec!["fooo", "fob", "foo", "bar", "foo", "fo"]
+        .into_iter()
+        .map(String::from)
+        .collect();
+    assert_eq!(suggest("foo", candidates), vec!["foo", "fo", "fob"]);
+}
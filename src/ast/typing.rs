@@ -1,5 +1,5 @@
 use crate::ast::*;
-use crate::config::Config;
+use crate::config::{Config, IntWidth};
 use crate::id::Id;
 use crate::prim::*;
 use crate::unification_pool::{NodeId, UnificationPool};
@@ -13,6 +13,12 @@ struct TyEnv {
     env: HashMap<Symbol, NodeId>,
     symbol_table: SymbolTable,
     pool: TypePool,
+    int_width: IntWidth,
+    /// word literals as written to check the range of after their types are resolved.
+    /// the value is `None` if it does not fit in 64 bits
+    word_literals: Vec<(String, Option<u64>, NodeId)>,
+    /// int literals to check the range of after their types are resolved
+    int_literals: Vec<(i64, NodeId)>,
 }
 
 #[derive(Debug)]
@@ -27,12 +33,25 @@ enum Typing {
     Variable(u64),
     Char,
     Int,
+    IntInf,
+    Word(WordSize),
     Real,
+    String,
     Fun(NodeId, NodeId),
     Tuple(Vec<NodeId>),
     Datatype(Symbol),
+    /// int, IntInf, real and words
     OverloadedNum,
+    /// int, IntInf, real, char and words
     OverloadedNumText,
+    /// int, IntInf and words
+    OverloadedWordInt,
+    /// words
+    OverloadedWord,
+    /// int, IntInf and real
+    OverloadedRealInt,
+    /// int and IntInf
+    OverloadedInt,
 }
 
 fn resolve(pool: &UnificationPool<Typing>, id: NodeId) -> Type {
@@ -45,7 +64,10 @@ fn conv_ty(pool: &UnificationPool<Typing>, ty: Typing) -> Type {
         Variable(id) => Type::Variable(id),
         Char => Type::Char,
         Int => Type::Int,
+        IntInf => Type::IntInf,
+        Word(size) => Type::Word(size),
         Real => Type::Real,
+        String => Type::String,
         Fun(param, body) => Type::Fun(
             Box::new(resolve(pool, param)),
             Box::new(resolve(pool, body)),
@@ -54,6 +76,43 @@ fn conv_ty(pool: &UnificationPool<Typing>, ty: Typing) -> Type {
         Datatype(type_id) => Type::Datatype(type_id),
         OverloadedNum => Type::Int,
         OverloadedNumText => Type::Int,
+        OverloadedWordInt => Type::Int,
+        OverloadedWord => Type::Word(WordSize::W32),
+        OverloadedRealInt => Type::Int,
+        OverloadedInt => Type::Int,
+    }
+}
+
+fn children(ty: &Typing) -> Vec<NodeId> {
+    use Typing::*;
+    match ty {
+        Fun(param, body) => vec![*param, *body],
+        Tuple(tys) => tys.clone(),
+        _ => vec![],
+    }
+}
+
+/// whether binding a type variable to `ty` makes an infinite type.
+/// it does if `ty` refers to the nodes being unified, which enclose the variable
+fn occurs(pool: &UnificationPool<Typing>, ty: &Typing) -> bool {
+    children(ty)
+        .into_iter()
+        .any(|id| pool.is_pending(id) || occurs(pool, pool.value_of(id)))
+}
+
+/// same as `conv_ty` but shows the nodes being unified as the variable `var`
+fn conv_ty_circular(pool: &UnificationPool<Typing>, ty: Typing, var: u64) -> Type {
+    let resolve = |id| {
+        if pool.is_pending(id) {
+            Type::Variable(var)
+        } else {
+            conv_ty_circular(pool, pool.value_of(id).clone(), var)
+        }
+    };
+    match ty {
+        Typing::Fun(param, body) => Type::Fun(Box::new(resolve(param)), Box::new(resolve(body))),
+        Typing::Tuple(tys) => Type::Tuple(tys.into_iter().map(resolve).collect()),
+        ty => conv_ty(pool, ty),
     }
 }
 
@@ -73,7 +132,64 @@ fn try_unify<'b, 'r>(
         (OverloadedNumText, OverloadedNum) | (OverloadedNum, OverloadedNumText) => {
             Ok(OverloadedNumText)
         }
-        (Variable(_), ty) | (ty, Variable(_)) => Ok(ty),
+        (Int, OverloadedWordInt) | (OverloadedWordInt, Int) => Ok(Int),
+        (Word(size), OverloadedNum)
+        | (OverloadedNum, Word(size))
+        | (Word(size), OverloadedNumText)
+        | (OverloadedNumText, Word(size))
+        | (Word(size), OverloadedWordInt)
+        | (OverloadedWordInt, Word(size))
+        | (Word(size), OverloadedWord)
+        | (OverloadedWord, Word(size)) => Ok(Word(size)),
+        (OverloadedWordInt, OverloadedNum)
+        | (OverloadedNum, OverloadedWordInt)
+        | (OverloadedWordInt, OverloadedNumText)
+        | (OverloadedNumText, OverloadedWordInt) => Ok(OverloadedWordInt),
+        (OverloadedWord, OverloadedNum)
+        | (OverloadedNum, OverloadedWord)
+        | (OverloadedWord, OverloadedNumText)
+        | (OverloadedNumText, OverloadedWord)
+        | (OverloadedWord, OverloadedWordInt)
+        | (OverloadedWordInt, OverloadedWord) => Ok(OverloadedWord),
+        (Int, OverloadedRealInt) | (OverloadedRealInt, Int) => Ok(Int),
+        (Real, OverloadedRealInt) | (OverloadedRealInt, Real) => Ok(Real),
+        (OverloadedRealInt, OverloadedNum)
+        | (OverloadedNum, OverloadedRealInt)
+        | (OverloadedRealInt, OverloadedNumText)
+        | (OverloadedNumText, OverloadedRealInt) => Ok(OverloadedRealInt),
+        (OverloadedRealInt, OverloadedWordInt) | (OverloadedWordInt, OverloadedRealInt) => {
+            Ok(OverloadedInt)
+        }
+        (IntInf, OverloadedNum)
+        | (OverloadedNum, IntInf)
+        | (IntInf, OverloadedNumText)
+        | (OverloadedNumText, IntInf)
+        | (IntInf, OverloadedWordInt)
+        | (OverloadedWordInt, IntInf)
+        | (IntInf, OverloadedRealInt)
+        | (OverloadedRealInt, IntInf)
+        | (IntInf, OverloadedInt)
+        | (OverloadedInt, IntInf) => Ok(IntInf),
+        (Int, OverloadedInt) | (OverloadedInt, Int) => Ok(Int),
+        (OverloadedInt, OverloadedNum)
+        | (OverloadedNum, OverloadedInt)
+        | (OverloadedInt, OverloadedNumText)
+        | (OverloadedNumText, OverloadedInt)
+        | (OverloadedInt, OverloadedWordInt)
+        | (OverloadedWordInt, OverloadedInt)
+        | (OverloadedInt, OverloadedRealInt)
+        | (OverloadedRealInt, OverloadedInt) => Ok(OverloadedInt),
+        (Variable(id), ty) | (ty, Variable(id)) => {
+            if occurs(pool, &ty) {
+                Err(TypeError::Circularity {
+                    var: Type::Variable(id),
+                    ty: conv_ty_circular(pool, ty, id),
+                    expr: None,
+                })
+            } else {
+                Ok(ty)
+            }
+        }
         (Fun(p1, b1), Fun(p2, b2)) => {
             let p = pool.try_unify_with(p1, p2, try_unify)?;
             let b = pool.try_unify_with(b1, b2, try_unify)?;
@@ -84,6 +200,8 @@ fn try_unify<'b, 'r>(
                 Err(TypeError::MisMatch {
                     expected: conv_ty(pool, Tuple(tu1)),
                     actual: conv_ty(pool, Tuple(tu2)),
+                    enclosing: None,
+                    expr: None,
                 })
             } else {
                 let tu = tu1
@@ -97,6 +215,8 @@ fn try_unify<'b, 'r>(
         (t1, t2) => Err(TypeError::MisMatch {
             expected: conv_ty(pool, t1),
             actual: conv_ty(pool, t2),
+            enclosing: None,
+            expr: None,
         }),
     }
 }
@@ -106,8 +226,8 @@ impl Typer {
         Typer
     }
 
-    fn generate_pass(&mut self, symbol_table: SymbolTable) -> TyEnv {
-        TyEnv::new(symbol_table)
+    fn generate_pass(&mut self, symbol_table: SymbolTable, config: &Config) -> TyEnv {
+        TyEnv::new(symbol_table, config.int_width)
     }
 }
 
@@ -125,7 +245,9 @@ impl TypePool {
     fn init(&mut self) {
         self.node_new(Typing::Char);
         self.node_new(Typing::Int);
+        self.node_new(Typing::IntInf);
         self.node_new(Typing::Real);
+        self.node_new(Typing::String);
     }
 
     fn feed_symbol_table(&mut self, symbol_table: &SymbolTable) {
@@ -146,6 +268,10 @@ impl TypePool {
         *self.cache.get(&Typing::Int).unwrap()
     }
 
+    fn ty_intinf(&mut self) -> NodeId {
+        *self.cache.get(&Typing::IntInf).unwrap()
+    }
+
     fn ty_char(&mut self) -> NodeId {
         *self.cache.get(&Typing::Char).unwrap()
     }
@@ -161,6 +287,10 @@ impl TypePool {
         *self.cache.get(&Typing::Real).unwrap()
     }
 
+    fn ty_word(&mut self) -> NodeId {
+        self.node_new(Typing::Word(WordSize::W32))
+    }
+
     fn ty_overloaded_num(&mut self) -> NodeId {
         self.node_new(Typing::OverloadedNum)
     }
@@ -169,10 +299,31 @@ impl TypePool {
         self.node_new(Typing::OverloadedNumText)
     }
 
+    fn ty_overloaded_word_int(&mut self) -> NodeId {
+        self.node_new(Typing::OverloadedWordInt)
+    }
+
+    fn ty_overloaded_word(&mut self) -> NodeId {
+        self.node_new(Typing::OverloadedWord)
+    }
+
+    fn ty_overloaded_real_int(&mut self) -> NodeId {
+        self.node_new(Typing::OverloadedRealInt)
+    }
+
+    fn ty_overloaded_int(&mut self) -> NodeId {
+        self.node_new(Typing::OverloadedInt)
+    }
+
     fn node_new(&mut self, t: Typing) -> NodeId {
         let node_id = self.pool.node_new(t.clone());
         match t {
-            t @ Typing::Char | t @ Typing::Int | t @ Typing::Real | t @ Typing::Datatype(_) => {
+            t @ Typing::Char
+            | t @ Typing::Int
+            | t @ Typing::IntInf
+            | t @ Typing::Real
+            | t @ Typing::String
+            | t @ Typing::Datatype(_) => {
                 self.cache.insert(t, node_id);
             }
             _ => (), // no cache
@@ -203,11 +354,14 @@ impl TypePool {
 }
 
 impl TyEnv {
-    pub fn new(symbol_table: SymbolTable) -> Self {
+    pub fn new(symbol_table: SymbolTable, int_width: IntWidth) -> Self {
         let mut ret = TyEnv {
             env: HashMap::new(),
             symbol_table: symbol_table,
             pool: TypePool::new(),
+            int_width,
+            word_literals: Vec::new(),
+            int_literals: Vec::new(),
         };
         ret.init();
 
@@ -236,6 +390,29 @@ impl TyEnv {
 
     pub fn infer<'a, 'b>(&'a mut self, ast: &mut ast::Core<NodeId>) -> Result<'b, ()> {
         self.infer_ast(ast)?;
+        self.check_int_literals()?;
+        self.check_word_literals()?;
+        Ok(())
+    }
+
+    fn check_int_literals<'b>(&self) -> Result<'b, ()> {
+        for (n, ty) in &self.int_literals {
+            if resolve(&self.pool.pool, *ty) == Type::Int && !self.int_width.contains(*n) {
+                return Err(TypeError::IntOutOfRange(*n));
+            }
+        }
+        Ok(())
+    }
+
+    fn check_word_literals<'b>(&self) -> Result<'b, ()> {
+        for (text, w, ty) in &self.word_literals {
+            if let Type::Word(size) = resolve(&self.pool.pool, *ty) {
+                let fits = matches!(w, Some(w) if size.bits() == 64 || w >> size.bits() == 0);
+                if !fits {
+                    return Err(TypeError::WordOutOfRange(text.clone(), size));
+                }
+            }
+        }
         Ok(())
     }
 
@@ -260,7 +437,10 @@ impl TyEnv {
             Type::Variable(v) => Typing::Variable(v),
             Type::Char => Typing::Char,
             Type::Int => Typing::Int,
+            Type::IntInf => Typing::IntInf,
+            Type::Word(size) => Typing::Word(size),
             Type::Real => Typing::Real,
+            Type::String => Typing::String,
             Type::Fun(arg, ret) => {
                 let arg_typing = self.convert(*arg);
                 let ret_typing = self.convert(*ret);
@@ -275,7 +455,13 @@ impl TyEnv {
                     })
                     .collect(),
             ),
-            Type::Datatype(name) => Typing::Datatype(name),
+            Type::Datatype(name) => match self.symbol_table.type_aliases.get(&name) {
+                Some(ty) => {
+                    let ty = ty.clone();
+                    self.convert(ty)
+                }
+                None => Typing::Datatype(name),
+            },
         }
     }
 }
@@ -291,7 +477,7 @@ impl TyEnv {
     fn infer_statement<'b, 'r>(&'b mut self, decl: &CoreDeclaration<NodeId>) -> Result<'r, ()> {
         use Declaration::*;
         match decl {
-            Datatype { .. } => Ok(()),
+            Datatype { .. } | Type { .. } => Ok(()),
             Val { rec, pattern, expr } => {
                 let names = pattern.binds();
                 if *rec {
@@ -301,7 +487,8 @@ impl TyEnv {
                 }
                 self.infer_expr(expr)?;
                 self.infer_pat(pattern)?;
-                self.unify(expr.ty(), pattern.ty())?;
+                self.unify(expr.ty(), pattern.ty())
+                    .map_err(|e| self.error_in(e, expr))?;
                 if !rec {
                     for &(name, ty) in &names {
                         self.insert(name.clone(), ty.clone());
@@ -309,17 +496,57 @@ impl TyEnv {
                 }
                 Ok(())
             }
+            Abstype { datatype, decls } => {
+                self.infer_statement(datatype)?;
+                for decl in decls {
+                    self.infer_statement(decl)?;
+                }
+                Ok(())
+            }
+            Local { binds, body } => {
+                for decl in binds.iter().chain(body) {
+                    self.infer_statement(decl)?;
+                }
+                Ok(())
+            }
+            And(decls) => {
+                // make the names of `val rec`s visible before inferring any of the group
+                for decl in decls {
+                    if let Val {
+                        rec: true, pattern, ..
+                    } = decl
+                    {
+                        for (name, ty) in pattern.binds() {
+                            self.insert(name.clone(), *ty);
+                        }
+                    }
+                }
+                for decl in decls {
+                    self.infer_statement(decl)?;
+                }
+                Ok(())
+            }
             D(d) => match *d {},
         }
     }
 
     fn infer_expr<'b, 'r>(&'b mut self, expr: &CoreExpr<NodeId>) -> Result<'r, ()> {
+        self.infer_expr_kind(expr)
+            .map_err(|e| self.error_in(e, expr))
+    }
+
+    fn infer_expr_kind<'r>(&mut self, expr: &CoreExpr<NodeId>) -> Result<'r, ()> {
         use crate::ast::ExprKind::*;
         let int = self.pool.ty_int();
+        let intinf = self.pool.ty_intinf();
         let real = self.pool.ty_real();
+        let word = self.pool.ty_word();
         let bool = self.pool.ty_bool();
         let overloaded_num = self.pool.ty_overloaded_num();
         let overloaded_num_text = self.pool.ty_overloaded_num_text();
+        let overloaded_word_int = self.pool.ty_overloaded_word_int();
+        let overloaded_word = self.pool.ty_overloaded_word();
+        let overloaded_real_int = self.pool.ty_overloaded_real_int();
         let ty = &expr.ty;
         match &expr.inner {
             Binds { binds, ret } => {
@@ -362,11 +589,36 @@ impl TyEnv {
                         let l = &args[0];
                         let r = &args[1];
 
-                        self.unify(l.ty(), int)?;
-                        self.unify(r.ty(), int)?;
-                        self.unify(*ty, int)?;
                         self.infer_expr(l)?;
                         self.infer_expr(r)?;
+                        self.unify(l.ty(), r.ty())?;
+                        self.unify(l.ty(), overloaded_word_int)?;
+                        self.unify(*ty, l.ty())?;
+                        Ok(())
+                    }
+                    Andb | Orb | Xorb => {
+                        assert!(args.len() == 2);
+                        let l = &args[0];
+                        let r = &args[1];
+
+                        self.infer_expr(l)?;
+                        self.infer_expr(r)?;
+                        self.unify(l.ty(), r.ty())?;
+                        self.unify(l.ty(), overloaded_word)?;
+                        self.unify(*ty, l.ty())?;
+                        Ok(())
+                    }
+                    // the shift amount is a word whatever the width of the shifted
+                    Lshift | Rshift | Arshift => {
+                        assert!(args.len() == 2);
+                        let l = &args[0];
+                        let r = &args[1];
+
+                        self.infer_expr(l)?;
+                        self.infer_expr(r)?;
+                        self.unify(r.ty(), word)?;
+                        self.unify(l.ty(), overloaded_word)?;
+                        self.unify(*ty, l.ty())?;
                         Ok(())
                     }
                     Divf => {
@@ -381,6 +633,65 @@ impl TyEnv {
                         self.infer_expr(r)?;
                         Ok(())
                     }
+                    Neg | Abs => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.infer_expr(arg)?;
+                        self.unify(arg.ty(), overloaded_real_int)?;
+                        self.unify(*ty, arg.ty())?;
+                        Ok(())
+                    }
+                    Real => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.unify(arg.ty(), int)?;
+                        self.unify(*ty, real)?;
+                        self.infer_expr(arg)?;
+                        Ok(())
+                    }
+                    Floor | Ceil | Round | Trunc => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.unify(arg.ty(), real)?;
+                        self.unify(*ty, int)?;
+                        self.infer_expr(arg)?;
+                        Ok(())
+                    }
+                    Sqrt => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.unify(arg.ty(), real)?;
+                        self.unify(*ty, real)?;
+                        self.infer_expr(arg)?;
+                        Ok(())
+                    }
+                    IntInfFromInt => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.unify(arg.ty(), int)?;
+                        self.unify(*ty, intinf)?;
+                        self.infer_expr(arg)?;
+                        Ok(())
+                    }
+                    IntInfToInt => {
+                        assert!(args.len() == 1);
+                        let arg = &args[0];
+
+                        self.unify(arg.ty(), intinf)?;
+                        self.unify(*ty, int)?;
+                        self.infer_expr(arg)?;
+                        Ok(())
+                    }
+                    Raise(_) => {
+                        // never returns, so it can be any type
+                        assert!(args.is_empty());
+                        Ok(())
+                    }
                 }
             }
             ExternCall {
@@ -470,7 +781,20 @@ impl TyEnv {
     fn infer_literal<'b, 'r>(&'b mut self, lit: &Literal, given: NodeId) -> Result<'r, ()> {
         use crate::prim::Literal::*;
         let ty = match lit {
-            Int(_) => self.pool.ty_int(),
+            Int(n) => {
+                self.int_literals.push((*n, given));
+                self.pool.ty_overloaded_int()
+            }
+            LargeInt(_) => self.pool.ty_intinf(),
+            Word(w) => {
+                self.word_literals
+                    .push((format!("0w{}", w), Some(*w), given));
+                self.pool.ty_overloaded_word()
+            }
+            LargeWord(text) => {
+                self.word_literals.push((text.clone(), None, given));
+                self.pool.ty_overloaded_word()
+            }
             Real(_) => self.pool.ty_real(),
             Char(_) => self.pool.ty_char(),
         };
@@ -478,7 +802,10 @@ impl TyEnv {
         Ok(())
     }
 
-    fn infer_constant<'b, 'r>(&'b mut self, _: &i64, given: NodeId) -> Result<'r, ()> {
+    fn infer_constant<'b, 'r>(&'b mut self, n: &i64, given: NodeId) -> Result<'r, ()> {
+        if !self.int_width.contains(*n) {
+            return Err(TypeError::IntOutOfRange(*n));
+        }
         let ty = self.pool.ty_int();
         self.unify(given, ty)?;
         Ok(())
@@ -497,9 +824,16 @@ impl TyEnv {
             Constant { value } => {
                 self.infer_constant(value, *ty)?;
             }
+            Word { value } => {
+                self.word_literals
+                    .push((format!("0w{}", value), Some(*value), *ty));
+                let word = self.pool.ty_overloaded_word();
+                self.unify(*ty, word)?;
+            }
             Char { value } => {
                 self.infer_char(value, *ty)?;
             }
+            Real { .. } => return Err(TypeError::RealPattern),
             Constructor { arg, name } => {
                 let type_name = self
                     .symbol_table()
@@ -561,7 +895,63 @@ impl TyEnv {
     }
 
     fn unify<'b, 'r>(&'b mut self, id1: NodeId, id2: NodeId) -> Result<'r, ()> {
-        self.pool.try_unify_with(id1, id2, try_unify).map(|_| ())
+        match self.pool.try_unify_with(id1, id2, try_unify) {
+            Ok(_) => Ok(()),
+            Err(TypeError::MisMatch {
+                expected,
+                actual,
+                enclosing: None,
+                expr,
+            }) => {
+                let whole = (resolve(&self.pool.pool, id1), resolve(&self.pool.pool, id2));
+                let enclosing = if whole == (expected.clone(), actual.clone()) {
+                    None
+                } else {
+                    Some(Box::new(whole))
+                };
+                Err(TypeError::MisMatch {
+                    expected,
+                    actual,
+                    enclosing,
+                    expr,
+                })
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// tell the expression in which the type error occurs unless an inner one is already told
+    fn error_in<'r>(&self, e: TypeError<'r>, in_expr: &CoreExpr<NodeId>) -> TypeError<'r> {
+        let resolved = || {
+            Some(Box::new(
+                in_expr
+                    .clone()
+                    .map_ty(&mut |ty| resolve(&self.pool.pool, ty)),
+            ))
+        };
+        match e {
+            TypeError::MisMatch {
+                expected,
+                actual,
+                enclosing,
+                expr: None,
+            } => TypeError::MisMatch {
+                expected,
+                actual,
+                enclosing,
+                expr: resolved(),
+            },
+            TypeError::Circularity {
+                var,
+                ty,
+                expr: None,
+            } => TypeError::Circularity {
+                var,
+                ty,
+                expr: resolved(),
+            },
+            e => e,
+        }
     }
 
     fn give<'b, 'r>(&'b mut self, id1: NodeId, ty: Typing) -> Result<'r, ()> {
@@ -577,9 +967,9 @@ impl<'a> Pass<(SymbolTable, UntypedCore), TypeError<'a>> for Typer {
     fn trans<'b>(
         &'b mut self,
         (symbol_table, ast): (SymbolTable, UntypedCore),
-        _: &Config,
+        config: &Config,
     ) -> Result<'a, Self::Target> {
-        let mut pass = self.generate_pass(symbol_table);
+        let mut pass = self.generate_pass(symbol_table, config);
         let mut typing_ast = pass.pool.typing_ast(ast);
         pass.infer(&mut typing_ast)?;
         let typed_ast = pass.pool.typed_ast(typing_ast);
@@ -11,11 +11,47 @@ pub trait Traverse<Ty> {
         use Declaration::*;
         match decl {
             Datatype { name, constructors } => self.traverse_datatype(name, constructors),
+            Type { name, ty } => self.traverse_type(name, ty),
             Val { rec, pattern, expr } => self.traverse_val(rec, pattern, expr),
+            Abstype { datatype, decls } => self.traverse_abstype(datatype, decls),
+            Local { binds, body } => self.traverse_local(binds, body),
+            And(decls) => self.traverse_and(decls),
             D(_) => (),
         }
     }
 
+    fn traverse_type(&mut self, _name: &mut Symbol, _ty: &mut Type) {}
+
+    fn traverse_abstype(
+        &mut self,
+        datatype: &mut CoreDeclaration<Ty>,
+        decls: &mut Vec<CoreDeclaration<Ty>>,
+    ) {
+        self.traverse_statement(datatype);
+        for decl in decls.iter_mut() {
+            self.traverse_statement(decl)
+        }
+    }
+
+    fn traverse_local(
+        &mut self,
+        binds: &mut Vec<CoreDeclaration<Ty>>,
+        body: &mut Vec<CoreDeclaration<Ty>>,
+    ) {
+        for decl in binds.iter_mut() {
+            self.traverse_statement(decl)
+        }
+        for decl in body.iter_mut() {
+            self.traverse_statement(decl)
+        }
+    }
+
+    fn traverse_and(&mut self, decls: &mut Vec<CoreDeclaration<Ty>>) {
+        for decl in decls.iter_mut() {
+            self.traverse_statement(decl)
+        }
+    }
+
     fn traverse_datatype(
         &mut self,
         _name: &mut Symbol,
@@ -125,7 +161,9 @@ pub trait Traverse<Ty> {
         use PatternKind::*;
         match &mut pattern.inner {
             Constant { value } => self.traverse_pat_constant(value),
+            Word { value } => self.traverse_pat_word(value),
             Char { value } => self.traverse_pat_char(value),
+            Real { value } => self.traverse_pat_real(value),
             Constructor { name, arg } => self.traverse_pat_constructor(name, arg),
             Tuple { tuple } => self.traverse_pat_tuple(tuple),
             Variable { name } => self.traverse_pat_variable(name),
@@ -134,7 +172,9 @@ pub trait Traverse<Ty> {
     }
 
     fn traverse_pat_constant(&mut self, _value: &mut i64) {}
+    fn traverse_pat_word(&mut self, _value: &mut u64) {}
     fn traverse_pat_char(&mut self, _value: &mut u32) {}
+    fn traverse_pat_real(&mut self, _value: &mut f64) {}
     fn traverse_pat_constructor(
         &mut self,
         _name: &mut Symbol,
@@ -159,11 +199,59 @@ pub trait Transform<Ty> {
         use Declaration::*;
         match decl {
             Datatype { name, constructors } => self.transform_datatype(name, constructors),
+            Type { name, ty } => self.transform_type(name, ty),
             Val { rec, pattern, expr } => self.transform_val(rec, pattern, expr),
+            Abstype { datatype, decls } => self.transform_abstype(*datatype, decls),
+            Local { binds, body } => self.transform_local(binds, body),
+            And(decls) => self.transform_and(decls),
             D(d) => match d {},
         }
     }
 
+    fn transform_type(&mut self, name: Symbol, ty: Type) -> CoreDeclaration<Ty> {
+        Declaration::Type { name, ty }
+    }
+
+    fn transform_abstype(
+        &mut self,
+        datatype: CoreDeclaration<Ty>,
+        decls: Vec<CoreDeclaration<Ty>>,
+    ) -> CoreDeclaration<Ty> {
+        Declaration::Abstype {
+            datatype: Box::new(self.transform_statement(datatype)),
+            decls: decls
+                .into_iter()
+                .map(|decl| self.transform_statement(decl))
+                .collect(),
+        }
+    }
+
+    fn transform_local(
+        &mut self,
+        binds: Vec<CoreDeclaration<Ty>>,
+        body: Vec<CoreDeclaration<Ty>>,
+    ) -> CoreDeclaration<Ty> {
+        Declaration::Local {
+            binds: binds
+                .into_iter()
+                .map(|decl| self.transform_statement(decl))
+                .collect(),
+            body: body
+                .into_iter()
+                .map(|decl| self.transform_statement(decl))
+                .collect(),
+        }
+    }
+
+    fn transform_and(&mut self, decls: Vec<CoreDeclaration<Ty>>) -> CoreDeclaration<Ty> {
+        Declaration::And(
+            decls
+                .into_iter()
+                .map(|decl| self.transform_statement(decl))
+                .collect(),
+        )
+    }
+
     fn transform_datatype(
         &mut self,
         name: Symbol,
@@ -312,7 +400,9 @@ pub trait Transform<Ty> {
         use PatternKind::*;
         pattern.inner = match pattern.inner {
             Constant { value } => self.transform_pat_constant(value),
+            Word { value } => self.transform_pat_word(value),
             Char { value } => self.transform_pat_char(value),
+            Real { value } => self.transform_pat_real(value),
             Constructor { arg, name } => self.transform_pat_constructor(arg, name),
             Tuple { tuple } => self.transform_pat_tuple(tuple),
             Variable { name } => self.transform_pat_variable(name),
@@ -325,10 +415,18 @@ pub trait Transform<Ty> {
         PatternKind::Constant { value }
     }
 
+    fn transform_pat_word(&mut self, value: u64) -> PatternKind<Ty> {
+        PatternKind::Word { value }
+    }
+
     fn transform_pat_char(&mut self, value: u32) -> PatternKind<Ty> {
         PatternKind::Char { value }
     }
 
+    fn transform_pat_real(&mut self, value: f64) -> PatternKind<Ty> {
+        PatternKind::Real { value }
+    }
+
     fn transform_pat_constructor(
         &mut self,
         arg: Option<Box<Pattern<Ty>>>,
@@ -357,3 +455,64 @@ pub trait Transform<Ty> {
         PatternKind::Wildcard {}
     }
 }
+
+/// the range covering the names written in the source of the nodes given
+#[derive(Debug, Default)]
+pub struct NamesSpan(pub Option<Span>);
+
+impl NamesSpan {
+    pub fn expr<Ty: Clone>(&mut self, expr: &CoreExpr<Ty>) {
+        self.traverse_expr(&mut expr.clone())
+    }
+
+    pub fn pattern<Ty: Clone>(&mut self, pattern: &Pattern<Ty>) {
+        self.traverse_pattern(&mut pattern.clone())
+    }
+
+    fn add(&mut self, name: &Symbol) {
+        if let Some(span) = name.2 {
+            self.0 = Some(match self.0 {
+                None => span,
+                Some(s) => Span {
+                    start: s.start.min(span.start),
+                    end: s.end.max(span.end),
+                },
+            })
+        }
+    }
+}
+
+impl<Ty> Traverse<Ty> for NamesSpan {
+    fn traverse_fn(&mut self, param: &mut Symbol, body: &mut Box<CoreExpr<Ty>>) {
+        self.add(param);
+        self.traverse_expr(body)
+    }
+
+    fn traverse_constructor(&mut self, arg: &mut Option<Box<CoreExpr<Ty>>>, name: &mut Symbol) {
+        self.add(name);
+        if let Some(arg) = arg {
+            self.traverse_expr(arg)
+        }
+    }
+
+    fn traverse_sym(&mut self, name: &mut Symbol) {
+        self.add(name)
+    }
+
+    fn traverse_pat_constructor(&mut self, name: &mut Symbol, arg: &mut Option<Box<Pattern<Ty>>>) {
+        self.add(name);
+        if let Some(arg) = arg {
+            self.traverse_pattern(arg)
+        }
+    }
+
+    fn traverse_pat_tuple(&mut self, tuple: &mut Vec<Pattern<Ty>>) {
+        for pattern in tuple {
+            self.traverse_pattern(pattern)
+        }
+    }
+
+    fn traverse_pat_variable(&mut self, name: &mut Symbol) {
+        self.add(name)
+    }
+}
@@ -48,7 +48,7 @@ impl VarToConstructorPass {
 
     fn gensym(&mut self) -> Symbol {
         let id = self.id.next();
-        Symbol("#arg".into(), id)
+        Symbol("#arg".into(), id, None)
     }
 }
 
@@ -0,0 +1,68 @@
+//! the Node.js script to run a compiled module, like the `importObj` of index.html
+use crate::interface::{Import, VALUES_MODULE};
+use serde_json::{json, Value};
+
+/// the script running the module `wasm`, a path relative to the script,
+/// with the runtime at the path `runtime`. the units of `imports` are loaded before it in order
+pub fn loader(imports: &[Import], wasm: &str, runtime: &str) -> String {
+    let units = imports
+        .iter()
+        .map(|import| json!([import.unit, import.wasm]))
+        .collect::<Vec<_>>();
+    format!(
+        r#"// generated by webml
+const fs = require("fs");
+const path = require("path");
+
+const exceptions = ["Overflow", "Div", "Match", "Bind"];
+let rt;
+const rtImports = {{
+    imports: {{
+        print: (x) => console.log(x),
+        print_string: (ptr, len) => console.log(new TextDecoder().decode(
+            new Uint8Array(rt.exports.memory.buffer, ptr, len))),
+    }},
+}};
+const importObj = {{
+    "js-ffi": {{
+        print: (x) => console.log(x),
+        sin: Math.sin,
+        cos: Math.cos,
+        exp: Math.exp,
+        ln: Math.log,
+        raise: (e) => {{
+            console.error("uncaught exception " + exceptions[e]);
+            process.exit(1);
+        }},
+    }},
+}};
+// the values the units export, handed over by `set unit.name` and taken by `unit.name`
+const values = {{}};
+importObj[{values}] = new Proxy({{}}, {{
+    get: (_, name) => name.startsWith("set ")
+        ? (x) => {{ values[name.slice(4)] = x; }}
+        : () => values[name],
+}});
+const units = {units};
+const load = (wasm) =>
+    WebAssembly.instantiate(fs.readFileSync(path.resolve(__dirname, wasm)), importObj);
+
+WebAssembly.instantiate(fs.readFileSync({runtime}), rtImports).then(async ({{instance}}) => {{
+    rt = instance;
+    // alloc, alloc_stack, init, memory and the IntInf, real and string routines
+    importObj["webml-rt"] = instance.exports;
+    for (const [unit, wasm] of units) {{
+        importObj[unit] = (await load(wasm)).instance.exports;
+    }}
+    await load({wasm});
+}}).catch((e) => {{
+    console.error(e);
+    process.exit(2);
+}});
+"#,
+        runtime = Value::from(runtime),
+        wasm = Value::from(wasm),
+        values = Value::from(VALUES_MODULE),
+        units = Value::from(units),
+    )
+}
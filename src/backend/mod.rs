@@ -1,3 +1,5 @@
 pub mod wasm;
 pub use self::wasm::LIR2WASM;
+pub mod js;
 mod pp;
+pub mod wat;
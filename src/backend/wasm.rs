@@ -6,6 +6,9 @@ use std::collections::{HashMap, HashSet};
 use wasm::builder::*;
 use wasm::*;
 
+/// the size of the stack the frames of each module are allocated in
+const STACK_SIZE: u32 = 256 * 1024;
+
 #[derive(Debug, Clone)]
 enum Control<'a> {
     Body(&'a lir::Block),
@@ -54,11 +57,20 @@ fn fun_type(f: &lir::Function) -> FuncType {
     }
 }
 
-pub struct LIR2WASM;
+pub struct LIR2WASM {
+    /// the functions to export by the names
+    exports: Vec<(String, Symbol)>,
+}
 
 impl LIR2WASM {
     pub fn new() -> Self {
-        Self
+        Self {
+            exports: Vec::new(),
+        }
+    }
+
+    pub fn with_exports(self, exports: Vec<(String, Symbol)>) -> Self {
+        Self { exports }
     }
 
     fn generate_pass(&mut self, extern_types: lir::ExternTypes) -> LIR2WASMPass {
@@ -85,7 +97,12 @@ impl LIR2WASM {
             let fun = md.function_index_of(funind).unwrap();
             extern_functions.insert((module, name), fun);
         }
-        LIR2WASMPass::new(md, extern_functions, function_type_table)
+        LIR2WASMPass::new(
+            md,
+            extern_functions,
+            function_type_table,
+            self.exports.clone(),
+        )
     }
 }
 
@@ -93,11 +110,16 @@ struct LIR2WASMPass {
     md: ModuleBuilder,
     init_fun: FunctionSpaceIndex,
     alloc_fun: FunctionSpaceIndex,
+    alloc_stack_fun: FunctionSpaceIndex,
+    /// the top of the stack and its end
+    stack_pointer: GlobalIndex,
+    stack_limit: GlobalIndex,
     extern_functions: HashMap<(String, String), FunctionSpaceIndex>,
     function_table: HashMap<Symbol, u32>,
     function_type_table: HashMap<FuncType, TypeIndex>,
     dynamic_function_table: HashMap<Symbol, u32>,
     dynamic_function_elements: Vec<FunctionSpaceIndex>,
+    exports: Vec<(String, Symbol)>,
 }
 
 impl LIR2WASMPass {
@@ -105,15 +127,18 @@ impl LIR2WASMPass {
         mut md: ModuleBuilder,
         extern_functions: HashMap<(String, String), FunctionSpaceIndex>,
         mut function_type_table: HashMap<FuncType, TypeIndex>,
+        exports: Vec<(String, Symbol)>,
     ) -> Self {
         let init_fun_ty = funtype!(());
         let alloc_fun_ty = funtype!((i32) -> i32);
         let init_fun_ty_index = md.add_type(init_fun_ty.clone());
         let alloc_fun_ty_index = md.add_type(alloc_fun_ty.clone());
-        let init_fun = md.import("webml-rt", "init", init_fun_ty_index);
+        let init_fun = md.import(lir::RUNTIME_MODULE, "init", init_fun_ty_index);
         let init_fun = md.function_index_of(init_fun).unwrap();
-        let alloc_fun = md.import("webml-rt", "alloc", alloc_fun_ty_index);
+        let alloc_fun = md.import(lir::RUNTIME_MODULE, "alloc", alloc_fun_ty_index);
         let alloc_fun = md.function_index_of(alloc_fun).unwrap();
+        let alloc_stack_fun = md.import(lir::RUNTIME_MODULE, "alloc_stack", alloc_fun_ty_index);
+        let alloc_stack_fun = md.function_index_of(alloc_stack_fun).unwrap();
 
         function_type_table.extend(vec![
             (init_fun_ty, init_fun_ty_index),
@@ -121,22 +146,36 @@ impl LIR2WASMPass {
         ]);
 
         md.import(
-            "webml-rt",
+            lir::RUNTIME_MODULE,
             "memory",
             MemoryType {
                 limits: ResizableLimits::new(2),
             },
         );
+        let mut new_pointer = || {
+            md.new_global(
+                GlobalType {
+                    content: ValueType::I32,
+                    mutable: true,
+                },
+                InitExpr(CodeBuilder::new().constant(0).end().build()),
+            )
+        };
+        let (stack_pointer, stack_limit) = (new_pointer(), new_pointer());
 
         Self {
             md,
             init_fun,
             alloc_fun,
+            alloc_stack_fun,
+            stack_pointer,
+            stack_limit,
             extern_functions,
             function_table: HashMap::new(),
             function_type_table,
             dynamic_function_table: HashMap::new(),
             dynamic_function_elements: vec![],
+            exports,
         }
     }
 
@@ -187,12 +226,23 @@ impl LIR2WASMPass {
         let main_function = FunctionBuilder::new(funtype!(()))
             .code(|cb, _params| {
                 cb.call(self.init_fun)
+                    .constant(STACK_SIZE as i32)
+                    .call(self.alloc_stack_fun)
+                    .set_global(self.stack_pointer)
+                    .get_global(self.stack_pointer)
+                    .constant(STACK_SIZE as i32)
+                    .i32_add()
+                    .set_global(self.stack_limit)
                     .call(self.function_index(&Symbol::new("sml-main")))
                     .return_()
             })
             .build();
         let main_function = self.md.new_function(main_function);
         self.md.start(main_function);
+        for (name, fname) in &self.exports {
+            let index = self.function_index(fname);
+            self.md.export(name.as_str(), index);
+        }
 
         let mut ret = ModuleBuilder::new();
         // FIXME:
@@ -218,12 +268,24 @@ impl LIR2WASMPass {
             .iter()
             .map(|reg| lty_to_valuetype(reg))
             .collect::<Vec<_>>();
-        let regtys = tys.split_off(nparams as usize);
+        let mut regtys = tys.split_off(nparams as usize);
         let mut fb = FunctionBuilder::new(ftype.clone());
 
+        let stack_alloc = body
+            .iter()
+            .flat_map(|block| block.body.iter())
+            .any(|op| matches!(op, lir::Op::StackAlloc(..)));
+        if stack_alloc {
+            regtys.push(ValueType::I32);
+        }
         let mut locals = fb.new_locals(regtys);
+        // the stack pointer at the entry, restored when returning
+        let frame = if stack_alloc { locals.pop() } else { None };
 
         let fb = fb.code(|mut cb, params| {
+            if let Some(frame) = frame {
+                cb = cb.get_global(self.stack_pointer).set_local(frame);
+            }
             let body = self.alloc_loop_block_break(&body);
             let mut params = params.to_vec();
             params.append(&mut locals);
@@ -392,6 +454,48 @@ impl LIR2WASMPass {
                                         .i32_le_u()
                                         .set_local(reg!(reg1))
                                 }
+                                AndU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_and()
+                                        .set_local(reg!(reg1))
+                                }
+                                OrU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_or()
+                                        .set_local(reg!(reg1))
+                                }
+                                XorU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_xor()
+                                        .set_local(reg!(reg1))
+                                }
+                                ShlU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_shl()
+                                        .set_local(reg!(reg1))
+                                }
+                                ShrU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_shr_u()
+                                        .set_local(reg!(reg1))
+                                }
+                                SarU32(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i32_shr_s()
+                                        .set_local(reg!(reg1))
+                                }
                                 MoveI32(reg1, reg2)
                                 | MoveU32(reg1, reg2)
                                 | MoveI64(reg1, reg2)
@@ -548,6 +652,48 @@ impl LIR2WASMPass {
                                         .i64_le_u()
                                         .set_local(reg!(reg1))
                                 }
+                                AndU64(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i64_and()
+                                        .set_local(reg!(reg1))
+                                }
+                                OrU64(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i64_or()
+                                        .set_local(reg!(reg1))
+                                }
+                                XorU64(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i64_xor()
+                                        .set_local(reg!(reg1))
+                                }
+                                ShlU64(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i64_shl()
+                                        .set_local(reg!(reg1))
+                                }
+                                ShrU64(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i64_shr_u()
+                                        .set_local(reg!(reg1))
+                                }
+                                SarU64(reg1, reg2, reg3) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg3))
+                                        .i64_shr_s()
+                                        .set_local(reg!(reg1))
+                                }
                                 LoadI64(reg, addr) | LoadU64(reg, addr) => {
                                     cb = cb
                                         .get_local(reg!(addr.0))
@@ -716,6 +862,87 @@ impl LIR2WASMPass {
                                         .f64_le()
                                         .set_local(reg!(reg1))
                                 }
+                                AbsI32(reg1, reg2) => {
+                                    // select(-x, x, x < 0)
+                                    cb = cb
+                                        .constant(0i32)
+                                        .get_local(reg!(reg2))
+                                        .i32_sub()
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg2))
+                                        .constant(0i32)
+                                        .i32_lt_s()
+                                        .select()
+                                        .set_local(reg!(reg1))
+                                }
+                                AbsI64(reg1, reg2) => {
+                                    // select(-x, x, x < 0)
+                                    cb = cb
+                                        .constant(0i64)
+                                        .get_local(reg!(reg2))
+                                        .i64_sub()
+                                        .get_local(reg!(reg2))
+                                        .get_local(reg!(reg2))
+                                        .constant(0i64)
+                                        .i64_lt_s()
+                                        .select()
+                                        .set_local(reg!(reg1))
+                                }
+                                ConvertI64F64(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .f64_convert_s_i64()
+                                        .set_local(reg!(reg1))
+                                }
+                                TruncF64I64(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .i64_trunc_s_f64()
+                                        .set_local(reg!(reg1))
+                                }
+                                ExtendI32I64(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .i64_extend_s_i32()
+                                        .set_local(reg!(reg1))
+                                }
+                                WrapI64I32(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .i32_wrap_i64()
+                                        .set_local(reg!(reg1))
+                                }
+                                NegF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_neg().set_local(reg!(reg1))
+                                }
+                                AbsF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_abs().set_local(reg!(reg1))
+                                }
+                                SqrtF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_sqrt().set_local(reg!(reg1))
+                                }
+                                FloorF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_floor().set_local(reg!(reg1))
+                                }
+                                CeilF64(reg1, reg2) => {
+                                    cb = cb.get_local(reg!(reg2)).f64_ceil().set_local(reg!(reg1))
+                                }
+                                NearestF64(reg1, reg2) => {
+                                    cb =
+                                        cb.get_local(reg!(reg2)).f64_nearest().set_local(reg!(reg1))
+                                }
+                                ConvertI32F64(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .f64_convert_s_i32()
+                                        .set_local(reg!(reg1))
+                                }
+                                TruncF64I32(reg1, reg2) => {
+                                    cb = cb
+                                        .get_local(reg!(reg2))
+                                        .i32_trunc_s_f64()
+                                        .set_local(reg!(reg1))
+                                }
                                 StoreF64(addr, value) => {
                                     cb = cb
                                         .get_local(reg!(addr.0))
@@ -741,12 +968,26 @@ impl LIR2WASMPass {
                                         .set_local(reg!(reg))
                                 }
                                 StackAlloc(reg, size, _tys) => {
-                                    // allocating to heap, not stack
+                                    // keeps the stack aligned to 8
+                                    let size = ((size + 7) & !7) as i32;
                                     cb = cb
-                                        .constant(*size as i32)
-                                        //.constant(tys_to_ptrbits(tys) as i32)
+                                        .get_global(self.stack_pointer)
+                                        .set_local(reg!(reg))
+                                        .get_global(self.stack_pointer)
+                                        .constant(size)
+                                        .i32_add()
+                                        .set_global(self.stack_pointer)
+                                        // to the heap when the stack is exhausted
+                                        .get_global(self.stack_pointer)
+                                        .get_global(self.stack_limit)
+                                        .i32_gt_u()
+                                        .if_(BlockType(None))
+                                        .get_local(reg!(reg))
+                                        .set_global(self.stack_pointer)
+                                        .constant(size)
                                         .call(self.alloc_fun)
                                         .set_local(reg!(reg))
+                                        .end();
                                 }
                                 StoreFnPtr(addr, value) => {
                                     cb = cb
@@ -816,10 +1057,25 @@ impl LIR2WASMPass {
                                 Jump(label) => {
                                     cb = cb.br(label!(&label));
                                 }
+                                RaiseIfI32(reg, exn) => {
+                                    let (module, fun) = lir::RAISE_FUNCTION;
+                                    let raise = self.extern_functions
+                                        [&(module.to_string(), fun.to_string())];
+                                    cb = cb
+                                        .get_local(reg!(reg))
+                                        .if_(BlockType(None))
+                                        .constant(*exn as i32)
+                                        .call(raise)
+                                        .unreachable()
+                                        .end();
+                                }
                                 Unreachable => {
                                     cb = cb.unreachable();
                                 }
                                 Ret(reg) => {
+                                    if let Some(frame) = frame {
+                                        cb = cb.get_local(frame).set_global(self.stack_pointer);
+                                    }
                                     cb = match reg {
                                         Some(r) => cb.get_local(reg!(r)),
                                         None => cb,
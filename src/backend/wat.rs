@@ -0,0 +1,654 @@
+//! prints a binary module in the text format, to read what the backend generated
+use std::fmt::{self, Write};
+
+/// the bytes are not a wasm module of the MVP. `offset` is the byte where it is found
+#[derive(Debug, Clone, PartialEq)]
+pub struct MalformedModule {
+    pub offset: usize,
+}
+
+impl fmt::Display for MalformedModule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed wasm module at byte {}", self.offset)
+    }
+}
+
+impl std::error::Error for MalformedModule {}
+
+type Result<T> = ::std::result::Result<T, MalformedModule>;
+
+/// the instructions without immediates, from `i32.eqz` (0x45) to `i64.extend32_s` (0xc4)
+static NUMERIC: &[&str] = &[
+    "i32.eqz",
+    "i32.eq",
+    "i32.ne",
+    "i32.lt_s",
+    "i32.lt_u",
+    "i32.gt_s",
+    "i32.gt_u",
+    "i32.le_s",
+    "i32.le_u",
+    "i32.ge_s",
+    "i32.ge_u",
+    "i64.eqz",
+    "i64.eq",
+    "i64.ne",
+    "i64.lt_s",
+    "i64.lt_u",
+    "i64.gt_s",
+    "i64.gt_u",
+    "i64.le_s",
+    "i64.le_u",
+    "i64.ge_s",
+    "i64.ge_u",
+    "f32.eq",
+    "f32.ne",
+    "f32.lt",
+    "f32.gt",
+    "f32.le",
+    "f32.ge",
+    "f64.eq",
+    "f64.ne",
+    "f64.lt",
+    "f64.gt",
+    "f64.le",
+    "f64.ge",
+    "i32.clz",
+    "i32.ctz",
+    "i32.popcnt",
+    "i32.add",
+    "i32.sub",
+    "i32.mul",
+    "i32.div_s",
+    "i32.div_u",
+    "i32.rem_s",
+    "i32.rem_u",
+    "i32.and",
+    "i32.or",
+    "i32.xor",
+    "i32.shl",
+    "i32.shr_s",
+    "i32.shr_u",
+    "i32.rotl",
+    "i32.rotr",
+    "i64.clz",
+    "i64.ctz",
+    "i64.popcnt",
+    "i64.add",
+    "i64.sub",
+    "i64.mul",
+    "i64.div_s",
+    "i64.div_u",
+    "i64.rem_s",
+    "i64.rem_u",
+    "i64.and",
+    "i64.or",
+    "i64.xor",
+    "i64.shl",
+    "i64.shr_s",
+    "i64.shr_u",
+    "i64.rotl",
+    "i64.rotr",
+    "f32.abs",
+    "f32.neg",
+    "f32.ceil",
+    "f32.floor",
+    "f32.trunc",
+    "f32.nearest",
+    "f32.sqrt",
+    "f32.add",
+    "f32.sub",
+    "f32.mul",
+    "f32.div",
+    "f32.min",
+    "f32.max",
+    "f32.copysign",
+    "f64.abs",
+    "f64.neg",
+    "f64.ceil",
+    "f64.floor",
+    "f64.trunc",
+    "f64.nearest",
+    "f64.sqrt",
+    "f64.add",
+    "f64.sub",
+    "f64.mul",
+    "f64.div",
+    "f64.min",
+    "f64.max",
+    "f64.copysign",
+    "i32.wrap_i64",
+    "i32.trunc_f32_s",
+    "i32.trunc_f32_u",
+    "i32.trunc_f64_s",
+    "i32.trunc_f64_u",
+    "i64.extend_i32_s",
+    "i64.extend_i32_u",
+    "i64.trunc_f32_s",
+    "i64.trunc_f32_u",
+    "i64.trunc_f64_s",
+    "i64.trunc_f64_u",
+    "f32.convert_i32_s",
+    "f32.convert_i32_u",
+    "f32.convert_i64_s",
+    "f32.convert_i64_u",
+    "f32.demote_f64",
+    "f64.convert_i32_s",
+    "f64.convert_i32_u",
+    "f64.convert_i64_s",
+    "f64.convert_i64_u",
+    "f64.promote_f32",
+    "i32.reinterpret_f32",
+    "i64.reinterpret_f64",
+    "f32.reinterpret_i32",
+    "f64.reinterpret_i64",
+    "i32.extend8_s",
+    "i32.extend16_s",
+    "i64.extend8_s",
+    "i64.extend16_s",
+    "i64.extend32_s",
+];
+
+/// the memory instructions, from `i32.load` (0x28) to `i64.store32` (0x3e)
+static MEMORY: &[&str] = &[
+    "i32.load",
+    "i64.load",
+    "f32.load",
+    "f64.load",
+    "i32.load8_s",
+    "i32.load8_u",
+    "i32.load16_s",
+    "i32.load16_u",
+    "i64.load8_s",
+    "i64.load8_u",
+    "i64.load16_s",
+    "i64.load16_u",
+    "i64.load32_s",
+    "i64.load32_u",
+    "i32.store",
+    "i64.store",
+    "f32.store",
+    "f64.store",
+    "i32.store8",
+    "i32.store16",
+    "i64.store8",
+    "i64.store16",
+    "i64.store32",
+];
+
+pub fn to_wat(wasm: &[u8]) -> Result<String> {
+    let mut reader = Reader {
+        bytes: wasm,
+        pos: 0,
+    };
+    let mut printer = Printer::default();
+    if reader.take(8)? != b"\0asm\x01\0\0\0" {
+        return Err(MalformedModule { offset: 0 });
+    }
+    while !reader.at_end() {
+        let id = reader.byte()?;
+        let size = reader.u32()? as usize;
+        let start = reader.pos;
+        let mut section = Reader {
+            bytes: &wasm[..start + size.min(wasm.len() - start)],
+            pos: start,
+        };
+        printer.section(id, &mut section)?;
+        if !section.at_end() || start + size > wasm.len() {
+            return Err(MalformedModule {
+                offset: section.pos,
+            });
+        }
+        reader.pos = start + size;
+    }
+    Ok(printer.finish())
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn error(&self) -> MalformedModule {
+        MalformedModule { offset: self.pos }
+    }
+
+    fn at_end(&self) -> bool {
+        self.pos == self.bytes.len()
+    }
+
+    fn take(&mut self, n: usize) -> Result<&'a [u8]> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + n)
+            .ok_or_else(|| self.error())?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn byte(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    // LEB128 of `bits` bits
+    fn leb(&mut self, bits: u32, signed: bool) -> Result<i128> {
+        let mut result = 0i128;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            result |= i128::from(byte & 0x7f) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                if signed && shift < 128 && byte & 0x40 != 0 {
+                    result |= -1i128 << shift;
+                }
+                return Ok(result);
+            }
+            if shift >= bits + 7 {
+                return Err(self.error());
+            }
+        }
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(self.leb(32, false)? as u32)
+    }
+
+    fn i32(&mut self) -> Result<i32> {
+        Ok(self.leb(32, true)? as i32)
+    }
+
+    fn i64(&mut self) -> Result<i64> {
+        Ok(self.leb(64, true)? as i64)
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let len = self.u32()? as usize;
+        let pos = self.pos;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| MalformedModule { offset: pos })
+    }
+
+    fn value_type(&mut self) -> Result<&'static str> {
+        match self.byte()? {
+            0x7f => Ok("i32"),
+            0x7e => Ok("i64"),
+            0x7d => Ok("f32"),
+            0x7c => Ok("f64"),
+            _ => Err(MalformedModule {
+                offset: self.pos - 1,
+            }),
+        }
+    }
+
+    fn limits(&mut self) -> Result<String> {
+        match self.byte()? {
+            0 => Ok(self.u32()?.to_string()),
+            1 => Ok(format!("{} {}", self.u32()?, self.u32()?)),
+            _ => Err(self.error()),
+        }
+    }
+
+    fn table_type(&mut self) -> Result<String> {
+        if self.byte()? != 0x70 {
+            return Err(self.error());
+        }
+        Ok(format!("{} funcref", self.limits()?))
+    }
+
+    fn global_type(&mut self) -> Result<String> {
+        let ty = self.value_type()?;
+        match self.byte()? {
+            0 => Ok(ty.to_string()),
+            1 => Ok(format!("(mut {})", ty)),
+            _ => Err(self.error()),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Printer {
+    out: String,
+    /// the signatures of the types, like `(param i32) (result i32)`
+    types: Vec<String>,
+    /// the types of the functions, imported ones first
+    functions: Vec<u32>,
+    imported_functions: u32,
+    /// the bodies printed so far
+    bodies: u32,
+    tables: u32,
+    memories: u32,
+    globals: u32,
+}
+
+impl Printer {
+    fn finish(self) -> String {
+        format!("(module\n{})\n", self.out)
+    }
+
+    fn section(&mut self, id: u8, r: &mut Reader) -> Result<()> {
+        if id == 0 {
+            // custom sections carry nothing to execute
+            r.pos = r.bytes.len();
+            return Ok(());
+        }
+        // the start section has no vector
+        if id == 8 {
+            let fun = r.u32()?;
+            writeln!(self.out, "  (start {})", fun).unwrap();
+            return Ok(());
+        }
+        let count = r.u32()?;
+        for _ in 0..count {
+            match id {
+                1 => self.type_entry(r)?,
+                2 => self.import(r)?,
+                3 => self.functions.push(r.u32()?),
+                4 => {
+                    let ty = r.table_type()?;
+                    writeln!(self.out, "  (table (;{};) {})", self.tables, ty).unwrap();
+                    self.tables += 1;
+                }
+                5 => {
+                    let limits = r.limits()?;
+                    writeln!(self.out, "  (memory (;{};) {})", self.memories, limits).unwrap();
+                    self.memories += 1;
+                }
+                6 => {
+                    let ty = r.global_type()?;
+                    let init = self.const_expr(r)?;
+                    writeln!(self.out, "  (global (;{};) {} {})", self.globals, ty, init).unwrap();
+                    self.globals += 1;
+                }
+                7 => self.export(r)?,
+                9 => {
+                    let table = r.u32()?;
+                    let offset = self.const_expr(r)?;
+                    let n = r.u32()?;
+                    let funs = (0..n)
+                        .map(|_| r.u32().map(|f| f.to_string()))
+                        .collect::<Result<Vec<_>>>()?;
+                    writeln!(
+                        self.out,
+                        "  (elem (table {}) {} func {})",
+                        table,
+                        offset,
+                        funs.join(" ")
+                    )
+                    .unwrap();
+                }
+                10 => self.code(r)?,
+                11 => {
+                    let memory = r.u32()?;
+                    let offset = self.const_expr(r)?;
+                    let len = r.u32()? as usize;
+                    let data = r.take(len)?;
+                    writeln!(
+                        self.out,
+                        "  (data (memory {}) {} \"{}\")",
+                        memory,
+                        offset,
+                        escape(data)
+                    )
+                    .unwrap();
+                }
+                _ => return Err(MalformedModule { offset: r.pos - 1 }),
+            }
+        }
+        Ok(())
+    }
+
+    fn type_entry(&mut self, r: &mut Reader) -> Result<()> {
+        if r.byte()? != 0x60 {
+            return Err(r.error());
+        }
+        let params = (0..r.u32()?)
+            .map(|_| r.value_type())
+            .collect::<Result<Vec<_>>>()?;
+        let results = (0..r.u32()?)
+            .map(|_| r.value_type())
+            .collect::<Result<Vec<_>>>()?;
+        let mut signature = String::new();
+        if !params.is_empty() {
+            write!(signature, " (param {})", params.join(" ")).unwrap();
+        }
+        if !results.is_empty() {
+            write!(signature, " (result {})", results.join(" ")).unwrap();
+        }
+        writeln!(
+            self.out,
+            "  (type (;{};) (func{}))",
+            self.types.len(),
+            signature
+        )
+        .unwrap();
+        self.types.push(signature);
+        Ok(())
+    }
+
+    fn import(&mut self, r: &mut Reader) -> Result<()> {
+        let module = r.name()?;
+        let name = r.name()?;
+        let desc = match r.byte()? {
+            0 => {
+                let ty = r.u32()?;
+                self.functions.push(ty);
+                self.imported_functions += 1;
+                format!("(func (;{};) (type {}))", self.functions.len() - 1, ty)
+            }
+            1 => {
+                self.tables += 1;
+                format!("(table (;{};) {})", self.tables - 1, r.table_type()?)
+            }
+            2 => {
+                self.memories += 1;
+                format!("(memory (;{};) {})", self.memories - 1, r.limits()?)
+            }
+            3 => {
+                self.globals += 1;
+                format!("(global (;{};) {})", self.globals - 1, r.global_type()?)
+            }
+            _ => return Err(MalformedModule { offset: r.pos - 1 }),
+        };
+        writeln!(self.out, "  (import {:?} {:?} {})", module, name, desc).unwrap();
+        Ok(())
+    }
+
+    fn export(&mut self, r: &mut Reader) -> Result<()> {
+        let name = r.name()?;
+        let kind = match r.byte()? {
+            0 => "func",
+            1 => "table",
+            2 => "memory",
+            3 => "global",
+            _ => return Err(MalformedModule { offset: r.pos - 1 }),
+        };
+        writeln!(self.out, "  (export {:?} ({} {}))", name, kind, r.u32()?).unwrap();
+        Ok(())
+    }
+
+    fn code(&mut self, r: &mut Reader) -> Result<()> {
+        let index = (self.imported_functions + self.bodies) as usize;
+        let ty = *self.functions.get(index).ok_or_else(|| r.error())?;
+        let signature = self.types.get(ty as usize).ok_or_else(|| r.error())?;
+        writeln!(self.out, "  (func (;{};) (type {}){}", index, ty, signature).unwrap();
+        let size = r.u32()? as usize;
+        let end = r.pos + size;
+        let mut locals = Vec::new();
+        for _ in 0..r.u32()? {
+            let n = r.u32()?;
+            let ty = r.value_type()?;
+            locals.extend((0..n).map(|_| ty));
+        }
+        if !locals.is_empty() {
+            writeln!(self.out, "    (local {})", locals.join(" ")).unwrap();
+        }
+        let mut depth = 0;
+        loop {
+            let op = r.byte()?;
+            match op {
+                // the end of the body
+                0x0b if depth == 0 => break,
+                0x0b => depth -= 1,
+                0x05 => depth -= 1,
+                _ => (),
+            }
+            let instr = self.instr(op, r)?;
+            writeln!(self.out, "    {}{}", "  ".repeat(depth), instr).unwrap();
+            if let 0x02..=0x05 = op {
+                depth += 1
+            }
+        }
+        self.out.push_str("  )\n");
+        self.bodies += 1;
+        if r.pos != end {
+            return Err(r.error());
+        }
+        Ok(())
+    }
+
+    /// an initializer expression like `(i32.const 0)`
+    fn const_expr(&mut self, r: &mut Reader) -> Result<String> {
+        let op = r.byte()?;
+        let instr = self.instr(op, r)?;
+        if r.byte()? != 0x0b {
+            return Err(r.error());
+        }
+        Ok(format!("({})", instr))
+    }
+
+    fn instr(&mut self, op: u8, r: &mut Reader) -> Result<String> {
+        let block_type = |r: &mut Reader| -> Result<String> {
+            match r.bytes.get(r.pos) {
+                Some(0x40) => {
+                    r.pos += 1;
+                    Ok(String::new())
+                }
+                _ => Ok(format!(" (result {})", r.value_type()?)),
+            }
+        };
+        let instr = match op {
+            0x00 => "unreachable".to_string(),
+            0x01 => "nop".to_string(),
+            0x02 => format!("block{}", block_type(r)?),
+            0x03 => format!("loop{}", block_type(r)?),
+            0x04 => format!("if{}", block_type(r)?),
+            0x05 => "else".to_string(),
+            0x0b => "end".to_string(),
+            0x0c => format!("br {}", r.u32()?),
+            0x0d => format!("br_if {}", r.u32()?),
+            0x0e => {
+                let labels = (0..=r.u32()?)
+                    .map(|_| r.u32().map(|l| l.to_string()))
+                    .collect::<Result<Vec<_>>>()?;
+                format!("br_table {}", labels.join(" "))
+            }
+            0x0f => "return".to_string(),
+            0x10 => format!("call {}", r.u32()?),
+            0x11 => {
+                let ty = r.u32()?;
+                let table = r.byte()?;
+                format!("call_indirect {} (type {})", table, ty)
+            }
+            0x1a => "drop".to_string(),
+            0x1b => "select".to_string(),
+            0x20 => format!("local.get {}", r.u32()?),
+            0x21 => format!("local.set {}", r.u32()?),
+            0x22 => format!("local.tee {}", r.u32()?),
+            0x23 => format!("global.get {}", r.u32()?),
+            0x24 => format!("global.set {}", r.u32()?),
+            0x28..=0x3e => {
+                let align = r.u32()?;
+                let offset = r.u32()?;
+                let mut instr = MEMORY[(op - 0x28) as usize].to_string();
+                if offset != 0 {
+                    write!(instr, " offset={}", offset).unwrap();
+                }
+                write!(instr, " align={}", 1u64 << align.min(63)).unwrap();
+                instr
+            }
+            0x3f | 0x40 => {
+                r.byte()?;
+                if op == 0x3f {
+                    "memory.size".to_string()
+                } else {
+                    "memory.grow".to_string()
+                }
+            }
+            0x41 => format!("i32.const {}", r.i32()?),
+            0x42 => format!("i64.const {}", r.i64()?),
+            0x43 => {
+                let bytes = r.take(4)?;
+                let f = f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                format!("f32.const {:?}", f)
+            }
+            0x44 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(r.take(8)?);
+                format!("f64.const {:?}", f64::from_le_bytes(bytes))
+            }
+            0x45..=0xc4 => NUMERIC[(op - 0x45) as usize].to_string(),
+            _ => return Err(MalformedModule { offset: r.pos - 1 }),
+        };
+        Ok(instr)
+    }
+}
+
+/// the bytes in a string of the text format
+fn escape(bytes: &[u8]) -> String {
+    let mut s = String::new();
+    for &b in bytes {
+        match b {
+            b'"' | b'\\' => write!(s, "\\{}", b as char).unwrap(),
+            0x20..=0x7e => s.push(b as char),
+            _ => write!(s, "\\{:02x}", b).unwrap(),
+        }
+    }
+    s
+}
+
+#[test]
+fn test_to_wat() {
+    #[rustfmt::skip]
+    let module = [
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00,
+        // type: (i32) -> i32, () -> ()
+        0x01, 0x09, 0x02, 0x60, 0x01, 0x7f, 0x01, 0x7f, 0x60, 0x00, 0x00,
+        // import js-ffi.print: type 0
+        0x02, 0x10, 0x01, 0x06, b'j', b's', b'-', b'f', b'f', b'i', 0x05, b'p', b'r', b'i', b'n', b't', 0x00, 0x00,
+        // function: type 0
+        0x03, 0x02, 0x01, 0x00,
+        // memory 1
+        0x05, 0x03, 0x01, 0x00, 0x01,
+        // export main: func 1
+        0x07, 0x08, 0x01, 0x04, b'm', b'a', b'i', b'n', 0x00, 0x01,
+        // code: local i64; if (result i32) local.get 0 else i32.const -1 end
+        0x0a, 0x12, 0x01, 0x10, 0x01, 0x01, 0x7e,
+        0x20, 0x00, 0x04, 0x7f, 0x20, 0x00, 0x05, 0x41, 0x7f, 0x0b, 0x10, 0x00, 0x0b,
+        // data: "a\n" at 8
+        0x0b, 0x08, 0x01, 0x00, 0x41, 0x08, 0x0b, 0x02, b'a', b'\n',
+    ];
+    assert_eq!(
+        to_wat(&module).unwrap(),
+        r#"(module
+  (type (;0;) (func (param i32) (result i32)))
+  (type (;1;) (func))
+  (import "js-ffi" "print" (func (;0;) (type 0)))
+  (memory (;0;) 1)
+  (export "main" (func 1))
+  (func (;1;) (type 0) (param i32) (result i32)
+    (local i64)
+    local.get 0
+    if (result i32)
+      local.get 0
+    else
+      i32.const -1
+    end
+    call 0
+  )
+  (data (memory 0) (i32.const 8) "a\0a")
+)
+"#
+    );
+    assert_eq!(to_wat(&module[..20]), Err(MalformedModule { offset: 20 }));
+}
@@ -0,0 +1,420 @@
+//! runs the passes up to typing over a document and indexes the names in it
+use std::collections::HashMap;
+use std::panic::{self, AssertUnwindSafe};
+use webml::ast::*;
+use webml::id::Id;
+use webml::prim::{Span, Symbol};
+use webml::{parse, Config, Diagnostic, Label, Pass, Severity, PRELUDE};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SymbolKind {
+    Function,
+    Variable,
+    Datatype,
+    Constructor,
+}
+
+/// a name declared at the top level and the names declared with it
+pub struct DocumentSymbol {
+    pub name: String,
+    pub kind: SymbolKind,
+    pub span: Span,
+    pub children: Vec<DocumentSymbol>,
+}
+
+/// a name written in the document, after renamed
+struct Occurrence {
+    span: Span,
+    symbol: Symbol,
+    ty: Option<Type>,
+    /// whether the name is bound here
+    binding: bool,
+}
+
+/// what is known of a version of a document. spans are in the bytes of the document
+pub struct Analysis {
+    pub text: String,
+    pub diagnostics: Vec<Diagnostic>,
+    pub symbols: Vec<DocumentSymbol>,
+    occurrences: Vec<Occurrence>,
+}
+
+impl Analysis {
+    pub fn new(text: String) -> Self {
+        let mut analysis = Analysis {
+            text,
+            diagnostics: Vec::new(),
+            symbols: Vec::new(),
+            occurrences: Vec::new(),
+        };
+        let input = format!("{}{}", PRELUDE, analysis.text);
+        // a bug of a pass must not bring the server down
+        if let Err(e) = panic::catch_unwind(AssertUnwindSafe(|| analysis.run(&input))) {
+            let message = e
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| e.downcast_ref::<String>().cloned())
+                .unwrap_or_default();
+            analysis.diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                code: "internal-error",
+                message: format!("internal compiler error: {}", message),
+                span: None,
+                labels: Vec::new(),
+            });
+        }
+        analysis
+    }
+
+    fn run(&mut self, input: &str) {
+        let config = Config::default();
+        let id = Id::new();
+        let ast = match parse(input) {
+            Ok(ast) => ast,
+            Err(errors) => return self.error(input, errors.into()),
+        };
+        self.symbols = top_symbols(&ast.0)
+            .into_iter()
+            .filter_map(|symbol| self.local_symbol(symbol))
+            .collect();
+
+        let (symbol_table, ast) = match resolve(ast, &id, &config) {
+            Ok(ast) => ast,
+            Err(e) => return self.error(input, e),
+        };
+        // index the untyped names first so that the definitions are found even if typing fails
+        let mut indexer = Indexer::new(HashMap::new());
+        indexer.decls(&ast.0);
+        self.set_occurrences(indexer.occurrences);
+
+        let typed = match Typer::new().trans((symbol_table, ast), &config) {
+            Ok(typed) => typed,
+            Err(e) => return self.error(input, e),
+        };
+        let mut indexer = Indexer::new(constructor_types(&typed.0));
+        indexer.decls(&(typed.1).0);
+        self.set_occurrences(indexer.occurrences);
+
+        // the match warnings are found on the way to the simple cases
+        let mut case_simplify = CaseSimplify::new(id);
+        let result = case_simplify.trans(typed, &config);
+        for warning in case_simplify.take_warnings() {
+            self.report(Diagnostic::from_warning(&warning))
+        }
+        if let Err(e) = result {
+            self.error(input, e)
+        }
+    }
+
+    /// the span in the document of a span of the input with the prelude
+    fn local_span(&self, span: Span) -> Option<Span> {
+        Some(Span {
+            start: span.start.checked_sub(PRELUDE.len())?,
+            end: span.end - PRELUDE.len(),
+        })
+    }
+
+    fn local_symbol(&self, symbol: DocumentSymbol) -> Option<DocumentSymbol> {
+        Some(DocumentSymbol {
+            span: self.local_span(symbol.span)?,
+            children: symbol
+                .children
+                .into_iter()
+                .filter_map(|child| self.local_symbol(child))
+                .collect(),
+            ..symbol
+        })
+    }
+
+    fn set_occurrences(&mut self, occurrences: Vec<Occurrence>) {
+        self.occurrences = occurrences
+            .into_iter()
+            .filter_map(|o| {
+                Some(Occurrence {
+                    span: self.local_span(o.span)?,
+                    ..o
+                })
+            })
+            .collect();
+    }
+
+    fn error(&mut self, input: &str, error: TypeError) {
+        for diagnostic in Diagnostic::from_error(input, &error) {
+            self.report(diagnostic)
+        }
+    }
+
+    /// the diagnostics without spans in the document are reported at the beginning
+    fn report(&mut self, diagnostic: Diagnostic) {
+        let diagnostic = Diagnostic {
+            span: diagnostic.span.and_then(|span| self.local_span(span)),
+            labels: diagnostic
+                .labels
+                .into_iter()
+                .filter_map(|label| {
+                    Some(Label {
+                        span: self.local_span(label.span)?,
+                        ..label
+                    })
+                })
+                .collect(),
+            ..diagnostic
+        };
+        self.diagnostics.push(diagnostic)
+    }
+
+    fn occurrence_at(&self, offset: usize) -> Option<&Occurrence> {
+        self.occurrences
+            .iter()
+            .find(|o| o.span.contains(offset) || o.span.end == offset)
+    }
+
+    /// the name at `offset` and its type in the source syntax
+    pub fn hover(&self, offset: usize) -> Option<(Span, String)> {
+        let occurrence = self.occurrence_at(offset)?;
+        let name = &occurrence.symbol.0;
+        let text = match &occurrence.ty {
+            Some(ty) => format!("{} : {}", name, Printer::new().ty(ty)),
+            None => name.clone(),
+        };
+        Some((occurrence.span, text))
+    }
+
+    /// where the name at `offset` is bound. the names bound in the prelude have no definitions
+    pub fn definition(&self, offset: usize) -> Option<Span> {
+        let symbol = &self.occurrence_at(offset)?.symbol;
+        self.occurrences
+            .iter()
+            .find(|o| o.binding && o.symbol == *symbol)
+            .map(|o| o.span)
+    }
+}
+
+/// the passes before typing
+fn resolve<'a>(ast: UntypedAst, id: &Id, config: &Config) -> Result<'a, (SymbolTable, UntypedCore)> {
+    let ast = Pass::<_, TypeError>::trans(&mut Desugar::new(id.clone()), ast, config)?;
+    let ast = Rename::new(id.clone()).trans(ast, config)?;
+    VarToConstructor::new(id.clone()).trans(ast, config)
+}
+
+fn constructor_types(symbol_table: &SymbolTable) -> HashMap<Symbol, Type> {
+    let mut types = HashMap::new();
+    for (name, info) in &symbol_table.types {
+        for (constructor, arg) in &info.constructors {
+            let datatype = Type::Datatype(name.clone());
+            let ty = match arg {
+                Some(arg) => Type::Fun(Box::new(arg.clone()), Box::new(datatype)),
+                None => datatype,
+            };
+            types.insert(constructor.clone(), ty);
+        }
+    }
+    types
+}
+
+/// the types of the nodes, if inferred
+trait Annotation {
+    fn ty(&self) -> Option<Type>;
+}
+
+impl Annotation for () {
+    fn ty(&self) -> Option<Type> {
+        None
+    }
+}
+
+impl Annotation for Type {
+    fn ty(&self) -> Option<Type> {
+        Some(self.clone())
+    }
+}
+
+/// collects the names written in the source, i.e. the ones with spans
+struct Indexer {
+    constructors: HashMap<Symbol, Type>,
+    occurrences: Vec<Occurrence>,
+}
+
+impl Indexer {
+    fn new(constructors: HashMap<Symbol, Type>) -> Self {
+        Indexer {
+            constructors,
+            occurrences: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, symbol: &Symbol, ty: Option<Type>, binding: bool) {
+        if let Some(span) = symbol.2 {
+            self.occurrences.push(Occurrence {
+                span,
+                symbol: symbol.clone(),
+                ty,
+                binding,
+            })
+        }
+    }
+
+    fn add_constructor(&mut self, symbol: &Symbol, binding: bool) {
+        let ty = self.constructors.get(symbol).cloned();
+        self.add(symbol, ty, binding)
+    }
+
+    fn decls<Ty: Annotation>(&mut self, decls: &[CoreDeclaration<Ty>]) {
+        for decl in decls {
+            self.decl(decl)
+        }
+    }
+
+    fn decl<Ty: Annotation>(&mut self, decl: &CoreDeclaration<Ty>) {
+        use Declaration::*;
+        match decl {
+            Datatype { constructors, .. } => {
+                for (name, _) in constructors {
+                    self.add_constructor(name, true)
+                }
+            }
+            Type { .. } | D(_) => (),
+            Val { pattern, expr, .. } => {
+                self.pattern(pattern);
+                self.expr(expr)
+            }
+            Abstype { datatype, decls } => {
+                self.decl(datatype);
+                self.decls(decls)
+            }
+            Local { binds, body } => {
+                self.decls(binds);
+                self.decls(body)
+            }
+            And(decls) => self.decls(decls),
+        }
+    }
+
+    fn expr<Ty: Annotation>(&mut self, expr: &CoreExpr<Ty>) {
+        use ExprKind::*;
+        match &expr.inner {
+            Binds { binds, ret } => {
+                self.decls(binds);
+                self.expr(ret)
+            }
+            BuiltinCall { args, .. } | ExternCall { args, .. } => {
+                for arg in args {
+                    self.expr(arg)
+                }
+            }
+            Fn { param, body } => {
+                let param_ty = match expr.ty.ty() {
+                    Some(Type::Fun(param_ty, _)) => Some(*param_ty),
+                    _ => None,
+                };
+                self.add(param, param_ty, true);
+                self.expr(body)
+            }
+            App { fun, arg } => {
+                self.expr(fun);
+                self.expr(arg)
+            }
+            Case { cond, clauses } => {
+                self.expr(cond);
+                for (pattern, expr) in clauses {
+                    self.pattern(pattern);
+                    self.expr(expr)
+                }
+            }
+            Tuple { tuple } => {
+                for e in tuple {
+                    self.expr(e)
+                }
+            }
+            Constructor { arg, name } => {
+                self.add_constructor(name, false);
+                if let Some(arg) = arg {
+                    self.expr(arg)
+                }
+            }
+            Symbol { name } => self.add(name, expr.ty.ty(), false),
+            Literal { .. } => (),
+            D(d) => match *d {},
+        }
+    }
+
+    fn pattern<Ty: Annotation>(&mut self, pattern: &Pattern<Ty>) {
+        use PatternKind::*;
+        match &pattern.inner {
+            Constructor { name, arg } => {
+                self.add_constructor(name, false);
+                if let Some(arg) = arg {
+                    self.pattern(arg)
+                }
+            }
+            Tuple { tuple } => {
+                for p in tuple {
+                    self.pattern(p)
+                }
+            }
+            Variable { name } => self.add(name, pattern.ty.ty(), true),
+            Constant { .. } | Word { .. } | Char { .. } | Real { .. } | Wildcard {} => (),
+        }
+    }
+}
+
+/// the names the top-level declarations bind, in the order they are written
+fn top_symbols(decls: &[UntypedDeclaration]) -> Vec<DocumentSymbol> {
+    let mut symbols = Vec::new();
+    for decl in decls {
+        match decl {
+            Declaration::Datatype { name, constructors } => {
+                let children = constructors
+                    .iter()
+                    .filter_map(|(name, _)| document_symbol(name, SymbolKind::Constructor))
+                    .collect();
+                if let Some(symbol) = document_symbol(name, SymbolKind::Datatype) {
+                    symbols.push(DocumentSymbol { children, ..symbol })
+                }
+            }
+            Declaration::Val { pattern, expr, .. } => {
+                let kind = match expr.inner {
+                    ExprKind::Fn { .. } => SymbolKind::Function,
+                    _ => SymbolKind::Variable,
+                };
+                let mut names = Vec::new();
+                pattern_variables(pattern, &mut names);
+                symbols.extend(names.iter().filter_map(|name| document_symbol(name, kind)))
+            }
+            Declaration::D(DerivedDeclaration::Fun { name, .. }) => {
+                symbols.extend(document_symbol(name, SymbolKind::Function))
+            }
+            Declaration::Local { body: decls, .. } | Declaration::And(decls) => {
+                symbols.extend(top_symbols(decls))
+            }
+            Declaration::Abstype { datatype, decls } => {
+                symbols.extend(top_symbols(std::slice::from_ref(datatype)));
+                symbols.extend(top_symbols(decls))
+            }
+            Declaration::Type { .. } | Declaration::D(DerivedDeclaration::Infix { .. }) => (),
+        }
+    }
+    symbols
+}
+
+fn document_symbol(name: &Symbol, kind: SymbolKind) -> Option<DocumentSymbol> {
+    Some(DocumentSymbol {
+        name: name.0.clone(),
+        kind,
+        span: name.2?,
+        children: Vec::new(),
+    })
+}
+
+fn pattern_variables<'a>(pattern: &'a UntypedPattern, names: &mut Vec<&'a Symbol>) {
+    match &pattern.inner {
+        PatternKind::Variable { name } => names.push(name),
+        PatternKind::Tuple { tuple } => {
+            for p in tuple {
+                pattern_variables(p, names)
+            }
+        }
+        PatternKind::Constructor { arg: Some(arg), .. } => pattern_variables(arg, names),
+        _ => (),
+    }
+}
@@ -0,0 +1,282 @@
+//! a language server of WebML, talking the Language Server Protocol over the stdio
+mod analysis;
+
+use crate::analysis::{Analysis, DocumentSymbol, SymbolKind};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::io::{self, prelude::*};
+use std::process;
+use webml::prim::Span;
+use webml::Severity;
+
+// https://microsoft.github.io/language-server-protocol/specifications/specification-current/
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const TEXT_DOCUMENT_SYNC_FULL: usize = 1;
+const SEVERITY_ERROR: usize = 1;
+const SEVERITY_WARNING: usize = 2;
+
+fn invalid_data(e: impl Into<Box<dyn std::error::Error + Send + Sync>>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+/// reads a message framed by the `Content-Length` header. `None` at the end of the input
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().map_err(invalid_data)?);
+        }
+    }
+    let length = length.ok_or_else(|| invalid_data("a message without Content-Length"))?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    let body = String::from_utf8(body).map_err(invalid_data)?;
+    serde_json::from_str(&body).map(Some).map_err(invalid_data)
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// the LSP position, 0-origin lines and columns in UTF-16, of the byte `offset` of `text`
+fn position(text: &str, offset: usize) -> Value {
+    let read = &text[..offset];
+    let line_start = read.rfind('\n').map(|n| n + 1).unwrap_or(0);
+    json!({
+        "line": read.matches('\n').count(),
+        "character": read[line_start..].encode_utf16().count(),
+    })
+}
+
+/// the byte of `text` at the LSP position. the positions past the lines are clamped
+fn offset(text: &str, position: &Value) -> Option<usize> {
+    let line = position["line"].as_u64()? as usize;
+    let character = position["character"].as_u64()? as usize;
+    let line_start = if line == 0 {
+        0
+    } else {
+        text.match_indices('\n').nth(line - 1)?.0 + 1
+    };
+    let line_text = text[line_start..].split('\n').next().unwrap_or("");
+    let mut units = 0;
+    for (i, c) in line_text.char_indices() {
+        if units >= character {
+            return Some(line_start + i);
+        }
+        units += c.len_utf16();
+    }
+    Some(line_start + line_text.len())
+}
+
+fn range(text: &str, span: Span) -> Value {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+fn symbol_kind(kind: SymbolKind) -> usize {
+    match kind {
+        SymbolKind::Datatype => 10,
+        SymbolKind::Function => 12,
+        SymbolKind::Variable => 13,
+        SymbolKind::Constructor => 22,
+    }
+}
+
+fn document_symbol(text: &str, symbol: &DocumentSymbol) -> Value {
+    json!({
+        "name": symbol.name,
+        "kind": symbol_kind(symbol.kind),
+        "range": range(text, symbol.span),
+        "selectionRange": range(text, symbol.span),
+        "children": symbol
+            .children
+            .iter()
+            .map(|child| document_symbol(text, child))
+            .collect::<Vec<_>>(),
+    })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+/// what the server returns for a request
+enum Response {
+    Result(Value),
+    Error(i64, String),
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Analysis>,
+    shutdown: bool,
+    /// the messages to send
+    outbox: Vec<Value>,
+}
+
+impl Server {
+    fn handle(&mut self, message: Value) {
+        let method = message["method"].as_str().unwrap_or("").to_string();
+        let params = &message["params"];
+        let id = &message["id"];
+        if id.is_null() {
+            self.notify(&method, params);
+            return;
+        }
+        let response = match self.request(&method, params) {
+            Response::Result(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Response::Error(code, message) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+        self.outbox.push(response)
+    }
+
+    fn request(&mut self, method: &str, params: &Value) -> Response {
+        let capabilities = json!({
+            "textDocumentSync": TEXT_DOCUMENT_SYNC_FULL,
+            "hoverProvider": true,
+            "definitionProvider": true,
+            "documentSymbolProvider": true,
+        });
+        match method {
+            "initialize" => Response::Result(json!({
+                "capabilities": capabilities,
+                "serverInfo": { "name": "webml-lsp" },
+            })),
+            "shutdown" => {
+                self.shutdown = true;
+                Response::Result(Value::Null)
+            }
+            "textDocument/hover" | "textDocument/definition" | "textDocument/documentSymbol" => {
+                let uri = &params["textDocument"]["uri"];
+                match uri.as_str().and_then(|uri| self.documents.get(uri)) {
+                    Some(doc) => Response::Result(document_request(method, uri, doc, params)),
+                    None => Response::Error(INVALID_PARAMS, "unknown document".into()),
+                }
+            }
+            _ => Response::Error(METHOD_NOT_FOUND, format!("unknown method {}", method)),
+        }
+    }
+
+    fn notify(&mut self, method: &str, params: &Value) {
+        let uri = params["textDocument"]["uri"].as_str();
+        match (method, uri) {
+            ("exit", _) => process::exit(if self.shutdown { 0 } else { 1 }),
+            ("textDocument/didOpen", Some(uri)) => {
+                if let Some(text) = params["textDocument"]["text"].as_str() {
+                    self.open(uri, text.to_string())
+                }
+            }
+            ("textDocument/didChange", Some(uri)) => {
+                // the changes are whole texts as the sync is full
+                let changes = params["contentChanges"].as_array();
+                let change = changes.and_then(|changes| changes.last());
+                if let Some(text) = change.and_then(|change| change["text"].as_str()) {
+                    self.open(uri, text.to_string())
+                }
+            }
+            ("textDocument/didClose", Some(uri)) => {
+                self.documents.remove(uri);
+                self.publish_diagnostics(uri, Vec::new())
+            }
+            _ => (),
+        }
+    }
+
+    fn open(&mut self, uri: &str, text: String) {
+        let analysis = Analysis::new(text);
+        let text = &analysis.text;
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|d| {
+                let severity = match d.severity {
+                    Severity::Error => SEVERITY_ERROR,
+                    Severity::Warning => SEVERITY_WARNING,
+                };
+                let related = d
+                    .labels
+                    .iter()
+                    .map(|label| {
+                        json!({
+                            "location": { "uri": uri, "range": range(text, label.span) },
+                            "message": label.message,
+                        })
+                    })
+                    .collect::<Vec<_>>();
+                let span = d.span.unwrap_or(Span { start: 0, end: 0 });
+                json!({
+                    "range": range(text, span),
+                    "severity": severity,
+                    "code": d.code,
+                    "source": "webml",
+                    "message": d.message,
+                    "relatedInformation": related,
+                })
+            })
+            .collect();
+        self.documents.insert(uri.to_string(), analysis);
+        self.publish_diagnostics(uri, diagnostics)
+    }
+
+    fn publish_diagnostics(&mut self, uri: &str, diagnostics: Vec<Value>) {
+        self.outbox.push(notification(
+            "textDocument/publishDiagnostics",
+            json!({ "uri": uri, "diagnostics": diagnostics }),
+        ))
+    }
+}
+
+fn document_request(method: &str, uri: &Value, doc: &Analysis, params: &Value) -> Value {
+    let text = &doc.text;
+    let offset = offset(text, &params["position"]);
+    match method {
+        "textDocument/hover" => offset
+            .and_then(|offset| doc.hover(offset))
+            .map(|(span, hover)| {
+                json!({
+                    "contents": { "kind": "markdown", "value": format!("```sml\n{}\n```", hover) },
+                    "range": range(text, span),
+                })
+            })
+            .into(),
+        "textDocument/definition" => offset
+            .and_then(|offset| doc.definition(offset))
+            .map(|span| json!({ "uri": uri, "range": range(text, span) }))
+            .into(),
+        _ => doc
+            .symbols
+            .iter()
+            .map(|symbol| document_symbol(text, symbol))
+            .collect::<Vec<_>>()
+            .into(),
+    }
+}
+
+fn main() -> io::Result<()> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let stdout = io::stdout();
+    let mut output = stdout.lock();
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input)? {
+        server.handle(message);
+        for message in server.outbox.drain(..) {
+            write_message(&mut output, &message)?;
+        }
+    }
+    Ok(())
+}
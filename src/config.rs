@@ -3,4 +3,39 @@ use std::collections::HashSet;
 #[derive(Clone, Debug, Default)]
 pub struct Config {
     pub pretty_print_ir: HashSet<String>,
+    pub int_width: IntWidth,
+    /// wrap around on overflow and trap on division by zero
+    /// instead of raising `Overflow` and `Div` as the Definition requires
+    pub unchecked_arith: bool,
+    pub opt_level: OptLevel,
+}
+
+/// bit width of `int`
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum IntWidth {
+    #[default]
+    Int32,
+    Int64,
+}
+
+impl IntWidth {
+    pub fn contains(&self, n: i64) -> bool {
+        match self {
+            IntWidth::Int32 => i64::from(n as i32) == n,
+            IntWidth::Int64 => true,
+        }
+    }
+}
+
+/// how much the passes optimize the program
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum OptLevel {
+    /// no optimizations
+    #[default]
+    O0,
+    /// the optimizations local to the ops: constant folding, copy propagation and
+    /// dead-code elimination
+    O1,
+    /// `O1` and the optimizations of the control flow
+    O2,
 }
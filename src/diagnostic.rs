@@ -0,0 +1,199 @@
+//! the errors and the warnings in the form the tools read
+use crate::ast::{TypeError, Warning};
+use crate::parser::Position;
+use crate::prim::Span;
+use crate::source::Sources;
+use serde_json::{json, Value};
+use std::fmt::{self, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// a note on a range of the source other than the one the diagnostic is on
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+}
+
+/// an error or a warning. the spans are in the bytes of the input compiled
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub span: Option<Span>,
+    pub labels: Vec<Label>,
+}
+
+impl Diagnostic {
+    /// the diagnostics of an error `input` is compiled into.
+    /// the parse errors are reported one by one
+    pub fn from_error(input: &str, error: &TypeError<'_>) -> Vec<Self> {
+        match error {
+            TypeError::ParseError(errors) => errors
+                .iter()
+                .map(|e| Diagnostic {
+                    severity: Severity::Error,
+                    code: error.code(),
+                    message: e.message(),
+                    span: Some(e.span),
+                    labels: e
+                        .context
+                        .iter()
+                        .map(|(open, start)| {
+                            let start = start.offset(input);
+                            Label {
+                                span: Span {
+                                    start,
+                                    end: start + open.len(),
+                                },
+                                message: format!("`{}` begins here", open),
+                            }
+                        })
+                        .collect(),
+                })
+                .collect(),
+            error => vec![Diagnostic {
+                severity: Severity::Error,
+                code: error.code(),
+                message: error.to_string(),
+                span: error.span(),
+                labels: Vec::new(),
+            }],
+        }
+    }
+
+    pub fn from_warning(warning: &Warning) -> Self {
+        Diagnostic {
+            severity: Severity::Warning,
+            code: warning.code(),
+            message: warning.to_string(),
+            span: warning.span(),
+            labels: Vec::new(),
+        }
+    }
+
+    /// the JSON object of the diagnostic. the spans out of the files are written as `null`
+    pub fn to_json(&self, sources: &Sources) -> Value {
+        let span = |span: Span| -> Value {
+            let (file, begin) = match sources.locate(span.start) {
+                Some(located) => located,
+                None => return Value::Null,
+            };
+            let text = sources.text(file);
+            let end = (span.end - file.span.start).min(text.len());
+            let (begin_pos, end_pos) = (Position::at(text, begin), Position::at(text, end));
+            json!({
+                "file": file.name,
+                "byte_start": begin,
+                "byte_end": end,
+                "line_start": begin_pos.line,
+                "column_start": begin_pos.column,
+                "line_end": end_pos.line,
+                "column_end": end_pos.column,
+            })
+        };
+        json!({
+            "severity": self.severity.to_string(),
+            "code": self.code,
+            "message": self.message,
+            "span": self.span.map(span),
+            "labels": self
+                .labels
+                .iter()
+                .map(|label| json!({ "span": span(label.span), "message": label.message }))
+                .collect::<Vec<_>>(),
+        })
+    }
+
+    /// the diagnostic for humans, quoting the source lines of the spans
+    pub fn render(&self, sources: &Sources) -> String {
+        let mut lines = self.message.lines();
+        let mut out = format!(
+            "{}[{}]: {}\n",
+            self.severity,
+            self.code,
+            lines.next().unwrap_or("")
+        );
+        let snippets = self
+            .span
+            .iter()
+            .map(|span| (*span, '^', ""))
+            .chain(
+                self.labels
+                    .iter()
+                    .map(|label| (label.span, '-', label.message.as_str())),
+            )
+            .filter_map(|(span, mark, message)| {
+                let (file, begin) = sources.locate(span.start)?;
+                Some((file, begin, span.end - span.start, mark, message))
+            })
+            .collect::<Vec<_>>();
+        let width = snippets
+            .iter()
+            .map(|(file, begin, ..)| {
+                let line = Position::at(sources.text(file), *begin).line;
+                line.to_string().len()
+            })
+            .max()
+            .unwrap_or(0);
+        for (i, (file, begin, len, mark, message)) in snippets.iter().enumerate() {
+            let text = sources.text(file);
+            let pos = Position::at(text, *begin);
+            let line_start = begin - text[..*begin].rsplit('\n').next().unwrap_or("").len();
+            let line = text[line_start..].lines().next().unwrap_or("");
+            // the marks stop at the end of the line
+            let end = (begin + len).min(text.len());
+            let marked = text[*begin..end]
+                .lines()
+                .next()
+                .unwrap_or("")
+                .chars()
+                .count()
+                .max(1);
+            let arrow = if i == 0 { "-->" } else { ":::" };
+            writeln!(
+                out,
+                "{:w$}{} {}:{}:{}",
+                "",
+                arrow,
+                file.name,
+                pos.line,
+                pos.column,
+                w = width
+            )
+            .unwrap();
+            writeln!(out, "{:w$} |", "", w = width).unwrap();
+            let quoted = format!("{:>w$} | {}", pos.line, line, w = width);
+            writeln!(out, "{}", quoted.trim_end()).unwrap();
+            let indent: String = line
+                .chars()
+                .take(pos.column - 1)
+                .map(|c| if c == '\t' { '\t' } else { ' ' })
+                .collect();
+            let marks = mark.to_string().repeat(marked);
+            let underline = format!("{}{} {}", indent, marks, message);
+            writeln!(out, "{:w$} | {}", "", underline.trim_end(), w = width).unwrap();
+            if i + 1 != snippets.len() {
+                writeln!(out, "{:w$} |", "", w = width).unwrap();
+            }
+        }
+        for line in lines {
+            writeln!(out, "{:w$} = {}", "", line.trim_start(), w = width).unwrap();
+        }
+        out
+    }
+}
@@ -50,7 +50,10 @@ fn conv_ty(ty: ast::Type) -> HTy {
     match ty {
         Char => HTy::Char,
         Int => HTy::Int,
+        IntInf => HTy::IntInf,
+        Word(size) => HTy::Word(size),
         Real => HTy::Real,
+        String => HTy::String,
         Tuple(tys) => HTy::Tuple(tys.into_iter().map(|ty| conv_ty(ty)).collect()),
         Fun(arg, ret) => HTy::fun(conv_ty(*arg), conv_ty(*ret)),
         Datatype(name) => HTy::Datatype(name),
@@ -68,7 +71,7 @@ impl AST2HIRPass {
 
     pub fn gensym(&mut self) -> Symbol {
         let id = self.id.next();
-        Symbol("#g".into(), id)
+        Symbol("#g".into(), id, None)
     }
 
     fn force_tuple(&self, ty: ast::Type) -> Vec<HTy> {
@@ -89,16 +92,21 @@ impl AST2HIRPass {
 
     fn conv_statement(&mut self, decl: ast::TypedCoreDeclaration) -> Vec<Val> {
         match decl {
-            ast::Declaration::Datatype { .. } => {
+            ast::Declaration::Datatype { .. } | ast::Declaration::Type { .. } => {
                 // ignore
                 vec![]
             }
+            ast::Declaration::Abstype { datatype, decls } => {
+                let mut vals = self.conv_statement(*datatype);
+                vals.extend(decls.into_iter().flat_map(|decl| self.conv_statement(decl)));
+                vals
+            }
             ast::Declaration::Val { rec, pattern, expr } => {
                 let ty = pattern.ty.clone();
                 match pattern.inner {
                     ast::PatternKind::Variable { name } => vec![Val {
                         ty: conv_ty(ty),
-                        rec: false,
+                        rec,
                         name: name,
                         expr: self.conv_expr(expr),
                     }],
@@ -127,12 +135,15 @@ impl AST2HIRPass {
                         name: self.gensym(),
                         expr: self.conv_expr(expr),
                     }],
-                    ast::PatternKind::Char { .. } => vec![Val {
+                    ast::PatternKind::Word { .. } | ast::PatternKind::Char { .. } => vec![Val {
                         ty: conv_ty(ty),
                         rec: false,
                         name: self.gensym(),
                         expr: self.conv_expr(expr),
                     }],
+                    ast::PatternKind::Real { .. } => {
+                        panic!("internal error: real pattern remains after typing")
+                    }
                     // when C(p1, p2, p3) binds var1 var2 var3, convert
                     //
                     // ```
@@ -229,6 +240,15 @@ impl AST2HIRPass {
                     }
                 }
             }
+            ast::Declaration::Local { binds, body } => binds
+                .into_iter()
+                .chain(body)
+                .flat_map(|decl| self.conv_statement(decl))
+                .collect(),
+            ast::Declaration::And(decls) => decls
+                .into_iter()
+                .flat_map(|decl| self.conv_statement(decl))
+                .collect(),
             ast::Declaration::D(d) => match d {},
         }
     }
@@ -311,6 +331,10 @@ impl AST2HIRPass {
                 value,
                 ty: conv_ty(ty),
             },
+            ast::PatternKind::Word { value } => Pattern::Word {
+                value,
+                ty: conv_ty(ty),
+            },
             ast::PatternKind::Char { value } => Pattern::Char {
                 value,
                 ty: conv_ty(ty),
@@ -347,6 +371,9 @@ impl AST2HIRPass {
                 name: Symbol::new("_"),
                 ty: conv_ty(ty),
             },
+            ast::PatternKind::Real { .. } => {
+                panic!("internal error: real pattern remains after typing")
+            }
         }
     }
 
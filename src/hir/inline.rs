@@ -0,0 +1,385 @@
+use crate::config::Config;
+use crate::hir::util::Transform;
+use crate::hir::*;
+use crate::id::Id;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// functions up to this size are inlined wherever they are called
+const SMALL: usize = 10;
+
+/// inlines the calls of the toplevel functions that are small or called only once.
+/// recursive functions and closures are not inlined.
+/// the functions are kept as they can be used as values or exported
+pub struct Inline {
+    id: Id,
+    /// the parameters and the bodies of the functions to inline, inlined themselves
+    funs: HashMap<Symbol, (Symbol, Expr)>,
+    /// the variables bound to the toplevel functions
+    aliases: HashMap<Symbol, Symbol>,
+    uses: HashMap<Symbol, usize>,
+}
+
+/// gives fresh names to the variables an inlined body binds and substitutes the argument
+/// for the parameter
+struct Rename<'a> {
+    id: &'a mut Id,
+    names: HashMap<Symbol, Symbol>,
+}
+
+impl Inline {
+    pub fn new(id: Id) -> Self {
+        Inline {
+            id,
+            funs: HashMap::new(),
+            aliases: HashMap::new(),
+            uses: HashMap::new(),
+        }
+    }
+
+    fn inline_hir(&mut self, mut hir: HIR) -> HIR {
+        self.funs.clear();
+        self.aliases.clear();
+        self.uses.clear();
+        for val in hir.0.iter() {
+            count_uses(&mut self.uses, &val.expr);
+        }
+        let tops = hir
+            .0
+            .iter()
+            .enumerate()
+            .filter_map(|(i, val)| match &val.expr {
+                Expr::Fun { captures, .. } if captures.is_empty() => Some((val.name.clone(), i)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let calls = hir
+            .0
+            .iter()
+            .map(|val| {
+                let mut uses = HashMap::new();
+                count_uses(&mut uses, &val.expr);
+                uses.into_keys()
+                    .filter_map(|name| tops.get(&name).cloned())
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+
+        // the callees first so that the bodies to inline are inlined themselves
+        let mut order = Vec::new();
+        let mut visited = HashSet::new();
+        for i in 0..hir.0.len() {
+            post_order(&calls, i, &mut visited, &mut order);
+        }
+        let mut vals = hir.0.into_iter().map(Some).collect::<Vec<_>>();
+        for i in order {
+            let mut val = vals[i].take().expect("visited once");
+            val.expr = self.inline_expr(val.expr);
+            if tops.contains_key(&val.name) && !reaches(&calls, i, i) {
+                if let Expr::Fun { param, body, .. } = &val.expr {
+                    self.funs
+                        .insert(val.name.clone(), (param.1.clone(), (**body).clone()));
+                }
+            }
+            vals[i] = Some(val);
+        }
+        hir.0 = vals.into_iter().map(|val| val.expect("visited")).collect();
+        hir
+    }
+
+    fn inline_expr(&mut self, expr: Expr) -> Expr {
+        use crate::hir::Expr::*;
+        match expr {
+            Binds { ty, binds, ret } => {
+                let mut vals = Vec::new();
+                for val in binds {
+                    self.inline_val(&mut vals, val);
+                }
+                let mut uses = HashMap::new();
+                for val in vals.iter() {
+                    count_uses(&mut uses, &val.expr);
+                }
+                count_uses(&mut uses, &ret);
+                // the variables the inlined calls used the functions through
+                vals.retain(|val| {
+                    uses.contains_key(&val.name) || !self.aliases.contains_key(&val.name)
+                });
+                Binds {
+                    ty,
+                    binds: vals,
+                    ret,
+                }
+            }
+            Fun {
+                param,
+                body_ty,
+                body,
+                captures,
+            } => Fun {
+                param,
+                body_ty,
+                body: Box::new(self.inline_expr(*body)),
+                captures,
+            },
+            Case { ty, expr, arms } => Case {
+                ty,
+                expr,
+                arms: arms
+                    .into_iter()
+                    .map(|(pat, arm)| (pat, self.inline_expr(arm)))
+                    .collect(),
+            },
+            expr => expr,
+        }
+    }
+
+    fn inline_val(&mut self, vals: &mut Vec<Val>, mut val: Val) {
+        match &val.expr {
+            Expr::Sym { name, .. } => {
+                let fname = self.aliases.get(name).unwrap_or(name).clone();
+                if self.funs.contains_key(&fname) {
+                    self.aliases.insert(val.name.clone(), fname);
+                }
+            }
+            Expr::App { fun, arg, .. } => {
+                let name = match &**fun {
+                    Expr::Sym { name, .. } => name,
+                    _ => unreachable!("internal error: not flattened"),
+                };
+                let fname = self.aliases.get(name).unwrap_or(name);
+                if let Some((param, body)) = self.funs.get(fname) {
+                    // called only here, directly or through the only variable bound to it
+                    let once = self.uses.get(fname) == Some(&1) && self.uses.get(name) == Some(&1);
+                    if once || self.size(body) <= SMALL {
+                        let (param, body) = (param.clone(), body.clone());
+                        let arg = (**arg).clone();
+                        self.beta(vals, val, param, body, arg);
+                        return;
+                    }
+                }
+            }
+            _ => (),
+        }
+        val.expr = self.inline_expr(val.expr);
+        vals.push(val)
+    }
+
+    /// `val x = (fn param => body) arg` to `val param' = arg; body'; val x = ret'`
+    fn beta(&mut self, vals: &mut Vec<Val>, val: Val, param: Symbol, body: Expr, arg: Expr) {
+        let mut rename = Rename {
+            id: &mut self.id,
+            names: HashMap::new(),
+        };
+        match arg {
+            Expr::Sym { name, .. } => {
+                rename.names.insert(param, name);
+            }
+            arg => {
+                let name = rename.fresh(&param);
+                vals.push(Val {
+                    ty: arg.ty(),
+                    rec: false,
+                    name,
+                    expr: arg,
+                })
+            }
+        }
+        let ret = match rename.transform_expr(body) {
+            Expr::Binds { mut binds, ret, .. } => {
+                vals.append(&mut binds);
+                *ret
+            }
+            body => body,
+        };
+        vals.push(Val { expr: ret, ..val })
+    }
+
+    /// the number of the operations in `expr`. variables, literals and `let`s cost nothing
+    fn size(&self, expr: &Expr) -> usize {
+        use crate::hir::Expr::*;
+        match expr {
+            Binds { binds, ret, .. } => {
+                binds.iter().map(|val| self.size(&val.expr)).sum::<usize>() + self.size(ret)
+            }
+            Sym { .. } | Lit { .. } => 0,
+            Fun { body, .. } => self.size(body),
+            BuiltinCall { args, .. } | ExternCall { args, .. } => {
+                1 + args.iter().map(|arg| self.size(arg)).sum::<usize>()
+            }
+            App { fun, arg, .. } => 1 + self.size(fun) + self.size(arg),
+            Case { expr, arms, .. } => {
+                1 + self.size(expr) + arms.iter().map(|(_, arm)| self.size(arm)).sum::<usize>()
+            }
+            Tuple { tuple, .. } => 1 + tuple.iter().map(|e| self.size(e)).sum::<usize>(),
+            Proj { tuple, .. } => 1 + self.size(tuple),
+            Constructor { arg, .. } => 1 + arg.as_ref().map_or(0, |arg| self.size(arg)),
+            Closure { .. } => 1,
+        }
+    }
+}
+
+fn count_uses(uses: &mut HashMap<Symbol, usize>, expr: &Expr) {
+    use crate::hir::Expr::*;
+    match expr {
+        Binds { binds, ret, .. } => {
+            for val in binds {
+                count_uses(uses, &val.expr);
+            }
+            count_uses(uses, ret)
+        }
+        Fun { body, .. } => count_uses(uses, body),
+        BuiltinCall { args, .. } | ExternCall { args, .. } => {
+            for arg in args {
+                count_uses(uses, arg)
+            }
+        }
+        App { fun, arg, .. } => {
+            count_uses(uses, fun);
+            count_uses(uses, arg)
+        }
+        Case { expr, arms, .. } => {
+            count_uses(uses, expr);
+            for (_, arm) in arms {
+                count_uses(uses, arm)
+            }
+        }
+        Tuple { tuple, .. } => {
+            for e in tuple {
+                count_uses(uses, e)
+            }
+        }
+        Proj { tuple, .. } => count_uses(uses, tuple),
+        Constructor { arg, .. } => {
+            if let Some(arg) = arg {
+                count_uses(uses, arg)
+            }
+        }
+        Closure { envs, fname, .. } => {
+            for (_, name) in envs {
+                *uses.entry(name.clone()).or_insert(0) += 1;
+            }
+            *uses.entry(fname.clone()).or_insert(0) += 1;
+        }
+        Sym { name, .. } => *uses.entry(name.clone()).or_insert(0) += 1,
+        Lit { .. } => (),
+    }
+}
+
+fn post_order(
+    calls: &[Vec<usize>],
+    i: usize,
+    visited: &mut HashSet<usize>,
+    order: &mut Vec<usize>,
+) {
+    if visited.insert(i) {
+        for &callee in &calls[i] {
+            post_order(calls, callee, visited, order);
+        }
+        order.push(i)
+    }
+}
+
+/// whether `from` calls `to` directly or indirectly
+fn reaches(calls: &[Vec<usize>], from: usize, to: usize) -> bool {
+    let mut visited = HashSet::new();
+    let mut stack = calls[from].clone();
+    while let Some(i) = stack.pop() {
+        if i == to {
+            return true;
+        }
+        if visited.insert(i) {
+            stack.extend(calls[i].iter().cloned());
+        }
+    }
+    false
+}
+
+impl<'a> Rename<'a> {
+    fn fresh(&mut self, name: &Symbol) -> Symbol {
+        let fresh = Symbol(name.0.clone(), self.id.next(), name.2);
+        self.names.insert(name.clone(), fresh.clone());
+        fresh
+    }
+
+    fn rename(&self, name: Symbol) -> Symbol {
+        self.names.get(&name).cloned().unwrap_or(name)
+    }
+}
+
+impl<'a> Transform for Rename<'a> {
+    fn transform_val(&mut self, mut val: Val) -> Val {
+        val.name = self.fresh(&val.name);
+        val.expr = self.transform_expr(val.expr);
+        val
+    }
+
+    fn transform_closure(
+        &mut self,
+        envs: Vec<(HTy, Symbol)>,
+        param_ty: HTy,
+        body_ty: HTy,
+        fname: Symbol,
+    ) -> Expr {
+        Expr::Closure {
+            envs: envs
+                .into_iter()
+                .map(|(ty, name)| (ty, self.rename(name)))
+                .collect(),
+            param_ty,
+            body_ty,
+            fname,
+        }
+    }
+
+    fn transform_case(&mut self, ty: HTy, cond: Box<Expr>, arms: Vec<(Pattern, Expr)>) -> Expr {
+        use crate::hir::Pattern::*;
+        let expr = Box::new(self.transform_expr(*cond));
+        let arms = arms
+            .into_iter()
+            .map(|(pat, arm)| {
+                let pat = match pat {
+                    Constructor {
+                        descriminant,
+                        arg,
+                        ty,
+                    } => Constructor {
+                        descriminant,
+                        arg: arg.map(|(ty, name)| (ty, self.fresh(&name))),
+                        ty,
+                    },
+                    Var { name, ty } => Var {
+                        name: self.fresh(&name),
+                        ty,
+                    },
+                    Tuple { tys, tuple } => Tuple {
+                        tys,
+                        tuple: tuple.iter().map(|name| self.fresh(name)).collect(),
+                    },
+                    pat => pat,
+                };
+                (pat, self.transform_expr(arm))
+            })
+            .collect();
+        Expr::Case { ty, expr, arms }
+    }
+
+    fn transform_sym(&mut self, ty: HTy, name: Symbol) -> Expr {
+        Expr::Sym {
+            ty,
+            name: self.rename(name),
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, HIR), E> for Inline {
+    type Target = (SymbolTable, HIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, hir): (SymbolTable, HIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.inline_hir(hir)))
+    }
+}
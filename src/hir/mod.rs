@@ -2,6 +2,7 @@ pub mod ast2hir;
 pub mod flat_expr;
 pub mod flat_let;
 pub mod force_closure;
+pub mod inline;
 pub mod pp;
 pub mod unnest_func;
 pub mod util;
@@ -10,6 +11,7 @@ pub use self::ast2hir::AST2HIR;
 pub use self::flat_expr::FlatExpr;
 pub use self::flat_let::FlatLet;
 pub use self::force_closure::ForceClosure;
+pub use self::inline::Inline;
 pub use self::unnest_func::UnnestFunc;
 use std::collections::HashMap;
 
@@ -21,6 +23,7 @@ pub struct HIR(pub Vec<Val>);
 #[derive(Debug, Clone)]
 pub struct Val {
     pub ty: HTy,
+    /// recursive function. adjacent `rec` vals can refer each other.
     pub rec: bool,
     pub name: Symbol,
     pub expr: Expr,
@@ -97,6 +100,10 @@ pub enum Pattern {
         value: i64,
         ty: HTy,
     },
+    Word {
+        value: u64,
+        ty: HTy,
+    },
     Char {
         value: u32,
         ty: HTy,
@@ -125,7 +132,10 @@ pub struct SymbolTable {
 pub enum HTy {
     Char,
     Int,
+    IntInf,
+    Word(WordSize),
     Real,
+    String,
     Fun(Box<HTy>, Box<HTy>),
     Tuple(Vec<HTy>),
     Datatype(Symbol),
@@ -175,14 +185,15 @@ impl Expr {
 }
 
 impl Pattern {
-    pub fn match_key(&self) -> u32 {
+    pub fn match_key(&self) -> u64 {
         use self::Pattern::*;
         // FIXME do not panic
         match self {
-            Constant { value, .. } => *value as u32,
-            Char { value, .. } => *value,
+            Constant { value, .. } => *value as u64,
+            Word { value, .. } => *value,
+            Char { value, .. } => *value as u64,
             Tuple { .. } => panic!("bug: non-variant expression does not have keys"),
-            Constructor { descriminant, .. } => *descriminant as u32,
+            Constructor { descriminant, .. } => *descriminant as u64,
             Var { .. } => panic!("bug: default like branch does not have keys"),
         }
     }
@@ -191,7 +202,7 @@ impl Pattern {
         use self::Pattern::*;
         // FIXME do not panic
         match self {
-            Constant { .. } | Char { .. } => None,
+            Constant { .. } | Word { .. } | Char { .. } => None,
             Tuple { .. } => panic!("bug: non-variant expression does not have keys"),
             Constructor { arg, .. } => arg.as_ref().map(|(_, name)| name.clone()),
             Var { name, .. } => Some(name.clone()),
@@ -201,7 +212,7 @@ impl Pattern {
     pub fn is_irrefutable(&self) -> bool {
         use self::Pattern::*;
         match *self {
-            Constructor { .. } | Constant { .. } | Char { .. } => false,
+            Constructor { .. } | Constant { .. } | Word { .. } | Char { .. } => false,
             Tuple { .. } | Var { .. } => true,
         }
     }
@@ -152,6 +152,7 @@ impl PP for Pattern {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
         match self {
             Pattern::Constant { value, .. } => write!(w, "{}", value),
+            Pattern::Word { value, .. } => write!(w, "0w{}", value),
             Pattern::Char { value, .. } => write!(w, r##"#"{}""##, value),
             Pattern::Constructor {
                 descriminant, arg, ..
@@ -186,7 +187,10 @@ impl PP for HTy {
         match self {
             Char => write!(w, "char")?,
             Int => write!(w, "int")?,
+            IntInf => write!(w, "IntInf.int")?,
+            Word(size) => size.pp(w, indent)?,
             Real => write!(w, "real")?,
+            String => write!(w, "string")?,
             Tuple(tys) => {
                 write!(w, "(")?;
                 inter_iter! {
@@ -53,7 +53,7 @@ impl<'a> Scope<'a> {
             Some(name) => format!("<{}>", name.0),
         };
         let id = self.id.next();
-        Symbol(new_name, id)
+        Symbol(new_name, id, None)
     }
 
     fn add_scope(&mut self, symbol: Symbol) {
@@ -78,58 +78,178 @@ impl<'a> Scope<'a> {
     }
 
     fn conv_hir(&mut self, mut hir: HIR) -> HIR {
-        let mut vals = hir
-            .0
-            .into_iter()
-            .map(|val| {
-                if val.rec {
-                    self.add_scope(val.name.clone());
-                    self.conv_top_val(val)
-                } else {
-                    let val = self.conv_top_val(val);
-                    self.add_scope(val.name.clone());
-                    val
-                }
-            })
-            .collect();
+        let mut vals = self.conv_vals(hir.0, true);
         let mut closures = self.0.tops.drain(..).collect::<Vec<_>>();
         closures.append(&mut vals);
         hir.0 = closures;
         hir
     }
 
-    fn conv_top_val(&mut self, mut val: Val) -> Val {
-        let bind_name = if val.rec {
-            Some(val.name.clone())
-        } else {
-            None
+    fn conv_vals(&mut self, vals: Vec<Val>, is_top: bool) -> Vec<Val> {
+        let mut ret = Vec::new();
+        let mut group = Vec::new();
+        for val in vals {
+            // adjacent recursive functions are converted together
+            if let (true, Expr::Fun { .. }) = (val.rec, &val.expr) {
+                group.push(val);
+                continue;
+            }
+            if !group.is_empty() {
+                let group = std::mem::take(&mut group);
+                ret.append(&mut self.conv_rec_group(group, is_top));
+            }
+            let mut val = val;
+            val.expr = self.conv_expr(val.expr, is_top);
+            val.rec = false;
+            if is_top {
+                self.add_scope(val.name.clone());
+            }
+            ret.push(val);
+        }
+        if !group.is_empty() {
+            ret.append(&mut self.conv_rec_group(group, is_top));
+        }
+        ret
+    }
+
+    // Functions in a recursive group share one environment, which is the union of their free variables.
+    // Each function additionally captures itself as the last element of the environment,
+    // and closures of the other functions in the group are rebuilt from the environment at its entry.
+    fn conv_rec_group(&mut self, group: Vec<Val>, is_top: bool) -> Vec<Val> {
+        if is_top {
+            for val in &group {
+                self.add_scope(val.name.clone());
+            }
+        }
+        let names = group.iter().map(|val| val.name.clone()).collect::<Vec<_>>();
+        let tys = group.iter().map(|val| val.ty.clone()).collect::<Vec<_>>();
+        let mut envs = Vec::new();
+        let mut funs = Vec::new();
+        for val in group {
+            let (param, body_ty, body) = match val.expr {
+                Expr::Fun {
+                    param,
+                    body_ty,
+                    body,
+                    captures,
+                } => {
+                    assert_eq!(captures.len(), 0);
+                    (param, body_ty, body)
+                }
+                _ => unreachable!("internal error: recursive group has non-function"),
+            };
+            let body = Box::new(self.conv_expr(*body, false));
+            let mut frees = Vec::new();
+            self.analyze_free_expr(&mut frees, &param.1, &body);
+            let (siblings, frees): (Vec<_>, Vec<_>) = frees
+                .into_iter()
+                .partition(|(_, name)| names.contains(name));
+            for free in frees {
+                if !envs.contains(&free) {
+                    envs.push(free);
+                }
+            }
+            funs.push((param, body_ty, body, siblings));
+        }
+
+        if envs.is_empty() && is_top {
+            // toplevel functions
+            return funs
+                .into_iter()
+                .zip(names.into_iter().zip(tys))
+                .map(|((param, body_ty, body, _), (name, ty))| Val {
+                    ty,
+                    rec: true,
+                    name,
+                    expr: Expr::Fun {
+                        param,
+                        body_ty,
+                        body,
+                        captures: Vec::new(),
+                    },
+                })
+                .collect();
+        }
+
+        let fnames = names
+            .iter()
+            .map(|name| self.new_fname(Some(name.clone())))
+            .collect::<Vec<_>>();
+        let closure_of = |i: usize| {
+            let mut envs = envs.clone();
+            envs.push((tys[i].clone(), names[i].clone()));
+            match &tys[i] {
+                HTy::Fun(param_ty, body_ty) => Expr::Closure {
+                    envs,
+                    param_ty: *param_ty.clone(),
+                    body_ty: *body_ty.clone(),
+                    fname: fnames[i].clone(),
+                },
+                _ => unreachable!("internal error: function has non-function type"),
+            }
         };
-        val.expr = self.conv_expr(val.expr, bind_name, true);
-        val
+        let mut ret = Vec::new();
+        for (i, (param, body_ty, body, siblings)) in funs.into_iter().enumerate() {
+            let mut prologue = Vec::new();
+            for (j, name) in names.iter().enumerate() {
+                if i != j && siblings.iter().any(|(_, sibling)| sibling == name) {
+                    prologue.push(Val {
+                        ty: tys[j].clone(),
+                        rec: true,
+                        name: name.clone(),
+                        expr: closure_of(j),
+                    })
+                }
+            }
+            let body = if prologue.is_empty() {
+                body
+            } else {
+                match *body {
+                    Expr::Binds { ty, mut binds, ret } => {
+                        prologue.append(&mut binds);
+                        Box::new(Expr::Binds {
+                            ty,
+                            binds: prologue,
+                            ret,
+                        })
+                    }
+                    body => Box::new(Expr::Binds {
+                        ty: body_ty.clone(),
+                        binds: prologue,
+                        ret: Box::new(body),
+                    }),
+                }
+            };
+            let mut captures = envs.clone();
+            captures.push((tys[i].clone(), names[i].clone()));
+            self.new_closure(Val {
+                ty: tys[i].clone(),
+                rec: true,
+                name: fnames[i].clone(),
+                expr: Expr::Fun {
+                    param,
+                    body_ty,
+                    body,
+                    captures,
+                },
+            });
+            // the closure captures itself
+            ret.push(Val {
+                ty: tys[i].clone(),
+                rec: true,
+                name: names[i].clone(),
+                expr: closure_of(i),
+            });
+        }
+        ret
     }
 
-    fn conv_expr(&mut self, expr: Expr, bind_name: Option<Symbol>, is_top: bool) -> Expr {
+    fn conv_expr(&mut self, expr: Expr, is_top: bool) -> Expr {
         use crate::hir::Expr::*;
         match expr {
-            Binds {
-                ty,
-                mut binds,
-                mut ret,
-            } => {
-                binds = binds
-                    .into_iter()
-                    .map(|mut bind| {
-                        let bind_name = if bind.rec {
-                            Some(bind.name.clone())
-                        } else {
-                            None
-                        };
-                        bind.expr = self.conv_expr(bind.expr, bind_name, false);
-                        bind.rec = false;
-                        bind
-                    })
-                    .collect();
-                ret = Box::new(self.conv_expr(*ret, None, false));
+            Binds { ty, binds, ret } => {
+                let binds = self.conv_vals(binds, false);
+                let ret = Box::new(self.conv_expr(*ret, false));
                 Binds { ty, binds, ret }
             }
             Fun {
@@ -140,7 +260,7 @@ impl<'a> Scope<'a> {
                 ..
             } => {
                 assert_eq!(captures.len(), 0);
-                body = Box::new(self.conv_expr(*body, None, false));
+                body = Box::new(self.conv_expr(*body, false));
                 let (param_ty, param) = param;
                 let mut frees = Vec::new();
                 self.analyze_free_expr(&mut frees, &param, &body);
@@ -157,8 +277,7 @@ impl<'a> Scope<'a> {
                     };
                 }
 
-                let fname = self.new_fname(bind_name.clone());
-                self.rename(&mut body, &bind_name, &fname);
+                let fname = self.new_fname(None);
                 let anonfun = Fun {
                     param: (param_ty.clone(), param),
                     body_ty: body_ty.clone(),
@@ -189,7 +308,7 @@ impl<'a> Scope<'a> {
             BuiltinCall { ty, fun, args } => {
                 let args = args
                     .into_iter()
-                    .map(|arg| self.conv_expr(arg, None, false))
+                    .map(|arg| self.conv_expr(arg, false))
                     .collect();
                 BuiltinCall { ty, fun, args }
             }
@@ -201,7 +320,7 @@ impl<'a> Scope<'a> {
             } => {
                 let args = args
                     .into_iter()
-                    .map(|arg| self.conv_expr(arg, None, false))
+                    .map(|arg| self.conv_expr(arg, false))
                     .collect();
                 ExternCall {
                     ty,
@@ -215,8 +334,8 @@ impl<'a> Scope<'a> {
                 mut fun,
                 mut arg,
             } => {
-                fun = Box::new(self.conv_expr(*fun, None, false));
-                arg = Box::new(self.conv_expr(*arg, None, false));
+                fun = Box::new(self.conv_expr(*fun, false));
+                arg = Box::new(self.conv_expr(*arg, false));
                 App { ty, fun, arg }
             }
             Case {
@@ -224,22 +343,22 @@ impl<'a> Scope<'a> {
                 mut expr,
                 mut arms,
             } => {
-                expr = Box::new(self.conv_expr(*expr, None, false));
+                expr = Box::new(self.conv_expr(*expr, false));
                 arms = arms
                     .into_iter()
-                    .map(|(pat, arm)| (pat, self.conv_expr(arm, None, false)))
+                    .map(|(pat, arm)| (pat, self.conv_expr(arm, false)))
                     .collect();
                 Case { ty, expr, arms }
             }
             Tuple { tys, tuple } => {
                 let tuple = tuple
                     .into_iter()
-                    .map(|t| self.conv_expr(t, None, false))
+                    .map(|t| self.conv_expr(t, false))
                     .collect();
                 Tuple { tys, tuple }
             }
             Proj { ty, index, tuple } => {
-                let tuple = self.conv_expr(*tuple, None, false);
+                let tuple = self.conv_expr(*tuple, false);
                 Proj {
                     ty,
                     tuple: Box::new(tuple),
@@ -251,7 +370,7 @@ impl<'a> Scope<'a> {
                 arg,
                 ty,
             } => {
-                let arg = arg.map(|a| Box::new(self.conv_expr(*a, None, false)));
+                let arg = arg.map(|a| Box::new(self.conv_expr(*a, false)));
                 Constructor {
                     descriminant,
                     arg,
@@ -320,6 +439,7 @@ impl<'a> Scope<'a> {
                             }
                         }
                         Constant { .. } => (),
+                        Word { .. } => (),
                         Char { .. } => (),
                         Tuple { tuple, .. } => {
                             for name in tuple {
@@ -357,56 +477,6 @@ impl<'a> Scope<'a> {
             Lit { .. } => (),
         }
     }
-
-    fn rename(&mut self, expr: &mut Expr, from: &Option<Symbol>, to: &Symbol) {
-        use crate::hir::Expr::*;
-        match expr {
-            Binds { binds, ret, .. } => {
-                for bind in binds.iter_mut() {
-                    self.rename(&mut bind.expr, from, to)
-                }
-                self.rename(ret, from, to);
-            }
-            Fun { body, .. } => self.rename(body, from, to),
-            BuiltinCall { args, .. } => {
-                for arg in args {
-                    self.rename(arg, from, to);
-                }
-            }
-            ExternCall { args, .. } => {
-                for arg in args {
-                    self.rename(arg, from, to);
-                }
-            }
-            App { fun, arg, .. } => {
-                self.rename(fun, from, to);
-                self.rename(arg, from, to);
-            }
-            Case { expr, arms, .. } => {
-                self.rename(expr, from, to);
-                for (_, arm) in arms.iter_mut() {
-                    self.rename(arm, from, to);
-                }
-            }
-            Tuple { tuple, .. } => {
-                for t in tuple.iter_mut() {
-                    self.rename(t, from, to);
-                }
-            }
-            Proj { tuple, .. } => self.rename(tuple, from, to),
-            Sym { name, .. } => {
-                if from.is_some() && name == from.as_ref().unwrap() {
-                    *name = to.clone()
-                }
-            }
-            Constructor { arg, .. } => {
-                if let Some(arg) = arg {
-                    self.rename(arg, from, to)
-                }
-            }
-            Closure { .. } | Lit { .. } => (),
-        }
-    }
 }
 
 impl UnnestFunc {
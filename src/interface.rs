@@ -0,0 +1,887 @@
+//! the interfaces of the compilation units.
+//!
+//! a unit is compiled into a wasm module and the interface, a JSON file describing what it exports.
+//! the units compiled against it only read the interface, and import the functions from its module.
+//! an imported function is bound by a wrapper calling it with `_externcall`.
+//! a curried function is exported as a worker taking all the parameters in a tuple,
+//! and the wrapper takes them one by one again.
+//! the other values go through the host: the unit hands them to `VALUES_MODULE` when it starts,
+//! and the importers take them from there.
+//! the other bindings having functions in them are rejected, as a function value points into
+//! the table of its own module.
+use crate::ast::{Declaration, Expr, ExprKind, Pattern, PatternKind, Printer, SymbolTable};
+use crate::ast::{Type, TypeError, TypedCore, TypedCoreDeclaration};
+use crate::config::{Config, IntWidth};
+use crate::id::Id;
+use crate::parser::Fixities;
+use crate::prim::*;
+use crate::source::Sources;
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+
+/// the module the values, not functions, are passed between the units through
+pub const VALUES_MODULE: &str = "webml-values";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Interface {
+    pub unit: String,
+    pub int_width: IntWidth,
+    /// the units to load before this unit, in order
+    pub imports: Vec<Import>,
+    /// infix operators declared at the top level, by priority
+    pub fixities: BTreeMap<u8, Vec<String>>,
+    /// every datatype the unit knows, including the imported ones, in the order declared
+    pub datatypes: Vec<Datatype>,
+    pub values: Vec<Binding>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub unit: String,
+    /// path to the wasm module of the unit
+    pub wasm: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Datatype {
+    /// the name the types of the interface refer to the datatype by
+    pub name: String,
+    /// the unit declaring the datatype and the name there. together they identify the datatype
+    pub unit: String,
+    pub origin: String,
+    pub constructors: Vec<(String, Option<Type>)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Binding {
+    pub name: String,
+    pub ty: Type,
+    /// exported from the module as a function, not passed as a value
+    pub function: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum InterfaceError {
+    /// the input is not JSON. the message of the reader
+    Syntax(String),
+    /// the member is missing or not of the kind expected
+    Malformed(&'static str),
+    /// the unit is compiled with the other width of int
+    IntWidth(String),
+}
+
+impl fmt::Display for InterfaceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InterfaceError::Syntax(e) => write!(f, "broken interface: {}", e),
+            InterfaceError::Malformed(member) => {
+                write!(f, "broken interface: bad member `{}`", member)
+            }
+            InterfaceError::IntWidth(unit) => {
+                write!(f, "unit `{}` is compiled with the other width of int", unit)
+            }
+        }
+    }
+}
+
+impl std::error::Error for InterfaceError {}
+
+fn type_to_json(ty: &Type) -> Value {
+    match ty {
+        Type::Char => "char".into(),
+        Type::Int => "int".into(),
+        Type::IntInf => "IntInf.int".into(),
+        Type::Word(WordSize::W8) => "Word8.word".into(),
+        Type::Word(WordSize::W32) => "word".into(),
+        Type::Word(WordSize::W64) => "Word64.word".into(),
+        Type::Real => "real".into(),
+        Type::String => "string".into(),
+        Type::Fun(param, ret) => json!({ "fun": [type_to_json(param), type_to_json(ret)] }),
+        Type::Tuple(tys) => json!({ "tuple": tys.iter().map(type_to_json).collect::<Vec<_>>() }),
+        Type::Datatype(name) => json!({ "datatype": name.0 }),
+        Type::Variable(_) => unreachable!("the top level types are resolved"),
+    }
+}
+
+fn type_from_json(value: &Value) -> Option<Type> {
+    let ty = match value.as_str() {
+        Some("char") => Type::Char,
+        Some("int") => Type::Int,
+        Some("IntInf.int") => Type::IntInf,
+        Some("Word8.word") => Type::Word(WordSize::W8),
+        Some("word") => Type::Word(WordSize::W32),
+        Some("Word64.word") => Type::Word(WordSize::W64),
+        Some("real") => Type::Real,
+        Some("string") => Type::String,
+        Some(_) => return None,
+        None => match (
+            value["fun"].as_array().map(Vec::as_slice),
+            value["tuple"].as_array(),
+            value["datatype"].as_str(),
+        ) {
+            (Some([param, ret]), _, _) => Type::fun(type_from_json(param)?, type_from_json(ret)?),
+            (_, Some(tys), _) => {
+                Type::Tuple(tys.iter().map(type_from_json).collect::<Option<Vec<_>>>()?)
+            }
+            (_, _, Some(name)) => Type::Datatype(Symbol::new(name)),
+            _ => return None,
+        },
+    };
+    Some(ty)
+}
+
+impl Interface {
+    pub fn to_json(&self) -> Value {
+        let int_width = match self.int_width {
+            IntWidth::Int32 => 32,
+            IntWidth::Int64 => 64,
+        };
+        let imports = self
+            .imports
+            .iter()
+            .map(|import| json!({ "unit": import.unit, "wasm": import.wasm }));
+        let fixities = self
+            .fixities
+            .iter()
+            .map(|(priority, names)| json!({ "priority": priority, "names": names }));
+        let datatypes = self.datatypes.iter().map(|datatype| {
+            let constructors = datatype
+                .constructors
+                .iter()
+                .map(|(name, arg)| json!({ "name": name, "arg": arg.as_ref().map(type_to_json) }));
+            json!({
+                "name": datatype.name,
+                "unit": datatype.unit,
+                "origin": datatype.origin,
+                "constructors": constructors.collect::<Vec<_>>(),
+            })
+        });
+        let values = self.values.iter().map(|binding| {
+            json!({
+                "name": binding.name,
+                "type": type_to_json(&binding.ty),
+                "function": binding.function,
+            })
+        });
+        json!({
+            "unit": self.unit,
+            "int_width": int_width,
+            "imports": imports.collect::<Vec<_>>(),
+            "fixities": fixities.collect::<Vec<_>>(),
+            "datatypes": datatypes.collect::<Vec<_>>(),
+            "values": values.collect::<Vec<_>>(),
+        })
+    }
+
+    pub fn from_json(value: &Value) -> Result<Self, InterfaceError> {
+        use self::InterfaceError::Malformed;
+
+        fn string(value: &Value, member: &'static str) -> Result<String, InterfaceError> {
+            value[member]
+                .as_str()
+                .map(ToString::to_string)
+                .ok_or(Malformed(member))
+        }
+        fn array<'a>(
+            value: &'a Value,
+            member: &'static str,
+        ) -> Result<&'a [Value], InterfaceError> {
+            value[member]
+                .as_array()
+                .map(Vec::as_slice)
+                .ok_or(Malformed(member))
+        }
+
+        let int_width = match value["int_width"].as_u64() {
+            Some(32) => IntWidth::Int32,
+            Some(64) => IntWidth::Int64,
+            _ => return Err(Malformed("int_width")),
+        };
+        let imports = array(value, "imports")?
+            .iter()
+            .map(|import| {
+                Ok(Import {
+                    unit: string(import, "unit")?,
+                    wasm: string(import, "wasm")?,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let mut fixities = BTreeMap::new();
+        for fixity in array(value, "fixities")? {
+            let priority = match fixity["priority"].as_u64() {
+                Some(priority) if priority <= 9 => priority as u8,
+                _ => return Err(Malformed("priority")),
+            };
+            let names = array(fixity, "names")?
+                .iter()
+                .map(|name| name.as_str().map(ToString::to_string))
+                .collect::<Option<_>>()
+                .ok_or(Malformed("names"))?;
+            fixities.insert(priority, names);
+        }
+        let datatypes = array(value, "datatypes")?
+            .iter()
+            .map(|datatype| {
+                let constructors = array(datatype, "constructors")?
+                    .iter()
+                    .map(|constructor| {
+                        let arg = &constructor["arg"];
+                        let arg = match arg.is_null() {
+                            true => None,
+                            false => Some(type_from_json(arg).ok_or(Malformed("arg"))?),
+                        };
+                        Ok((string(constructor, "name")?, arg))
+                    })
+                    .collect::<Result<_, _>>()?;
+                Ok(Datatype {
+                    name: string(datatype, "name")?,
+                    unit: string(datatype, "unit")?,
+                    origin: string(datatype, "origin")?,
+                    constructors,
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        let values = array(value, "values")?
+            .iter()
+            .map(|binding| {
+                Ok(Binding {
+                    name: string(binding, "name")?,
+                    ty: type_from_json(&binding["type"]).ok_or(Malformed("type"))?,
+                    function: match binding["function"] {
+                        Value::Bool(function) => function,
+                        _ => return Err(Malformed("function")),
+                    },
+                })
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Interface {
+            unit: string(value, "unit")?,
+            int_width,
+            imports,
+            fixities,
+            datatypes,
+            values,
+        })
+    }
+
+    pub fn parse(input: &str) -> Result<Self, InterfaceError> {
+        let value =
+            serde_json::from_str(input).map_err(|e| InterfaceError::Syntax(e.to_string()))?;
+        Self::from_json(&value)
+    }
+}
+
+/// the units a unit is compiled against, and the declarations binding what they export.
+/// the declarations are put in front of the unit
+#[derive(Debug, Clone, Default)]
+pub struct Imports {
+    pub units: Vec<Import>,
+    prologue: String,
+    /// the datatypes the prologue declares by the names in it, with the unit and the name they come from
+    datatypes: HashMap<String, (String, String)>,
+    /// the range of the input the prologue is put in
+    span: Option<Span>,
+}
+
+fn is_symbolic(name: &str) -> bool {
+    !name.starts_with(|c: char| c.is_ascii_alphabetic())
+}
+
+impl Imports {
+    /// the interfaces with the paths to their wasm modules
+    pub fn new(
+        interfaces: &[(Interface, String)],
+        config: &Config,
+    ) -> Result<Self, InterfaceError> {
+        let mut units = Vec::<Import>::new();
+        for (interface, wasm) in interfaces {
+            if interface.int_width != config.int_width {
+                return Err(InterfaceError::IntWidth(interface.unit.clone()));
+            }
+            let import = Import {
+                unit: interface.unit.clone(),
+                wasm: wasm.clone(),
+            };
+            for import in interface.imports.iter().chain(Some(&import)) {
+                if units.iter().all(|u| u.unit != import.unit) {
+                    units.push(import.clone())
+                }
+            }
+        }
+
+        // a datatype imported through several units is declared once.
+        // the ones sharing a name are told apart by the suffix
+        let mut origins = Vec::<(&str, &str)>::new();
+        for (interface, _) in interfaces {
+            for datatype in &interface.datatypes {
+                let origin = (datatype.unit.as_str(), datatype.origin.as_str());
+                if !origins.contains(&origin) {
+                    origins.push(origin)
+                }
+            }
+        }
+        let names = origins
+            .iter()
+            .enumerate()
+            .map(|(i, &(unit, name))| {
+                let shared = origins.iter().filter(|(_, n)| *n == name).count() > 1;
+                let local = match shared {
+                    true => format!("{}'{}", name, i),
+                    false => name.to_string(),
+                };
+                ((unit, name), local)
+            })
+            .collect::<HashMap<_, _>>();
+
+        let mut prologue = String::new();
+        let mut declared = HashSet::new();
+        let mut printer = Printer::new();
+        for (interface, _) in interfaces {
+            // the names in the interface to the names in the prologue
+            let local = interface
+                .datatypes
+                .iter()
+                .map(|d| {
+                    (
+                        d.name.as_str(),
+                        names[&(d.unit.as_str(), d.origin.as_str())].as_str(),
+                    )
+                })
+                .collect::<HashMap<_, _>>();
+            let mut ty = |ty: &Type| {
+                let ty = rename_datatypes(ty, &|name| {
+                    local.get(name.0.as_str()).map(|n| n.to_string())
+                });
+                printer.ty(&ty)
+            };
+
+            for datatype in &interface.datatypes {
+                let name = local[datatype.name.as_str()];
+                if !declared.insert(name) {
+                    continue;
+                }
+                let constructors = datatype
+                    .constructors
+                    .iter()
+                    .map(|(constructor, arg)| match arg {
+                        Some(arg) => format!("{} of {}", constructor, ty(arg)),
+                        None => constructor.clone(),
+                    })
+                    .collect::<Vec<_>>();
+                prologue += &format!("datatype {} = {}\n", name, constructors.join(" | "));
+            }
+            for binding in &interface.values {
+                let name = &binding.name;
+                let op = if is_symbolic(name) { "op " } else { "" };
+                match &binding.ty {
+                    Type::Fun(..) if binding.function => {
+                        let (params, ret) = uncurry(&binding.ty);
+                        // the worker of a curried function takes the parameters in a tuple
+                        let (args, arg) = match params.len() {
+                            1 => ("x".to_string(), "x".to_string()),
+                            n => {
+                                let args = (1..=n).map(|i| format!("x{}", i)).collect::<Vec<_>>();
+                                (args.join(" "), format!("({})", args.join(", ")))
+                            }
+                        };
+                        let curried = params.len() > 1;
+                        let params = params
+                            .into_iter()
+                            .map(|param| match param {
+                                Type::Tuple(_) if curried => format!("({})", ty(param)),
+                                _ => ty(param),
+                            })
+                            .collect::<Vec<_>>();
+                        prologue += &format!(
+                            "fun {}{} {} = _externcall({:?}.{:?}: ({}) -> {})({})\n",
+                            op,
+                            name,
+                            args,
+                            interface.unit,
+                            name,
+                            params.join(" * "),
+                            ty(ret),
+                            arg
+                        )
+                    }
+                    _ => {
+                        prologue += &format!(
+                            "val {}{} = _externcall({:?}.{:?}: (unit) -> {})(())\n",
+                            op,
+                            name,
+                            VALUES_MODULE,
+                            format!("{}.{}", interface.unit, name),
+                            ty(&binding.ty)
+                        )
+                    }
+                }
+            }
+            for (priority, names) in &interface.fixities {
+                prologue += &format!("infix {} {}\n", priority, names.join(" "));
+            }
+        }
+
+        Ok(Imports {
+            units,
+            prologue,
+            datatypes: names
+                .into_iter()
+                .map(|((unit, name), local)| (local, (unit.to_string(), name.to_string())))
+                .collect(),
+            span: None,
+        })
+    }
+
+    pub fn prologue(&self) -> &str {
+        &self.prologue
+    }
+
+    /// puts the prologue in the sources. it should come before the files of the unit
+    pub fn add_to(&mut self, sources: &mut Sources) {
+        if self.units.is_empty() {
+            return;
+        }
+        sources.add_file("<imports>", &self.prologue);
+        self.span = sources.files().last().map(|file| file.span);
+    }
+
+    /// whether the name is bound in the prologue, not in the unit
+    fn declares(&self, name: &Symbol) -> bool {
+        match (self.span, name.2) {
+            (Some(span), Some(at)) => span.start <= at.start && at.end <= span.end,
+            _ => false,
+        }
+    }
+
+    /// the unit and the name the datatype comes from, if imported
+    fn origin(&self, name: &Symbol) -> Option<&(String, String)> {
+        if self.declares(name) {
+            self.datatypes.get(&name.0)
+        } else {
+            None
+        }
+    }
+}
+
+fn rename_datatypes(ty: &Type, rename: &impl Fn(&Symbol) -> Option<String>) -> Type {
+    match ty {
+        Type::Datatype(name) => match rename(name) {
+            Some(name) => Type::Datatype(Symbol::new(name)),
+            None => ty.clone(),
+        },
+        Type::Fun(param, ret) => Type::fun(
+            rename_datatypes(param, rename),
+            rename_datatypes(ret, rename),
+        ),
+        Type::Tuple(tys) => {
+            Type::Tuple(tys.iter().map(|ty| rename_datatypes(ty, rename)).collect())
+        }
+        _ => ty.clone(),
+    }
+}
+
+/// the parameters of the curried function type and the result
+fn uncurry(ty: &Type) -> (Vec<&Type>, &Type) {
+    let mut params = Vec::new();
+    let mut ret = ty;
+    while let Type::Fun(param, r) = ret {
+        params.push(&**param);
+        ret = r;
+    }
+    (params, ret)
+}
+
+/// whether the values of the type have no functions in them
+fn first_order(ty: &Type, symbol_table: &SymbolTable, seen: &mut HashSet<Symbol>) -> bool {
+    match ty {
+        Type::Variable(_) | Type::Fun(..) => false,
+        Type::Tuple(tys) => tys.iter().all(|ty| first_order(ty, symbol_table, seen)),
+        Type::Datatype(name) => {
+            if !seen.insert(name.clone()) {
+                return true;
+            }
+            symbol_table.get_type(name).is_none_or(|info| {
+                info.constructors.iter().all(|(_, arg)| {
+                    arg.iter().all(|arg| {
+                        let arg = symbol_table.expand_type_aliases(arg.clone());
+                        first_order(&arg, symbol_table, seen)
+                    })
+                })
+            })
+        }
+        Type::Char | Type::Int | Type::IntInf | Type::Word(_) | Type::Real | Type::String => true,
+    }
+}
+
+/// the names the top level binds, each with the type and whether it is bound to a `fn` by
+/// `val rec` or `fun`, which lowers to a function of the name.
+/// the later bindings shadow the former
+fn top_bindings<'a>(
+    decls: &'a [TypedCoreDeclaration],
+    bindings: &mut Vec<(&'a Symbol, &'a Type, bool)>,
+) {
+    for decl in decls {
+        match decl {
+            Declaration::Val { rec, pattern, expr } => {
+                let function = *rec
+                    && matches!(
+                        (&pattern.inner, &expr.inner),
+                        (PatternKind::Variable { .. }, ExprKind::Fn { .. })
+                    );
+                for (name, ty) in pattern.binds() {
+                    bindings.retain(|(bound, _, _)| bound.0 != name.0);
+                    bindings.push((name, ty, function))
+                }
+            }
+            Declaration::Local { body, .. } => top_bindings(body, bindings),
+            Declaration::Abstype { decls, .. } => top_bindings(decls, bindings),
+            Declaration::And(decls) => top_bindings(decls, bindings),
+            Declaration::Datatype { .. } | Declaration::Type { .. } => (),
+            Declaration::D(d) => match *d {},
+        }
+    }
+}
+
+/// the interface of the typed unit, and the functions its module should export by the names.
+/// the bindings of the values to pass to the other units and the workers of the curried
+/// functions are appended to `core`
+pub fn export<'a>(
+    unit: &str,
+    imports: &Imports,
+    fixities: &Fixities,
+    symbol_table: &SymbolTable,
+    core: &mut TypedCore,
+    mut id: Id,
+    config: &Config,
+) -> Result<(Interface, Vec<(String, Symbol)>), TypeError<'a>> {
+    // the builtin datatypes have the id 0
+    let mut types = symbol_table
+        .types
+        .iter()
+        .filter(|(name, _)| name.1 != 0)
+        .collect::<Vec<_>>();
+    types.sort_by_key(|(name, _)| name.1);
+    let names = types
+        .iter()
+        .map(|(name, _)| {
+            // the last of the datatypes sharing the name keeps the name
+            let shadowed = types.iter().any(|(n, _)| n.0 == name.0 && n.1 > name.1);
+            let local = match shadowed {
+                true => format!("{}'{}", name.0, name.1),
+                false => name.0.clone(),
+            };
+            ((*name).clone(), local)
+        })
+        .collect::<HashMap<_, _>>();
+    let local = |ty: &Type| {
+        let ty = symbol_table.expand_type_aliases(ty.clone());
+        rename_datatypes(&ty, &|name| names.get(name).cloned())
+    };
+    let datatypes = types
+        .iter()
+        .map(|(name, info)| {
+            let (unit, origin) = match imports.origin(name) {
+                Some((unit, origin)) => (unit.clone(), origin.clone()),
+                None => (unit.to_string(), names[name].clone()),
+            };
+            Datatype {
+                name: names[name].clone(),
+                unit,
+                origin,
+                constructors: info
+                    .constructors
+                    .iter()
+                    .map(|(constructor, arg)| (constructor.0.clone(), arg.as_ref().map(&local)))
+                    .collect(),
+            }
+        })
+        .collect();
+
+    let mut bindings = Vec::new();
+    top_bindings(&core.0, &mut bindings);
+    // the imported ones are imported again from their units
+    bindings.retain(|(name, _, _)| !imports.declares(name));
+    let mut values = Vec::new();
+    let mut exports = Vec::new();
+    let mut decls = Vec::new();
+    for (name, ty, function) in bindings {
+        let first_order = |ty| first_order(ty, symbol_table, &mut HashSet::new());
+        let expanded = symbol_table.expand_type_aliases(ty.clone());
+        let (params, ret) = uncurry(&expanded);
+        if params.is_empty() && first_order(ty) {
+            decls.push(setter(unit, name, ty));
+            values.push(Binding {
+                name: name.0.clone(),
+                ty: local(ty),
+                function: false,
+            });
+        } else if !params.is_empty()
+            && params.iter().all(|param| first_order(param))
+            && first_order(ret)
+        {
+            // a function of a parameter declared by `fun` is exported as is
+            let export = if function && params.len() == 1 {
+                name.clone()
+            } else {
+                let (worker, decl) = worker(name, &params, ret, &mut id);
+                decls.push(decl);
+                worker
+            };
+            exports.push((name.0.clone(), export));
+            values.push(Binding {
+                name: name.0.clone(),
+                ty: local(ty),
+                function: true,
+            });
+        } else {
+            return Err(TypeError::NotExported(name.0.clone(), local(ty), name.2));
+        }
+    }
+    core.0.extend(decls);
+
+    let interface = Interface {
+        unit: unit.to_string(),
+        int_width: config.int_width,
+        imports: imports.units.clone(),
+        fixities: fixities
+            .iter()
+            .map(|(&priority, names)| {
+                let names = names
+                    .iter()
+                    .filter(|name| !imports.declares(name))
+                    .map(|name| name.0.clone())
+                    .collect::<Vec<_>>();
+                (priority, names)
+            })
+            .filter(|(_, names)| !names.is_empty())
+            .collect(),
+        datatypes,
+        values,
+    };
+    Ok((interface, exports))
+}
+
+/// `val rec name' = fn t => case t of (x1, ..., xn) => name x1 ... xn`,
+/// the function `name` taking the parameters `params` in a tuple
+fn worker(
+    name: &Symbol,
+    params: &[&Type],
+    ret: &Type,
+    id: &mut Id,
+) -> (Symbol, TypedCoreDeclaration) {
+    let mut gensym = |name: &str| Symbol(format!("#{}", name), id.next(), None);
+    let worker = gensym(&name.0);
+    let tuple = gensym("t");
+    let args = params
+        .iter()
+        .map(|param| ((*param).clone(), gensym("x")))
+        .collect::<Vec<_>>();
+    let tuple_ty = match params {
+        [param] => (*param).clone(),
+        _ => Type::Tuple(params.iter().map(|param| (*param).clone()).collect()),
+    };
+    let variable = |(ty, name): &(Type, Symbol)| Pattern {
+        ty: ty.clone(),
+        inner: PatternKind::Variable { name: name.clone() },
+    };
+    let pattern = match args.as_slice() {
+        [arg] => variable(arg),
+        _ => Pattern {
+            ty: tuple_ty.clone(),
+            inner: PatternKind::Tuple {
+                tuple: args.iter().map(variable).collect(),
+            },
+        },
+    };
+    // the applications to the parameters one by one, from the last
+    let fun_ty = args
+        .iter()
+        .rev()
+        .fold(ret.clone(), |ret, (ty, _)| Type::fun(ty.clone(), ret));
+    let call = args.iter().fold(
+        Expr {
+            ty: fun_ty,
+            inner: ExprKind::Symbol { name: name.clone() },
+        },
+        |fun, (ty, arg)| {
+            let ret = match &fun.ty {
+                Type::Fun(_, ret) => (**ret).clone(),
+                _ => unreachable!(),
+            };
+            Expr {
+                ty: ret,
+                inner: ExprKind::App {
+                    fun: fun.boxed(),
+                    arg: Expr {
+                        ty: ty.clone(),
+                        inner: ExprKind::Symbol { name: arg.clone() },
+                    }
+                    .boxed(),
+                },
+            }
+        },
+    );
+    // bound by `val rec` to be lowered to a function of the name
+    let decl = Declaration::Val {
+        rec: true,
+        pattern: Pattern {
+            ty: Type::fun(tuple_ty.clone(), ret.clone()),
+            inner: PatternKind::Variable {
+                name: worker.clone(),
+            },
+        },
+        expr: Expr {
+            ty: Type::fun(tuple_ty.clone(), ret.clone()),
+            inner: ExprKind::Fn {
+                param: tuple.clone(),
+                body: Expr {
+                    ty: ret.clone(),
+                    inner: ExprKind::Case {
+                        cond: Expr {
+                            ty: tuple_ty,
+                            inner: ExprKind::Symbol { name: tuple },
+                        }
+                        .boxed(),
+                        clauses: vec![(pattern, call)],
+                    },
+                }
+                .boxed(),
+            },
+        },
+    };
+    (worker, decl)
+}
+
+/// `val _ = _externcall(VALUES_MODULE."set unit.name": (ty) -> unit)(name)`
+fn setter(unit: &str, name: &Symbol, ty: &Type) -> TypedCoreDeclaration {
+    Declaration::Val {
+        rec: false,
+        pattern: Pattern {
+            ty: Type::unit(),
+            inner: PatternKind::Wildcard {},
+        },
+        expr: Expr {
+            ty: Type::unit(),
+            inner: ExprKind::ExternCall {
+                module: VALUES_MODULE.to_string(),
+                fun: format!("set {}.{}", unit, name.0),
+                args: vec![Expr {
+                    ty: ty.clone(),
+                    inner: ExprKind::Symbol { name: name.clone() },
+                }],
+                argty: vec![ty.clone()],
+                retty: Type::unit(),
+            },
+        },
+    }
+}
+
+#[test]
+fn test_json() {
+    let interface = Interface {
+        unit: "shapes".into(),
+        int_width: IntWidth::Int64,
+        imports: vec![Import {
+            unit: "prelude".into(),
+            wasm: "/lib/prelude.wasm".into(),
+        }],
+        fixities: vec![(6, vec!["++".to_string()])].into_iter().collect(),
+        datatypes: vec![Datatype {
+            name: "shape".into(),
+            unit: "shapes".into(),
+            origin: "shape".into(),
+            constructors: vec![("Circle".into(), Some(Type::Real)), ("Point".into(), None)],
+        }],
+        values: vec![
+            Binding {
+                name: "area".into(),
+                ty: Type::fun(Type::Datatype(Symbol::new("shape")), Type::Real),
+                function: true,
+            },
+            Binding {
+                name: "origin".into(),
+                ty: Type::Tuple(vec![Type::Int, Type::Word(WordSize::W8)]),
+                function: false,
+            },
+        ],
+    };
+    let json = interface.to_json().to_string();
+    assert_eq!(Interface::parse(&json), Ok(interface));
+    assert_eq!(
+        Interface::parse(r#"{"unit": "a"}"#),
+        Err(InterfaceError::Malformed("int_width"))
+    );
+}
+
+#[test]
+fn test_prologue() {
+    let shape = |unit: &str, name: &str| Datatype {
+        name: name.into(),
+        unit: unit.into(),
+        origin: "shape".into(),
+        constructors: vec![("Square".into(), Some(Type::Real))],
+    };
+    let a = Interface {
+        unit: "a".into(),
+        int_width: IntWidth::Int32,
+        imports: vec![],
+        fixities: vec![(6, vec!["++".to_string()])].into_iter().collect(),
+        datatypes: vec![shape("a", "shape")],
+        values: vec![
+            Binding {
+                name: "++".into(),
+                ty: Type::fun(Type::Tuple(vec![Type::Int, Type::Int]), Type::Int),
+                function: true,
+            },
+            Binding {
+                name: "add".into(),
+                ty: Type::fun(Type::Int, Type::fun(Type::Int, Type::Int)),
+                function: true,
+            },
+            Binding {
+                name: "unit".into(),
+                ty: Type::Datatype(Symbol::new("shape")),
+                function: false,
+            },
+        ],
+    };
+    // `b` imports `a` and declares a datatype of the same name
+    let b = Interface {
+        unit: "b".into(),
+        imports: vec![Import {
+            unit: "a".into(),
+            wasm: "a.wasm".into(),
+        }],
+        fixities: BTreeMap::new(),
+        datatypes: vec![shape("a", "shape'3"), shape("b", "shape")],
+        values: vec![Binding {
+            name: "grow".into(),
+            ty: Type::fun(
+                Type::Datatype(Symbol::new("shape'3")),
+                Type::Datatype(Symbol::new("shape")),
+            ),
+            function: true,
+        }],
+        ..a.clone()
+    };
+    let interfaces = [(a, "a.wasm".to_string()), (b, "b.wasm".to_string())];
+    let imports = Imports::new(&interfaces, &Config::default()).unwrap();
+    let units = imports
+        .units
+        .iter()
+        .map(|u| u.unit.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(units, vec!["a", "b"]);
+    assert_eq!(
+        imports.prologue(),
+        r#"datatype shape'0 = Square of real
+fun op ++ x = _externcall("a"."++": (int * int) -> int)(x)
+fun add x1 x2 = _externcall("a"."add": (int * int) -> int)((x1, x2))
+val unit = _externcall("webml-values"."a.unit": (unit) -> shape'0)(())
+infix 6 ++
+datatype shape'1 = Square of real
+fun grow x = _externcall("b"."grow": (shape'0) -> shape'1)(x)
+"#
+    );
+}
@@ -0,0 +1,527 @@
+//! splits the source into tokens. spaces and comments are dropped here
+use crate::parser::{Expected, ParseError, Position};
+use crate::prim::Span;
+
+static RESERVED_WORDS: &[&str] = &[
+    "abstype",
+    "and",
+    "andalso",
+    "as",
+    "case",
+    "datatype",
+    "do",
+    "else",
+    "end",
+    "exception",
+    "fn",
+    "fun",
+    "handle",
+    "if",
+    "in",
+    "infix",
+    "infixr",
+    "let",
+    "local",
+    "nonfix",
+    "of",
+    "op",
+    "open",
+    "orelse",
+    "raise",
+    "rec",
+    "then",
+    "type",
+    "val",
+    "with",
+    "withtype",
+    "while",
+];
+
+/// symbolic sequences which are not identifiers
+static RESERVED_SYMBOLS: &[&str] = &["=", "=>", "->", "|", ":", ":>", "#"];
+
+static PUNCTUATIONS: &[&str] = &["...", "(", ")", "[", "]", "{", "}", ",", ";", ".", "_"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Token<'a> {
+    pub kind: TokenKind<'a>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TokenKind<'a> {
+    /// reserved words, reserved symbols and punctuations
+    Reserved(&'static str),
+    /// alphanumeric or symbolic identifiers
+    Ident(&'a str),
+    /// qualified identifiers like `Math.sin`
+    LongIdent(&'a str),
+    /// `'a` or `''a`
+    TyVar(&'a str),
+    /// decimal digits, with `-` for negatives. may not fit in `i64`
+    Int(String),
+    Word(u64),
+    /// a word beyond 64 bits as written. left to typing to report
+    LargeWord(&'a str),
+    Real(f64),
+    Char(u32),
+    String(String),
+    /// the end of the input, always at the last
+    Eof,
+}
+
+pub fn lex(input: &str) -> Result<Vec<Token<'_>>, Vec<ParseError<'_>>> {
+    let mut lexer = Lexer {
+        input,
+        pos: 0,
+        tokens: Vec::new(),
+        errors: Vec::new(),
+    };
+    lexer.run();
+    if lexer.errors.is_empty() {
+        Ok(lexer.tokens)
+    } else {
+        Err(lexer.errors)
+    }
+}
+
+fn is_symbolic(c: char) -> bool {
+    "!%&$#+-/:<=>?@\\~`^|*".contains(c)
+}
+
+fn is_alphanumeric(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_' || c == '\''
+}
+
+struct Lexer<'a> {
+    input: &'a str,
+    pos: usize,
+    tokens: Vec<Token<'a>>,
+    errors: Vec<ParseError<'a>>,
+}
+
+impl<'a> Lexer<'a> {
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn peek(&self, n: usize) -> Option<char> {
+        self.rest().chars().nth(n)
+    }
+
+    /// the length of the prefix of the rest of which the chars satisfy `f`
+    fn span_of(&self, from: usize, f: impl Fn(char) -> bool) -> usize {
+        let rest = &self.input[from..];
+        rest.find(|c| !f(c)).unwrap_or(rest.len())
+    }
+
+    fn error(&mut self, start: usize, end: usize, expected: Expected) {
+        self.errors.push(ParseError {
+            position: Position::at(self.input, start),
+            span: Span { start, end },
+            found: &self.input[start..end],
+            expected: vec![expected],
+            context: None,
+        })
+    }
+
+    fn run(&mut self) {
+        loop {
+            self.skip_spaces();
+            let start = self.pos;
+            let c = match self.peek(0) {
+                Some(c) => c,
+                None => break,
+            };
+            let kind = match c {
+                '~' if self.peek(1).is_some_and(|c| c.is_ascii_digit()) => self.number(),
+                c if c.is_ascii_digit() => self.number(),
+                '"' => self.string(),
+                '#' if self.peek(1) == Some('"') => self.char(),
+                '\'' => {
+                    let quotes = self.span_of(self.pos, |c| c == '\'');
+                    self.pos += quotes;
+                    self.pos += self.span_of(self.pos, is_alphanumeric);
+                    Some(TokenKind::TyVar(&self.input[start..self.pos]))
+                }
+                c if c.is_ascii_alphabetic() || c == '_' => Some(self.identifier()),
+                c if is_symbolic(c) => {
+                    self.pos += self.span_of(self.pos, is_symbolic);
+                    let symbol = &self.input[start..self.pos];
+                    match RESERVED_SYMBOLS.iter().find(|s| **s == symbol) {
+                        Some(reserved) => Some(TokenKind::Reserved(reserved)),
+                        None => Some(TokenKind::Ident(symbol)),
+                    }
+                }
+                c => match PUNCTUATIONS.iter().find(|p| self.rest().starts_with(*p)) {
+                    Some(p) => {
+                        self.pos += p.len();
+                        Some(TokenKind::Reserved(p))
+                    }
+                    None => {
+                        self.pos += c.len_utf8();
+                        self.error(start, self.pos, Expected::Label("a token"));
+                        None
+                    }
+                },
+            };
+            if let Some(kind) = kind {
+                self.push(kind, start)
+            }
+        }
+        self.push(TokenKind::Eof, self.pos)
+    }
+
+    fn push(&mut self, kind: TokenKind<'a>, start: usize) {
+        self.tokens.push(Token {
+            kind,
+            span: Span {
+                start,
+                end: self.pos,
+            },
+        })
+    }
+
+    // comments nest as `(* (* *) *)`
+    fn skip_spaces(&mut self) {
+        loop {
+            self.pos += self.span_of(self.pos, char::is_whitespace);
+            if !self.rest().starts_with("(*") {
+                return;
+            }
+            let start = self.pos;
+            let mut depth = 0;
+            loop {
+                let rest = self.rest();
+                if rest.starts_with("(*") {
+                    depth += 1;
+                    self.pos += 2;
+                } else if rest.starts_with("*)") {
+                    depth -= 1;
+                    self.pos += 2;
+                    if depth == 0 {
+                        break;
+                    }
+                } else if let Some(c) = rest.chars().next() {
+                    self.pos += c.len_utf8();
+                } else {
+                    self.errors.push(ParseError {
+                        position: Position::at(self.input, self.pos),
+                        span: Span {
+                            start: self.pos,
+                            end: self.pos,
+                        },
+                        found: "",
+                        expected: vec![Expected::Token("*)")],
+                        context: Some(("(*", Position::at(self.input, start))),
+                    });
+                    return;
+                }
+            }
+        }
+    }
+
+    // reserved words, identifiers and long identifiers like `Math.sin` or `Int.+`
+    fn identifier(&mut self) -> TokenKind<'a> {
+        let start = self.pos;
+        self.pos += self.span_of(self.pos, is_alphanumeric);
+        let word = &self.input[start..self.pos];
+        if let Some(reserved) = RESERVED_WORDS
+            .iter()
+            .chain(PUNCTUATIONS)
+            .find(|w| **w == word)
+        {
+            return TokenKind::Reserved(reserved);
+        }
+        let mut long = false;
+        while self.peek(0) == Some('.') {
+            match self.peek(1) {
+                Some(c) if c.is_ascii_alphabetic() => {
+                    self.pos += 1;
+                    self.pos += self.span_of(self.pos, is_alphanumeric);
+                    long = true;
+                }
+                Some(c) if is_symbolic(c) => {
+                    self.pos += 1;
+                    self.pos += self.span_of(self.pos, is_symbolic);
+                    long = true;
+                    break;
+                }
+                _ => break,
+            }
+        }
+        let ident = &self.input[start..self.pos];
+        if long {
+            TokenKind::LongIdent(ident)
+        } else {
+            TokenKind::Ident(ident)
+        }
+    }
+
+    // `123`, `~0x7f`, `0w255`, `0wxff`, `1.5`, `1e10` and `~1.5E~3`
+    fn number(&mut self) -> Option<TokenKind<'a>> {
+        let start = self.pos;
+        let negative = self.peek(0) == Some('~');
+        if negative {
+            self.pos += 1;
+        }
+        let digits = |this: &Self, from: usize| this.span_of(from, |c| c.is_ascii_digit());
+        let hex_digits = |this: &Self, from: usize| this.span_of(from, |c| c.is_ascii_hexdigit());
+        let rest = self.rest();
+        let radix = if rest.starts_with("0wx") && hex_digits(self, self.pos + 3) > 0 && !negative {
+            Some((3, 16, true))
+        } else if rest.starts_with("0w") && digits(self, self.pos + 2) > 0 && !negative {
+            Some((2, 10, true))
+        } else if rest.starts_with("0x") && hex_digits(self, self.pos + 2) > 0 {
+            Some((2, 16, false))
+        } else {
+            None
+        };
+        if let Some((prefix, radix, word)) = radix {
+            let from = self.pos + prefix;
+            let len = if radix == 16 {
+                hex_digits(self, from)
+            } else {
+                digits(self, from)
+            };
+            self.pos = from + len;
+            let digits = &self.input[from..self.pos];
+            return if word {
+                match u64::from_str_radix(digits, radix) {
+                    Ok(w) => Some(TokenKind::Word(w)),
+                    Err(_) => Some(TokenKind::LargeWord(&self.input[start..self.pos])),
+                }
+            } else {
+                let digits = if negative {
+                    format!("-{}", digits)
+                } else {
+                    digits.to_string()
+                };
+                match i64::from_str_radix(&digits, radix) {
+                    Ok(n) => Some(TokenKind::Int(n.to_string())),
+                    Err(_) => {
+                        self.error(start, self.pos, Expected::Label("an integer of 64 bits"));
+                        None
+                    }
+                }
+            };
+        }
+
+        self.pos += digits(self, self.pos);
+        let mut real = false;
+        if self.peek(0) == Some('.') && self.peek(1).is_some_and(|c| c.is_ascii_digit()) {
+            self.pos += 1;
+            self.pos += digits(self, self.pos);
+            real = true;
+        }
+        if let Some('e') | Some('E') = self.peek(0) {
+            let sign = if self.peek(1) == Some('~') { 1 } else { 0 };
+            let len = digits(self, self.pos + 1 + sign);
+            if len > 0 {
+                self.pos += 1 + sign + len;
+                real = true;
+            }
+        }
+        let text = self.input[start..self.pos].replace('~', "-");
+        if real {
+            match text.parse() {
+                Ok(f) => Some(TokenKind::Real(f)),
+                Err(_) => {
+                    self.error(start, self.pos, Expected::Label("a real"));
+                    None
+                }
+            }
+        } else {
+            Some(TokenKind::Int(text))
+        }
+    }
+
+    fn string(&mut self) -> Option<TokenKind<'a>> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        let mut ok = true;
+        loop {
+            let c = match self.peek(0) {
+                Some(c) => c,
+                None => {
+                    self.errors.push(ParseError {
+                        position: Position::at(self.input, self.pos),
+                        span: Span {
+                            start: self.pos,
+                            end: self.pos,
+                        },
+                        found: "",
+                        expected: vec![Expected::Token("\"")],
+                        context: Some(("\"", Position::at(self.input, start))),
+                    });
+                    return None;
+                }
+            };
+            self.pos += c.len_utf8();
+            match c {
+                '"' => break,
+                '\\' => match self.escape() {
+                    Ok(Some(c)) => s.push(c),
+                    Ok(None) => (),
+                    Err(escape_start) => {
+                        self.error(
+                            escape_start,
+                            self.pos,
+                            Expected::Label("an escape sequence"),
+                        );
+                        ok = false;
+                    }
+                },
+                c => s.push(c),
+            }
+        }
+        if ok {
+            Some(TokenKind::String(s))
+        } else {
+            None
+        }
+    }
+
+    /// reads an escape sequence after `\`. `None` for `\ ... \`, which is ignored.
+    /// on errors, returns where the sequence began
+    fn escape(&mut self) -> Result<Option<char>, usize> {
+        let start = self.pos - 1;
+        let c = self.peek(0).ok_or(start)?;
+        self.pos += c.len_utf8();
+        let code = |this: &mut Self, len: usize, radix: u32| {
+            let digits = this.rest().get(..len).ok_or(start)?;
+            let code = u32::from_str_radix(digits, radix).map_err(|_| start)?;
+            this.pos += len;
+            std::char::from_u32(code).ok_or(start)
+        };
+        let c = match c {
+            'a' => '\x07',
+            'b' => '\x08',
+            't' => '\t',
+            'n' => '\n',
+            'v' => '\x0b',
+            'f' => '\x0c',
+            'r' => '\r',
+            '\\' => '\\',
+            '"' => '"',
+            '^' => match self.peek(0) {
+                Some(c @ '@'..='_') => {
+                    self.pos += 1;
+                    (c as u8 - b'@') as char
+                }
+                _ => return Err(start),
+            },
+            'u' => code(self, 4, 16)?,
+            c if c.is_ascii_digit() => {
+                self.pos -= 1;
+                code(self, 3, 10)?
+            }
+            c if c.is_whitespace() => {
+                self.pos += self.span_of(self.pos, char::is_whitespace);
+                if self.peek(0) != Some('\\') {
+                    return Err(start);
+                }
+                self.pos += 1;
+                return Ok(None);
+            }
+            _ => return Err(start),
+        };
+        Ok(Some(c))
+    }
+
+    // `#"c"`
+    fn char(&mut self) -> Option<TokenKind<'a>> {
+        let start = self.pos;
+        self.pos += 1;
+        match self.string()? {
+            TokenKind::String(s) => {
+                let mut chars = s.chars();
+                match (chars.next(), chars.next()) {
+                    (Some(c), None) => Some(TokenKind::Char(c as u32)),
+                    _ => {
+                        self.error(
+                            start,
+                            self.pos,
+                            Expected::Label("a string of one character"),
+                        );
+                        None
+                    }
+                }
+            }
+            _ => unreachable!("string literal"),
+        }
+    }
+}
+
+#[cfg(test)]
+fn kinds(input: &str) -> Vec<TokenKind<'_>> {
+    lex(input)
+        .unwrap()
+        .into_iter()
+        .map(|token| token.kind)
+        .collect()
+}
+
+#[test]
+fn test_lex_keywords_and_identifiers() {
+    use TokenKind::*;
+    assert_eq!(
+        kinds("if iffy then x' else Math.sin op+ Int.+ 'a"),
+        vec![
+            Reserved("if"),
+            Ident("iffy"),
+            Reserved("then"),
+            Ident("x'"),
+            Reserved("else"),
+            LongIdent("Math.sin"),
+            Reserved("op"),
+            Ident("+"),
+            LongIdent("Int.+"),
+            TyVar("'a"),
+            Eof,
+        ]
+    )
+}
+
+#[test]
+fn test_lex_literals() {
+    use TokenKind::*;
+    assert_eq!(
+        kinds(r#"~1 0x1f 0w2 0wxff 0wx10000000000000000 1.5 ~2e~1 #"\n" "a\097\^A\   \b""#),
+        vec![
+            Int("-1".into()),
+            Int("31".into()),
+            Word(2),
+            Word(255),
+            LargeWord("0wx10000000000000000"),
+            Real(1.5),
+            Real(-0.2),
+            Char('\n' as u32),
+            String("aa\u{1}b".into()),
+            Eof,
+        ]
+    )
+}
+
+#[test]
+fn test_lex_comments() {
+    use TokenKind::*;
+    assert_eq!(
+        kinds("val (* a (* nested *) comment *) x = (1)"),
+        vec![
+            Reserved("val"),
+            Ident("x"),
+            Reserved("="),
+            Reserved("("),
+            Int("1".into()),
+            Reserved(")"),
+            Eof,
+        ]
+    );
+    let errors = lex("val x (* open").unwrap_err();
+    assert_eq!(
+        errors[0].to_string(),
+        "syntax error at 1:14: expected `*)` to close `(*` at 1:7, found end of input"
+    );
+}
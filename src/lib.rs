@@ -3,48 +3,140 @@ pub mod util;
 pub mod ast;
 pub mod backend;
 mod config;
+mod diagnostic;
 pub mod hir;
 pub mod id;
+pub mod interface;
+mod lexer;
 pub mod lir;
 pub mod mir;
+pub mod mlb;
 mod parser;
 pub mod pass;
+pub mod pipeline;
 pub mod prim;
+pub mod source;
 mod unification_pool;
 
-pub use crate::ast::TypeError;
-pub use crate::config::Config;
-pub use crate::parser::parse;
+pub use crate::ast::{TypeError, Warning};
+pub use crate::config::{Config, IntWidth, OptLevel};
+pub use crate::diagnostic::{Diagnostic, Label, Severity};
+pub use crate::parser::{parse, Expected, Fixities, ParseError, Position};
 pub use crate::pass::{Chain, Pass};
+pub use crate::pipeline::{Pipeline, IR};
+pub use crate::source::{SourceFile, Sources};
 
-pub fn compile_str<'a>(input: &'a str, config: &Config) -> Result<Vec<u8>, TypeError<'a>> {
-    use crate::pass::{ConvError, PrintablePass};
+/// the standard library the programs are compiled with
+pub const PRELUDE: &str = include_str!("../ml_src/prelude.sml");
+
+/// checks the program without compiling it. gives the warnings on the matches
+pub fn check_str<'a>(input: &'a str, config: &Config) -> Result<Vec<Warning>, TypeError<'a>> {
+    let mut pipeline = Pipeline::new();
+    pipeline.run(input, "case_simplify", config)?;
+    Ok(pipeline.take_warnings())
+}
+
+/// `run_to` on a new pipeline, giving the warnings with the IR
+fn compile_to<'a, T: pipeline::Level>(
+    input: &'a str,
+    config: &Config,
+) -> Result<(T, Vec<Warning>), TypeError<'a>> {
+    let mut pipeline = Pipeline::new();
+    let ir = pipeline.run_to(input, config)?;
+    Ok((ir, pipeline.take_warnings()))
+}
+
+/// compiles the program down to HIR. gives the warnings found on the way with it
+pub fn compile_to_hir<'a>(
+    input: &'a str,
+    config: &Config,
+) -> Result<((hir::SymbolTable, hir::HIR), Vec<Warning>), TypeError<'a>> {
+    compile_to(input, config)
+}
+
+/// compiles the program down to MIR. gives the warnings found on the way with it
+pub fn compile_to_mir<'a>(
+    input: &'a str,
+    config: &Config,
+) -> Result<((mir::SymbolTable, mir::MIR), Vec<Warning>), TypeError<'a>> {
+    compile_to(input, config)
+}
+
+/// compiles the program down to LIR, the last IR before the backend.
+/// gives the warnings found on the way with it
+pub fn compile_to_lir<'a>(
+    input: &'a str,
+    config: &Config,
+) -> Result<((lir::ExternTypes, lir::LIR), Vec<Warning>), TypeError<'a>> {
+    compile_to(input, config)
+}
+
+/// compiles the program as the unit `unit` down to LIR. gives the interface of the unit and
+/// the functions the module should export by the names, to pass to `emit_wasm`,
+/// and the warnings found on the way
+#[allow(clippy::type_complexity)]
+pub fn compile_unit<'a>(
+    input: &'a str,
+    unit: &str,
+    imports: &interface::Imports,
+    config: &Config,
+) -> Result<
+    (
+        (
+            interface::Interface,
+            Vec<(String, prim::Symbol)>,
+            (lir::ExternTypes, lir::LIR),
+        ),
+        Vec<Warning>,
+    ),
+    TypeError<'a>,
+> {
+    let mut pipeline = Pipeline::new();
+    let (symbol_table, mut core) = match pipeline.run(input, "typing", config)? {
+        IR::Typed(typed) => typed,
+        _ => unreachable!("typing gives the typed AST"),
+    };
+    let (interface, exports) = interface::export(
+        unit,
+        imports,
+        pipeline.fixities(),
+        &symbol_table,
+        &mut core,
+        pipeline.id(),
+        config,
+    )?;
+    let ir = IR::Typed((symbol_table, core));
+    let lir = match pipeline.resume(ir, "typing", "mir_to_lir", config)? {
+        IR::Lir(lir) => lir,
+        _ => unreachable!("mir_to_lir gives LIR"),
+    };
+    Ok(((interface, exports, lir), pipeline.take_warnings()))
+}
+
+/// generates the wasm module of the LIR, exporting the functions by the names
+pub fn emit_wasm(
+    lir: (lir::ExternTypes, lir::LIR),
+    exports: Vec<(String, prim::Symbol)>,
+    config: &Config,
+) -> Vec<u8> {
     use wasm::Dump;
 
-    let id = id::Id::new();
-
-    let mut passes = compile_pass![
-       parse: ConvError::new(parse),
-       desugar: ast::Desugar::new(id.clone()),
-       rename: ast::Rename::new(id.clone()),
-       var_to_constructor: ast::VarToConstructor::new(id.clone()),
-       typing: ast::Typer::new(),
-       case_simplify: ast::CaseSimplify::new(id.clone()),
-       ast_to_hir: hir::AST2HIR::new(id.clone()),
-       flattening_expression: hir::FlatExpr::new(id.clone()),
-       flattening_let: hir::FlatLet::new(),
-       unnest_functions: hir::UnnestFunc::new(id.clone()),
-       closure_conversion: hir::ForceClosure::new(),
-       hir_to_mir: mir::HIR2MIR::new(id),
-       unalias: mir::UnAlias::new(),
-       block_arrange: mir::BlockArrange::new(),
-       mir_to_lir: lir::MIR2LIR::new(),
-       backend: backend::LIR2WASM::new(),
-    ];
-
-    let module: wasm::Module = passes.trans(input, config)?;
+    let mut pipeline = Pipeline::new();
+    pipeline.export(exports);
+    let module = match pipeline.resume(IR::Lir(lir), "mir_to_lir", "backend", config) {
+        Ok(IR::Wasm(module)) => module,
+        _ => unreachable!("the backend does not fail"),
+    };
 
     let mut code = Vec::new();
     module.dump(&mut code);
-    Ok(code)
+    code
+}
+
+pub fn compile_str<'a>(
+    input: &'a str,
+    config: &Config,
+) -> Result<(Vec<u8>, Vec<Warning>), TypeError<'a>> {
+    let (lir, warnings) = compile_to_lir(input, config)?;
+    Ok((emit_wasm(lir, Vec::new(), config), warnings))
 }
@@ -1,16 +1,36 @@
-use crate::config::Config;
+use crate::config::{Config, IntWidth, OptLevel};
 use crate::lir::*;
 use crate::mir;
+use crate::mir::UnionRepr;
 use crate::pass::Pass;
 use crate::prim::*;
 use log::debug;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 pub struct MIR2LIR {}
 
+#[derive(Debug, Clone, Copy)]
+enum Shift {
+    Lshift,
+    Rshift,
+    Arshift,
+}
+
 pub struct MIR2LIRPass {
     extern_types: ExternTypes,
     symbol_table: mir::SymbolTable,
+    int_width: IntWidth,
+    unchecked_arith: bool,
+    stack_alloc: bool,
+    /// the functions and the values they make that do not escape them
+    non_escaping: HashMap<Symbol, HashSet<Symbol>>,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Arith {
+    Add,
+    Sub,
+    Mul,
 }
 
 impl MIR2LIR {
@@ -18,16 +38,480 @@ impl MIR2LIR {
         MIR2LIR {}
     }
 
-    fn generate_pass(&mut self, symbol_table: mir::SymbolTable) -> MIR2LIRPass {
-        MIR2LIRPass::new(symbol_table)
+    fn generate_pass(&mut self, symbol_table: mir::SymbolTable, config: &Config) -> MIR2LIRPass {
+        MIR2LIRPass::new(symbol_table, config)
+    }
+}
+
+fn word_size(ty: &mir::EbbTy) -> &WordSize {
+    match ty {
+        mir::EbbTy::Word(size) => size,
+        ty => panic!("internal error: {:?} is not a word type", ty),
     }
 }
 
 impl MIR2LIRPass {
-    fn new(symbol_table: mir::SymbolTable) -> Self {
+    fn new(symbol_table: mir::SymbolTable, config: &Config) -> Self {
         Self {
             extern_types: HashMap::new(),
             symbol_table,
+            int_width: config.int_width,
+            unchecked_arith: config.unchecked_arith,
+            stack_alloc: config.opt_level >= OptLevel::O1,
+            non_escaping: HashMap::new(),
+        }
+    }
+
+    fn int_lty(&self) -> LTy {
+        match self.int_width {
+            IntWidth::Int32 => LTy::I32,
+            IntWidth::Int64 => LTy::I64,
+        }
+    }
+
+    fn int_min(&self) -> i64 {
+        match self.int_width {
+            IntWidth::Int32 => i64::from(i32::MIN),
+            IntWidth::Int64 => i64::MIN,
+        }
+    }
+
+    fn int_const(&self, new_reg: &mut dyn FnMut(LTy) -> Reg, ops: &mut Vec<Op>, n: i64) -> Reg {
+        let reg = new_reg(self.int_lty());
+        match self.int_width {
+            IntWidth::Int32 => ops.push(Op::ConstI32(reg.clone(), n as u32)),
+            IntWidth::Int64 => ops.push(Op::ConstI64(reg.clone(), n as u64)),
+        }
+        reg
+    }
+
+    fn int_eq(
+        &self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        l: Reg,
+        r: Reg,
+    ) -> Reg {
+        let flag = new_reg(LTy::I32);
+        match self.int_width {
+            IntWidth::Int32 => ops.push(Op::EqI32(flag.clone(), l, r)),
+            IntWidth::Int64 => ops.push(Op::EqI64(flag.clone(), l, r)),
+        }
+        flag
+    }
+
+    fn raise_if(&mut self, ops: &mut Vec<Op>, flag: Reg, exn: BuiltinExn) {
+        let (module, fun) = RAISE_FUNCTION;
+        self.extern_types.insert(
+            (module.to_string(), fun.to_string()),
+            (vec![LTy::I32], LTy::Unit),
+        );
+        ops.push(Op::RaiseIfI32(flag, exn));
+    }
+
+    /// `var <- fun(args)` with `fun` a routine of the runtime
+    fn runtime_call(&mut self, ops: &mut Vec<Op>, var: Reg, fun: &str, args: Vec<Reg>) {
+        self.extern_types.insert(
+            (RUNTIME_MODULE.to_string(), fun.to_string()),
+            (args.iter().map(|a| a.0.clone()).collect(), var.0.clone()),
+        );
+        ops.push(Op::ExternCall(
+            var,
+            RUNTIME_MODULE.to_string(),
+            fun.to_string(),
+            args,
+        ));
+    }
+
+    fn intinf_of_i64(
+        &mut self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        var: Reg,
+        n: i64,
+    ) {
+        let reg = new_reg(LTy::I64);
+        ops.push(Op::ConstI64(reg.clone(), n as u64));
+        self.runtime_call(ops, var, "intinf_of_i64", vec![reg]);
+    }
+
+    /// `var <- n` where n is an IntInf constant in decimal
+    fn intinf_const(
+        &mut self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        var: Reg,
+        n: &str,
+    ) {
+        // i64 holds any 18 digits
+        const CHUNK: usize = 18;
+        let (negative, digits) = match n.strip_prefix('-') {
+            Some(digits) => (true, digits),
+            None => (false, n),
+        };
+        let head = match digits.len() % CHUNK {
+            0 => CHUNK,
+            len => len,
+        };
+        let acc = new_reg(LTy::Ptr);
+        self.intinf_of_i64(new_reg, ops, acc.clone(), digits[..head].parse().unwrap());
+        let base = new_reg(LTy::Ptr);
+        self.intinf_of_i64(new_reg, ops, base.clone(), 10i64.pow(CHUNK as u32));
+        let mut rest = &digits[head..];
+        while !rest.is_empty() {
+            let chunk = new_reg(LTy::Ptr);
+            self.intinf_of_i64(new_reg, ops, chunk.clone(), rest[..CHUNK].parse().unwrap());
+            self.runtime_call(
+                ops,
+                acc.clone(),
+                "intinf_mul",
+                vec![acc.clone(), base.clone()],
+            );
+            self.runtime_call(ops, acc.clone(), "intinf_add", vec![acc.clone(), chunk]);
+            rest = &rest[CHUNK..];
+        }
+        if negative {
+            self.runtime_call(ops, var, "intinf_neg", vec![acc]);
+        } else {
+            ops.push(Op::MoveI32(var, acc));
+        }
+    }
+
+    /// compare IntInfs and return the result and 0 to compare it with
+    fn intinf_compare(
+        &mut self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        l: Reg,
+        r: Reg,
+    ) -> (Reg, Reg) {
+        let order = new_reg(LTy::I32);
+        let zero = new_reg(LTy::I32);
+        self.runtime_call(ops, order.clone(), "intinf_compare", vec![l, r]);
+        ops.push(Op::ConstI32(zero.clone(), 0));
+        (order, zero)
+    }
+
+    /// `var <- l op r` on int, raising `Overflow` if the result doesn't fit in int
+    fn int_arith(
+        &mut self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        arith: Arith,
+        var: Reg,
+        l: Reg,
+        r: Reg,
+    ) {
+        use crate::lir::Op::*;
+        let op32 = match arith {
+            Arith::Add => AddI32,
+            Arith::Sub => SubI32,
+            Arith::Mul => MulI32,
+        };
+        let op64 = match arith {
+            Arith::Add => AddI64,
+            Arith::Sub => SubI64,
+            Arith::Mul => MulI64,
+        };
+        match (self.int_width, self.unchecked_arith) {
+            (IntWidth::Int32, true) => ops.push(op32(var, l, r)),
+            (IntWidth::Int64, true) => ops.push(op64(var, l, r)),
+            (IntWidth::Int32, false) => {
+                // calculate in 64 bit and see if the result survives the round trip
+                let l64 = new_reg(LTy::I64);
+                let r64 = new_reg(LTy::I64);
+                let wide = new_reg(LTy::I64);
+                let back = new_reg(LTy::I64);
+                let flag = new_reg(LTy::I32);
+                ops.push(ExtendI32I64(l64.clone(), l));
+                ops.push(ExtendI32I64(r64.clone(), r));
+                ops.push(op64(wide.clone(), l64, r64));
+                ops.push(WrapI64I32(var.clone(), wide.clone()));
+                ops.push(ExtendI32I64(back.clone(), var));
+                ops.push(NeqI64(flag.clone(), wide, back));
+                self.raise_if(ops, flag, BuiltinExn::Overflow);
+            }
+            (IntWidth::Int64, false) => {
+                ops.push(op64(var.clone(), l.clone(), r.clone()));
+                let zero = self.int_const(new_reg, ops, 0);
+                let flag = new_reg(LTy::I32);
+                match arith {
+                    Arith::Add | Arith::Sub => {
+                        // adding a negative number must make the result smaller, and vice versa
+                        let moved = new_reg(LTy::I32);
+                        let negative = new_reg(LTy::I32);
+                        match arith {
+                            Arith::Add => ops.push(LtI64(moved.clone(), var, l)),
+                            _ => ops.push(GtI64(moved.clone(), var, l)),
+                        }
+                        ops.push(LtI64(negative.clone(), r, zero));
+                        ops.push(NeqI32(flag.clone(), moved, negative));
+                    }
+                    Arith::Mul => {
+                        // the product overflowed iff l != 0 and var / l != r.
+                        // the division itself overflows for l = -1 and var = MIN,
+                        // which is an overflow of the product, so divide by 1 instead and flag it
+                        let minus_one = self.int_const(new_reg, ops, -1);
+                        let min = self.int_const(new_reg, ops, i64::MIN);
+                        let l_zero = self.int_eq(new_reg, ops, l.clone(), zero);
+                        let l_minus_one = self.int_eq(new_reg, ops, l.clone(), minus_one);
+                        let var_min = self.int_eq(new_reg, ops, var.clone(), min);
+                        let special = new_reg(LTy::I32);
+                        ops.push(MulI32(special.clone(), l_minus_one, var_min));
+                        // divisor = l + l_zero + 2 * special
+                        let adjust = new_reg(LTy::I32);
+                        let adjust64 = new_reg(LTy::I64);
+                        let divisor = new_reg(LTy::I64);
+                        ops.push(AddI32(adjust.clone(), special.clone(), special.clone()));
+                        ops.push(AddI32(adjust.clone(), adjust.clone(), l_zero.clone()));
+                        ops.push(ExtendI32I64(adjust64.clone(), adjust));
+                        ops.push(AddI64(divisor.clone(), l, adjust64));
+                        let quotient = new_reg(LTy::I64);
+                        let differs = new_reg(LTy::I32);
+                        ops.push(DivI64(quotient.clone(), var, divisor));
+                        ops.push(NeqI64(differs.clone(), quotient, r));
+                        // differs && !l_zero
+                        ops.push(GtI32(flag.clone(), differs, l_zero));
+                        ops.push(AddI32(flag.clone(), flag.clone(), special));
+                    }
+                }
+                self.raise_if(ops, flag, BuiltinExn::Overflow);
+            }
+        }
+    }
+
+    /// raise `Div` if the divisor is 0
+    fn check_divisor(&mut self, new_reg: &mut dyn FnMut(LTy) -> Reg, ops: &mut Vec<Op>, r: Reg) {
+        use crate::lir::Op::*;
+        let flag = new_reg(LTy::I32);
+        if r.0 == LTy::Ptr {
+            // IntInf. the runtime doesn't trap on 0 so check it even if unchecked
+            let sign = new_reg(LTy::I32);
+            let zero = new_reg(LTy::I32);
+            self.runtime_call(ops, sign.clone(), "intinf_sign", vec![r]);
+            ops.push(ConstI32(zero.clone(), 0));
+            ops.push(EqI32(flag.clone(), sign, zero));
+            self.raise_if(ops, flag, BuiltinExn::Div);
+            return;
+        }
+        if self.unchecked_arith {
+            return;
+        }
+        let zero = new_reg(r.0.clone());
+        match r.0 {
+            LTy::I32 => {
+                ops.push(ConstI32(zero.clone(), 0));
+                ops.push(EqI32(flag.clone(), r, zero));
+            }
+            LTy::U32 => {
+                ops.push(ConstU32(zero.clone(), 0));
+                ops.push(EqU32(flag.clone(), r, zero));
+            }
+            LTy::I64 => {
+                ops.push(ConstI64(zero.clone(), 0));
+                ops.push(EqI64(flag.clone(), r, zero));
+            }
+            LTy::U64 => {
+                ops.push(ConstU64(zero.clone(), 0));
+                ops.push(EqU64(flag.clone(), r, zero));
+            }
+            ref ty => panic!("internal error: {:?} is not an integer type", ty),
+        }
+        self.raise_if(ops, flag, BuiltinExn::Div);
+    }
+
+    /// raise `Overflow` if `arg` is the minimum int, which has no positive counterpart
+    fn check_negatable(
+        &mut self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        arg: Reg,
+    ) {
+        if self.unchecked_arith {
+            return;
+        }
+        let min = self.int_const(new_reg, ops, self.int_min());
+        let flag = self.int_eq(new_reg, ops, arg, min);
+        self.raise_if(ops, flag, BuiltinExn::Overflow);
+    }
+
+    /// `var <- arg` truncating the integral real `arg` into int
+    fn real_to_int(
+        &mut self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        var: Reg,
+        arg: Reg,
+    ) {
+        use crate::lir::Op::*;
+        if !self.unchecked_arith {
+            // NaN fails both of the comparisons
+            let lower = new_reg(LTy::F64);
+            let upper = new_reg(LTy::F64);
+            let above = new_reg(LTy::I32);
+            let below = new_reg(LTy::I32);
+            let flag = new_reg(LTy::I32);
+            let zero = new_reg(LTy::I32);
+            match self.int_width {
+                IntWidth::Int32 => {
+                    ops.push(ConstF64(lower.clone(), -2_147_483_649.0));
+                    ops.push(GtF64(above.clone(), arg.clone(), lower));
+                    ops.push(ConstF64(upper.clone(), 2_147_483_648.0));
+                }
+                IntWidth::Int64 => {
+                    ops.push(ConstF64(lower.clone(), -9_223_372_036_854_775_808.0));
+                    ops.push(GeF64(above.clone(), arg.clone(), lower));
+                    ops.push(ConstF64(upper.clone(), 9_223_372_036_854_775_808.0));
+                }
+            }
+            ops.push(LtF64(below.clone(), arg.clone(), upper));
+            ops.push(MulI32(flag.clone(), above, below));
+            ops.push(ConstI32(zero.clone(), 0));
+            ops.push(EqI32(flag.clone(), flag.clone(), zero));
+            self.raise_if(ops, flag, BuiltinExn::Overflow);
+        }
+        match self.int_width {
+            IntWidth::Int32 => ops.push(TruncF64I32(var, arg)),
+            IntWidth::Int64 => ops.push(TruncF64I64(var, arg)),
+        }
+    }
+
+    /// `var <- l div r` or `var <- l mod r`. wasm truncates the quotient but SML floors it,
+    /// so the results are corrected when the remainder and `r` differ in sign
+    fn int_div_mod(
+        &self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        div: bool,
+        var: Reg,
+        l: Reg,
+        r: Reg,
+    ) {
+        use crate::lir::Op::*;
+        let rem = new_reg(self.int_lty());
+        let zero = self.int_const(new_reg, ops, 0);
+        let nonzero = new_reg(LTy::I32);
+        let rem_negative = new_reg(LTy::I32);
+        let r_negative = new_reg(LTy::I32);
+        match self.int_width {
+            IntWidth::Int32 => {
+                ops.push(ModI32(rem.clone(), l.clone(), r.clone()));
+                ops.push(NeqI32(nonzero.clone(), rem.clone(), zero.clone()));
+                ops.push(LtI32(rem_negative.clone(), rem.clone(), zero.clone()));
+                ops.push(LtI32(r_negative.clone(), r.clone(), zero));
+            }
+            IntWidth::Int64 => {
+                ops.push(ModI64(rem.clone(), l.clone(), r.clone()));
+                ops.push(NeqI64(nonzero.clone(), rem.clone(), zero.clone()));
+                ops.push(LtI64(rem_negative.clone(), rem.clone(), zero.clone()));
+                ops.push(LtI64(r_negative.clone(), r.clone(), zero));
+            }
+        }
+        // adjust = rem != 0 && (rem < 0) != (r < 0)
+        let adjust = new_reg(LTy::I32);
+        ops.push(NeqI32(adjust.clone(), rem_negative, r_negative));
+        ops.push(MulI32(adjust.clone(), adjust.clone(), nonzero));
+        // div: l quot r - adjust, mod: l rem r + r * adjust
+        match (self.int_width, div) {
+            (IntWidth::Int32, true) => {
+                ops.push(DivI32(var.clone(), l, r));
+                ops.push(SubI32(var.clone(), var, adjust));
+            }
+            (IntWidth::Int32, false) => {
+                ops.push(MulI32(adjust.clone(), r, adjust.clone()));
+                ops.push(AddI32(var, rem, adjust));
+            }
+            (IntWidth::Int64, _) => {
+                let adjust64 = new_reg(LTy::I64);
+                ops.push(ExtendI32I64(adjust64.clone(), adjust));
+                if div {
+                    ops.push(DivI64(var.clone(), l, r));
+                    ops.push(SubI64(var.clone(), var, adjust64));
+                } else {
+                    ops.push(MulI64(adjust64.clone(), r, adjust64.clone()));
+                    ops.push(AddI64(var, rem, adjust64));
+                }
+            }
+        }
+    }
+
+    /// `var <- l shifted by amount` for the word of `size`. wasm takes the amount modulo the
+    /// width, so the amounts of the width or more are clamped to give 0 or the sign
+    #[allow(clippy::too_many_arguments)]
+    fn word_shift(
+        &self,
+        new_reg: &mut dyn FnMut(LTy) -> Reg,
+        ops: &mut Vec<Op>,
+        shift: Shift,
+        size: WordSize,
+        var: Reg,
+        l: Reg,
+        amount: Reg,
+    ) {
+        use crate::lir::Op::*;
+        // the amount is always a word
+        let bound = new_reg(LTy::U32);
+        let in_range = new_reg(LTy::U32);
+        ops.push(ConstU32(bound.clone(), size.bits()));
+        ops.push(LtU32(in_range.clone(), amount.clone(), bound.clone()));
+        // shifting by the width - 1 fills with the sign, so clamp the amount:
+        // amount - (amount - (width - 1)) * !in_range
+        let amount = match shift {
+            Shift::Arshift => {
+                let one = new_reg(LTy::U32);
+                let excess = new_reg(LTy::U32);
+                let out_of_range = new_reg(LTy::U32);
+                let clamped = new_reg(LTy::U32);
+                ops.push(ConstU32(one.clone(), 1));
+                ops.push(SubU32(bound.clone(), bound.clone(), one.clone()));
+                ops.push(SubU32(excess.clone(), amount.clone(), bound));
+                ops.push(SubU32(out_of_range.clone(), one, in_range.clone()));
+                ops.push(MulU32(excess.clone(), excess.clone(), out_of_range));
+                ops.push(SubU32(clamped.clone(), amount, excess));
+                clamped
+            }
+            Shift::Lshift | Shift::Rshift => amount,
+        };
+        let amount = match size {
+            WordSize::W64 => {
+                let extended = new_reg(LTy::U64);
+                ops.push(ExtendI32I64(extended.clone(), amount));
+                extended
+            }
+            WordSize::W8 | WordSize::W32 => amount,
+        };
+        match (shift, size) {
+            (Shift::Lshift, WordSize::W64) => ops.push(ShlU64(var.clone(), l, amount)),
+            (Shift::Lshift, _) => ops.push(ShlU32(var.clone(), l, amount)),
+            (Shift::Rshift, WordSize::W64) => ops.push(ShrU64(var.clone(), l, amount)),
+            (Shift::Rshift, _) => ops.push(ShrU32(var.clone(), l, amount)),
+            (Shift::Arshift, WordSize::W8) => {
+                // move the sign bit of the byte to the top of the register,
+                // shift it and move the byte back
+                let offset = new_reg(LTy::U32);
+                ops.push(ConstU32(offset.clone(), 24));
+                ops.push(ShlU32(var.clone(), l, offset.clone()));
+                ops.push(SarU32(var.clone(), var.clone(), amount));
+                ops.push(ShrU32(var.clone(), var.clone(), offset));
+            }
+            (Shift::Arshift, WordSize::W32) => ops.push(SarU32(var.clone(), l, amount)),
+            (Shift::Arshift, WordSize::W64) => ops.push(SarU64(var.clone(), l, amount)),
+        }
+        // the logical shifts give 0 for the amounts out of range: var & -in_range
+        if let Shift::Lshift | Shift::Rshift = shift {
+            let zero = new_reg(LTy::U32);
+            let mask = new_reg(LTy::U32);
+            ops.push(ConstU32(zero.clone(), 0));
+            ops.push(SubU32(mask.clone(), zero, in_range));
+            match size {
+                WordSize::W64 => {
+                    // sign extended to all the 64 bits
+                    let mask64 = new_reg(LTy::U64);
+                    ops.push(ExtendI32I64(mask64.clone(), mask));
+                    ops.push(AndU64(var.clone(), var, mask64));
+                }
+                WordSize::W8 | WordSize::W32 => ops.push(AndU32(var.clone(), var, mask)),
+            }
         }
     }
 
@@ -36,19 +520,38 @@ impl MIR2LIRPass {
         match ty {
             Unit => LTy::Unit,
             Char => LTy::U32,
-            Int => LTy::I32,
+            Int => self.int_lty(),
+            IntInf => LTy::Ptr,
+            Word(WordSize::W8) | Word(WordSize::W32) => LTy::U32,
+            Word(WordSize::W64) => LTy::U64,
             Float => LTy::F64,
+            String => LTy::Ptr,
             Bool => LTy::I32,
             Tuple(_) => LTy::Ptr,
-            //FIXME
-            Union(_) => LTy::Ptr,
+            Union(tys) => match UnionRepr::of(tys) {
+                UnionRepr::Enum => LTy::I32,
+                UnionRepr::Unboxed => self.ebbty_to_lty(&tys[0]),
+                UnionRepr::Boxed | UnionRepr::Mixed => LTy::Ptr,
+            },
             Cls { .. } => LTy::Ptr,
             Ebb { .. } => LTy::FPtr,
             Variable(name) => self.ebbty_to_lty(self.symbol_table.canonical_value(name).unwrap()),
         }
     }
 
+    /// the arguments of the constructors if `ty` is a datatype
+    fn union_tys(&self, ty: &mir::EbbTy) -> Option<Vec<mir::EbbTy>> {
+        match ty {
+            mir::EbbTy::Union(tys) => Some(tys.clone()),
+            mir::EbbTy::Variable(name) => self.union_tys(self.symbol_table.canonical_value(name)?),
+            _ => None,
+        }
+    }
+
     pub fn trans_mir(&mut self, mir: mir::MIR) -> LIR {
+        if self.stack_alloc {
+            self.non_escaping = mir::escape::non_escaping(&mir);
+        }
         LIR(mir.0.into_iter().map(|f| self.trans_function(f)).collect())
     }
 
@@ -63,6 +566,7 @@ impl MIR2LIRPass {
         } = f;
         let nparams = body[0].params.len() as u32;
         let ret_ty = self.ebbty_to_lty(&body_ty);
+        let stack = self.non_escaping.remove(&name).unwrap_or_default();
         let mut regs = Vec::new();
         let mut id = 0;
         let mut blocks = Vec::new();
@@ -76,7 +580,7 @@ impl MIR2LIRPass {
                 reg
             };
 
-            let symbol_table = self.make_symbol_table(body.as_ref(), &mut new_reg);
+            let (symbol_table, unions) = self.make_symbol_table(body.as_ref(), &mut new_reg);
             let target_table = self.make_target_table(body.as_ref(), &symbol_table);
             macro_rules! reg {
                 ($var: expr) => {
@@ -86,6 +590,46 @@ impl MIR2LIRPass {
                         .clone()
                 };
             }
+            // the values not escaping the function are allocated in its stack frame
+            macro_rules! alloc {
+                ($var: expr, $reg: expr, $size: expr, $tys: expr) => {
+                    if stack.contains($var) {
+                        StackAlloc($reg, $size, $tys)
+                    } else {
+                        HeapAlloc($reg, I($size as i32), $tys)
+                    }
+                };
+            }
+            // Word8.word is held in a 32 bit register
+            macro_rules! word_op {
+                ($size: expr, $op32: ident, $op64: ident, $var: expr, $l: expr, $r: expr) => {
+                    match $size {
+                        WordSize::W8 | WordSize::W32 => $op32(reg!($var), reg!($l), reg!($r)),
+                        WordSize::W64 => $op64(reg!($var), reg!($l), reg!($r)),
+                    }
+                };
+            }
+            // clear the bits overflowed from Word8.word
+            macro_rules! truncate_word {
+                ($ops: expr, $size: expr, $var: expr) => {
+                    if *$size == WordSize::W8 {
+                        let mask = new_reg(LTy::U32);
+                        $ops.push(ConstU32(mask.clone(), 0xff));
+                        $ops.push(AndU32(reg!($var), reg!($var), mask));
+                    }
+                };
+            }
+
+            // the closures with environments made in this function are called directly
+            // with the pointer to the environment
+            let known_closures = body
+                .iter()
+                .flat_map(|ebb| ebb.body.iter())
+                .filter_map(|op| match op {
+                    m::Closure { var, fun, env, .. } if !env.is_empty() => Some((var, fun)),
+                    _ => None,
+                })
+                .collect::<HashMap<_, _>>();
 
             for ebb in body.iter() {
                 let mut ops = Vec::new();
@@ -93,11 +637,29 @@ impl MIR2LIRPass {
                     debug!(target: "mir_to_lir", "op: {:?}", op);
                     match op {
                         &m::Lit {
-                            ref var, ref value, ..
+                            ref var,
+                            ref ty,
+                            ref value,
                         } => match value {
                             &Literal::Char(c) => ops.push(ConstI32(reg!(var), c as u32)),
-                            &Literal::Int(i) => ops.push(ConstI32(reg!(var), i as u32)),
+                            // ints and bools, which folded comparisons give
+                            &Literal::Int(i) => match (ty, self.ebbty_to_lty(ty)) {
+                                (mir::EbbTy::IntInf, _) => {
+                                    self.intinf_of_i64(&mut new_reg, &mut ops, reg!(var), i)
+                                }
+                                (_, LTy::I64) => ops.push(ConstI64(reg!(var), i as u64)),
+                                _ => ops.push(ConstI32(reg!(var), i as u32)),
+                            },
+                            &Literal::Word(w) => match word_size(ty) {
+                                WordSize::W8 => ops.push(ConstU32(reg!(var), w as u8 as u32)),
+                                WordSize::W32 => ops.push(ConstU32(reg!(var), w as u32)),
+                                WordSize::W64 => ops.push(ConstU64(reg!(var), w)),
+                            },
+                            &Literal::LargeWord(_) => unreachable!("rejected by typing"),
                             &Literal::Real(f) => ops.push(ConstF64(reg!(var), f as f64)),
+                            &Literal::LargeInt(ref n) => {
+                                self.intinf_const(&mut new_reg, &mut ops, reg!(var), n)
+                            }
                         },
                         &m::Alias {
                             ref var,
@@ -125,47 +687,129 @@ impl MIR2LIRPass {
                             ref ty,
                             ref l,
                             ref r,
-                        } => {
-                            if ty == &mir::EbbTy::Int {
-                                ops.push(AddI32(reg!(var), reg!(l), reg!(r)));
-                            } else {
+                        } => match ty {
+                            mir::EbbTy::IntInf => self.runtime_call(
+                                &mut ops,
+                                reg!(var),
+                                "intinf_add",
+                                vec![reg!(l), reg!(r)],
+                            ),
+                            mir::EbbTy::Int => self.int_arith(
+                                &mut new_reg,
+                                &mut ops,
+                                Arith::Add,
+                                reg!(var),
+                                reg!(l),
+                                reg!(r),
+                            ),
+                            mir::EbbTy::Word(size) => {
+                                ops.push(word_op!(size, AddU32, AddU64, var, l, r));
+                                truncate_word!(ops, size, var);
+                            }
+                            ty => {
                                 assert_eq!(ty, &mir::EbbTy::Float);
                                 ops.push(AddF64(reg!(var), reg!(l), reg!(r)));
                             }
-                        }
+                        },
                         &m::Sub {
                             ref var,
                             ref ty,
                             ref l,
                             ref r,
-                        } => {
-                            if ty == &mir::EbbTy::Int {
-                                ops.push(SubI32(reg!(var), reg!(l), reg!(r)));
-                            } else {
+                        } => match ty {
+                            mir::EbbTy::IntInf => self.runtime_call(
+                                &mut ops,
+                                reg!(var),
+                                "intinf_sub",
+                                vec![reg!(l), reg!(r)],
+                            ),
+                            mir::EbbTy::Int => self.int_arith(
+                                &mut new_reg,
+                                &mut ops,
+                                Arith::Sub,
+                                reg!(var),
+                                reg!(l),
+                                reg!(r),
+                            ),
+                            mir::EbbTy::Word(size) => {
+                                ops.push(word_op!(size, SubU32, SubU64, var, l, r));
+                                truncate_word!(ops, size, var);
+                            }
+                            ty => {
                                 assert_eq!(ty, &mir::EbbTy::Float);
                                 ops.push(SubF64(reg!(var), reg!(l), reg!(r)));
                             }
-                        }
+                        },
                         &m::Mul {
                             ref var,
                             ref ty,
                             ref l,
                             ref r,
-                        } => {
-                            if ty == &mir::EbbTy::Int {
-                                ops.push(MulI32(reg!(var), reg!(l), reg!(r)));
-                            } else {
+                        } => match ty {
+                            mir::EbbTy::IntInf => self.runtime_call(
+                                &mut ops,
+                                reg!(var),
+                                "intinf_mul",
+                                vec![reg!(l), reg!(r)],
+                            ),
+                            mir::EbbTy::Int => self.int_arith(
+                                &mut new_reg,
+                                &mut ops,
+                                Arith::Mul,
+                                reg!(var),
+                                reg!(l),
+                                reg!(r),
+                            ),
+                            mir::EbbTy::Word(size) => {
+                                ops.push(word_op!(size, MulU32, MulU64, var, l, r));
+                                truncate_word!(ops, size, var);
+                            }
+                            ty => {
                                 assert_eq!(ty, &mir::EbbTy::Float);
                                 ops.push(MulF64(reg!(var), reg!(l), reg!(r)));
                             }
-                        }
+                        },
                         &m::DivInt {
                             ref var,
+                            ref ty,
                             ref l,
                             ref r,
-                            ..
                         } => {
-                            ops.push(DivI32(reg!(var), reg!(l), reg!(r)));
+                            self.check_divisor(&mut new_reg, &mut ops, reg!(r));
+                            match ty {
+                                mir::EbbTy::IntInf => self.runtime_call(
+                                    &mut ops,
+                                    reg!(var),
+                                    "intinf_div",
+                                    vec![reg!(l), reg!(r)],
+                                ),
+                                mir::EbbTy::Word(size) => {
+                                    ops.push(word_op!(size, DivU32, DivU64, var, l, r))
+                                }
+                                _ => {
+                                    // MIN div ~1
+                                    if !self.unchecked_arith {
+                                        let min =
+                                            self.int_const(&mut new_reg, &mut ops, self.int_min());
+                                        let minus_one = self.int_const(&mut new_reg, &mut ops, -1);
+                                        let l_min =
+                                            self.int_eq(&mut new_reg, &mut ops, reg!(l), min);
+                                        let r_minus_one =
+                                            self.int_eq(&mut new_reg, &mut ops, reg!(r), minus_one);
+                                        let flag = new_reg(LTy::I32);
+                                        ops.push(MulI32(flag.clone(), l_min, r_minus_one));
+                                        self.raise_if(&mut ops, flag, BuiltinExn::Overflow);
+                                    }
+                                    self.int_div_mod(
+                                        &mut new_reg,
+                                        &mut ops,
+                                        true,
+                                        reg!(var),
+                                        reg!(l),
+                                        reg!(r),
+                                    );
+                                }
+                            }
                         }
                         &m::DivFloat {
                             ref var,
@@ -177,11 +821,233 @@ impl MIR2LIRPass {
                         }
                         &m::Mod {
                             ref var,
+                            ref ty,
                             ref l,
                             ref r,
-                            ..
                         } => {
-                            ops.push(ModI32(reg!(var), reg!(l), reg!(r)));
+                            self.check_divisor(&mut new_reg, &mut ops, reg!(r));
+                            match ty {
+                                mir::EbbTy::IntInf => self.runtime_call(
+                                    &mut ops,
+                                    reg!(var),
+                                    "intinf_mod",
+                                    vec![reg!(l), reg!(r)],
+                                ),
+                                mir::EbbTy::Word(size) => {
+                                    ops.push(word_op!(size, ModU32, ModU64, var, l, r))
+                                }
+                                _ => self.int_div_mod(
+                                    &mut new_reg,
+                                    &mut ops,
+                                    false,
+                                    reg!(var),
+                                    reg!(l),
+                                    reg!(r),
+                                ),
+                            }
+                        }
+                        &m::Andb {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => ops.push(word_op!(word_size(ty), AndU32, AndU64, var, l, r)),
+                        &m::Orb {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => ops.push(word_op!(word_size(ty), OrU32, OrU64, var, l, r)),
+                        &m::Xorb {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => ops.push(word_op!(word_size(ty), XorU32, XorU64, var, l, r)),
+                        &m::Lshift {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => {
+                            let size = word_size(ty);
+                            self.word_shift(
+                                &mut new_reg,
+                                &mut ops,
+                                Shift::Lshift,
+                                *size,
+                                reg!(var),
+                                reg!(l),
+                                reg!(r),
+                            );
+                            truncate_word!(ops, size, var);
+                        }
+                        &m::Rshift {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => self.word_shift(
+                            &mut new_reg,
+                            &mut ops,
+                            Shift::Rshift,
+                            *word_size(ty),
+                            reg!(var),
+                            reg!(l),
+                            reg!(r),
+                        ),
+                        &m::Arshift {
+                            ref var,
+                            ref ty,
+                            ref l,
+                            ref r,
+                        } => self.word_shift(
+                            &mut new_reg,
+                            &mut ops,
+                            Shift::Arshift,
+                            *word_size(ty),
+                            reg!(var),
+                            reg!(l),
+                            reg!(r),
+                        ),
+                        &m::Neg {
+                            ref var,
+                            ref ty,
+                            ref arg,
+                        } => match ty {
+                            mir::EbbTy::IntInf => self.runtime_call(
+                                &mut ops,
+                                reg!(var),
+                                "intinf_neg",
+                                vec![reg!(arg)],
+                            ),
+                            mir::EbbTy::Int => {
+                                self.check_negatable(&mut new_reg, &mut ops, reg!(arg));
+                                let zero = self.int_const(&mut new_reg, &mut ops, 0);
+                                match self.int_width {
+                                    IntWidth::Int32 => ops.push(SubI32(reg!(var), zero, reg!(arg))),
+                                    IntWidth::Int64 => ops.push(SubI64(reg!(var), zero, reg!(arg))),
+                                }
+                            }
+                            ty => {
+                                assert_eq!(ty, &mir::EbbTy::Float);
+                                ops.push(NegF64(reg!(var), reg!(arg)));
+                            }
+                        },
+                        &m::Abs {
+                            ref var,
+                            ref ty,
+                            ref arg,
+                        } => match ty {
+                            mir::EbbTy::IntInf => self.runtime_call(
+                                &mut ops,
+                                reg!(var),
+                                "intinf_abs",
+                                vec![reg!(arg)],
+                            ),
+                            mir::EbbTy::Int => {
+                                self.check_negatable(&mut new_reg, &mut ops, reg!(arg));
+                                match self.int_width {
+                                    IntWidth::Int32 => ops.push(AbsI32(reg!(var), reg!(arg))),
+                                    IntWidth::Int64 => ops.push(AbsI64(reg!(var), reg!(arg))),
+                                }
+                            }
+                            ty => {
+                                assert_eq!(ty, &mir::EbbTy::Float);
+                                ops.push(AbsF64(reg!(var), reg!(arg)));
+                            }
+                        },
+                        &m::Real {
+                            ref var, ref arg, ..
+                        } => match self.int_width {
+                            IntWidth::Int32 => ops.push(ConvertI32F64(reg!(var), reg!(arg))),
+                            IntWidth::Int64 => ops.push(ConvertI64F64(reg!(var), reg!(arg))),
+                        },
+                        &m::Floor {
+                            ref var, ref arg, ..
+                        } => {
+                            let rounded = new_reg(LTy::F64);
+                            ops.push(FloorF64(rounded.clone(), reg!(arg)));
+                            self.real_to_int(&mut new_reg, &mut ops, reg!(var), rounded);
+                        }
+                        &m::Ceil {
+                            ref var, ref arg, ..
+                        } => {
+                            let rounded = new_reg(LTy::F64);
+                            ops.push(CeilF64(rounded.clone(), reg!(arg)));
+                            self.real_to_int(&mut new_reg, &mut ops, reg!(var), rounded);
+                        }
+                        &m::Round {
+                            ref var, ref arg, ..
+                        } => {
+                            let rounded = new_reg(LTy::F64);
+                            ops.push(NearestF64(rounded.clone(), reg!(arg)));
+                            self.real_to_int(&mut new_reg, &mut ops, reg!(var), rounded);
+                        }
+                        &m::Trunc {
+                            ref var, ref arg, ..
+                        } => self.real_to_int(&mut new_reg, &mut ops, reg!(var), reg!(arg)),
+                        &m::Sqrt {
+                            ref var, ref arg, ..
+                        } => ops.push(SqrtF64(reg!(var), reg!(arg))),
+                        &m::IntInfFromInt {
+                            ref var, ref arg, ..
+                        } => {
+                            let arg = match self.int_width {
+                                IntWidth::Int32 => {
+                                    let extended = new_reg(LTy::I64);
+                                    ops.push(ExtendI32I64(extended.clone(), reg!(arg)));
+                                    extended
+                                }
+                                IntWidth::Int64 => reg!(arg),
+                            };
+                            self.runtime_call(&mut ops, reg!(var), "intinf_of_i64", vec![arg]);
+                        }
+                        &m::Raise { exn, .. } => {
+                            let flag = new_reg(LTy::I32);
+                            ops.push(ConstI32(flag.clone(), 1));
+                            self.raise_if(&mut ops, flag, exn);
+                        }
+                        &m::IntInfToInt {
+                            ref var, ref arg, ..
+                        } => {
+                            if !self.unchecked_arith {
+                                let bits = new_reg(LTy::I32);
+                                let fits = new_reg(LTy::I32);
+                                let flag = new_reg(LTy::I32);
+                                let bit_width = match self.int_width {
+                                    IntWidth::Int32 => 32,
+                                    IntWidth::Int64 => 64,
+                                };
+                                ops.push(ConstI32(bits.clone(), bit_width));
+                                self.runtime_call(
+                                    &mut ops,
+                                    fits.clone(),
+                                    "intinf_fits",
+                                    vec![reg!(arg), bits],
+                                );
+                                ops.push(ConstI32(flag.clone(), 0));
+                                ops.push(EqI32(flag.clone(), fits, flag.clone()));
+                                self.raise_if(&mut ops, flag, BuiltinExn::Overflow);
+                            }
+                            match self.int_width {
+                                IntWidth::Int32 => {
+                                    let wide = new_reg(LTy::I64);
+                                    self.runtime_call(
+                                        &mut ops,
+                                        wide.clone(),
+                                        "intinf_to_i64",
+                                        vec![reg!(arg)],
+                                    );
+                                    ops.push(WrapI64I32(reg!(var), wide));
+                                }
+                                IntWidth::Int64 => self.runtime_call(
+                                    &mut ops,
+                                    reg!(var),
+                                    "intinf_to_i64",
+                                    vec![reg!(arg)],
+                                ),
+                            }
                         }
                         &m::Eq {
                             ref var,
@@ -195,6 +1061,12 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(EqU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(EqF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(EqF64(reg!(var), reg!(l), reg!(r))),
+                            // IntInf
+                            (&LTy::Ptr, &LTy::Ptr) => {
+                                let (order, zero) =
+                                    self.intinf_compare(&mut new_reg, &mut ops, reg!(l), reg!(r));
+                                ops.push(EqI32(reg!(var), order, zero))
+                            }
                             ty => panic!("unknown overloaded ty {:?} for eq", ty),
                         },
                         &m::Neq {
@@ -209,6 +1081,12 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(NeqU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(NeqF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(NeqF64(reg!(var), reg!(l), reg!(r))),
+                            // IntInf
+                            (&LTy::Ptr, &LTy::Ptr) => {
+                                let (order, zero) =
+                                    self.intinf_compare(&mut new_reg, &mut ops, reg!(l), reg!(r));
+                                ops.push(NeqI32(reg!(var), order, zero))
+                            }
                             ty => panic!("unknown overloaded ty {:?} for neq", ty),
                         },
                         &m::Gt {
@@ -223,6 +1101,12 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(GtU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(GtF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(GtF64(reg!(var), reg!(l), reg!(r))),
+                            // IntInf
+                            (&LTy::Ptr, &LTy::Ptr) => {
+                                let (order, zero) =
+                                    self.intinf_compare(&mut new_reg, &mut ops, reg!(l), reg!(r));
+                                ops.push(GtI32(reg!(var), order, zero))
+                            }
                             ty => panic!("unknown overloaded ty {:?} for gt", ty),
                         },
                         &m::Ge {
@@ -237,6 +1121,12 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(GeU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(GeF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(GeF64(reg!(var), reg!(l), reg!(r))),
+                            // IntInf
+                            (&LTy::Ptr, &LTy::Ptr) => {
+                                let (order, zero) =
+                                    self.intinf_compare(&mut new_reg, &mut ops, reg!(l), reg!(r));
+                                ops.push(GeI32(reg!(var), order, zero))
+                            }
                             ty => panic!("unknown overloaded ty {:?} for ge", ty),
                         },
                         &m::Lt {
@@ -251,6 +1141,12 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(LtU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(LtF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(LtF64(reg!(var), reg!(l), reg!(r))),
+                            // IntInf
+                            (&LTy::Ptr, &LTy::Ptr) => {
+                                let (order, zero) =
+                                    self.intinf_compare(&mut new_reg, &mut ops, reg!(l), reg!(r));
+                                ops.push(LtI32(reg!(var), order, zero))
+                            }
                             ty => panic!("unknown overloaded ty {:?} for lt", ty),
                         },
                         &m::Le {
@@ -265,6 +1161,12 @@ impl MIR2LIRPass {
                             (&LTy::U64, &LTy::U64) => ops.push(LeU64(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F32, &LTy::F32) => ops.push(LeF32(reg!(var), reg!(l), reg!(r))),
                             (&LTy::F64, &LTy::F64) => ops.push(LeF64(reg!(var), reg!(l), reg!(r))),
+                            // IntInf
+                            (&LTy::Ptr, &LTy::Ptr) => {
+                                let (order, zero) =
+                                    self.intinf_compare(&mut new_reg, &mut ops, reg!(l), reg!(r));
+                                ops.push(LeI32(reg!(var), order, zero))
+                            }
                             ty => panic!("unknown overloaded ty {:?} for le", ty),
                         },
                         &m::Tuple {
@@ -279,39 +1181,11 @@ impl MIR2LIRPass {
                             let size: u32 = tys.iter().map(|_| 8).sum();
                             // let size: u32 = tys.iter().map(|ty| ty.size()).sum();
 
-                            ops.push(HeapAlloc(reg.clone(), I(size as i32), tys.clone()));
+                            ops.push(alloc!(var, reg.clone(), size, tys.clone()));
 
                             let mut acc = 0;
                             for (var, ty) in tuple.iter().zip(tys) {
-                                match ty {
-                                    LTy::Unit => {
-                                        // do nothing
-                                    }
-                                    LTy::I32 => {
-                                        ops.push(StoreI32(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::U32 => {
-                                        ops.push(StoreU32(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::I64 => {
-                                        ops.push(StoreI64(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::U64 => {
-                                        ops.push(StoreU64(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::F32 => {
-                                        ops.push(StoreF32(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::F64 => {
-                                        ops.push(StoreF64(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::Ptr => {
-                                        ops.push(StoreI32(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                    LTy::FPtr => {
-                                        ops.push(StoreI32(Addr(reg.clone(), acc), reg!(var)))
-                                    }
-                                }
+                                ops.extend(store_op(&ty, Addr(reg.clone(), acc), reg!(var)));
                                 acc += 8;
                             }
                         }
@@ -321,26 +1195,8 @@ impl MIR2LIRPass {
                             ref index,
                             ref tuple,
                         } => {
-                            #[allow(clippy::never_loop)]
-                            loop {
-                                let ctor = match self.ebbty_to_lty(ty) {
-                                    LTy::F32 => LoadF32,
-                                    LTy::F64 => LoadF64,
-                                    LTy::I32 => LoadI32,
-                                    LTy::U32 => LoadU32,
-                                    LTy::I64 => LoadI64,
-                                    LTy::U64 => LoadU64,
-                                    LTy::Ptr => LoadI32,
-                                    LTy::FPtr => LoadI32,
-                                    LTy::Unit =>
-                                    // do nothing
-                                    {
-                                        break
-                                    }
-                                };
-                                ops.push(ctor(reg!(var), Addr(reg!(tuple), *index * 8)));
-                                break;
-                            }
+                            let ty = self.ebbty_to_lty(ty);
+                            ops.extend(load_op(&ty, reg!(var), Addr(reg!(tuple), *index * 8)));
                         }
 
                         &m::Union {
@@ -350,49 +1206,49 @@ impl MIR2LIRPass {
                             ref index,
                         } => {
                             let ty = &tys[*index as usize];
-                            #[allow(clippy::never_loop)]
-                            loop {
-                                let ctor = match self.ebbty_to_lty(ty) {
-                                    LTy::F32 => MoveF32,
-                                    LTy::F64 => MoveF64,
-                                    LTy::I32 => MoveI32,
-                                    LTy::U32 => MoveU32,
-                                    LTy::I64 => MoveI64,
-                                    LTy::U64 => MoveU64,
-                                    LTy::Ptr => MoveI32,
-                                    LTy::FPtr => MoveI32,
-                                    LTy::Unit => MoveI32,
-                                };
-                                ops.push(ctor(reg!(var), reg!(variant)));
-                                break;
+                            match (UnionRepr::of(tys), ty) {
+                                (UnionRepr::Enum, _) | (UnionRepr::Mixed, mir::EbbTy::Unit) => {
+                                    ops.push(ConstI32(reg!(var), *index))
+                                }
+                                (UnionRepr::Unboxed, _) => ops.extend(move_op(
+                                    &self.ebbty_to_lty(ty),
+                                    reg!(var),
+                                    reg!(variant),
+                                )),
+                                (UnionRepr::Boxed, _) | (UnionRepr::Mixed, _) => {
+                                    // the descriminant followed by the argument, aligned as tuples
+                                    let reg = reg!(var);
+                                    let ty = self.ebbty_to_lty(ty);
+                                    let desc = new_reg(LTy::I32);
+                                    ops.push(alloc!(
+                                        var,
+                                        reg.clone(),
+                                        16,
+                                        vec![LTy::I32, ty.clone()]
+                                    ));
+                                    ops.push(ConstI32(desc.clone(), *index));
+                                    ops.push(StoreI32(Addr(reg.clone(), 0), desc));
+                                    ops.extend(store_op(&ty, Addr(reg, 8), reg!(variant)));
+                                }
                             }
                         }
+                        m::Select { var, ty, union, .. }
+                            if matches!(
+                                unions.get(union).map(|tys| UnionRepr::of(tys)),
+                                Some(UnionRepr::Boxed) | Some(UnionRepr::Mixed)
+                            ) =>
+                        {
+                            let ty = self.ebbty_to_lty(ty);
+                            ops.extend(load_op(&ty, reg!(var), Addr(reg!(union), 8)));
+                        }
                         &m::Select {
                             ref var,
                             ref ty,
                             ref union,
                             ..
                         } => {
-                            #[allow(clippy::never_loop)]
-                            loop {
-                                let ctor = match self.ebbty_to_lty(ty) {
-                                    LTy::F32 => MoveF32,
-                                    LTy::F64 => MoveF64,
-                                    LTy::I32 => MoveI32,
-                                    LTy::U32 => MoveU32,
-                                    LTy::I64 => MoveI64,
-                                    LTy::U64 => MoveU64,
-                                    LTy::Ptr => MoveI32,
-                                    LTy::FPtr => MoveI32,
-                                    LTy::Unit =>
-                                    // do nothing
-                                    {
-                                        break
-                                    }
-                                };
-                                ops.push(ctor(reg!(var), reg!(union)));
-                                break;
-                            }
+                            let ty = self.ebbty_to_lty(ty);
+                            ops.extend(move_op(&ty, reg!(var), reg!(union)));
                         }
 
                         &m::Closure {
@@ -417,7 +1273,7 @@ impl MIR2LIRPass {
                             for &(ref ty, _) in env.iter() {
                                 tys.push(self.ebbty_to_lty(ty));
                             }
-                            ops.push(HeapAlloc(reg.clone(), I(size as i32), tys));
+                            ops.push(alloc!(var, reg.clone(), size, tys));
                             // FIXME: explicitly take fun pointer
                             ops.push(StoreFnPtr(Addr(reg.clone(), 0), fun.clone()));
                             let mut acc = LTy::FPtr.size();
@@ -463,10 +1319,10 @@ impl MIR2LIRPass {
                             ref args,
                             ..
                         } => {
-                            let args = args.iter().map(|a| reg!(a)).collect();
+                            let args: Vec<Reg> = args.iter().map(|a| reg!(a)).collect();
                             self.extern_types.insert(
                                 (module.to_string(), fun.to_string()),
-                                (vec![LTy::I32], LTy::Unit),
+                                (args.iter().map(|a| a.0.clone()).collect(), reg!(var).0),
                             );
                             ops.push(ExternCall(
                                 reg!(var),
@@ -481,10 +1337,21 @@ impl MIR2LIRPass {
                             ref args,
                             ..
                         } => {
-                            let args = args.iter().map(|a| reg!(a)).collect();
-                            match symbol_table.get(fun) {
-                                Some(r) => ops.push(ClosureCall(reg!(var), r.clone(), args)),
-                                None => ops.push(FunCall(reg!(var), fun.clone(), args)),
+                            let mut args = args.iter().map(|a| reg!(a)).collect::<Vec<_>>();
+                            match (symbol_table.get(fun), known_closures.get(fun)) {
+                                (Some(r), Some(&f)) => {
+                                    // the environment follows the function pointer
+                                    let offset = new_reg(LTy::I32);
+                                    let env = new_reg(LTy::Ptr);
+                                    ops.push(ConstI32(offset.clone(), LTy::FPtr.size()));
+                                    ops.push(AddI32(env.clone(), r.clone(), offset));
+                                    args.insert(0, env);
+                                    ops.push(FunCall(reg!(var), f.clone(), args))
+                                }
+                                (Some(r), None) => {
+                                    ops.push(ClosureCall(reg!(var), r.clone(), args))
+                                }
+                                (None, _) => ops.push(FunCall(reg!(var), fun.clone(), args)),
                             }
                         }
                         &m::Branch {
@@ -518,7 +1385,42 @@ impl MIR2LIRPass {
                                 }
                             };
 
-                            if !clauses.is_empty()
+                            // the descriminant of the value of a datatype
+                            let key = match unions.get(cond).map(|tys| UnionRepr::of(tys)) {
+                                None | Some(UnionRepr::Enum) => reg!(cond),
+                                Some(UnionRepr::Unboxed) => {
+                                    let key = new_reg(LTy::I32);
+                                    ops.push(ConstI32(key.clone(), 0));
+                                    key
+                                }
+                                Some(UnionRepr::Boxed) => {
+                                    let key = new_reg(LTy::I32);
+                                    ops.push(LoadI32(key.clone(), Addr(reg!(cond), 0)));
+                                    key
+                                }
+                                Some(UnionRepr::Mixed) => {
+                                    // `if cond < ntys then cond else *cond`, without branching.
+                                    // loading from the small addresses is harmless
+                                    let ntys = unions[cond].len() as u32;
+                                    let (key, bound, immediate, diff) = (
+                                        new_reg(LTy::I32),
+                                        new_reg(LTy::I32),
+                                        new_reg(LTy::I32),
+                                        new_reg(LTy::I32),
+                                    );
+                                    ops.push(ConstI32(bound.clone(), ntys));
+                                    ops.push(LtU32(immediate.clone(), reg!(cond), bound));
+                                    ops.push(LoadI32(key.clone(), Addr(reg!(cond), 0)));
+                                    ops.push(SubI32(diff.clone(), reg!(cond), key.clone()));
+                                    ops.push(MulI32(diff.clone(), diff.clone(), immediate));
+                                    ops.push(AddI32(key.clone(), key.clone(), diff));
+                                    key
+                                }
+                            };
+
+                            // jump tables take only 32 bit indices
+                            if !matches!(key.0, LTy::I64 | LTy::U64)
+                                && !clauses.is_empty()
                                 && clauses[0].0 == 0
                                 && clauses
                                     .iter()
@@ -527,7 +1429,7 @@ impl MIR2LIRPass {
                             {
                                 // use jump table
                                 ops.push(JumpTableI32(
-                                    reg!(cond),
+                                    key,
                                     clauses
                                         .into_iter()
                                         .map(|(_, label, _)| Label(label))
@@ -535,7 +1437,7 @@ impl MIR2LIRPass {
                                     default_label,
                                 ))
                             } else {
-                                let cond = reg!(cond);
+                                let cond = key;
 
                                 match cond.0 {
                                     LTy::I32 => {
@@ -564,7 +1466,33 @@ impl MIR2LIRPass {
                                             ops.push(JumpIfI32(boolean.clone(), Label(label)))
                                         }
                                     }
-                                    _ => panic!("internal error: branching currently supports only 32 bit types"),
+                                    LTy::I64 => {
+                                        let boolean = new_reg(LTy::I32);
+                                        let constant = new_reg(LTy::I64);
+                                        for (key, label, _) in clauses {
+                                            ops.push(ConstI64(constant.clone(), key));
+                                            ops.push(EqI64(
+                                                boolean.clone(),
+                                                cond.clone(),
+                                                constant.clone(),
+                                            ));
+                                            ops.push(JumpIfI32(boolean.clone(), Label(label)))
+                                        }
+                                    }
+                                    LTy::U64 => {
+                                        let boolean = new_reg(LTy::I32);
+                                        let constant = new_reg(LTy::U64);
+                                        for (key, label, _) in clauses {
+                                            ops.push(ConstU64(constant.clone(), key));
+                                            ops.push(EqU64(
+                                                boolean.clone(),
+                                                cond.clone(),
+                                                constant.clone(),
+                                            ));
+                                            ops.push(JumpIfI32(boolean.clone(), Label(label)))
+                                        }
+                                    }
+                                    _ => panic!("internal error: branching currently supports only integer types"),
                                 }
                                 if let Some(label) = default_label {
                                     ops.push(Jump(label))
@@ -622,11 +1550,16 @@ impl MIR2LIRPass {
         &self,
         body: &'a [mir::EBB],
         mut new_reg: F,
-    ) -> HashMap<&'a Symbol, Reg>
+    ) -> (
+        HashMap<&'a Symbol, Reg>,
+        HashMap<&'a Symbol, Vec<mir::EbbTy>>,
+    )
     where
         F: FnMut(LTy) -> Reg,
     {
         let mut table = HashMap::new();
+        // the variables of datatypes and the arguments of their constructors
+        let mut unions = HashMap::new();
         macro_rules! intern {
             ($ty: expr, $var: expr) => {{
                 if table.get(&$var).is_none() {
@@ -634,15 +1567,23 @@ impl MIR2LIRPass {
                 };
             }};
         }
+        macro_rules! intern_ty {
+            ($ty: expr, $var: expr) => {{
+                if let Some(tys) = self.union_tys($ty) {
+                    unions.insert($var, tys);
+                }
+                intern!(self.ebbty_to_lty($ty), $var)
+            }};
+        }
 
         // allocate function params first
         for &(ref ty, ref param) in &body[0].params {
-            intern!(self.ebbty_to_lty(ty), param);
+            intern_ty!(ty, param);
         }
 
         for ebb in body {
             for &(ref ty, ref param) in &ebb.params {
-                intern!(self.ebbty_to_lty(ty), param);
+                intern_ty!(ty, param);
             }
 
             for op in ebb.body.iter() {
@@ -689,6 +1630,57 @@ impl MIR2LIRPass {
                     | &mir::Op::Le {
                         ref var, ref ty, ..
                     }
+                    | &mir::Op::Andb {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Orb {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Xorb {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Lshift {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Rshift {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Arshift {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Neg {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Abs {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Real {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Floor {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Ceil {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Round {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Trunc {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Sqrt {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::IntInfFromInt {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::IntInfToInt {
+                        ref var, ref ty, ..
+                    }
+                    | &mir::Op::Raise {
+                        ref var, ref ty, ..
+                    }
                     | &mir::Op::Proj {
                         ref var, ref ty, ..
                     }
@@ -701,18 +1693,20 @@ impl MIR2LIRPass {
                     | &mir::Op::Call {
                         ref var, ref ty, ..
                     } => {
-                        intern!(self.ebbty_to_lty(ty), var);
+                        intern_ty!(ty, var);
                     }
                     &mir::Op::Tuple { ref var, .. } | &mir::Op::Closure { ref var, .. } => {
                         intern!(LTy::Ptr, var);
                     }
-                    &mir::Op::Union { ref var, .. } => intern!(LTy::Ptr, var),
+                    &mir::Op::Union {
+                        ref var, ref tys, ..
+                    } => intern_ty!(&mir::EbbTy::Union(tys.clone()), var),
                     _ => (),
                 }
             }
         }
 
-        table
+        (table, unions)
     }
 
     fn make_target_table<'a>(
@@ -733,15 +1727,60 @@ impl MIR2LIRPass {
     }
 }
 
+/// the move of the value of `ty`, nothing for unit
+fn move_op(ty: &LTy, var: Reg, value: Reg) -> Option<Op> {
+    use crate::lir::Op::*;
+    let ctor = match ty {
+        LTy::Unit => return None,
+        LTy::I32 | LTy::Ptr | LTy::FPtr => MoveI32,
+        LTy::U32 => MoveU32,
+        LTy::I64 => MoveI64,
+        LTy::U64 => MoveU64,
+        LTy::F32 => MoveF32,
+        LTy::F64 => MoveF64,
+    };
+    Some(ctor(var, value))
+}
+
+/// the load of the value of `ty`, nothing for unit
+fn load_op(ty: &LTy, var: Reg, addr: Addr) -> Option<Op> {
+    use crate::lir::Op::*;
+    let ctor = match ty {
+        LTy::Unit => return None,
+        LTy::I32 | LTy::Ptr | LTy::FPtr => LoadI32,
+        LTy::U32 => LoadU32,
+        LTy::I64 => LoadI64,
+        LTy::U64 => LoadU64,
+        LTy::F32 => LoadF32,
+        LTy::F64 => LoadF64,
+    };
+    Some(ctor(var, addr))
+}
+
+/// the store of the value of `ty`, nothing for unit
+fn store_op(ty: &LTy, addr: Addr, value: Reg) -> Option<Op> {
+    use crate::lir::Op::*;
+    let ctor = match ty {
+        LTy::Unit => return None,
+        LTy::I32 | LTy::Ptr | LTy::FPtr => StoreI32,
+        LTy::U32 => StoreU32,
+        LTy::I64 => StoreI64,
+        LTy::U64 => StoreU64,
+        LTy::F32 => StoreF32,
+        LTy::F64 => StoreF64,
+    };
+    Some(ctor(addr, value))
+}
+
 impl<E> Pass<(mir::SymbolTable, mir::MIR), E> for MIR2LIR {
     type Target = (ExternTypes, LIR);
 
     fn trans(
         &mut self,
         (symbol_table, mir): (mir::SymbolTable, mir::MIR),
-        _: &Config,
+        config: &Config,
     ) -> ::std::result::Result<Self::Target, E> {
-        let mut pass = self.generate_pass(symbol_table);
+        let mut pass = self.generate_pass(symbol_table, config);
         let lir = pass.trans_mir(mir);
         let types = pass.extern_types.drain().collect();
         Ok((types, lir))
@@ -41,6 +41,12 @@ impl LTy {
 
 pub type ExternTypes = HashMap<(String, String), (Vec<LTy>, LTy)>;
 
+/// the module the runtime is imported as
+pub const RUNTIME_MODULE: &str = "webml-rt";
+
+/// the host function called with a `BuiltinExn` to raise it
+pub const RAISE_FUNCTION: (&str, &str) = ("js-ffi", "raise");
+
 #[derive(Debug, Clone)]
 pub struct Reg(pub LTy, pub u32);
 #[derive(Debug, Clone, Eq, PartialEq, Hash)]
@@ -96,6 +102,7 @@ pub enum Op {
     GeI32(Reg, Reg, Reg),
     LtI32(Reg, Reg, Reg),
     LeI32(Reg, Reg, Reg),
+    AbsI32(Reg, Reg),
     StoreI32(Addr, Reg),
     LoadI32(Reg, Addr),
 
@@ -115,6 +122,14 @@ pub enum Op {
     GeU32(Reg, Reg, Reg),
     LtU32(Reg, Reg, Reg),
     LeU32(Reg, Reg, Reg),
+    AndU32(Reg, Reg, Reg),
+    OrU32(Reg, Reg, Reg),
+    XorU32(Reg, Reg, Reg),
+    ShlU32(Reg, Reg, Reg),
+    /// logical right shift
+    ShrU32(Reg, Reg, Reg),
+    /// arithmetic right shift
+    SarU32(Reg, Reg, Reg),
     StoreU32(Addr, Reg),
     LoadU32(Reg, Addr),
 
@@ -131,6 +146,7 @@ pub enum Op {
     GeI64(Reg, Reg, Reg),
     LtI64(Reg, Reg, Reg),
     LeI64(Reg, Reg, Reg),
+    AbsI64(Reg, Reg),
     StoreI64(Addr, Reg),
     LoadI64(Reg, Addr),
 
@@ -147,6 +163,14 @@ pub enum Op {
     GeU64(Reg, Reg, Reg),
     LtU64(Reg, Reg, Reg),
     LeU64(Reg, Reg, Reg),
+    AndU64(Reg, Reg, Reg),
+    OrU64(Reg, Reg, Reg),
+    XorU64(Reg, Reg, Reg),
+    ShlU64(Reg, Reg, Reg),
+    /// logical right shift
+    ShrU64(Reg, Reg, Reg),
+    /// arithmetic right shift
+    SarU64(Reg, Reg, Reg),
     StoreU64(Addr, Reg),
     LoadU64(Reg, Addr),
 
@@ -177,8 +201,24 @@ pub enum Op {
     GeF64(Reg, Reg, Reg),
     LtF64(Reg, Reg, Reg),
     LeF64(Reg, Reg, Reg),
+    NegF64(Reg, Reg),
+    AbsF64(Reg, Reg),
+    SqrtF64(Reg, Reg),
+    FloorF64(Reg, Reg),
+    CeilF64(Reg, Reg),
+    /// round to the nearest, ties to even
+    NearestF64(Reg, Reg),
     StoreF64(Addr, Reg),
     LoadF64(Reg, Addr),
+    /// signed int to f64
+    ConvertI32F64(Reg, Reg),
+    /// f64 to signed int, rounding toward zero. traps if out of range
+    TruncF64I32(Reg, Reg),
+    ConvertI64F64(Reg, Reg),
+    TruncF64I64(Reg, Reg),
+    /// sign extension
+    ExtendI32I64(Reg, Reg),
+    WrapI64I32(Reg, Reg),
 
     HeapAlloc(Reg, Value, Vec<LTy>),
     StackAlloc(Reg, u32, Vec<LTy>),
@@ -188,6 +228,8 @@ pub enum Op {
     FunCall(Reg, Symbol, Vec<Reg>),
     ClosureCall(Reg, Reg, Vec<Reg>),
     Jump(Label),
+    /// raise the exception if the flag is not 0
+    RaiseIfI32(Reg, BuiltinExn),
     Unreachable,
     Ret(Option<Reg>),
 }
@@ -313,6 +313,60 @@ impl PP for Op {
                 write!(w, " < ")?;
                 r3.pp(w, indent)?;
             }
+            AndU32(r1, r2, r3) | AndU64(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " andb ")?;
+                r3.pp(w, indent)?;
+            }
+            OrU32(r1, r2, r3) | OrU64(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " orb ")?;
+                r3.pp(w, indent)?;
+            }
+            XorU32(r1, r2, r3) | XorU64(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " xorb ")?;
+                r3.pp(w, indent)?;
+            }
+            ShlU32(r1, r2, r3) | ShlU64(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " << ")?;
+                r3.pp(w, indent)?;
+            }
+            ShrU32(r1, r2, r3) | ShrU64(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " >> ")?;
+                r3.pp(w, indent)?;
+            }
+            SarU32(r1, r2, r3) | SarU64(r1, r2, r3) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ")?;
+                r2.pp(w, indent)?;
+                write!(w, " ~>> ")?;
+                r3.pp(w, indent)?;
+            }
             LeI32(r1, r2, r3)
             | LeU32(r1, r2, r3)
             | LeI64(r1, r2, r3)
@@ -327,6 +381,62 @@ impl PP for Op {
                 write!(w, " <= ")?;
                 r3.pp(w, indent)?;
             }
+            AbsI32(r1, r2) | AbsI64(r1, r2) | AbsF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- abs ")?;
+                r2.pp(w, indent)?;
+            }
+            NegF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- neg ")?;
+                r2.pp(w, indent)?;
+            }
+            SqrtF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- sqrt ")?;
+                r2.pp(w, indent)?;
+            }
+            FloorF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- floor ")?;
+                r2.pp(w, indent)?;
+            }
+            CeilF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- ceil ")?;
+                r2.pp(w, indent)?;
+            }
+            NearestF64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- nearest ")?;
+                r2.pp(w, indent)?;
+            }
+            ConvertI32F64(r1, r2) | ConvertI64F64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- convert ")?;
+                r2.pp(w, indent)?;
+            }
+            TruncF64I32(r1, r2) | TruncF64I64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- trunc ")?;
+                r2.pp(w, indent)?;
+            }
             ConstF32(reg, i) => {
                 reg.pp(w, indent)?;
                 write!(w, ": ")?;
@@ -400,6 +510,24 @@ impl PP for Op {
                 write!(w, "jump ")?;
                 label.pp(w, indent)?;
             }
+            ExtendI32I64(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- extend ")?;
+                r2.pp(w, indent)?;
+            }
+            WrapI64I32(r1, r2) => {
+                r1.pp(w, indent)?;
+                write!(w, ": ")?;
+                r1.0.pp(w, indent)?;
+                write!(w, " <- wrap ")?;
+                r2.pp(w, indent)?;
+            }
+            RaiseIfI32(reg, exn) => {
+                write!(w, "raise {:?} if ", exn)?;
+                reg.pp(w, indent)?;
+            }
             Unreachable => {
                 write!(w, "unreachable")?;
             }
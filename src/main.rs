@@ -1,37 +1,171 @@
-use clap::{app_from_crate, crate_authors, crate_description, crate_name, crate_version, Arg};
+use clap::{
+    app_from_crate, crate_authors, crate_description, crate_name, crate_version, AppSettings, Arg,
+    ArgMatches, SubCommand,
+};
 use std::collections::HashSet;
+use std::env;
 use std::fs;
-use std::io::{self, prelude::*};
-use std::path::Path;
-use webml::{compile_str, Config};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use webml::backend::{js, wat};
+use webml::interface::{Imports, Interface};
+use webml::util::PP;
+use webml::{
+    check_str, compile_to_lir, compile_unit, emit_wasm, mlb, Config, Diagnostic, IntWidth,
+    OptLevel, Sources, TypeError, Warning,
+};
 
-fn read_and_append_to_string(path: impl AsRef<Path>, buf: &mut String) -> io::Result<usize> {
-    let file = fs::File::open(path)?;
-    let mut input = io::BufReader::new(file);
-    input.read_to_string(buf)
+/// the program has errors
+const EXIT_ERROR: i32 = 1;
+/// the files could not be read or written, or the program could not be run
+const EXIT_FAILURE: i32 = 2;
+
+/// the file name of the runtime
+const RUNTIME: &str = "webml_rt.wasm";
+/// where the runtime is built in the source tree, from the directory of the executable in `target`
+const RUNTIME_IN_TREE: &str = "../../webml-rt/target/wasm32-unknown-unknown/release/webml_rt.wasm";
+
+fn fail(message: impl std::fmt::Display) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(EXIT_FAILURE)
 }
 
-fn main() {
-    env_logger::init();
-    let matches = app_from_crate!()
-        .arg(
-            Arg::with_name("PRINT_IR")
-                .long("print-ir")
-                .help("print the output of IR")
-                .value_name("IR")
-                .takes_value(true)
-                .multiple(true),
-        )
-        .arg(
-            Arg::with_name("INPUT")
-                .help("file to compile")
-                .required(true),
-        )
-        .get_matches();
+fn common_args<'a, 'b>() -> Vec<Arg<'a, 'b>> {
+    vec![
+        Arg::with_name("PRINT_IR")
+            .long("print-ir")
+            .help("print the output of IR")
+            .value_name("IR")
+            .takes_value(true)
+            .multiple(true),
+        Arg::with_name("INT_WIDTH")
+            .long("int-width")
+            .help("width of int")
+            .value_name("WIDTH")
+            .takes_value(true)
+            .possible_values(&["32", "64"])
+            .default_value("32"),
+        Arg::with_name("OPT_LEVEL")
+            .short("O")
+            .help("optimization level")
+            .value_name("LEVEL")
+            .takes_value(true)
+            .possible_values(&["0", "1", "2"])
+            .default_value("0"),
+        Arg::with_name("UNCHECKED_ARITH")
+            .long("unchecked-arith")
+            .help("wrap around on overflow instead of raising Overflow and Div"),
+        Arg::with_name("ERROR_FORMAT")
+            .long("error-format")
+            .help("format of errors and warnings")
+            .value_name("FORMAT")
+            .takes_value(true)
+            .possible_values(&["human", "json"])
+            .default_value("human"),
+        Arg::with_name("NO_PRELUDE")
+            .long("no-prelude")
+            .help("compile without the prelude"),
+        Arg::with_name("PRELUDE")
+            .long("prelude")
+            .help("use the file as the prelude instead of the bundled one")
+            .value_name("FILE")
+            .takes_value(true)
+            .conflicts_with("NO_PRELUDE"),
+        Arg::with_name("IMPORT")
+            .long("import")
+            .help("compile against the unit of the interface (.wmi). its module is the .wasm next to it")
+            .value_name("FILE")
+            .takes_value(true)
+            .number_of_values(1)
+            .multiple(true),
+        Arg::with_name("INPUT")
+            .help("files to compile in order, or basis files (.mlb) listing them")
+            .required(true)
+            .multiple(true),
+    ]
+}
+
+fn runtime_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("RUNTIME")
+        .long("runtime")
+        .help("path to webml_rt.wasm the program is run with. defaults to $WEBML_RUNTIME or the one next to the executable")
+        .value_name("PATH")
+        .takes_value(true)
+}
+
+/// the runtime given by `--runtime` or `WEBML_RUNTIME`, or else the one next to the executable
+/// or built in the source tree of the executable, if any
+fn runtime(matches: &ArgMatches) -> Option<PathBuf> {
+    if let Some(runtime) = matches.value_of("RUNTIME") {
+        return Some(runtime.into());
+    }
+    if let Some(runtime) = env::var_os("WEBML_RUNTIME") {
+        return Some(runtime.into());
+    }
+    let exe = env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    vec![dir.join(RUNTIME), dir.join(RUNTIME_IN_TREE)]
+        .into_iter()
+        .find(|runtime| runtime.is_file())
+}
+
+/// the input files with the prelude and the declarations of the imported units prepended
+fn load(matches: &ArgMatches, config: &Config) -> (Sources, Imports) {
+    let read = |path: &str| {
+        fs::read_to_string(path).unwrap_or_else(|e| fail(format!("cannot read {}: {}", path, e)))
+    };
+    let error = |e: &dyn std::fmt::Display| -> ! {
+        eprintln!("error: {}", e);
+        process::exit(EXIT_ERROR)
+    };
+    let mut sources = Sources::new();
+    if let Some(prelude) = matches.value_of("PRELUDE") {
+        sources.add_file(prelude, &read(prelude))
+    } else if !matches.is_present("NO_PRELUDE") {
+        sources.add_file("<prelude>", webml::PRELUDE)
+    }
+    let interfaces = matches
+        .values_of("IMPORT")
+        .into_iter()
+        .flatten()
+        .map(|path| {
+            let interface = Interface::parse(&read(path))
+                .unwrap_or_else(|e| error(&format!("{}: {}", path, e)));
+            let wasm = fs::canonicalize(path)
+                .unwrap_or_else(|e| fail(format!("cannot read {}: {}", path, e)))
+                .with_extension("wasm");
+            (interface, wasm.to_string_lossy().into_owned())
+        })
+        .collect::<Vec<_>>();
+    let mut imports = Imports::new(&interfaces, config).unwrap_or_else(|e| error(&e));
+    imports.add_to(&mut sources);
+    for input in matches.values_of("INPUT").unwrap() {
+        if Path::new(input).extension().is_some_and(|e| e == "mlb") {
+            mlb::load(input, &mut sources).unwrap_or_else(|e| error(&e))
+        } else {
+            sources.add_file(input, &read(input))
+        }
+    }
+    (sources, imports)
+}
 
-    let filename = matches
-        .value_of("INPUT")
-        .unwrap_or("ml_example/example1.sml");
+fn report(
+    matches: &ArgMatches,
+    sources: &Sources,
+    diagnostics: impl IntoIterator<Item = Diagnostic>,
+) {
+    let json = matches.value_of("ERROR_FORMAT") == Some("json");
+    for diagnostic in diagnostics {
+        if json {
+            eprintln!("{}", diagnostic.to_json(sources));
+        } else {
+            eprintln!("{}", diagnostic.render(sources));
+        }
+    }
+}
+
+fn config(matches: &ArgMatches) -> Config {
     let pretty_print_ir = matches
         .values_of("PRINT_IR")
         .into_iter()
@@ -39,14 +173,206 @@ fn main() {
         .map(|s| s.to_string())
         .collect::<HashSet<String>>();
 
-    let config = Config {
+    let int_width = match matches.value_of("INT_WIDTH") {
+        Some("64") => IntWidth::Int64,
+        _ => IntWidth::Int32,
+    };
+
+    let opt_level = match matches.value_of("OPT_LEVEL") {
+        Some("1") => OptLevel::O1,
+        Some("2") => OptLevel::O2,
+        _ => OptLevel::O0,
+    };
+
+    Config {
         pretty_print_ir,
-        ..Default::default()
+        int_width,
+        unchecked_arith: matches.is_present("UNCHECKED_ARITH"),
+        opt_level,
+    }
+}
+
+/// reports the warnings and the error of the compilation, exiting on the error
+fn finish<T>(
+    sources: &Sources,
+    matches: &ArgMatches,
+    result: Result<(T, Vec<Warning>), TypeError>,
+) -> T {
+    match result {
+        Ok((t, warnings)) => {
+            report(
+                matches,
+                sources,
+                warnings.iter().map(Diagnostic::from_warning),
+            );
+            t
+        }
+        Err(e) => {
+            report(
+                matches,
+                sources,
+                Diagnostic::from_error(sources.input(), &e),
+            );
+            process::exit(EXIT_ERROR)
+        }
+    }
+}
+
+fn write(path: &Path, contents: impl AsRef<[u8]>) {
+    fs::write(path, contents)
+        .unwrap_or_else(|e| fail(format!("cannot write {}: {}", path.display(), e)))
+}
+
+fn build(matches: &ArgMatches) {
+    let config = config(matches);
+    let (sources, imports) = load(matches, &config);
+    let emits = matches.values_of("EMIT").unwrap().collect::<Vec<_>>();
+    // a single output is written to the path as is, and the others next to it
+    let output = match matches.value_of("OUTPUT") {
+        Some(output) => PathBuf::from(output),
+        None => {
+            let first = matches.value_of("INPUT").unwrap();
+            let stem = Path::new(first).file_stem().unwrap_or_default();
+            Path::new(stem).with_extension("wasm")
+        }
+    };
+    let path = |kind: &str| match matches.value_of("OUTPUT") {
+        Some(_) if emits.len() == 1 => output.clone(),
+        _ => output.with_extension(kind),
     };
 
-    let prelude = include_str!("../ml_src/prelude.sml").to_string();
-    let mut input = prelude;
-    read_and_append_to_string(filename, &mut input).expect("failed to load file");
-    let code = compile_str(&input, &config).unwrap();
-    fs::write("out.wasm", &code).unwrap()
+    // only the units compiled for the others export their bindings
+    let (lir, exports) = if emits.contains(&"wmi") {
+        let wmi = path("wmi");
+        let unit = wmi.file_stem().unwrap_or_default().to_string_lossy();
+        let result = compile_unit(sources.input(), &unit, &imports, &config);
+        let (interface, exports, lir) = finish(&sources, matches, result);
+        write(&wmi, format!("{}\n", interface.to_json()));
+        (lir, exports)
+    } else {
+        let result = compile_to_lir(sources.input(), &config);
+        (finish(&sources, matches, result), Vec::new())
+    };
+
+    if emits.contains(&"ir") {
+        let mut ir = Vec::new();
+        lir.pp(&mut ir, 0).unwrap();
+        write(&path("ir"), ir);
+    }
+    let code = emit_wasm(lir, exports, &config);
+    if emits.contains(&"wasm") {
+        write(&path("wasm"), &code);
+    }
+    if emits.contains(&"wat") {
+        let text = wat::to_wat(&code).unwrap_or_else(|e| fail(e));
+        write(&path("wat"), text);
+    }
+    if emits.contains(&"js") {
+        let wasm = path("wasm");
+        let wasm = wasm.file_name().unwrap_or_default().to_string_lossy();
+        // the script can be moved next to the runtime if it is not found yet
+        let runtime = runtime(matches).unwrap_or_else(|| RUNTIME.into());
+        write(
+            &path("js"),
+            js::loader(&imports.units, &wasm, &runtime.to_string_lossy()),
+        );
+    }
+}
+
+fn check(matches: &ArgMatches) {
+    let config = config(matches);
+    let (sources, _) = load(matches, &config);
+    let result = check_str(sources.input(), &config).map(|warnings| ((), warnings));
+    finish(&sources, matches, result)
+}
+
+fn run(matches: &ArgMatches) -> io::Result<i32> {
+    let config = config(matches);
+    let (sources, imports) = load(matches, &config);
+    let result = compile_to_lir(sources.input(), &config);
+    let lir = finish(&sources, matches, result);
+    let code = emit_wasm(lir, Vec::new(), &config);
+
+    let runtime = match runtime(matches) {
+        Some(runtime) if runtime.is_file() => runtime,
+        Some(runtime) => fail(format!(
+            "the runtime is not found at {}. build webml-rt or give --runtime",
+            runtime.display()
+        )),
+        None => {
+            fail("the runtime is not found. build webml-rt, or give --runtime or WEBML_RUNTIME")
+        }
+    };
+    let dir = std::env::temp_dir().join(format!("webml-{}", process::id()));
+    fs::create_dir_all(&dir)?;
+    fs::write(dir.join("main.wasm"), &code)?;
+    fs::write(
+        dir.join("main.js"),
+        js::loader(&imports.units, "main.wasm", &runtime.to_string_lossy()),
+    )?;
+    let status = Command::new(matches.value_of("RUNNER").unwrap())
+        .arg(dir.join("main.js"))
+        .status();
+    fs::remove_dir_all(&dir)?;
+    Ok(status?.code().unwrap_or(EXIT_FAILURE))
+}
+
+fn main() {
+    env_logger::init();
+    let matches = app_from_crate!()
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(
+            SubCommand::with_name("build")
+                .about("compile the file into a wasm module")
+                .args(&common_args())
+                .arg(
+                    Arg::with_name("OUTPUT")
+                        .short("o")
+                        .long("output")
+                        .help("path of the output. defaults to the name of the input in the current directory")
+                        .value_name("PATH")
+                        .takes_value(true),
+                )
+                .arg(
+                    Arg::with_name("EMIT")
+                        .long("emit")
+                        .help("kinds of the output")
+                        .value_name("KINDS")
+                        .takes_value(true)
+                        .use_delimiter(true)
+                        .possible_values(&["wasm", "wat", "js", "ir", "wmi"])
+                        .default_value("wasm"),
+                )
+                .arg(runtime_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("check the file for errors without compiling it")
+                .args(&common_args()),
+        )
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("compile the file and run it")
+                .args(&common_args())
+                .arg(
+                    Arg::with_name("RUNNER")
+                        .long("runner")
+                        .help("JavaScript engine to run the program with")
+                        .value_name("COMMAND")
+                        .takes_value(true)
+                        .default_value("node"),
+                )
+                .arg(runtime_arg()),
+        )
+        .get_matches();
+
+    match matches.subcommand() {
+        ("build", Some(matches)) => build(matches),
+        ("check", Some(matches)) => check(matches),
+        ("run", Some(matches)) => {
+            let code = run(matches).unwrap_or_else(|e| fail(format!("cannot run: {}", e)));
+            process::exit(code)
+        }
+        _ => unreachable!(),
+    }
 }
@@ -0,0 +1,104 @@
+use crate::config::Config;
+use crate::mir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashMap;
+
+/// turns the branches on literals and constructed values into the jumps to the arms taken.
+/// the arms left are removed by `RemoveUnreachable`
+#[derive(Default)]
+pub struct BranchFold {
+    keys: HashMap<Symbol, u64>,
+}
+
+impl BranchFold {
+    pub fn new() -> Self {
+        BranchFold {
+            keys: HashMap::new(),
+        }
+    }
+
+    fn fold_mir(&mut self, mir: MIR) -> MIR {
+        MIR(mir.0.into_iter().map(|f| self.fold_fun(f)).collect())
+    }
+
+    fn fold_fun(&mut self, mut fun: Function) -> Function {
+        self.keys.clear();
+        for ebb in fun.body.iter() {
+            for op in ebb.body.iter() {
+                match op {
+                    Op::Lit { var, ty, value } => {
+                        // the same as the keys of the patterns
+                        let key = match (ty, value) {
+                            (EbbTy::IntInf, _) => continue,
+                            (_, Literal::Int(n)) => *n as u64,
+                            (_, Literal::Word(w)) => *w,
+                            (_, Literal::Char(c)) => *c as u64,
+                            _ => continue,
+                        };
+                        self.keys.insert(var.clone(), key);
+                    }
+                    // the values of datatypes are branched on by their descriminants
+                    Op::Union { var, index, .. } => {
+                        self.keys.insert(var.clone(), *index as u64);
+                    }
+                    _ => (),
+                }
+            }
+        }
+        let has_params = fun
+            .body
+            .iter()
+            .map(|ebb| (ebb.name.clone(), !ebb.params.is_empty()))
+            .collect::<HashMap<_, _>>();
+        for ebb in fun.body.iter_mut() {
+            let last = ebb.body.last_mut().expect("EBB ends with a jump");
+            let jump = match last {
+                Op::Branch {
+                    cond,
+                    clauses,
+                    default,
+                } => match self.keys.get(cond) {
+                    None => continue,
+                    Some(key) => {
+                        match clauses.iter().find(|(k, _, _)| k == key) {
+                            Some((_, target, forward)) => Op::Jump {
+                                target: target.clone(),
+                                forward: *forward,
+                                args: vec![],
+                            },
+                            // the default arm takes the value branched on
+                            None => match default {
+                                Some((target, forward)) => Op::Jump {
+                                    target: target.clone(),
+                                    forward: *forward,
+                                    args: if has_params[target] {
+                                        vec![cond.clone()]
+                                    } else {
+                                        vec![]
+                                    },
+                                },
+                                None => continue,
+                            },
+                        }
+                    }
+                },
+                _ => continue,
+            };
+            *last = jump;
+        }
+        fun
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for BranchFold {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.fold_mir(mir)))
+    }
+}
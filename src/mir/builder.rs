@@ -106,6 +106,91 @@ impl EBBBuilder {
         self
     }
 
+    pub fn andb(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Andb { var, ty, l, r });
+        self
+    }
+
+    pub fn orb(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Orb { var, ty, l, r });
+        self
+    }
+
+    pub fn xorb(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Xorb { var, ty, l, r });
+        self
+    }
+
+    pub fn lshift(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Lshift { var, ty, l, r });
+        self
+    }
+
+    pub fn rshift(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Rshift { var, ty, l, r });
+        self
+    }
+
+    pub fn arshift(&mut self, var: Symbol, ty: EbbTy, l: Symbol, r: Symbol) -> &mut Self {
+        self.push(Op::Arshift { var, ty, l, r });
+        self
+    }
+
+    pub fn neg(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Neg { var, ty, arg });
+        self
+    }
+
+    pub fn abs(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Abs { var, ty, arg });
+        self
+    }
+
+    pub fn real(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Real { var, ty, arg });
+        self
+    }
+
+    pub fn floor(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Floor { var, ty, arg });
+        self
+    }
+
+    pub fn ceil(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Ceil { var, ty, arg });
+        self
+    }
+
+    pub fn round(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Round { var, ty, arg });
+        self
+    }
+
+    pub fn trunc(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Trunc { var, ty, arg });
+        self
+    }
+
+    pub fn sqrt(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::Sqrt { var, ty, arg });
+        self
+    }
+
+    pub fn intinf_from_int(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::IntInfFromInt { var, ty, arg });
+        self
+    }
+
+    pub fn intinf_to_int(&mut self, var: Symbol, ty: EbbTy, arg: Symbol) -> &mut Self {
+        self.push(Op::IntInfToInt { var, ty, arg });
+        self
+    }
+
+    pub fn raise(&mut self, var: Symbol, ty: EbbTy, exn: BuiltinExn) -> &mut Self {
+        self.push(Op::Raise { var, ty, exn });
+        self
+    }
+
     pub fn closure(
         &mut self,
         var: Symbol,
@@ -191,7 +276,7 @@ impl EBBBuilder {
     pub fn branch(
         mut self,
         cond: Symbol,
-        clauses: Vec<(u32, Symbol, bool)>,
+        clauses: Vec<(u64, Symbol, bool)>,
         default: Option<(Symbol, bool)>,
     ) -> EBB {
         self.push(Op::Branch {
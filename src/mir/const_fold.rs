@@ -0,0 +1,374 @@
+use crate::config::{Config, IntWidth};
+use crate::mir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashMap;
+
+/// computes the ops on literals at compile time. results are literals in turn
+/// and fold the ops using them.
+/// ops that raise or trap at run time are left as they are
+#[derive(Default)]
+pub struct ConstFold {
+    consts: HashMap<Symbol, Literal>,
+    int_width: IntWidth,
+    unchecked_arith: bool,
+}
+
+macro_rules! compare {
+    ($l: expr, $r: expr, $op: tt) => {
+        match ($l, $r) {
+            (Literal::Int(l), Literal::Int(r)) => Some(l $op r),
+            (Literal::Word(l), Literal::Word(r)) => Some(l $op r),
+            (Literal::Char(l), Literal::Char(r)) => Some(l $op r),
+            (Literal::Real(l), Literal::Real(r)) => Some(l $op r),
+            _ => None,
+        }
+    };
+}
+
+fn bits(size: WordSize) -> u64 {
+    match size {
+        WordSize::W8 => 8,
+        WordSize::W32 => 32,
+        WordSize::W64 => 64,
+    }
+}
+
+fn word(size: WordSize, w: u64) -> Literal {
+    match size {
+        WordSize::W8 => Literal::Word(w as u8 as u64),
+        WordSize::W32 => Literal::Word(w as u32 as u64),
+        WordSize::W64 => Literal::Word(w),
+    }
+}
+
+impl ConstFold {
+    pub fn new() -> Self {
+        ConstFold {
+            consts: HashMap::new(),
+            int_width: IntWidth::default(),
+            unchecked_arith: false,
+        }
+    }
+
+    fn fold_mir(&mut self, mir: MIR) -> MIR {
+        MIR(mir.0.into_iter().map(|f| self.fold_fun(f)).collect())
+    }
+
+    fn fold_fun(&mut self, mut fun: Function) -> Function {
+        self.consts.clear();
+        for ebb in fun.body.iter_mut() {
+            for op in ebb.body.iter_mut() {
+                if let Some(value) = self.fold_op(op) {
+                    let var = op.var().expect("folded op defines a variable").clone();
+                    let ty = match op {
+                        Op::Lit { ty, .. } | Op::Alias { ty, .. } => ty.clone(),
+                        _ => self.ty(op),
+                    };
+                    self.consts.insert(var.clone(), value.clone());
+                    *op = Op::Lit { var, ty, value };
+                }
+            }
+        }
+        fun
+    }
+
+    fn ty(&self, op: &Op) -> EbbTy {
+        use crate::mir::Op::*;
+        match op {
+            Add { ty, .. }
+            | Sub { ty, .. }
+            | Mul { ty, .. }
+            | DivInt { ty, .. }
+            | DivFloat { ty, .. }
+            | Mod { ty, .. }
+            | Eq { ty, .. }
+            | Neq { ty, .. }
+            | Gt { ty, .. }
+            | Ge { ty, .. }
+            | Lt { ty, .. }
+            | Le { ty, .. }
+            | Andb { ty, .. }
+            | Orb { ty, .. }
+            | Xorb { ty, .. }
+            | Lshift { ty, .. }
+            | Rshift { ty, .. }
+            | Arshift { ty, .. }
+            | Neg { ty, .. }
+            | Abs { ty, .. }
+            | Real { ty, .. } => ty.clone(),
+            op => unreachable!("{:?} is not folded", op),
+        }
+    }
+
+    /// the value of `op` if it is known at compile time
+    fn fold_op(&self, op: &Op) -> Option<Literal> {
+        use crate::mir::Op::*;
+        match op {
+            // IntInfs are pointers to the runtime
+            Lit { ty, value, .. } if ty != &EbbTy::IntInf => Some(value.clone()),
+            Alias { sym, .. } => self.consts.get(sym).cloned(),
+            Add { ty, l, r, .. } => {
+                self.binop(ty, l, r, i64::checked_add, u64::wrapping_add, |l, r| l + r)
+            }
+            Sub { ty, l, r, .. } => {
+                self.binop(ty, l, r, i64::checked_sub, u64::wrapping_sub, |l, r| l - r)
+            }
+            Mul { ty, l, r, .. } => {
+                self.binop(ty, l, r, i64::checked_mul, u64::wrapping_mul, |l, r| l * r)
+            }
+            DivFloat { l, r, .. } => match (self.consts.get(l)?, self.consts.get(r)?) {
+                (Literal::Real(l), Literal::Real(r)) => Some(Literal::Real(l / r)),
+                _ => None,
+            },
+            // flooring as SML does. `Div` and `Overflow` are left to the run time
+            DivInt { ty, l, r, .. } | Mod { ty, l, r, .. } => {
+                let div = matches!(op, DivInt { .. });
+                match (ty, self.consts.get(l)?, self.consts.get(r)?) {
+                    (EbbTy::Int, Literal::Int(l), Literal::Int(r)) => {
+                        let (q, m) = (l.checked_div(*r)?, l.checked_rem(*r)?);
+                        let adjust = m != 0 && (m < 0) != (*r < 0);
+                        let n = match (div, adjust) {
+                            (true, true) => q - 1,
+                            (true, false) => q,
+                            (false, true) => m + r,
+                            (false, false) => m,
+                        };
+                        self.int_width.contains(n).then_some(Literal::Int(n))
+                    }
+                    (EbbTy::Word(size), Literal::Word(l), Literal::Word(r)) => {
+                        let w = if div {
+                            l.checked_div(*r)
+                        } else {
+                            l.checked_rem(*r)
+                        }?;
+                        Some(word(*size, w))
+                    }
+                    _ => None,
+                }
+            }
+            Eq { l, r, .. } => self.compare(l, r, |l, r| compare!(l, r, ==)),
+            Neq { l, r, .. } => self.compare(l, r, |l, r| compare!(l, r, !=)),
+            Gt { l, r, .. } => self.compare(l, r, |l, r| compare!(l, r, >)),
+            Ge { l, r, .. } => self.compare(l, r, |l, r| compare!(l, r, >=)),
+            Lt { l, r, .. } => self.compare(l, r, |l, r| compare!(l, r, <)),
+            Le { l, r, .. } => self.compare(l, r, |l, r| compare!(l, r, <=)),
+            Andb { ty, l, r, .. } => self.bitop(ty, l, r, |l, r| Some(l & r)),
+            Orb { ty, l, r, .. } => self.bitop(ty, l, r, |l, r| Some(l | r)),
+            Xorb { ty, l, r, .. } => self.bitop(ty, l, r, |l, r| Some(l ^ r)),
+            // shifts by the width or more give 0, or the sign for `~>>`
+            Lshift { ty, l, r, .. } => {
+                let width = bits(*word_size(ty)?);
+                self.bitop(ty, l, r, |l, r| Some(if r < width { l << r } else { 0 }))
+            }
+            Rshift { ty, l, r, .. } => {
+                let width = bits(*word_size(ty)?);
+                self.bitop(ty, l, r, |l, r| Some(if r < width { l >> r } else { 0 }))
+            }
+            Arshift { ty, l, r, .. } => {
+                let size = *word_size(ty)?;
+                self.bitop(ty, l, r, |l, r| {
+                    let r = r.min(bits(size) - 1);
+                    Some(match size {
+                        WordSize::W8 => ((l as u8 as i8) >> r) as u64,
+                        WordSize::W32 => ((l as u32 as i32) >> r) as u64,
+                        WordSize::W64 => ((l as i64) >> r) as u64,
+                    })
+                })
+            }
+            Neg { ty, arg, .. } => match (ty, self.consts.get(arg)?) {
+                (EbbTy::Int, Literal::Int(n)) => self.int(n.checked_neg(), n.wrapping_neg()),
+                (EbbTy::Float, Literal::Real(f)) => Some(Literal::Real(-f)),
+                _ => None,
+            },
+            Abs { ty, arg, .. } => match (ty, self.consts.get(arg)?) {
+                (EbbTy::Int, Literal::Int(n)) => self.int(n.checked_abs(), n.wrapping_abs()),
+                (EbbTy::Float, Literal::Real(f)) => Some(Literal::Real(f.abs())),
+                _ => None,
+            },
+            Real { arg, .. } => match self.consts.get(arg)? {
+                Literal::Int(n) => Some(Literal::Real(*n as f64)),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// the int `checked` computes if it fits in int, or `wrapped` to fit in int
+    /// when the arithmetics are unchecked
+    fn int(&self, checked: Option<i64>, wrapped: i64) -> Option<Literal> {
+        match checked {
+            Some(n) if self.int_width.contains(n) => Some(Literal::Int(n)),
+            _ if self.unchecked_arith => Some(Literal::Int(match self.int_width {
+                IntWidth::Int32 => wrapped as i32 as i64,
+                IntWidth::Int64 => wrapped,
+            })),
+            _ => None,
+        }
+    }
+
+    fn binop(
+        &self,
+        ty: &EbbTy,
+        l: &Symbol,
+        r: &Symbol,
+        int: fn(i64, i64) -> Option<i64>,
+        word_: fn(u64, u64) -> u64,
+        real: fn(f64, f64) -> f64,
+    ) -> Option<Literal> {
+        match (ty, self.consts.get(l)?, self.consts.get(r)?) {
+            (EbbTy::Int, Literal::Int(l), Literal::Int(r)) => {
+                let wrapped = word_(*l as u64, *r as u64) as i64;
+                self.int(int(*l, *r), wrapped)
+            }
+            (EbbTy::Word(size), Literal::Word(l), Literal::Word(r)) => {
+                Some(word(*size, word_(*l, *r)))
+            }
+            (EbbTy::Float, Literal::Real(l), Literal::Real(r)) => Some(Literal::Real(real(*l, *r))),
+            _ => None,
+        }
+    }
+
+    fn bitop(
+        &self,
+        ty: &EbbTy,
+        l: &Symbol,
+        r: &Symbol,
+        f: impl Fn(u64, u64) -> Option<u64>,
+    ) -> Option<Literal> {
+        match (ty, self.consts.get(l)?, self.consts.get(r)?) {
+            (EbbTy::Word(size), Literal::Word(l), Literal::Word(r)) => {
+                Some(word(*size, f(*l, *r)?))
+            }
+            _ => None,
+        }
+    }
+
+    /// comparisons give bools as 0 and 1
+    fn compare(
+        &self,
+        l: &Symbol,
+        r: &Symbol,
+        f: impl Fn(&Literal, &Literal) -> Option<bool>,
+    ) -> Option<Literal> {
+        let b = f(self.consts.get(l)?, self.consts.get(r)?)?;
+        Some(Literal::Int(b as i64))
+    }
+}
+
+fn word_size(ty: &EbbTy) -> Option<&WordSize> {
+    match ty {
+        EbbTy::Word(size) => Some(size),
+        _ => None,
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for ConstFold {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        config: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        self.int_width = config.int_width;
+        self.unchecked_arith = config.unchecked_arith;
+        Ok((symbol_table, self.fold_mir(mir)))
+    }
+}
+
+#[test]
+fn test_fold() {
+    use crate::mir::builder::*;
+    use std::convert::Infallible;
+
+    let sym = |name: &str| Symbol(name.into(), 0, None);
+    let bool_ty = EbbTy::Variable(sym("bool"));
+    let mut fb = FunctionBuilder::new(sym("f"), EbbTy::Int);
+    let mut eb = EBBBuilder::new(sym("entry"), vec![]);
+    eb.lit(sym("a"), EbbTy::Int, Literal::Int(1))
+        .lit(sym("b"), EbbTy::Int, Literal::Int(2))
+        .add(sym("c"), EbbTy::Int, sym("a"), sym("b"))
+        .lit(sym("max"), EbbTy::Int, Literal::Int(i32::MAX as i64))
+        .add(sym("overflow"), EbbTy::Int, sym("max"), sym("a"))
+        .add(sym("unused"), EbbTy::Int, sym("a"), sym("a"))
+        .eq(sym("cond"), bool_ty, sym("c"), sym("b"));
+    fb.add_ebb(eb.branch(
+        sym("cond"),
+        vec![(0, sym("else"), true), (1, sym("then"), true)],
+        None,
+    ));
+    let mut eb = EBBBuilder::new(sym("then"), vec![]);
+    eb.alias(sym("d"), EbbTy::Int, sym("a"));
+    fb.add_ebb(eb.ret(sym("d"), EbbTy::Int));
+    let eb = EBBBuilder::new(sym("else"), vec![]);
+    fb.add_ebb(eb.ret(sym("c"), EbbTy::Int));
+    let mir = MIR(vec![fb.build()]);
+    let symbol_table = SymbolTable {
+        table: HashMap::new(),
+    };
+
+    let config = Config::default();
+    let mut passes: Vec<Box<dyn Pass<_, Infallible, Target = _>>> = vec![
+        Box::new(ConstFold::new()),
+        Box::new(CopyProp::new()),
+        Box::new(BranchFold::new()),
+        Box::new(RemoveUnreachable::new()),
+        Box::new(DeadCode::new()),
+    ];
+    let (_, mir) = passes
+        .iter_mut()
+        .try_fold((symbol_table, mir), |mir, pass| pass.trans(mir, &config))
+        .unwrap();
+
+    let body = &mir.0[0].body;
+    let names = body
+        .iter()
+        .map(|ebb| ebb.name.0.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(names, ["entry", "else"]);
+    let vars = body[0]
+        .body
+        .iter()
+        .filter_map(|op| op.var())
+        .map(|var| var.0.as_str())
+        .collect::<Vec<_>>();
+    // `max + 1` raises `Overflow` and is left
+    assert_eq!(vars, ["a", "c", "max", "overflow"]);
+    match &body[0].body[1] {
+        Op::Lit { value, .. } => assert_eq!(value, &Literal::Int(3)),
+        op => panic!("not folded: {:?}", op),
+    }
+}
+
+#[test]
+fn test_fold_floor() {
+    let sym = |name: &str| Symbol(name.into(), 0, None);
+    let mut fold = ConstFold::new();
+    // (l, r, l div r, l mod r)
+    let cases = [
+        (7, 2, 3, 1),
+        (-7, 2, -4, 1),
+        (7, -2, -4, -1),
+        (-7, -2, 3, -1),
+        (-6, 2, -3, 0),
+    ];
+    for (l, r, div, mod_) in cases.iter() {
+        fold.consts.insert(sym("l"), Literal::Int(*l));
+        fold.consts.insert(sym("r"), Literal::Int(*r));
+        let op = Op::DivInt {
+            var: sym("div"),
+            ty: EbbTy::Int,
+            l: sym("l"),
+            r: sym("r"),
+        };
+        assert_eq!(fold.fold_op(&op), Some(Literal::Int(*div)));
+        let op = Op::Mod {
+            var: sym("mod"),
+            ty: EbbTy::Int,
+            l: sym("l"),
+            r: sym("r"),
+        };
+        assert_eq!(fold.fold_op(&op), Some(Literal::Int(*mod_)));
+    }
+}
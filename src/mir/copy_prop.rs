@@ -0,0 +1,187 @@
+use crate::config::Config;
+use crate::mir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// replaces the variables that are copies of other variables by the originals:
+/// aliases, projections of tuples and selections of unions made in the same function,
+/// and the parameters of the blocks every jump passes the same variable to
+#[derive(Default)]
+pub struct CopyProp {
+    copies: HashMap<Symbol, Symbol>,
+    tuples: HashMap<Symbol, Vec<Symbol>>,
+    unions: HashMap<Symbol, (u32, Symbol)>,
+    /// the blocks and whether their parameters are removed
+    removed_params: HashMap<Symbol, Vec<bool>>,
+}
+
+impl CopyProp {
+    pub fn new() -> Self {
+        CopyProp {
+            copies: HashMap::new(),
+            tuples: HashMap::new(),
+            unions: HashMap::new(),
+            removed_params: HashMap::new(),
+        }
+    }
+
+    fn conv_mir(&mut self, mir: MIR) -> MIR {
+        MIR(mir.0.into_iter().map(|f| self.conv_fun(f)).collect())
+    }
+
+    fn conv_fun(&mut self, mut fun: Function) -> Function {
+        use crate::mir::Op::*;
+        self.copies.clear();
+        self.tuples.clear();
+        self.unions.clear();
+        self.param_copies(&mut fun);
+        for ebb in fun.body.iter() {
+            for op in ebb.body.iter() {
+                match op {
+                    Alias { var, sym, .. } => {
+                        let orig = sym.clone();
+                        self.copy(var, orig);
+                    }
+                    Tuple { var, tuple, .. } => {
+                        let tuple = tuple.iter().map(|v| self.resolve(v)).collect();
+                        self.tuples.insert(var.clone(), tuple);
+                    }
+                    Union {
+                        var,
+                        index,
+                        variant,
+                        ..
+                    } => {
+                        let variant = self.resolve(variant);
+                        self.unions.insert(var.clone(), (*index, variant));
+                    }
+                    Proj {
+                        var, index, tuple, ..
+                    } => {
+                        let orig = self
+                            .tuples
+                            .get(&self.resolve(tuple))
+                            .map(|tuple| tuple[*index as usize].clone());
+                        if let Some(orig) = orig {
+                            self.copy(var, orig);
+                        }
+                    }
+                    Select {
+                        var, index, union, ..
+                    } => match self.unions.get(&self.resolve(union)) {
+                        Some((i, orig)) if i == index => {
+                            let orig = orig.clone();
+                            self.copy(var, orig);
+                        }
+                        _ => (),
+                    },
+                    _ => (),
+                }
+            }
+        }
+        for ebb in fun.body.iter_mut() {
+            let body = std::mem::take(&mut ebb.body);
+            ebb.body = body
+                .into_iter()
+                .filter(|op| op.var().is_none_or(|var| !self.copies.contains_key(var)))
+                .map(|mut op| {
+                    if let Jump { target, args, .. } = &mut op {
+                        if let Some(removed) = self.removed_params.get(target) {
+                            let mut removed = removed.iter();
+                            args.retain(|_| !removed.next().expect("as many args as params"));
+                        }
+                    }
+                    for arg in op.args_mut() {
+                        *arg = self.resolve(arg);
+                    }
+                    op
+                })
+                .collect();
+        }
+        fun
+    }
+
+    /// removes the parameters of the blocks only jumped to with the same variable
+    /// and makes them the copies of it. the targets of branches keep their parameter
+    fn param_copies(&mut self, fun: &mut Function) {
+        use crate::mir::Op::*;
+        let mut incoming: HashMap<Symbol, Vec<Option<Symbol>>> = HashMap::new();
+        let mut branched = HashSet::new();
+        for op in fun.body.iter().flat_map(|ebb| ebb.body.iter()) {
+            match op {
+                Jump { target, args, .. } => match incoming.get_mut(target) {
+                    None => {
+                        incoming.insert(target.clone(), args.iter().cloned().map(Some).collect());
+                    }
+                    Some(params) => {
+                        for (param, arg) in params.iter_mut().zip(args) {
+                            if param.as_ref() != Some(arg) {
+                                *param = None
+                            }
+                        }
+                    }
+                },
+                Branch {
+                    clauses, default, ..
+                } => {
+                    branched.extend(clauses.iter().map(|(_, target, _)| target.clone()));
+                    branched.extend(default.iter().map(|(target, _)| target.clone()));
+                }
+                _ => (),
+            }
+        }
+        self.removed_params.clear();
+        // the entry is called with the arguments
+        for ebb in fun.body.iter_mut().skip(1) {
+            let args = match incoming.get(&ebb.name) {
+                Some(args) if !branched.contains(&ebb.name) => args,
+                _ => continue,
+            };
+            let mut removed = Vec::new();
+            let mut params = Vec::new();
+            for (param, arg) in ebb.params.drain(..).zip(args) {
+                match arg {
+                    Some(arg) if self.resolve(arg) != param.1 => {
+                        self.copy(&param.1, arg.clone());
+                        removed.push(true);
+                    }
+                    _ => {
+                        params.push(param);
+                        removed.push(false);
+                    }
+                }
+            }
+            ebb.params = params;
+            self.removed_params.insert(ebb.name.clone(), removed);
+        }
+    }
+
+    /// makes `var` a copy of `orig` unless it makes a cycle, which unreachable blocks can have
+    fn copy(&mut self, var: &Symbol, orig: Symbol) {
+        let orig = self.resolve(&orig);
+        if orig != *var {
+            self.copies.insert(var.clone(), orig);
+        }
+    }
+
+    fn resolve(&self, sym: &Symbol) -> Symbol {
+        let mut sym = sym;
+        while let Some(orig) = self.copies.get(sym) {
+            sym = orig;
+        }
+        sym.clone()
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for CopyProp {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.conv_mir(mir)))
+    }
+}
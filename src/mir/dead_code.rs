@@ -0,0 +1,65 @@
+use crate::config::Config;
+use crate::mir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::HashSet;
+
+/// removes the pure ops whose variables are not used
+#[derive(Default)]
+pub struct DeadCode {
+    used: HashSet<Symbol>,
+}
+
+impl DeadCode {
+    pub fn new() -> Self {
+        DeadCode {
+            used: HashSet::new(),
+        }
+    }
+
+    fn conv_mir(&mut self, mir: MIR, unchecked_arith: bool) -> MIR {
+        MIR(mir
+            .0
+            .into_iter()
+            .map(|f| self.conv_fun(f, unchecked_arith))
+            .collect())
+    }
+
+    fn conv_fun(&mut self, mut fun: Function, unchecked_arith: bool) -> Function {
+        // removing an op can make the ops it uses dead
+        loop {
+            self.used = fun
+                .body
+                .iter()
+                .flat_map(|ebb| ebb.body.iter())
+                .flat_map(|op| op.args())
+                .cloned()
+                .collect();
+            let mut removed = false;
+            for ebb in fun.body.iter_mut() {
+                let len = ebb.body.len();
+                let used = &self.used;
+                ebb.body.retain(|op| match op.var() {
+                    Some(var) => used.contains(var) || !op.is_pure(unchecked_arith),
+                    None => true,
+                });
+                removed |= ebb.body.len() != len;
+            }
+            if !removed {
+                return fun;
+            }
+        }
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for DeadCode {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        config: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.conv_mir(mir, config.unchecked_arith)))
+    }
+}
@@ -0,0 +1,148 @@
+use crate::mir::*;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+use std::iter;
+
+/// the tuples, closures and unions each function makes that reach no returns or unknown
+/// calls, directly or through the values holding them. they can live in the stack frame
+pub fn non_escaping(mir: &MIR) -> HashMap<Symbol, HashSet<Symbol>> {
+    let mut escape = Escape {
+        params: mir
+            .0
+            .iter()
+            .map(|f| (f.name.clone(), vec![false; f.body[0].params.len()]))
+            .collect(),
+    };
+    // the escaping parameters only increase
+    loop {
+        let mut changed = false;
+        for fun in mir.0.iter() {
+            let escaping = escape.escaping(fun);
+            let params = fun.body[0]
+                .params
+                .iter()
+                .map(|(_, param)| escaping.contains(param))
+                .collect::<Vec<_>>();
+            if escape.params[&fun.name] != params {
+                escape.params.insert(fun.name.clone(), params);
+                changed = true;
+            }
+        }
+        if !changed {
+            break;
+        }
+    }
+    mir.0
+        .iter()
+        .map(|fun| {
+            let escaping = escape.escaping(fun);
+            let allocs = fun
+                .body
+                .iter()
+                .flat_map(|ebb| ebb.body.iter())
+                .filter_map(|op| match op {
+                    Op::Tuple { var, .. } | Op::Closure { var, .. } | Op::Union { var, .. }
+                        if !escaping.contains(var) =>
+                    {
+                        Some(var.clone())
+                    }
+                    _ => None,
+                })
+                .collect();
+            (fun.name.clone(), allocs)
+        })
+        .collect()
+}
+
+struct Escape {
+    /// the functions and whether their parameters escape
+    params: HashMap<Symbol, Vec<bool>>,
+}
+
+impl Escape {
+    /// the variables of `fun` holding the values that escape
+    fn escaping(&self, fun: &Function) -> HashSet<Symbol> {
+        use crate::mir::Op::*;
+        let params = fun
+            .body
+            .iter()
+            .map(|ebb| (&ebb.name, &ebb.params))
+            .collect::<HashMap<_, _>>();
+        let closures = fun
+            .body
+            .iter()
+            .flat_map(|ebb| ebb.body.iter())
+            .filter_map(|op| match op {
+                Closure { var, fun, .. } => Some((var, fun)),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+        let mut escaping = HashSet::new();
+        // the variables escaping if the others do: the values passed to them, the values
+        // held in them and the values the pointers loaded from them are held in
+        let mut flows = Vec::new();
+        for op in fun.body.iter().flat_map(|ebb| ebb.body.iter()) {
+            match op {
+                Ret {
+                    value: Some(value), ..
+                } => {
+                    escaping.insert(value);
+                }
+                Tuple { var, tuple, .. } => flows.extend(tuple.iter().map(|elem| (elem, var))),
+                Union { var, variant, .. } => flows.push((variant, var)),
+                Closure { var, env, .. } => flows.extend(env.iter().map(|(_, elem)| (elem, var))),
+                Proj { var, ty, tuple, .. } if is_pointer(ty) => flows.push((tuple, var)),
+                Select { var, ty, union, .. } if is_pointer(ty) => flows.push((union, var)),
+                ExternCall { args, .. } => escaping.extend(args),
+                Alias { var, sym, .. } => flows.push((sym, var)),
+                Jump { target, args, .. } => {
+                    flows.extend(args.iter().zip(params[target].iter().map(|(_, p)| p)))
+                }
+                // the default arm takes the value branched on
+                Branch {
+                    cond,
+                    default: Some((target, _)),
+                    ..
+                } => flows.extend(params[target].iter().map(|(_, param)| (cond, param))),
+                Call { fun, args, .. } => {
+                    // closures take themselves as the environment
+                    let (callee, passed) = match closures.get(fun) {
+                        Some(f) => (Some(*f), iter::once(fun).chain(args).collect()),
+                        None if self.params.contains_key(fun) => (Some(fun), args.iter().collect()),
+                        None => (None, iter::once(fun).chain(args).collect::<Vec<_>>()),
+                    };
+                    match callee.and_then(|f| self.params.get(f)) {
+                        Some(escapes) if escapes.len() == passed.len() => escaping.extend(
+                            passed
+                                .into_iter()
+                                .zip(escapes)
+                                .filter(|(_, escapes)| **escapes)
+                                .map(|(arg, _)| arg),
+                        ),
+                        _ => escaping.extend(passed),
+                    }
+                }
+                _ => (),
+            }
+        }
+        loop {
+            let mut changed = false;
+            for (from, to) in flows.iter() {
+                if escaping.contains(to) {
+                    changed |= escaping.insert(from);
+                }
+            }
+            if !changed {
+                return escaping.into_iter().cloned().collect();
+            }
+        }
+    }
+}
+
+/// whether the values of `ty` can point to the values allocated
+fn is_pointer(ty: &EbbTy) -> bool {
+    matches!(
+        ty,
+        EbbTy::Tuple(_) | EbbTy::Union(_) | EbbTy::Cls { .. } | EbbTy::Variable(_)
+    )
+}
@@ -42,13 +42,13 @@ impl HIR2MIRPass {
         let name = name.to_string();
         let label = self.label;
         self.label += 1;
-        Symbol(name, label)
+        Symbol(name, label, None)
     }
 
     fn gensym(&mut self, name: &str) -> Symbol {
         let name = name.to_string();
         let id = self.id.next();
-        Symbol(name, id)
+        Symbol(name, id, None)
     }
 
     fn generate_symbol_table(&self) -> SymbolTable {
@@ -70,7 +70,7 @@ impl HIR2MIRPass {
             .map(|arg| arg.unwrap_or(EbbTy::Unit))
             .collect();
 
-        EbbTy::Tuple(vec![EbbTy::Int, EbbTy::Union(union)])
+        EbbTy::Union(union)
     }
 
     fn trans_ty(&self, ty: &hir::HTy) -> EbbTy {
@@ -78,7 +78,10 @@ impl HIR2MIRPass {
         match ty {
             Char => EbbTy::Char,
             Int => EbbTy::Int,
+            IntInf => EbbTy::IntInf,
+            Word(size) => EbbTy::Word(*size),
             Real => EbbTy::Float,
+            String => EbbTy::String,
             Tuple(tys) => match tys.len() {
                 0 => EbbTy::Unit,
                 // TODO: treat 1-tuple as inner type
@@ -253,6 +256,23 @@ impl HIR2MIRPass {
                     Ge => eb.ge(name, self.trans_ty(&ty), pop!(), pop!()),
                     Lt => eb.lt(name, self.trans_ty(&ty), pop!(), pop!()),
                     Le => eb.le(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Andb => eb.andb(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Orb => eb.orb(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Xorb => eb.xorb(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Lshift => eb.lshift(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Rshift => eb.rshift(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Arshift => eb.arshift(name, self.trans_ty(&ty), pop!(), pop!()),
+                    Neg => eb.neg(name, self.trans_ty(&ty), pop!()),
+                    Abs => eb.abs(name, self.trans_ty(&ty), pop!()),
+                    Real => eb.real(name, self.trans_ty(&ty), pop!()),
+                    Floor => eb.floor(name, self.trans_ty(&ty), pop!()),
+                    Ceil => eb.ceil(name, self.trans_ty(&ty), pop!()),
+                    Round => eb.round(name, self.trans_ty(&ty), pop!()),
+                    Trunc => eb.trunc(name, self.trans_ty(&ty), pop!()),
+                    Sqrt => eb.sqrt(name, self.trans_ty(&ty), pop!()),
+                    IntInfFromInt => eb.intinf_from_int(name, self.trans_ty(&ty), pop!()),
+                    IntInfToInt => eb.intinf_to_int(name, self.trans_ty(&ty), pop!()),
+                    Raise(exn) => eb.raise(name, self.trans_ty(&ty), exn),
                 };
                 eb
             }
@@ -306,11 +326,11 @@ impl HIR2MIRPass {
                     .collect::<Vec<_>>();
 
                 let descriminant = self.gensym("descriminant");
-                let arg = self.gensym("arg");
                 enum MatchTy {
                     Tuple(Vec<EbbTy>),
                     Datatype(Vec<EbbTy>),
                     Int,
+                    Word(WordSize),
                     Char,
                 }
 
@@ -331,20 +351,21 @@ impl HIR2MIRPass {
                             .collect(),
                     ),
                     hir::HTy::Int => MatchTy::Int,
+                    hir::HTy::Word(size) => MatchTy::Word(size),
                     hir::HTy::Char => MatchTy::Char,
                     ty => unreachable!("{:?}", ty),
                 };
                 match &exprty {
-                    MatchTy::Tuple(_) => {
+                    // the lowering finds the descriminants of datatypes
+                    MatchTy::Tuple(_) | MatchTy::Datatype(_) => {
                         // noop
                     }
-                    MatchTy::Datatype(tys) => {
-                        eb.proj(descriminant.clone(), EbbTy::Int, 0, var.clone());
-                        eb.proj(arg.clone(), EbbTy::Union(tys.clone()), 1, var.clone());
-                    }
                     MatchTy::Int => {
                         eb.alias(descriminant.clone(), EbbTy::Int, var.clone());
                     }
+                    MatchTy::Word(size) => {
+                        eb.alias(descriminant.clone(), EbbTy::Word(*size), var.clone());
+                    }
                     MatchTy::Char => {
                         eb.alias(descriminant.clone(), EbbTy::Char, var.clone());
                     }
@@ -355,7 +376,11 @@ impl HIR2MIRPass {
                     let (label, is_forward) = default_label.clone().unwrap();
                     ebb = eb.jump(label, is_forward, vec![var.clone()]);
                 } else {
-                    ebb = eb.branch(descriminant, labels, default_label.clone());
+                    let cond = match &exprty {
+                        MatchTy::Datatype(_) => var.clone(),
+                        _ => descriminant,
+                    };
+                    ebb = eb.branch(cond, labels, default_label.clone());
                 }
 
                 fb.add_ebb(ebb);
@@ -369,7 +394,7 @@ impl HIR2MIRPass {
                                 None => self.gensym("vararg"),
                             };
                             let argty = tys[key as usize].clone();
-                            eb.select(vararg, argty, key, arg.clone());
+                            eb.select(vararg, argty, key as u32, var.clone());
                         }
                         _ => {
                             //noop
@@ -398,6 +423,7 @@ impl HIR2MIRPass {
                             }
                             hir::Pattern::Constructor { .. }
                             | hir::Pattern::Constant { .. }
+                            | hir::Pattern::Word { .. }
                             | hir::Pattern::Char { .. } => unreachable!(),
                         };
 
@@ -448,41 +474,21 @@ impl HIR2MIRPass {
                 descriminant,
             } => {
                 assert_eq!(ty, ty_);
-                let ty = match self.trans_ty_canonical(&ty) {
-                    EbbTy::Tuple(tys) => tys,
-                    ty => unreachable!("{:?}", ty),
-                };
-                assert_eq!(ty.len(), 2);
-                let arg_ty = match &ty[1] {
+                let arg_ty = match self.trans_ty_canonical(&ty) {
                     EbbTy::Union(tys) => tys,
                     ty => unreachable!("{:?}", ty),
                 };
-                let desc_sym = self.gensym("descriminant");
-                eb.lit(
-                    desc_sym.clone(),
-                    EbbTy::Int,
-                    Literal::Int(descriminant as i64),
-                );
-                let arg_sym = self.gensym("arg");
-                // FIXME: create union
+                // the representation of the value is chosen when lowering
                 match arg {
                     None => {
                         let void_sym = self.gensym("arg");
-                        // eb.tuple(void_sym.clone(), vec![], vec![]);
                         eb.lit(void_sym.clone(), EbbTy::Int, Literal::Int(0));
-                        eb.union(arg_sym.clone(), arg_ty.clone(), descriminant, void_sym);
+                        eb.union(name, arg_ty, descriminant, void_sym);
                     }
                     Some(arg) => {
-                        eb.union(
-                            arg_sym.clone(),
-                            arg_ty.clone(),
-                            descriminant,
-                            force_symbol(*arg),
-                        );
+                        eb.union(name, arg_ty, descriminant, force_symbol(*arg));
                     }
                 };
-                let tuple = vec![desc_sym, arg_sym];
-                eb.tuple(name, ty, tuple);
                 eb
             }
             Lit { ty, value } => {
@@ -0,0 +1,108 @@
+use crate::config::Config;
+use crate::mir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// calls the functions directly instead of through the closures of them that capture nothing,
+/// and removes such closures no longer used.
+/// the calls of the closures with environments made in the same function are made direct
+/// when lowering to LIR
+#[derive(Default)]
+pub struct KnownCall {
+    /// the closure wrappers and the functions they call
+    wrapped: HashMap<Symbol, Symbol>,
+    /// the variables holding the closures of the wrappers, and the wrapped functions
+    closures: HashMap<Symbol, Symbol>,
+}
+
+impl KnownCall {
+    pub fn new() -> Self {
+        KnownCall {
+            wrapped: HashMap::new(),
+            closures: HashMap::new(),
+        }
+    }
+
+    fn conv_mir(&mut self, mir: MIR) -> MIR {
+        let funs = mir.0.iter().map(|f| &f.name).collect::<HashSet<_>>();
+        self.wrapped = mir
+            .0
+            .iter()
+            .filter_map(|f| wrapped_fun(f).map(|fun| (f.name.clone(), fun.clone())))
+            .filter(|(_, fun)| funs.contains(fun))
+            .collect();
+        MIR(mir.0.into_iter().map(|f| self.conv_fun(f)).collect())
+    }
+
+    fn conv_fun(&mut self, mut fun: Function) -> Function {
+        use crate::mir::Op::*;
+        self.closures.clear();
+        for op in fun.body.iter().flat_map(|ebb| ebb.body.iter()) {
+            if let Closure {
+                var,
+                fun: wrapper,
+                env,
+                ..
+            } = op
+            {
+                if let (true, Some(f)) = (env.is_empty(), self.wrapped.get(wrapper)) {
+                    self.closures.insert(var.clone(), f.clone());
+                }
+            }
+        }
+        if self.closures.is_empty() {
+            return fun;
+        }
+        for op in fun.body.iter_mut().flat_map(|ebb| ebb.body.iter_mut()) {
+            if let Call { fun, .. } = op {
+                if let Some(f) = self.closures.get(fun) {
+                    *fun = f.clone();
+                }
+            }
+        }
+        let used = fun
+            .body
+            .iter()
+            .flat_map(|ebb| ebb.body.iter())
+            .flat_map(|op| op.args())
+            .cloned()
+            .collect::<HashSet<_>>();
+        for ebb in fun.body.iter_mut() {
+            let closures = &self.closures;
+            ebb.body.retain(|op| match op {
+                Closure { var, .. } => used.contains(var) || !closures.contains_key(var),
+                _ => true,
+            });
+        }
+        fun
+    }
+}
+
+/// the function a closure wrapper, `fun w(_: (), param) = f(param)`, calls
+pub(super) fn wrapped_fun(fun: &Function) -> Option<&Symbol> {
+    use crate::mir::Op::*;
+    match &fun.body[..] {
+        [ebb] if ebb.params.len() == 2 => match &ebb.body[..] {
+            [Call {
+                var, fun: f, args, ..
+            }, Ret {
+                value: Some(ret), ..
+            }] if args[..] == [ebb.params[1].1.clone()] && ret == var => Some(f),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for KnownCall {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.conv_mir(mir)))
+    }
+}
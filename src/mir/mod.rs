@@ -1,13 +1,29 @@
 mod block_arrange;
+mod branch_fold;
 mod builder;
 pub mod cfg;
+mod const_fold;
+mod copy_prop;
+mod dead_code;
+pub mod escape;
 mod hir2mir;
+mod known_call;
 pub mod pp;
 mod unalias;
+mod uncurry;
+mod unreachable;
+pub mod util;
 
 pub use self::block_arrange::BlockArrange;
+pub use self::branch_fold::BranchFold;
+pub use self::const_fold::ConstFold;
+pub use self::copy_prop::CopyProp;
+pub use self::dead_code::DeadCode;
 pub use self::hir2mir::HIR2MIR;
+pub use self::known_call::KnownCall;
 pub use self::unalias::UnAlias;
+pub use self::uncurry::Uncurry;
+pub use self::unreachable::RemoveUnreachable;
 use crate::prim::*;
 use std::collections::HashMap;
 
@@ -114,6 +130,99 @@ pub enum Op {
         l: Symbol,
         r: Symbol,
     },
+    Andb {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Orb {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Xorb {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Lshift {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Rshift {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Arshift {
+        var: Symbol,
+        ty: EbbTy,
+        l: Symbol,
+        r: Symbol,
+    },
+    Neg {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Abs {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    /// int to real
+    Real {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Floor {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Ceil {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Round {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Trunc {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    Sqrt {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    IntInfFromInt {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    IntInfToInt {
+        var: Symbol,
+        ty: EbbTy,
+        arg: Symbol,
+    },
+    /// never returns. `var` is only to keep the block well typed
+    Raise {
+        var: Symbol,
+        ty: EbbTy,
+        exn: BuiltinExn,
+    },
     Closure {
         var: Symbol,
         param_ty: EbbTy,
@@ -163,7 +272,7 @@ pub enum Op {
 
     Branch {
         cond: Symbol,
-        clauses: Vec<(u32, Symbol, bool)>,
+        clauses: Vec<(u64, Symbol, bool)>,
         default: Option<(Symbol, bool)>,
     },
     Jump {
@@ -187,7 +296,12 @@ pub enum EbbTy {
     Unit,
     Char,
     Int,
+    /// pointer to an arbitrary-precision integer in the runtime
+    IntInf,
+    Word(WordSize),
     Float,
+    /// pointer to a string in the runtime: the byte length followed by the bytes
+    String,
     Bool,
     Tuple(Vec<EbbTy>),
     Union(Vec<EbbTy>),
@@ -209,6 +323,34 @@ impl MIR {
     }
 }
 
+/// how the values of a datatype, the union of the arguments of its constructors, are held
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnionRepr {
+    /// the constructors take no arguments: the descriminant itself
+    Enum,
+    /// the only constructor takes an argument: the argument itself
+    Unboxed,
+    /// the pointer to the descriminant and the argument
+    Boxed,
+    /// the descriminant itself for the constructors taking no arguments, and the pointer
+    /// for the others. the pointers to the heap are never as small as descriminants
+    Mixed,
+}
+
+impl UnionRepr {
+    /// the representation of the union of the arguments `tys`, `Unit` for no argument
+    pub fn of(tys: &[EbbTy]) -> Self {
+        let nullary = tys.iter().filter(|ty| **ty == EbbTy::Unit).count();
+        match tys {
+            _ if nullary == tys.len() => UnionRepr::Enum,
+            // the recursive ones would be infinite
+            [ty] if !matches!(ty, EbbTy::Variable(_)) => UnionRepr::Unboxed,
+            _ if nullary == 0 => UnionRepr::Boxed,
+            _ => UnionRepr::Mixed,
+        }
+    }
+}
+
 impl SymbolTable {
     pub fn canonical_value(&self, name: &Symbol) -> Option<&EbbTy> {
         match self.table.get(name) {
@@ -52,7 +52,10 @@ impl PP for EbbTy {
             Bool => write!(w, "bool")?,
             Char => write!(w, "char")?,
             Int => write!(w, "int")?,
+            IntInf => write!(w, "intinf")?,
+            Word(size) => size.pp(w, indent)?,
             Float => write!(w, "float")?,
+            String => write!(w, "string")?,
             Tuple(tys) => {
                 write!(w, "(")?;
                 inter_iter! {
@@ -151,6 +154,24 @@ fn pp_binop<W: io::Write>(
     Ok(())
 }
 
+fn pp_unop<W: io::Write>(
+    w: &mut W,
+    indent: usize,
+    space: &str,
+    name: &str,
+    var: &Symbol,
+    ty: &EbbTy,
+    arg: &Symbol,
+) -> io::Result<()> {
+    write!(w, "{}", space)?;
+    var.pp(w, indent)?;
+    write!(w, ": ")?;
+    ty.pp(w, indent)?;
+    write!(w, " := {} ", name)?;
+    arg.pp(w, indent)?;
+    Ok(())
+}
+
 impl PP for Op {
     fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
         use crate::mir::Op::*;
@@ -209,6 +230,61 @@ impl PP for Op {
             Le { var, ty, l, r } => {
                 pp_binop(w, indent, &space, "<=", var, ty, l, r)?;
             }
+            Andb { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "andb", var, ty, l, r)?;
+            }
+            Orb { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "orb", var, ty, l, r)?;
+            }
+            Xorb { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "xorb", var, ty, l, r)?;
+            }
+            Lshift { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "<<", var, ty, l, r)?;
+            }
+            Rshift { var, ty, l, r } => {
+                pp_binop(w, indent, &space, ">>", var, ty, l, r)?;
+            }
+            Arshift { var, ty, l, r } => {
+                pp_binop(w, indent, &space, "~>>", var, ty, l, r)?;
+            }
+            Neg { var, ty, arg } => {
+                pp_unop(w, indent, &space, "~", var, ty, arg)?;
+            }
+            Abs { var, ty, arg } => {
+                pp_unop(w, indent, &space, "abs", var, ty, arg)?;
+            }
+            Real { var, ty, arg } => {
+                pp_unop(w, indent, &space, "real", var, ty, arg)?;
+            }
+            Floor { var, ty, arg } => {
+                pp_unop(w, indent, &space, "floor", var, ty, arg)?;
+            }
+            Ceil { var, ty, arg } => {
+                pp_unop(w, indent, &space, "ceil", var, ty, arg)?;
+            }
+            Round { var, ty, arg } => {
+                pp_unop(w, indent, &space, "round", var, ty, arg)?;
+            }
+            Trunc { var, ty, arg } => {
+                pp_unop(w, indent, &space, "trunc", var, ty, arg)?;
+            }
+            Sqrt { var, ty, arg } => {
+                pp_unop(w, indent, &space, "sqrt", var, ty, arg)?;
+            }
+            IntInfFromInt { var, ty, arg } => {
+                pp_unop(w, indent, &space, "IntInf.fromInt", var, ty, arg)?;
+            }
+            IntInfToInt { var, ty, arg } => {
+                pp_unop(w, indent, &space, "IntInf.toInt", var, ty, arg)?;
+            }
+            Raise { var, ty, exn } => {
+                write!(w, "{}", space)?;
+                var.pp(w, indent)?;
+                write!(w, ": ")?;
+                ty.pp(w, indent)?;
+                write!(w, " := raise {:?}", exn)?;
+            }
             Closure {
                 var,
                 param_ty,
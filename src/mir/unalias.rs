@@ -100,10 +100,52 @@ impl UnAlias {
                     ref mut l,
                     ref mut r,
                     ..
+                }
+                | &mut Andb {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Orb {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Xorb {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Lshift {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Rshift {
+                    ref mut l,
+                    ref mut r,
+                    ..
+                }
+                | &mut Arshift {
+                    ref mut l,
+                    ref mut r,
+                    ..
                 } => {
                     self.resolv_alias(l);
                     self.resolv_alias(r);
                 }
+                &mut Neg { ref mut arg, .. }
+                | &mut Abs { ref mut arg, .. }
+                | &mut Real { ref mut arg, .. }
+                | &mut Floor { ref mut arg, .. }
+                | &mut Ceil { ref mut arg, .. }
+                | &mut Round { ref mut arg, .. }
+                | &mut Trunc { ref mut arg, .. }
+                | &mut Sqrt { ref mut arg, .. }
+                | &mut IntInfFromInt { ref mut arg, .. }
+                | &mut IntInfToInt { ref mut arg, .. } => {
+                    self.resolv_alias(arg);
+                }
                 &mut Tuple { ref mut tuple, .. } => {
                     for v in tuple.iter_mut() {
                         self.resolv_alias(v);
@@ -157,7 +199,7 @@ impl UnAlias {
                         self.resolv_alias(v)
                     }
                 }
-                &mut Lit { .. } => (),
+                &mut Lit { .. } | &mut Raise { .. } => (),
                 &mut Branch { ref mut cond, .. } => self.resolv_alias(cond),
             }
             body.push(op)
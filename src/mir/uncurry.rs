@@ -0,0 +1,382 @@
+use crate::config::Config;
+use crate::id::Id;
+use crate::mir::known_call::wrapped_fun;
+use crate::mir::*;
+use crate::pass::Pass;
+use crate::prim::*;
+use std::collections::{HashMap, HashSet};
+
+/// the parameters of a function, with the types of the elements for the tuples the worker
+/// takes elementwise
+type Flattening = Vec<Option<Vec<EbbTy>>>;
+
+/// makes the functions taking tuples, including the environments of closures, wrappers of the
+/// workers taking the elements as the parameters, and calls the workers where the elements are
+/// known.
+/// curried functions are the chains of the functions only making the closures of the next ones,
+/// and the saturated calls of them call the worker of the last function with all the arguments
+pub struct Uncurry {
+    id: Id,
+    /// the functions only making the closure of the next function of a curried function,
+    /// and whether they take environments
+    stages: HashMap<Symbol, (Symbol, bool)>,
+    /// the workers of the functions and how they take the parameters
+    workers: HashMap<Symbol, (Symbol, Flattening)>,
+}
+
+impl Uncurry {
+    pub fn new(id: Id) -> Self {
+        Uncurry {
+            id,
+            stages: HashMap::new(),
+            workers: HashMap::new(),
+        }
+    }
+
+    fn fresh(&mut self, name: &Symbol) -> Symbol {
+        Symbol(name.0.clone(), self.id.next(), None)
+    }
+
+    fn conv_mir(&mut self, mir: MIR) -> MIR {
+        self.stages = mir
+            .0
+            .iter()
+            .filter_map(|f| {
+                let takes_env = f.body[0].params.len() == 2;
+                next_stage(f).map(|next| (f.name.clone(), (next.clone(), takes_env)))
+            })
+            .collect();
+        self.workers.clear();
+        let mut funs = Vec::new();
+        for fun in mir.0 {
+            let flattening = fun.body[0]
+                .params
+                .iter()
+                .map(|(ty, _)| match ty {
+                    EbbTy::Tuple(tys) if !tys.is_empty() => Some(tys.clone()),
+                    _ => None,
+                })
+                .collect::<Flattening>();
+            // the closure wrappers call the workers of the functions they wrap
+            if self.stages.contains_key(&fun.name)
+                || wrapped_fun(&fun).is_some()
+                || flattening.iter().all(Option::is_none)
+            {
+                funs.push(fun);
+                continue;
+            }
+            let worker = self.worker(fun.clone(), &flattening);
+            self.workers
+                .insert(fun.name.clone(), (worker.name.clone(), flattening));
+            funs.push(self.wrapper(fun, &worker.name));
+            funs.push(worker);
+        }
+        // the calls are converted after all the workers are made. the wrappers call them already
+        MIR(funs
+            .into_iter()
+            .map(|f| {
+                if self.workers.contains_key(&f.name) {
+                    f
+                } else {
+                    self.conv_fun(f)
+                }
+            })
+            .collect())
+    }
+
+    /// `fun f(p: (a, b)) = body` to `fun f'(p0: a, p1: b) = { p := (p0, p1); body }`,
+    /// with the projections of `p` replaced by the elements
+    fn worker(&mut self, mut fun: Function, flattening: &Flattening) -> Function {
+        let name = Symbol(format!("{}_worker", fun.name.0), self.id.next(), None);
+        let entry = &mut fun.body[0];
+        let mut params = Vec::new();
+        let mut tuples = Vec::new();
+        for ((ty, param), flat) in entry.params.drain(..).zip(flattening) {
+            match flat {
+                Some(tys) => {
+                    let elems = tys
+                        .iter()
+                        .map(|ty| (ty.clone(), Symbol(param.0.clone(), self.id.next(), None)))
+                        .collect::<Vec<_>>();
+                    tuples.push(Op::Tuple {
+                        var: param,
+                        tys: tys.clone(),
+                        tuple: elems.iter().map(|(_, elem)| elem.clone()).collect(),
+                    });
+                    params.extend(elems)
+                }
+                None => params.push((ty, param)),
+            }
+        }
+        let mut elems = HashMap::new();
+        for op in tuples.iter() {
+            if let Op::Tuple { var, tuple, .. } = op {
+                elems.insert(var.clone(), tuple.clone());
+            }
+        }
+        entry.params = params;
+        tuples.append(&mut entry.body);
+        entry.body = tuples;
+
+        let mut projs = HashMap::new();
+        for op in fun.body.iter().flat_map(|ebb| ebb.body.iter()) {
+            if let Op::Proj {
+                var, index, tuple, ..
+            } = op
+            {
+                if let Some(elems) = elems.get(tuple) {
+                    projs.insert(var.clone(), elems[*index as usize].clone());
+                }
+            }
+        }
+        for ebb in fun.body.iter_mut() {
+            ebb.body
+                .retain(|op| op.var().is_none_or(|var| !projs.contains_key(var)));
+            for arg in ebb.body.iter_mut().flat_map(|op| op.args_mut()) {
+                if let Some(elem) = projs.get(arg) {
+                    *arg = elem.clone();
+                }
+            }
+        }
+        Function { name, ..fun }
+    }
+
+    /// `fun f(p: (a, b)) = f'(#0 p, #1 p)`
+    fn wrapper(&mut self, fun: Function, worker: &Symbol) -> Function {
+        let Function {
+            name,
+            mut body,
+            body_ty,
+        } = fun;
+        let params = body.swap_remove(0).params;
+        let (_, flattening) = &self.workers[&name];
+        let flattening = flattening.clone();
+        let mut ops = Vec::new();
+        let mut args = Vec::new();
+        for ((_, param), flat) in params.iter().zip(flattening) {
+            args.append(&mut self.flatten_arg(&mut ops, &HashMap::new(), param, &flat));
+        }
+        let ret = Symbol::new("ret");
+        ops.push(Op::Call {
+            var: ret.clone(),
+            ty: body_ty.clone(),
+            fun: worker.clone(),
+            args,
+        });
+        ops.push(Op::Ret {
+            value: Some(ret),
+            ty: body_ty.clone(),
+        });
+        Function {
+            name,
+            body: vec![EBB {
+                name: Symbol::new("entry"),
+                params,
+                body: ops,
+            }],
+            body_ty,
+        }
+    }
+
+    /// the elements of the tuple argument, projected here unless made in the function
+    fn flatten_arg(
+        &mut self,
+        ops: &mut Vec<Op>,
+        tuples: &HashMap<Symbol, Vec<Symbol>>,
+        arg: &Symbol,
+        flat: &Option<Vec<EbbTy>>,
+    ) -> Vec<Symbol> {
+        let tys = match flat {
+            None => return vec![arg.clone()],
+            Some(tys) => tys,
+        };
+        if let Some(elems) = tuples.get(arg) {
+            return elems.clone();
+        }
+        tys.iter()
+            .enumerate()
+            .map(|(index, ty)| {
+                let var = self.fresh(arg);
+                ops.push(Op::Proj {
+                    var: var.clone(),
+                    ty: ty.clone(),
+                    index: index as u32,
+                    tuple: arg.clone(),
+                });
+                var
+            })
+            .collect()
+    }
+
+    fn conv_fun(&mut self, mut fun: Function) -> Function {
+        use crate::mir::Op::*;
+        let mut tuples = HashMap::new();
+        // the variables holding the closures of the known functions, and their environments
+        let mut closures: HashMap<Symbol, (Symbol, Vec<Symbol>)> = HashMap::new();
+        for ebb in fun.body.iter_mut() {
+            let body = std::mem::take(&mut ebb.body);
+            for op in body {
+                match op {
+                    Tuple {
+                        ref var, ref tuple, ..
+                    } => {
+                        tuples.insert(var.clone(), tuple.clone());
+                    }
+                    Closure {
+                        ref var,
+                        fun: ref f,
+                        ref env,
+                        ..
+                    } if !env.is_empty() => {
+                        let env = env.iter().map(|(_, var)| var.clone()).collect();
+                        closures.insert(var.clone(), (f.clone(), env));
+                    }
+                    Call {
+                        var,
+                        ty,
+                        fun: f,
+                        args,
+                    } => {
+                        let op =
+                            self.conv_call(&mut ebb.body, &tuples, &mut closures, var, ty, f, args);
+                        ebb.body.push(op);
+                        continue;
+                    }
+                    _ => (),
+                }
+                ebb.body.push(op)
+            }
+        }
+
+        // the closures of the stages no longer used
+        loop {
+            let used = fun
+                .body
+                .iter()
+                .flat_map(|ebb| ebb.body.iter())
+                .flat_map(|op| op.args())
+                .cloned()
+                .collect::<HashSet<_>>();
+            let mut removed = false;
+            for ebb in fun.body.iter_mut() {
+                let len = ebb.body.len();
+                ebb.body.retain(|op| match op {
+                    Closure { var, .. } | Call { var, .. } => {
+                        used.contains(var) || !closures.contains_key(var)
+                    }
+                    _ => true,
+                });
+                removed |= ebb.body.len() != len;
+            }
+            if !removed {
+                return fun;
+            }
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn conv_call(
+        &mut self,
+        ops: &mut Vec<Op>,
+        tuples: &HashMap<Symbol, Vec<Symbol>>,
+        closures: &mut HashMap<Symbol, (Symbol, Vec<Symbol>)>,
+        var: Symbol,
+        ty: EbbTy,
+        fun: Symbol,
+        args: Vec<Symbol>,
+    ) -> Op {
+        // the function called and the environment of the closure, if known
+        let (known, env) = match closures.get(&fun) {
+            Some((f, env)) => (f.clone(), Some(env.clone())),
+            None => (fun.clone(), None),
+        };
+        if let Some((next, takes_env)) = self.stages.get(&known) {
+            if *takes_env != env.is_some() {
+                return Op::Call { var, ty, fun, args };
+            }
+            let mut env = env.unwrap_or_default();
+            env.extend(args.iter().cloned());
+            closures.insert(var.clone(), (next.clone(), env));
+            return Op::Call { var, ty, fun, args };
+        }
+        let (worker, flattening) = match self.workers.get(&known) {
+            Some(worker) => worker.clone(),
+            None => return Op::Call { var, ty, fun, args },
+        };
+        let mut params = flattening.iter();
+        let mut worker_args = Vec::new();
+        if let Some(env) = env {
+            // the closure takes the environment elementwise first
+            match params.next() {
+                Some(Some(tys)) if tys.len() == env.len() => worker_args.extend(env),
+                _ => return Op::Call { var, ty, fun, args },
+            }
+        }
+        assert_eq!(params.len(), args.len());
+        for (arg, flat) in args.iter().zip(params) {
+            worker_args.append(&mut self.flatten_arg(ops, tuples, arg, flat));
+        }
+        Op::Call {
+            var,
+            ty,
+            fun: worker,
+            args: worker_args,
+        }
+    }
+}
+
+/// the next function of a curried function the closure of which `fun` only makes:
+/// `fun f(env: (a, b), p) = { x := #0 env; y := #1 env; <g>.__close(x, y, p) }`, or
+/// `fun f(p) = <g>.__close(p)` at the top
+fn next_stage(fun: &Function) -> Option<&Symbol> {
+    use crate::mir::Op::*;
+    let ebb = match &fun.body[..] {
+        [ebb] => ebb,
+        _ => return None,
+    };
+    let (env, param) = match &ebb.params[..] {
+        [(_, param)] => (None, param),
+        [(EbbTy::Tuple(_), env), (_, param)] => (Some(env), param),
+        _ => return None,
+    };
+    let (last, projs) = match ebb.body.len() {
+        len if len >= 2 => {
+            let (projs, last) = ebb.body.split_at(len - 2);
+            (last, projs)
+        }
+        _ => return None,
+    };
+    let mut captured = Vec::new();
+    for (i, op) in projs.iter().enumerate() {
+        match op {
+            Proj {
+                var, index, tuple, ..
+            } if Some(tuple) == env && *index as usize == i => captured.push(var),
+            _ => return None,
+        }
+    }
+    captured.push(param);
+    match last {
+        [Closure {
+            var,
+            fun: next,
+            env: closure_env,
+            ..
+        }, Ret {
+            value: Some(ret), ..
+        }] if ret == var && closure_env.iter().map(|(_, var)| var).eq(captured) => Some(next),
+        _ => None,
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for Uncurry {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.conv_mir(mir)))
+    }
+}
@@ -0,0 +1,46 @@
+use crate::config::Config;
+use crate::mir::*;
+use crate::pass::Pass;
+use std::collections::HashSet;
+
+/// removes the EBBs no path from the entry reaches
+#[derive(Default)]
+pub struct RemoveUnreachable;
+
+impl RemoveUnreachable {
+    pub fn new() -> Self {
+        RemoveUnreachable
+    }
+
+    fn conv_mir(&mut self, mir: MIR) -> MIR {
+        MIR(mir.0.into_iter().map(|f| self.conv_fun(f)).collect())
+    }
+
+    fn conv_fun(&mut self, mut fun: Function) -> Function {
+        let cfg = fun.cfg();
+        let reachable = cfg
+            .node_indices()
+            .map(|node| cfg[node])
+            .collect::<HashSet<usize>>();
+        fun.body = fun
+            .body
+            .into_iter()
+            .enumerate()
+            .filter(|(i, _)| reachable.contains(i))
+            .map(|(_, ebb)| ebb)
+            .collect();
+        fun
+    }
+}
+
+impl<E> Pass<(SymbolTable, MIR), E> for RemoveUnreachable {
+    type Target = (SymbolTable, MIR);
+
+    fn trans(
+        &mut self,
+        (symbol_table, mir): (SymbolTable, MIR),
+        _: &Config,
+    ) -> ::std::result::Result<Self::Target, E> {
+        Ok((symbol_table, self.conv_mir(mir)))
+    }
+}
@@ -0,0 +1,158 @@
+use crate::mir::*;
+use std::iter;
+
+// binding modes make the same arms work on `&Op` and `&mut Op`
+macro_rules! args {
+    ($op: expr) => {
+        match $op {
+            Alias { sym, .. } => vec![sym],
+            Add { l, r, .. }
+            | Sub { l, r, .. }
+            | Mul { l, r, .. }
+            | DivInt { l, r, .. }
+            | DivFloat { l, r, .. }
+            | Mod { l, r, .. }
+            | Eq { l, r, .. }
+            | Neq { l, r, .. }
+            | Gt { l, r, .. }
+            | Ge { l, r, .. }
+            | Lt { l, r, .. }
+            | Le { l, r, .. }
+            | Andb { l, r, .. }
+            | Orb { l, r, .. }
+            | Xorb { l, r, .. }
+            | Lshift { l, r, .. }
+            | Rshift { l, r, .. }
+            | Arshift { l, r, .. } => vec![l, r],
+            Neg { arg, .. }
+            | Abs { arg, .. }
+            | Real { arg, .. }
+            | Floor { arg, .. }
+            | Ceil { arg, .. }
+            | Round { arg, .. }
+            | Trunc { arg, .. }
+            | Sqrt { arg, .. }
+            | IntInfFromInt { arg, .. }
+            | IntInfToInt { arg, .. } => vec![arg],
+            Closure { fun, env, .. } => iter::once(fun)
+                .chain(env.into_iter().map(|(_, var)| var))
+                .collect(),
+            ExternCall { args, .. } | Jump { args, .. } => args.into_iter().collect(),
+            Call { fun, args, .. } => iter::once(fun).chain(args).collect(),
+            Tuple { tuple, .. } => tuple.into_iter().collect(),
+            Proj { tuple, .. } => vec![tuple],
+            Union { variant, .. } => vec![variant],
+            Select { union, .. } => vec![union],
+            Branch { cond, .. } => vec![cond],
+            Ret { value, .. } => value.into_iter().collect(),
+            Lit { .. } | Raise { .. } => vec![],
+        }
+    };
+}
+
+impl Op {
+    /// the variable the op defines
+    pub fn var(&self) -> Option<&Symbol> {
+        use crate::mir::Op::*;
+        match self {
+            Lit { var, .. }
+            | Alias { var, .. }
+            | Add { var, .. }
+            | Sub { var, .. }
+            | Mul { var, .. }
+            | DivInt { var, .. }
+            | DivFloat { var, .. }
+            | Mod { var, .. }
+            | Eq { var, .. }
+            | Neq { var, .. }
+            | Gt { var, .. }
+            | Ge { var, .. }
+            | Lt { var, .. }
+            | Le { var, .. }
+            | Andb { var, .. }
+            | Orb { var, .. }
+            | Xorb { var, .. }
+            | Lshift { var, .. }
+            | Rshift { var, .. }
+            | Arshift { var, .. }
+            | Neg { var, .. }
+            | Abs { var, .. }
+            | Real { var, .. }
+            | Floor { var, .. }
+            | Ceil { var, .. }
+            | Round { var, .. }
+            | Trunc { var, .. }
+            | Sqrt { var, .. }
+            | IntInfFromInt { var, .. }
+            | IntInfToInt { var, .. }
+            | Raise { var, .. }
+            | Closure { var, .. }
+            | ExternCall { var, .. }
+            | Call { var, .. }
+            | Tuple { var, .. }
+            | Proj { var, .. }
+            | Union { var, .. }
+            | Select { var, .. } => Some(var),
+            Branch { .. } | Jump { .. } | Ret { .. } => None,
+        }
+    }
+
+    /// the variables the op uses
+    pub fn args(&self) -> Vec<&Symbol> {
+        use crate::mir::Op::*;
+        args!(self)
+    }
+
+    pub fn args_mut(&mut self) -> Vec<&mut Symbol> {
+        use crate::mir::Op::*;
+        args!(self)
+    }
+
+    /// whether removing the op when its variable is unused keeps the behavior.
+    /// arithmetics on int can raise `Overflow` unless `unchecked_arith`
+    pub fn is_pure(&self, unchecked_arith: bool) -> bool {
+        use crate::mir::Op::*;
+        match self {
+            Add { ty, .. } | Sub { ty, .. } | Mul { ty, .. } | Neg { ty, .. } | Abs { ty, .. } => {
+                unchecked_arith || ty != &EbbTy::Int
+            }
+            Lit { .. }
+            | Alias { .. }
+            | DivFloat { .. }
+            | Eq { .. }
+            | Neq { .. }
+            | Gt { .. }
+            | Ge { .. }
+            | Lt { .. }
+            | Le { .. }
+            | Andb { .. }
+            | Orb { .. }
+            | Xorb { .. }
+            | Lshift { .. }
+            | Rshift { .. }
+            | Arshift { .. }
+            | Real { .. }
+            | Sqrt { .. }
+            | IntInfFromInt { .. }
+            | Closure { .. }
+            | Tuple { .. }
+            | Proj { .. }
+            | Union { .. }
+            | Select { .. } => true,
+            // `Div`, `Overflow`, side effects or control flows
+            DivInt { .. }
+            | Mod { .. }
+            | Floor { .. }
+            | Ceil { .. }
+            | Round { .. }
+            | Trunc { .. }
+            | IntInfToInt { .. }
+            | Raise { .. }
+            | ExternCall { .. }
+            | Call { .. }
+            | Branch { .. }
+            | Jump { .. }
+            | Ret { .. } => false,
+        }
+    }
+}
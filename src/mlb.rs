@@ -0,0 +1,524 @@
+//! reads ML Basis files, the project files of MLton and MLKit, into the sources of a program.
+//!
+//! the files listed are put in order, `local` of the basis becoming `local` of the core language.
+//! `basis` binds the declarations to a name without elaborating them, and `open` elaborates them
+//! there. as WebML has no modules, `structure`, `signature` and `functor` bindings are rejected,
+//! and the paths in `$(SML_LIB)` are skipped, the prelude standing in for the Basis Library.
+use crate::parser::Position;
+use crate::source::Sources;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MlbError {
+    pub path: PathBuf,
+    /// where in `path` it is found. `None` for the errors on the whole file
+    pub position: Option<Position>,
+    pub message: String,
+}
+
+impl fmt::Display for MlbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.position {
+            Some(position) => write!(f, "{}:{}: {}", self.path.display(), position, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for MlbError {}
+
+type Result<T> = ::std::result::Result<T, MlbError>;
+
+/// adds the files the basis file at `path` lists to `sources`, in order
+pub fn load(path: impl AsRef<Path>, sources: &mut Sources) -> Result<()> {
+    let mut loader = Loader {
+        sources,
+        reading: Vec::new(),
+    };
+    loader.file(path.as_ref(), None)?;
+    Ok(())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Path(String),
+    Equal,
+    Semicolon,
+    Eof,
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::Word(w) => write!(f, "`{}`", w),
+            Token::Path(p) => write!(f, "`{}`", p),
+            Token::Equal => write!(f, "`=`"),
+            Token::Semicolon => write!(f, "`;`"),
+            Token::Eof => write!(f, "end of file"),
+        }
+    }
+}
+
+const EXTENSIONS: &[&str] = &["sml", "sig", "fun", "ML", "mlb"];
+
+fn lex(input: &str) -> ::std::result::Result<Vec<(Token, usize)>, (usize, String)> {
+    let bytes = input.as_bytes();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        let start = i;
+        match bytes[i] {
+            b if b.is_ascii_whitespace() => i += 1,
+            b'(' if bytes.get(i + 1) == Some(&b'*') => {
+                let mut depth = 0;
+                loop {
+                    if i >= bytes.len() {
+                        return Err((start, "unclosed comment".into()));
+                    }
+                    if bytes[i..].starts_with(b"(*") {
+                        depth += 1;
+                        i += 2;
+                    } else if bytes[i..].starts_with(b"*)") {
+                        depth -= 1;
+                        i += 2;
+                        if depth == 0 {
+                            break;
+                        }
+                    } else {
+                        i += 1;
+                    }
+                }
+            }
+            b'=' => {
+                tokens.push((Token::Equal, start));
+                i += 1
+            }
+            b';' => {
+                tokens.push((Token::Semicolon, start));
+                i += 1
+            }
+            b'"' => {
+                let len = input[i + 1..]
+                    .find('"')
+                    .ok_or((start, "unclosed string".to_string()))?;
+                tokens.push((Token::Path(input[i + 1..i + 1 + len].to_string()), start));
+                i += len + 2;
+            }
+            _ => {
+                while i < bytes.len() {
+                    match bytes[i] {
+                        b'$' if bytes.get(i + 1) == Some(&b'(') => {
+                            let len = input[i..]
+                                .find(')')
+                                .ok_or((i, "unclosed path variable".to_string()))?;
+                            i += len + 1;
+                        }
+                        b if b.is_ascii_alphanumeric() || b"_'./-~".contains(&b) => i += 1,
+                        _ => break,
+                    }
+                }
+                if i == start {
+                    let c = input[i..].chars().next().unwrap();
+                    return Err((start, format!("unexpected `{}`", c)));
+                }
+                let word = &input[start..i];
+                let is_path = word.contains('/')
+                    || Path::new(word)
+                        .extension()
+                        .is_some_and(|e| EXTENSIONS.iter().any(|ext| e == *ext));
+                let token = if is_path {
+                    Token::Path(word.to_string())
+                } else {
+                    Token::Word(word.to_string())
+                };
+                tokens.push((token, start));
+            }
+        }
+    }
+    tokens.push((Token::Eof, input.len()));
+    Ok(tokens)
+}
+
+#[derive(Debug, Clone)]
+enum BasDec {
+    /// the path as written and the byte it is at
+    File(String, usize),
+    Local(Vec<BasDec>, Vec<BasDec>),
+    Basis(Vec<(String, BasExp)>),
+    Open(Vec<(String, usize)>),
+}
+
+#[derive(Debug, Clone)]
+enum BasExp {
+    Bas(Vec<BasDec>),
+    Id(String, usize),
+    Let(Vec<BasDec>, Box<BasExp>),
+}
+
+struct Parser {
+    tokens: Vec<(Token, usize)>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> &Token {
+        &self.tokens[self.pos].0
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens[self.pos].1
+    }
+
+    fn next(&mut self) -> Token {
+        let token = self.peek().clone();
+        if token != Token::Eof {
+            self.pos += 1;
+        }
+        token
+    }
+
+    fn error<T>(&self, expected: &str) -> ::std::result::Result<T, (usize, String)> {
+        Err((
+            self.offset(),
+            format!("expected {}, found {}", expected, self.peek()),
+        ))
+    }
+
+    fn keyword(&mut self, keyword: &str) -> ::std::result::Result<(), (usize, String)> {
+        match self.peek() {
+            Token::Word(w) if w == keyword => {
+                self.next();
+                Ok(())
+            }
+            _ => self.error(&format!("`{}`", keyword)),
+        }
+    }
+
+    fn ident(&mut self) -> ::std::result::Result<(String, usize), (usize, String)> {
+        let offset = self.offset();
+        match self.peek() {
+            Token::Word(w) if !is_keyword(w) => {
+                let w = w.clone();
+                self.next();
+                Ok((w, offset))
+            }
+            _ => self.error("a basis name"),
+        }
+    }
+
+    fn decs(&mut self) -> ::std::result::Result<Vec<BasDec>, (usize, String)> {
+        let mut decs = Vec::new();
+        loop {
+            let offset = self.offset();
+            match self.peek().clone() {
+                Token::Semicolon => {
+                    self.next();
+                }
+                Token::Path(path) => {
+                    self.next();
+                    decs.push(BasDec::File(path, offset))
+                }
+                Token::Word(w) => match w.as_str() {
+                    "local" => {
+                        self.next();
+                        let local = self.decs()?;
+                        self.keyword("in")?;
+                        let body = self.decs()?;
+                        self.keyword("end")?;
+                        decs.push(BasDec::Local(local, body))
+                    }
+                    "basis" => {
+                        self.next();
+                        let mut binds = Vec::new();
+                        loop {
+                            let (name, _) = self.ident()?;
+                            if self.next() != Token::Equal {
+                                self.pos -= 1;
+                                return self.error("`=`");
+                            }
+                            binds.push((name, self.exp()?));
+                            match self.peek() {
+                                Token::Word(w) if w == "and" => {
+                                    self.next();
+                                }
+                                _ => break,
+                            }
+                        }
+                        decs.push(BasDec::Basis(binds))
+                    }
+                    "open" => {
+                        self.next();
+                        let mut names = vec![self.ident()?];
+                        while let Token::Word(w) = self.peek() {
+                            if is_keyword(w) {
+                                break;
+                            }
+                            names.push(self.ident()?);
+                        }
+                        decs.push(BasDec::Open(names))
+                    }
+                    "structure" | "signature" | "functor" => {
+                        return Err((
+                            offset,
+                            format!("`{}` bindings are not supported as WebML has no modules", w),
+                        ))
+                    }
+                    "ann" => {
+                        return Err((offset, "annotations are not supported".into()));
+                    }
+                    _ => return Ok(decs),
+                },
+                Token::Equal | Token::Eof => return Ok(decs),
+            }
+        }
+    }
+
+    fn exp(&mut self) -> ::std::result::Result<BasExp, (usize, String)> {
+        match self.peek() {
+            Token::Word(w) if w == "bas" => {
+                self.next();
+                let decs = self.decs()?;
+                self.keyword("end")?;
+                Ok(BasExp::Bas(decs))
+            }
+            Token::Word(w) if w == "let" => {
+                self.next();
+                let decs = self.decs()?;
+                self.keyword("in")?;
+                let exp = self.exp()?;
+                self.keyword("end")?;
+                Ok(BasExp::Let(decs, Box::new(exp)))
+            }
+            Token::Word(_) => {
+                let (name, offset) = self.ident()?;
+                Ok(BasExp::Id(name, offset))
+            }
+            _ => self.error("`bas`, `let` or a basis name"),
+        }
+    }
+}
+
+fn is_keyword(word: &str) -> bool {
+    [
+        "and",
+        "ann",
+        "bas",
+        "basis",
+        "end",
+        "functor",
+        "in",
+        "let",
+        "local",
+        "open",
+        "signature",
+        "structure",
+    ]
+    .contains(&word)
+}
+
+/// the names of the bases in scope. the later ones shadow the earlier ones
+type Env = Vec<(String, Rc<Closure>)>;
+
+/// a basis not elaborated yet, with where it is written
+#[derive(Debug)]
+struct Closure {
+    exp: BasExp,
+    env: Env,
+    file: Rc<BasisFile>,
+}
+
+/// a basis file read
+#[derive(Debug)]
+struct BasisFile {
+    path: PathBuf,
+    text: String,
+}
+
+impl BasisFile {
+    fn error(&self, offset: usize, message: impl Into<String>) -> MlbError {
+        MlbError {
+            path: self.path.clone(),
+            position: Some(Position::at(&self.text, offset)),
+            message: message.into(),
+        }
+    }
+
+    /// the path written in the file, relative to the directory of the file
+    fn resolve(&self, offset: usize, path: &str) -> Result<Option<PathBuf>> {
+        if path.starts_with("$(SML_LIB)") {
+            return Ok(None);
+        }
+        let mut resolved = String::new();
+        let mut rest = path;
+        while let Some(start) = rest.find("$(") {
+            resolved.push_str(&rest[..start]);
+            let end = start
+                + rest[start..]
+                    .find(')')
+                    .ok_or_else(|| self.error(offset, "unclosed path variable"))?;
+            let name = &rest[start + 2..end];
+            let value = std::env::var(name)
+                .map_err(|_| self.error(offset, format!("unknown path variable `{}`", name)))?;
+            resolved.push_str(&value);
+            rest = &rest[end + 1..];
+        }
+        resolved.push_str(rest);
+        let dir = self.path.parent().unwrap_or_else(|| Path::new(""));
+        Ok(Some(dir.join(resolved)))
+    }
+}
+
+struct Loader<'a> {
+    sources: &'a mut Sources,
+    /// the basis files being read, to find the ones including themselves
+    reading: Vec<PathBuf>,
+}
+
+impl<'a> Loader<'a> {
+    /// reads a basis file, returning the bases it binds
+    fn file(&mut self, path: &Path, from: Option<(&BasisFile, usize)>) -> Result<Env> {
+        let read_error = |e: std::io::Error| match from {
+            Some((file, offset)) => {
+                file.error(offset, format!("cannot read {}: {}", path.display(), e))
+            }
+            None => MlbError {
+                path: path.to_path_buf(),
+                position: None,
+                message: format!("cannot read: {}", e),
+            },
+        };
+        let canonical = path.canonicalize().map_err(read_error)?;
+        if self.reading.contains(&canonical) {
+            let (file, offset) = from.expect("a file including itself is included");
+            return Err(file.error(offset, format!("{} includes itself", path.display())));
+        }
+        let text = fs::read_to_string(path).map_err(read_error)?;
+        let file = Rc::new(BasisFile {
+            path: path.to_path_buf(),
+            text,
+        });
+        let tokens = lex(&file.text).map_err(|(offset, message)| file.error(offset, message))?;
+        let mut parser = Parser { tokens, pos: 0 };
+        let decs = parser
+            .decs()
+            .map_err(|(offset, message)| file.error(offset, message))?;
+        if parser.peek() != &Token::Eof {
+            return Err(file.error(parser.offset(), format!("unexpected {}", parser.peek())));
+        }
+
+        self.reading.push(canonical);
+        let mut env = Env::new();
+        self.decs(&file, &decs, &mut env)?;
+        self.reading.pop();
+        Ok(env)
+    }
+
+    fn decs(&mut self, file: &Rc<BasisFile>, decs: &[BasDec], env: &mut Env) -> Result<()> {
+        for dec in decs {
+            self.dec(file, dec, env)?
+        }
+        Ok(())
+    }
+
+    fn dec(&mut self, file: &Rc<BasisFile>, dec: &BasDec, env: &mut Env) -> Result<()> {
+        match dec {
+            BasDec::File(path, offset) => {
+                let path = match file.resolve(*offset, path)? {
+                    Some(path) => path,
+                    None => return Ok(()),
+                };
+                if path.extension().is_some_and(|e| e == "mlb") {
+                    let bound = self.file(&path, Some((&**file, *offset)))?;
+                    env.extend(bound);
+                } else {
+                    let text = fs::read_to_string(&path).map_err(|e| {
+                        file.error(*offset, format!("cannot read {}: {}", path.display(), e))
+                    })?;
+                    self.sources.add_file(path.display().to_string(), &text);
+                }
+            }
+            BasDec::Local(local, body) => {
+                let outer = env.len();
+                self.sources.add_text("local");
+                self.decs(file, local, env)?;
+                let inner = env.len();
+                self.sources.add_text("in");
+                self.decs(file, body, env)?;
+                self.sources.add_text("end");
+                env.drain(outer..inner);
+            }
+            BasDec::Basis(binds) => {
+                let closures = binds
+                    .iter()
+                    .map(|(name, exp)| {
+                        let closure = Closure {
+                            exp: exp.clone(),
+                            env: env.clone(),
+                            file: file.clone(),
+                        };
+                        (name.clone(), Rc::new(closure))
+                    })
+                    .collect::<Vec<_>>();
+                env.extend(closures);
+            }
+            BasDec::Open(names) => {
+                for (name, offset) in names {
+                    let closure = lookup(env, name)
+                        .ok_or_else(|| file.error(*offset, format!("unbound basis `{}`", name)))?;
+                    let bound = self.open(&closure.file, &closure.exp, closure.env.clone())?;
+                    env.extend(bound);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// elaborates the basis, returning the bases it binds
+    fn open(&mut self, file: &Rc<BasisFile>, exp: &BasExp, mut env: Env) -> Result<Env> {
+        let outer = env.len();
+        match exp {
+            BasExp::Bas(decs) => self.decs(file, decs, &mut env)?,
+            BasExp::Id(name, offset) => {
+                let closure = lookup(&env, name)
+                    .ok_or_else(|| file.error(*offset, format!("unbound basis `{}`", name)))?;
+                let bound = self.open(&closure.file, &closure.exp, closure.env.clone())?;
+                return Ok(bound);
+            }
+            BasExp::Let(decs, exp) => {
+                self.sources.add_text("local");
+                self.decs(file, decs, &mut env)?;
+                self.sources.add_text("in");
+                let bound = self.open(file, exp, env)?;
+                self.sources.add_text("end");
+                return Ok(bound);
+            }
+        }
+        Ok(env.split_off(outer))
+    }
+}
+
+fn lookup(env: &Env, name: &str) -> Option<Rc<Closure>> {
+    env.iter()
+        .rev()
+        .find(|(n, _)| n == name)
+        .map(|(_, closure)| closure.clone())
+}
+
+#[test]
+fn test_parse() {
+    let tokens = lex("(* (* nested *) *) $(SML_LIB)/basis/basis.mlb\nlocal a.sml in \"b c.sml\" end; basis B = bas d.fun end open B").unwrap();
+    let mut parser = Parser { tokens, pos: 0 };
+    let decs = parser.decs().unwrap();
+    assert_eq!(parser.peek(), &Token::Eof);
+    assert_eq!(
+        format!("{:?}", decs),
+        r#"[File("$(SML_LIB)/basis/basis.mlb", 19), Local([File("a.sml", 52)], [File("b c.sml", 61)]), Basis([("B", Bas([File("d.fun", 90)]))]), Open([("B", 105)])]"#
+    );
+    let tokens = lex("local a.sml end").unwrap();
+    let error = Parser { tokens, pos: 0 }.decs().unwrap_err();
+    assert_eq!(error, (12, "expected `in`, found `end`".to_string()));
+}
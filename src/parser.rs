@@ -1,25 +1,269 @@
 use crate::ast::*;
+use crate::lexer::{lex, Token, TokenKind};
 use crate::prim::*;
 use nom::branch::alt;
-use nom::bytes::complete::tag;
-use nom::character::complete::{alphanumeric1, digit1, multispace0, multispace1};
-use nom::combinator::{all_consuming, complete, map, map_res, opt, recognize, value, verify};
-use nom::multi::{many1, separated_list, separated_nonempty_list};
-use nom::number::complete::recognize_float;
+use nom::combinator::{cut, map, opt, value};
+use nom::error::ErrorKind;
+use nom::multi::{many0, many1, separated_nonempty_list};
 use nom::sequence::{preceded, terminated, tuple};
-use nom::IResult;
 use std::cell::RefCell;
 use std::collections::{BTreeMap, HashMap};
+use std::fmt;
 
-static KEYWORDS: &[&str] = &[
-    "val", "fun", "fn", "let", "in", "end", "if", "then", "else", "case", "of", "_", "datatype",
-    "op", "=>", "infix", "infixr",
+type Tokens<'a> = &'a [Token<'a>];
+type IResult<I, O> = nom::IResult<I, O, Error<I>>;
+
+/// the declarations a line begins with where the parser resumes after an error
+static DECL_KEYWORDS: &[&str] = &[
+    "val", "fun", "datatype", "type", "infix", "local", "abstype",
 ];
 
-static RESERVED: &[&str] = &["|", "=", "#"];
+/// what the parser was looking for when it failed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expected {
+    /// a token as written, like `end` or `)`
+    Token(&'static str),
+    /// a class of the syntax, like "an expression"
+    Label(&'static str),
+}
+
+impl fmt::Display for Expected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Expected::Token(token) => write!(f, "`{}`", token),
+            Expected::Label(label) => write!(f, "{}", label),
+        }
+    }
+}
+
+/// a line and a column in the source, both 1-origin
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Position {
+    /// the position of the byte `offset` of `input`
+    pub(crate) fn at(input: &str, offset: usize) -> Self {
+        let read = &input[..offset];
+        let line_start = read.rfind('\n').map(|n| n + 1).unwrap_or(0);
+        Position {
+            line: read.matches('\n').count() + 1,
+            column: read[line_start..].chars().count() + 1,
+        }
+    }
+
+    /// the byte of `input` at the position
+    pub(crate) fn offset(&self, input: &str) -> usize {
+        let line_start = input
+            .split_inclusive('\n')
+            .take(self.line - 1)
+            .map(str::len)
+            .sum::<usize>();
+        input[line_start..]
+            .char_indices()
+            .nth(self.column - 1)
+            .map(|(i, _)| line_start + i)
+            .unwrap_or(input.len())
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError<'a> {
+    pub position: Position,
+    /// the range of `found`
+    pub span: Span,
+    /// the token at `position`. empty at the end of the input
+    pub found: &'a str,
+    pub expected: Vec<Expected>,
+    /// the construct left open and where it began
+    pub context: Option<(&'static str, Position)>,
+}
+
+impl<'a> ParseError<'a> {
+    fn new(input: &'a str, e: Error<Tokens>) -> Self {
+        let span = e.input[0].span;
+        ParseError {
+            position: Position::at(input, span.start),
+            span,
+            found: &input[span.start..span.end],
+            expected: e.expected,
+            context: e
+                .context
+                .map(|(open, start)| (open, Position::at(input, start[0].span.start))),
+        }
+    }
+
+    /// the error without the positions, for the tools showing it in place with the context as a label
+    pub fn message(&self) -> String {
+        self.describe(false)
+    }
+
+    fn describe(&self, with_context: bool) -> String {
+        let mut message = String::new();
+        match self.expected.split_last() {
+            None => message.push_str("unexpected "),
+            Some((last, init)) => {
+                message.push_str("expected ");
+                if !init.is_empty() {
+                    let init = init.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                    message += &format!("{} or ", init.join(", "));
+                }
+                message += &last.to_string();
+                if let Some((open, start)) = &self.context {
+                    message += &format!(" to close `{}`", open);
+                    if with_context {
+                        message += &format!(" at {}", start);
+                    }
+                }
+                message.push_str(", found ");
+            }
+        }
+        match self.found {
+            "" => message.push_str("end of input"),
+            found => message += &format!("`{}`", found),
+        }
+        message
+    }
+}
+
+impl<'a> fmt::Display for ParseError<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "syntax error at {}: {}",
+            self.position,
+            self.describe(true)
+        )
+    }
+}
+
+/// the error of the combinators. of the alternatives, the one read the furthest is kept
+#[derive(Debug, Clone, PartialEq)]
+struct Error<I> {
+    input: I,
+    expected: Vec<Expected>,
+    context: Option<(&'static str, I)>,
+}
+
+impl<I> Error<I> {
+    fn new(input: I, expected: Expected) -> Self {
+        Error {
+            input,
+            expected: vec![expected],
+            context: None,
+        }
+    }
+}
+
+impl<'a> nom::error::ParseError<Tokens<'a>> for Error<Tokens<'a>> {
+    fn from_error_kind(input: Tokens<'a>, _: ErrorKind) -> Self {
+        Error {
+            input,
+            expected: vec![],
+            context: None,
+        }
+    }
+
+    fn append(_: Tokens<'a>, _: ErrorKind, other: Self) -> Self {
+        other
+    }
+
+    fn or(mut self, other: Self) -> Self {
+        use std::cmp::Ordering::*;
+        match self.input.len().cmp(&other.input.len()) {
+            Less => self,
+            Greater => other,
+            Equal => {
+                for expected in other.expected {
+                    if !self.expected.contains(&expected) {
+                        self.expected.push(expected)
+                    }
+                }
+                self.context = self.context.or(other.context);
+                self
+            }
+        }
+    }
+}
+
+/// the token `f` accepts. the input always ends with `Eof`, which nothing accepts
+fn token<'a, O>(
+    expected: Expected,
+    f: impl Fn(&'a TokenKind<'a>) -> Option<O>,
+) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, O> {
+    move |i: Tokens<'a>| match f(&i[0].kind) {
+        Some(o) => Ok((&i[1..], o)),
+        None => Err(nom::Err::Error(Error::new(i, expected))),
+    }
+}
+
+/// a reserved word, a reserved symbol or a punctuation
+fn reserved<'a>(word: &'static str) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, ()> {
+    token(Expected::Token(word), move |kind| match kind {
+        TokenKind::Reserved(w) if *w == word => Some(()),
+        _ => None,
+    })
+}
+
+/// an identifier with the name, like `true` or `*`. returns where it is written
+fn ident<'a>(name: &'static str) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, Span> {
+    move |i: Tokens<'a>| {
+        let span = i[0].span;
+        token(Expected::Token(name), move |kind| match kind {
+            TokenKind::Ident(n) if *n == name => Some(span),
+            _ => None,
+        })(i)
+    }
+}
+
+/// the token closing the construct `open` began at `start`
+fn close<'a>(
+    open: &'static str,
+    start: Tokens<'a>,
+    word: &'static str,
+) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, ()> {
+    move |i| {
+        reserved(word)(i).map_err(|e| {
+            e.map(|e| Error {
+                context: Some((open, start)),
+                ..e
+            })
+        })
+    }
+}
+
+/// the input at the point, not reading anything
+fn here(i: Tokens) -> IResult<Tokens, Tokens> {
+    Ok((i, i))
+}
+
+/// names what `p` reads in place of the tokens expected when it fails before reading anything
+fn label<'a, O>(
+    name: &'static str,
+    p: impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, O>,
+) -> impl Fn(Tokens<'a>) -> IResult<Tokens<'a>, O> {
+    move |i| match p(i) {
+        Err(nom::Err::Error(e)) if e.input.len() == i.len() => Err(nom::Err::Error(Error {
+            expected: vec![Expected::Label(name)],
+            ..e
+        })),
+        r => r,
+    }
+}
+
+/// the infix operators by priority
+pub type Fixities = BTreeMap<u8, Vec<Symbol>>;
 
 struct Parser {
-    infixes: RefCell<Vec<BTreeMap<u8, Vec<Symbol>>>>,
+    infixes: RefCell<Vec<Fixities>>,
 }
 
 impl Parser {
@@ -30,10 +274,15 @@ impl Parser {
     }
 
     fn with_scope<R>(&self, f: impl FnOnce() -> R) -> R {
+        self.with_scope_fixities(f).0
+    }
+
+    /// `with_scope` returning the fixities declared in the scope too
+    fn with_scope_fixities<R>(&self, f: impl FnOnce() -> R) -> (R, Fixities) {
         self.infixes.borrow_mut().push(BTreeMap::default());
         let r = f();
-        self.infixes.borrow_mut().pop();
-        r
+        let fixities = self.infixes.borrow_mut().pop().unwrap();
+        (r, fixities)
     }
 
     // TODO: support let scopes
@@ -47,7 +296,7 @@ impl Parser {
             .append(&mut names)
     }
 
-    fn get_table(&self) -> BTreeMap<u8, Vec<Symbol>> {
+    fn get_table(&self) -> Fixities {
         self.infixes
             .borrow()
             .iter()
@@ -68,84 +317,201 @@ impl Parser {
 }
 
 impl Parser {
-    fn top(&self) -> impl Fn(&str) -> IResult<&str, UntypedAst> + '_ {
+    // on an error, skips to the next line beginning with a declaration
+    // and goes on to find the errors after it
+    fn top<'a>(
+        &self,
+        input: &str,
+        tokens: Tokens<'a>,
+    ) -> ::std::result::Result<UntypedAst, Vec<Error<Tokens<'a>>>> {
+        let mut tops = Vec::new();
+        let mut errors = Vec::new();
+        let mut i = tokens;
+        while i[0].kind != TokenKind::Eof {
+            match self.decl()(i) {
+                Ok((rest, decl)) => {
+                    tops.push(decl);
+                    i = rest;
+                }
+                Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => {
+                    let at = e.input;
+                    errors.push(e);
+                    i = resume_point(input, i, at);
+                }
+                Err(nom::Err::Incomplete(_)) => unreachable!("complete parsers are used"),
+            }
+        }
+        if errors.is_empty() {
+            Ok(AST(tops))
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn decl(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
         move |i| {
-            let (i, _) = multispace0(i)?;
-            let (i, tops) = separated_list(multispace1, self.decl())(i)?;
-            let (i, _) = multispace0(i)?;
-            Ok((i, AST(tops)))
+            label(
+                "a declaration",
+                alt((
+                    self.decl_datatype(),
+                    self.decl_abstype(),
+                    self.decl_local(),
+                    self.decl_type(),
+                    self.decl_val(),
+                    self.decl_fun(),
+                    self.decl_infix(),
+                )),
+            )(i)
         }
     }
-    fn decl(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+
+    fn decl_datatype(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
         move |i| {
-            alt((
-                self.decl_datatype(),
-                self.decl_val(),
-                self.decl_fun(),
-                self.decl_infix(),
-            ))(i)
+            let (i, _) = reserved("datatype")(i)?;
+            cut(alt((self.datatype_replication(), self.datbinds_withtype())))(i)
         }
     }
 
-    fn decl_datatype(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+    // `datatype t = datatype u` is an abbreviation of `u`
+    fn datatype_replication(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
         move |i| {
-            let (i, _) = tag("datatype")(i)?;
-            let (i, _) = multispace1(i)?;
             let (i, name) = self.symbol()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("=")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, constructors) = separated_nonempty_list(
-                tuple((multispace0, tag("|"), multispace0)),
-                self.constructor_def(),
-            )(i)?;
+            let (i, _) = reserved("=")(i)?;
+            let (i, _) = reserved("datatype")(i)?;
+            let (i, orig) = self.symbol()(i)?;
+            let ty = Type::Datatype(orig);
+            Ok((i, Declaration::Type { name, ty }))
+        }
+    }
+
+    fn datbinds_withtype(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let (i, mut decls) = separated_nonempty_list(self.and_sep(), self.datbind())(i)?;
+            let (i, typbinds) = opt(preceded(
+                reserved("withtype"),
+                separated_nonempty_list(self.and_sep(), self.typbind()),
+            ))(i)?;
+            decls.extend(typbinds.into_iter().flatten());
+            Ok((i, and_group(decls)))
+        }
+    }
+
+    fn decl_abstype(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = reserved("abstype")(i)?;
+            cut(move |i| {
+                let (i, datatype) = self.datbinds_withtype()(i)?;
+                let (i, _) = reserved("with")(i)?;
+                let (i, decls) = many0(self.decl())(i)?;
+                let (i, _) = close("abstype", start, "end")(i)?;
+                Ok((
+                    i,
+                    Declaration::Abstype {
+                        datatype: Box::new(datatype),
+                        decls,
+                    },
+                ))
+            })(i)
+        }
+    }
+
+    fn decl_local(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = reserved("local")(i)?;
+            cut(move |i| {
+                let (i, (binds, body, fixities)) = self.with_scope(|| {
+                    let (i, binds) = many0(self.decl())(i)?;
+                    let (i, _) = reserved("in")(i)?;
+                    let (body, fixities) = self.with_scope_fixities(|| many0(self.decl())(i));
+                    let (i, body) = body?;
+                    Ok((i, (binds, body, fixities)))
+                })?;
+                // the fixities of the body outlive the declaration, but not the ones of binds
+                for (priority, names) in fixities {
+                    self.new_infix_op(Some(priority), names);
+                }
+                let (i, _) = close("local", start, "end")(i)?;
+                Ok((i, Declaration::Local { binds, body }))
+            })(i)
+        }
+    }
+
+    fn decl_type(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let (i, _) = reserved("type")(i)?;
+            let (i, decls) = cut(separated_nonempty_list(self.and_sep(), self.typbind()))(i)?;
+            Ok((i, and_group(decls)))
+        }
+    }
+
+    fn typbind(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let (i, name) = self.symbol()(i)?;
+            let (i, _) = reserved("=")(i)?;
+            let (i, ty) = self.typename()(i)?;
+            Ok((i, Declaration::Type { name, ty }))
+        }
+    }
+
+    fn datbind(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let (i, name) = self.symbol()(i)?;
+            let (i, _) = reserved("=")(i)?;
+            let (i, constructors) =
+                separated_nonempty_list(reserved("|"), self.constructor_def())(i)?;
             Ok((i, Declaration::Datatype { name, constructors }))
         }
     }
 
-    fn decl_val(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+    fn decl_val(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let (i, _) = reserved("val")(i)?;
+            let (i, rec) = opt(reserved("rec"))(i)?;
+            let (i, decls) = cut(separated_nonempty_list(
+                self.and_sep(),
+                self.valbind(rec.is_some()),
+            ))(i)?;
+            Ok((i, and_group(decls)))
+        }
+    }
+
+    fn valbind(&self, rec: bool) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
         move |i| {
-            let (i, _) = tag("val")(i)?;
-            let (i, _) = multispace1(i)?;
             let (i, pattern) = self.pattern()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("=")(i)?;
-            let (i, _) = multispace0(i)?;
+            let (i, _) = reserved("=")(i)?;
             let (i, expr) = self.expr()(i)?;
-            Ok((
-                i,
-                Declaration::Val {
-                    rec: false,
-                    pattern,
-                    expr,
-                },
-            ))
+            Ok((i, Declaration::Val { rec, pattern, expr }))
+        }
+    }
+
+    fn decl_fun(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
+        move |i| {
+            let (i, _) = reserved("fun")(i)?;
+            let (i, decls) = cut(separated_nonempty_list(self.and_sep(), self.fvalbind()))(i)?;
+            Ok((i, and_group(decls)))
         }
     }
 
-    fn decl_fun(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+    fn fvalbind(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
         move |i| {
-            let (i, _) = tag("fun")(i)?;
-            let (i, _) = multispace1(i)?;
             let (i, cs) = separated_nonempty_list(
-                tuple((multispace0, tag("|"), multispace0)),
+                reserved("|"),
                 map(
-                    tuple((
-                        self.decl_funbind(),
-                        multispace0,
-                        tag("="),
-                        multispace0,
-                        self.expr(),
-                    )),
-                    |((name, params), _, _, _, e)| (name, params, e),
+                    tuple((here, self.decl_funbind(), reserved("="), self.expr())),
+                    |(start, (name, params), _, e)| (start, name, params, e),
                 ),
             )(i)?;
             let mut cs = cs.into_iter();
-            let (name, params, expr) = cs.next().expect("nonempty list empty");
+            let (_, name, params, expr) = cs.next().expect("nonempty list empty");
             let mut clauses = vec![(params, expr)];
-            for (new_name, params, expr) in cs {
+            for (start, new_name, params, expr) in cs {
                 if name != new_name {
-                    return Err(nom::Err::Error((i, nom::error::ErrorKind::Tag)));
+                    return Err(nom::Err::Failure(Error::new(
+                        start,
+                        Expected::Label("the same function name in every clause"),
+                    )));
                 }
                 clauses.push((params, expr))
             }
@@ -153,42 +519,31 @@ impl Parser {
         }
     }
 
-    fn decl_funbind(&self) -> impl Fn(&str) -> IResult<&str, (Symbol, Vec<Pattern<()>>)> + '_ {
-        move |i| {
-            map(
-                tuple((
-                    self.op_symbol_eq(),
-                    multispace0,
-                    separated_nonempty_list(multispace1, self.pattern_atmic()),
-                )),
-                |(name, _, pats)| (name, pats),
-            )(i)
-        }
+    fn and_sep(&self) -> impl Fn(Tokens) -> IResult<Tokens, ()> + '_ {
+        move |i| reserved("and")(i)
     }
 
-    fn constructor_def(&self) -> impl Fn(&str) -> IResult<&str, (Symbol, Option<Type>)> + '_ {
+    fn decl_funbind(&self) -> impl Fn(Tokens) -> IResult<Tokens, (Symbol, Vec<Pattern<()>>)> + '_ {
+        move |i| tuple((self.op_symbol_eq(), many1(self.pattern_atmic())))(i)
+    }
+
+    fn constructor_def(&self) -> impl Fn(Tokens) -> IResult<Tokens, (Symbol, Option<Type>)> + '_ {
         move |i| {
             let (i, name) = self.symbol()(i)?;
-            let (i, param) = opt(complete(map(
-                tuple((multispace1, tag("of"), multispace1, self.typename())),
-                |(_, _, _, ty)| ty,
-            )))(i)?;
-
+            let (i, param) = opt(preceded(reserved("of"), self.typename()))(i)?;
             Ok((i, (name, param)))
         }
     }
 
-    fn decl_infix(&self) -> impl Fn(&str) -> IResult<&str, Declaration<()>> + '_ {
+    fn decl_infix(&self) -> impl Fn(Tokens) -> IResult<Tokens, Declaration<()>> + '_ {
         move |i| {
-            let (i, _) = tag("infix")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, priority) = opt(digit1)(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, names) = separated_nonempty_list(multispace1, self.symbol_eq())(i)?;
-            let priority = priority.map(|s| {
-                s.parse()
-                    .expect("internal error: falied to parse digits as integer")
+            let (i, _) = reserved("infix")(i)?;
+            let priority_literal = token(Expected::Label("a precedence"), |kind| match kind {
+                TokenKind::Int(digits) => digits.parse::<u8>().ok(),
+                _ => None,
             });
+            let (i, (priority, names)) =
+                cut(tuple((opt(priority_literal), many1(self.symbol_eq()))))(i)?;
             self.new_infix_op(priority, names.clone());
             Ok((
                 i,
@@ -197,70 +552,72 @@ impl Parser {
         }
     }
 
-    fn expr(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            alt((
-                self.expr_bind(),
-                self.expr_fun(),
-                self.expr_if(),
-                self.expr_case(),
-                self.expr_infix_and_app(),
-            ))(i)
+            label(
+                "an expression",
+                alt((
+                    self.expr_bind(),
+                    self.expr_fun(),
+                    self.expr_if(),
+                    self.expr_case(),
+                    self.expr_infix_and_app(),
+                )),
+            )(i)
         }
     }
 
-    fn expr1(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            alt((
-                self.expr1_tuple(),
-                self.expr1_unit(),
-                self.expr1_paren(),
-                self.expr1_float(),
-                self.expr1_int(),
-                self.expr1_char(),
-                self.expr1_bool(),
-                self.expr1_sym(),
-                self.expr1_builtincall(),
-                self.expr1_externcall(),
-            ))(i)
+            label(
+                "an expression",
+                alt((
+                    self.expr1_tuple(),
+                    self.expr1_unit(),
+                    self.expr1_paren(),
+                    self.expr1_float(),
+                    self.expr1_word(),
+                    self.expr1_int(),
+                    self.expr1_char(),
+                    self.expr1_bool(),
+                    self.expr1_builtincall(),
+                    self.expr1_externcall(),
+                    self.expr1_sym(),
+                )),
+            )(i)
         }
     }
 
-    fn expr_bind(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr_bind(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
             self.with_scope(|| {
-                let (i, _) = tag("let")(i)?;
-                let (i, _) = multispace1(i)?;
-                let (i, binds) = separated_list(multispace1, self.decl())(i)?;
-                let (i, _) = multispace1(i)?;
-                let (i, _) = tag("in")(i)?;
-                let (i, _) = multispace1(i)?;
-                let (i, ret) = self.expr()(i)?;
-                let (i, _) = multispace1(i)?;
-                let (i, _) = tag("end")(i)?;
-                Ok((
-                    i,
-                    Expr {
-                        ty: (),
-                        inner: ExprKind::Binds {
-                            binds: binds,
-                            ret: ret.boxed(),
+                let start = i;
+                let (i, _) = reserved("let")(i)?;
+                cut(move |i| {
+                    let (i, binds) = many0(self.decl())(i)?;
+                    let (i, _) = reserved("in")(i)?;
+                    let (i, ret) = self.expr()(i)?;
+                    let (i, _) = close("let", start, "end")(i)?;
+                    Ok((
+                        i,
+                        Expr {
+                            ty: (),
+                            inner: ExprKind::Binds {
+                                binds: binds,
+                                ret: ret.boxed(),
+                            },
                         },
-                    },
-                ))
+                    ))
+                })(i)
             })
         }
     }
 
-    fn expr_fun(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr_fun(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            let (i, _) = tag("fn")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, param) = self.symbol()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("=>")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, body) = self.expr()(i)?;
+            let (i, _) = reserved("fn")(i)?;
+            let (i, (param, _, body)) =
+                cut(tuple((self.symbol(), reserved("=>"), self.expr())))(i)?;
             Ok((
                 i,
                 Expr {
@@ -274,19 +631,16 @@ impl Parser {
         }
     }
 
-    fn expr_if(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr_if(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            let (i, _) = tag("if")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, cond) = self.expr()(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, _) = tag("then")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, then) = self.expr()(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, _) = tag("else")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, else_) = self.expr()(i)?;
+            let (i, _) = reserved("if")(i)?;
+            let (i, (cond, _, then, _, else_)) = cut(tuple((
+                self.expr(),
+                reserved("then"),
+                self.expr(),
+                reserved("else"),
+                self.expr(),
+            )))(i)?;
             Ok((
                 i,
                 Expr {
@@ -301,27 +655,17 @@ impl Parser {
         }
     }
 
-    fn expr_case(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr_case(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            let (i, _) = tag("case")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, cond) = self.expr()(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, _) = tag("of")(i)?;
-            let (i, _) = multispace1(i)?;
-            let (i, clauses) = separated_nonempty_list(
-                tuple((multispace0, tag("|"), multispace0)),
+            let (i, _) = reserved("case")(i)?;
+            let (i, cond) = cut(terminated(self.expr(), reserved("of")))(i)?;
+            let (i, clauses) = cut(separated_nonempty_list(
+                reserved("|"),
                 map(
-                    tuple((
-                        self.pattern(),
-                        multispace0,
-                        tag("=>"),
-                        multispace0,
-                        self.expr(),
-                    )),
-                    |(pat, _, _, _, expr)| (pat, expr),
+                    tuple((self.pattern(), reserved("=>"), self.expr())),
+                    |(pat, _, expr)| (pat, expr),
                 ),
-            )(i)?;
+            ))(i)?;
             Ok((
                 i,
                 Expr {
@@ -336,10 +680,9 @@ impl Parser {
     }
 
     // treat all of the infix operators and applications, i.e. sequeces of expressions
-    fn expr_infix_and_app(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr_infix_and_app(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            // TODO: support 1+1
-            let (i, mixed) = many1(map(tuple((multispace0, self.expr1())), |(_, e)| e))(i)?;
+            let (i, mixed) = many1(self.expr1())(i)?;
             #[derive(Debug)]
             enum Mixed {
                 E(Expr<()>),
@@ -408,131 +751,172 @@ impl Parser {
             let mut rest = (1u8..=9)
                 .rev()
                 .fold(rest, |rest, n| reduce_infixl_n(n, rest));
-            assert_eq!(rest.len(), 1);
-            let e = match rest.remove(0) {
-                E(e) => e,
-                Fix(..) => unreachable!("infix alone"),
+            let e = match (rest.pop(), rest.is_empty()) {
+                (Some(E(e)), true) => e,
+                // an infix operator lacks an operand
+                _ => {
+                    return Err(nom::Err::Error(Error::new(
+                        i,
+                        Expected::Label("an operand"),
+                    )))
+                }
             };
             Ok((i, e))
         }
     }
-    fn expr1_sym(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+
+    fn expr1_sym(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
             // = is allowed to be used in expression exceptionally
-            map(alt((self.symbol(), map(tag("="), Symbol::new))), |name| {
+            let name = alt((self.longid(), value(Symbol::new("="), reserved("="))));
+            map(label("an identifier", name), |name| Expr {
+                ty: (),
+                inner: ExprKind::Symbol { name },
+            })(i)
+        }
+    }
+
+    fn expr1_int(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
+        move |i| {
+            token(Expected::Label("an integer"), |kind| match kind {
+                TokenKind::Int(digits) => {
+                    let value = match digits.parse() {
+                        Ok(n) => Literal::Int(n),
+                        // only IntInf can hold it
+                        Err(_) => Literal::LargeInt(digits.clone()),
+                    };
+                    Some(Expr {
+                        ty: (),
+                        inner: ExprKind::Literal { value },
+                    })
+                }
+                _ => None,
+            })(i)
+        }
+    }
+
+    fn int_literal(&self) -> impl Fn(Tokens) -> IResult<Tokens, i64> + '_ {
+        move |i| {
+            token(Expected::Label("an integer"), |kind| match kind {
+                TokenKind::Int(digits) => digits.parse().ok(),
+                _ => None,
+            })(i)
+        }
+    }
+
+    fn expr1_word(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
+        move |i| {
+            let (i, value) = token(Expected::Label("a word"), |kind| match kind {
+                TokenKind::Word(w) => Some(Literal::Word(*w)),
+                TokenKind::LargeWord(text) => Some(Literal::LargeWord(text.to_string())),
+                _ => None,
+            })(i)?;
+            Ok((
+                i,
                 Expr {
                     ty: (),
-                    inner: ExprKind::Symbol { name },
-                }
+                    inner: ExprKind::Literal { value },
+                },
+            ))
+        }
+    }
+
+    fn word_literal(&self) -> impl Fn(Tokens) -> IResult<Tokens, u64> + '_ {
+        move |i| {
+            token(Expected::Label("a word"), |kind| match kind {
+                TokenKind::Word(w) => Some(*w),
+                _ => None,
             })(i)
         }
     }
 
-    fn expr1_int(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_float(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            map(digit1, |s: &str| Expr {
+            map(self.real_literal(), |f| Expr {
                 ty: (),
                 inner: ExprKind::Literal {
-                    value: Literal::Int(s.parse().unwrap()),
+                    value: Literal::Real(f),
                 },
             })(i)
         }
     }
 
-    fn expr1_float(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn real_literal(&self) -> impl Fn(Tokens) -> IResult<Tokens, f64> + '_ {
         move |i| {
-            let not_int = verify(recognize_float, |s: &&str| s.contains('.'));
+            token(Expected::Label("a real"), |kind| match kind {
+                TokenKind::Real(f) => Some(*f),
+                _ => None,
+            })(i)
+        }
+    }
 
-            map(not_int, |s: &str| Expr {
+    fn expr1_char(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
+        move |i| {
+            map(self.char_literal(), |c| Expr {
                 ty: (),
                 inner: ExprKind::Literal {
-                    value: Literal::Real(s.parse().unwrap()),
+                    value: Literal::Char(c),
                 },
             })(i)
         }
     }
 
-    fn expr1_char(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn char_literal(&self) -> impl Fn(Tokens) -> IResult<Tokens, u32> + '_ {
         move |i| {
-            let (i, _) = tag("#")(i)?;
-            let (i, s) = self.string_literal()(i)?;
-            assert_eq!(s.iter().count(), 1);
-            let c = s.into_iter().next().unwrap();
-            Ok((
-                i,
-                Expr {
-                    ty: (),
-                    inner: ExprKind::Literal {
-                        value: Literal::Char(c),
-                    },
-                },
-            ))
+            token(Expected::Label("a character"), |kind| match kind {
+                TokenKind::Char(c) => Some(*c),
+                _ => None,
+            })(i)
         }
     }
 
-    fn string_literal(&self) -> impl Fn(&str) -> IResult<&str, Vec<u32>> + '_ {
+    fn string_literal(&self) -> impl Fn(Tokens) -> IResult<Tokens, String> + '_ {
         move |i| {
-            let (i, _) = tag("\"")(i)?;
-            let mut s = vec![];
-            let mut chars = i.chars();
-            while let Some(c) = chars.next() {
-                if c == '"' {
-                    break;
-                }
-                s.push(c as u32)
-            }
-            let i = chars.as_str();
-            Ok((i, s))
+            token(Expected::Label("a string"), |kind| match kind {
+                TokenKind::String(s) => Some(s.clone()),
+                _ => None,
+            })(i)
         }
     }
 
-    fn expr1_bool(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_bool(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
             alt((
-                value(
-                    Expr {
-                        ty: (),
-                        inner: ExprKind::Constructor {
-                            name: Symbol::new("true"),
-                            arg: None,
-                        },
+                map(ident("true"), |span| Expr {
+                    ty: (),
+                    inner: ExprKind::Constructor {
+                        name: Symbol::new("true").with_span(span),
+                        arg: None,
                     },
-                    tag("true"),
-                ),
-                value(
-                    Expr {
-                        ty: (),
-                        inner: ExprKind::Constructor {
-                            name: Symbol::new("false"),
-                            arg: None,
-                        },
+                }),
+                map(ident("false"), |span| Expr {
+                    ty: (),
+                    inner: ExprKind::Constructor {
+                        name: Symbol::new("false").with_span(span),
+                        arg: None,
                     },
-                    tag("false"),
-                ),
+                }),
             ))(i)
         }
     }
 
-    fn expr1_paren(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_paren(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            let (i, _) = tag("(")(i)?;
-            let (i, _) = multispace0(i)?;
+            let start = i;
+            let (i, _) = reserved("(")(i)?;
             let (i, e) = self.expr()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = close("(", start, ")")(i)?;
             Ok((i, e))
         }
     }
 
-    fn expr1_tuple(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_tuple(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            let (i, _) = tag("(")(i)?;
-            let (i, _) = multispace0(i)?;
-            let sep = tuple((multispace0, tag(","), multispace0));
-            let (i, es) = many1(map(tuple((self.expr(), sep)), |(e, _)| e))(i)?;
+            let start = i;
+            let (i, _) = reserved("(")(i)?;
+            let (i, es) = many1(terminated(self.expr(), reserved(",")))(i)?;
             let (i, e) = self.expr()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = close("(", start, ")")(i)?;
 
             let mut es = es;
             es.push(e);
@@ -546,46 +930,42 @@ impl Parser {
         }
     }
 
-    fn expr1_unit(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_unit(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
             value(
                 Expr {
                     ty: (),
                     inner: ExprKind::Tuple { tuple: vec![] },
                 },
-                tuple((tag("("), multispace0, tag(")"))),
+                tuple((reserved("("), reserved(")"))),
             )(i)
         }
     }
 
-    fn expr1_builtincall(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_builtincall(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            let (i, _) = tag("_builtincall")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("\"")(i)?;
-            let (i, fun) = map_res(alphanumeric1, |name| match name {
-                "add" => Ok(BIF::Add),
-                "sub" => Ok(BIF::Sub),
-                "mul" => Ok(BIF::Mul),
-                "div" => Ok(BIF::Div),
-                "divf" => Ok(BIF::Divf),
-                "mod" => Ok(BIF::Mod),
-                "eq" => Ok(BIF::Eq),
-                "neq" => Ok(BIF::Neq),
-                "gt" => Ok(BIF::Gt),
-                "ge" => Ok(BIF::Ge),
-                "lt" => Ok(BIF::Lt),
-                "le" => Ok(BIF::Le),
-                _ => Err(nom::Err::Error(nom::error::ErrorKind::Tag)),
+            let (i, _) = ident("_builtincall")(i)?;
+            let (i, fun) = token(Expected::Label("a builtin function"), |kind| match kind {
+                TokenKind::String(name) => match name.as_str() {
+                    "add" => Some(BIF::Add),
+                    "sub" => Some(BIF::Sub),
+                    "mul" => Some(BIF::Mul),
+                    "div" => Some(BIF::Div),
+                    "divf" => Some(BIF::Divf),
+                    "mod" => Some(BIF::Mod),
+                    "eq" => Some(BIF::Eq),
+                    "neq" => Some(BIF::Neq),
+                    "gt" => Some(BIF::Gt),
+                    "ge" => Some(BIF::Ge),
+                    "lt" => Some(BIF::Lt),
+                    "le" => Some(BIF::Le),
+                    _ => None,
+                },
+                _ => None,
             })(i)?;
-            let (i, _) = tag("\"")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("(")(i)?;
-            let (i, args) = separated_nonempty_list(
-                tuple((multispace0, tag(","), multispace0)),
-                self.expr(),
-            )(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = reserved("(")(i)?;
+            let (i, args) = separated_nonempty_list(reserved(","), self.expr())(i)?;
+            let (i, _) = reserved(")")(i)?;
             Ok((
                 i,
                 Expr {
@@ -597,43 +977,23 @@ impl Parser {
     }
 
     /// `_externcall ("module"."fun": (arg, ty) -> retty) (arg, s)`
-    fn expr1_externcall(&self) -> impl Fn(&str) -> IResult<&str, Expr<()>> + '_ {
+    fn expr1_externcall(&self) -> impl Fn(Tokens) -> IResult<Tokens, Expr<()>> + '_ {
         move |i| {
-            fn name_parser(i: &str) -> IResult<&str, &str> {
-                let allowed = recognize(many1(nom::character::complete::none_of("\"")));
-                preceded(tag("\""), terminated(allowed, tag("\"")))(i)
-            }
-            let (i, _) = tag("_externcall")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("(")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, module) = map(name_parser, String::from)(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(".")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, fun) = map(name_parser, String::from)(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(":")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("(")(i)?;
-            let (i, argty) = separated_nonempty_list(
-                tuple((multispace0, tag(","), multispace0)),
-                self.typename(),
-            )(i)?;
-            let (i, _) = tag(")")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("->")(i)?;
-            let (i, _) = multispace0(i)?;
+            let (i, _) = ident("_externcall")(i)?;
+            let (i, _) = reserved("(")(i)?;
+            let (i, module) = self.string_literal()(i)?;
+            let (i, _) = reserved(".")(i)?;
+            let (i, fun) = self.string_literal()(i)?;
+            let (i, _) = reserved(":")(i)?;
+            let (i, _) = reserved("(")(i)?;
+            let (i, argty) = separated_nonempty_list(reserved(","), self.typename())(i)?;
+            let (i, _) = reserved(")")(i)?;
+            let (i, _) = reserved("->")(i)?;
             let (i, retty) = self.typename()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(")")(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("(")(i)?;
-            let (i, args) = separated_nonempty_list(
-                tuple((multispace0, tag(","), multispace0)),
-                self.expr(),
-            )(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = reserved(")")(i)?;
+            let (i, _) = reserved("(")(i)?;
+            let (i, args) = separated_nonempty_list(reserved(","), self.expr())(i)?;
+            let (i, _) = reserved(")")(i)?;
             Ok((
                 i,
                 Expr {
@@ -650,38 +1010,39 @@ impl Parser {
         }
     }
 
-    fn typename(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
-        move |i| self.typename0()(i)
+    fn typename(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
+        move |i| label("a type", self.typename0())(i)
     }
 
-    fn typename0(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
-        move |i| alt((complete(self.typename0_fun()), self.typename1()))(i)
+    fn typename0(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
+        move |i| alt((self.typename0_fun(), self.typename1()))(i)
     }
 
-    fn typename1(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    fn typename1(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
         move |i| alt((self.typename1_tuple(), self.typename2()))(i)
     }
 
-    fn typename2(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
-        move |i| alt((self.typename2_paren(), self.typename2_datatype()))(i)
+    fn typename2(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
+        move |i| {
+            label(
+                "a type",
+                alt((self.typename2_paren(), self.typename2_datatype())),
+            )(i)
+        }
     }
 
-    fn typename0_fun(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    fn typename0_fun(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
         move |i| {
             let (i, arg) = self.typename1()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag("->")(i)?;
-            let (i, _) = multispace0(i)?;
+            let (i, _) = reserved("->")(i)?;
             let (i, ret) = self.typename()(i)?;
             Ok((i, Type::Fun(Box::new(arg), Box::new(ret))))
         }
     }
 
-    fn typename1_tuple(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    fn typename1_tuple(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
         move |i| {
-            let sep = tuple((multispace0, tag("*"), multispace0));
-
-            let (i, tys) = many1(map(tuple((self.typename2(), sep)), |(ty, _)| ty))(i)?;
+            let (i, tys) = many1(terminated(self.typename2(), ident("*")))(i)?;
             let (i, ty) = self.typename2()(i)?;
 
             let mut tys = tys;
@@ -690,112 +1051,119 @@ impl Parser {
         }
     }
 
-    fn typename2_paren(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    fn typename2_paren(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
         move |i| {
-            let (i, _) = tag("(")(i)?;
-            let (i, _) = multispace0(i)?;
+            let start = i;
+            let (i, _) = reserved("(")(i)?;
             let (i, ty) = self.typename()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = close("(", start, ")")(i)?;
             Ok((i, ty))
         }
     }
 
-    fn typename2_datatype(&self) -> impl Fn(&str) -> IResult<&str, Type> + '_ {
+    fn typename2_datatype(&self) -> impl Fn(Tokens) -> IResult<Tokens, Type> + '_ {
         move |i| {
-            map(self.symbol(), |name| match name.0.as_str() {
+            map(self.longid(), |name| match name.0.as_str() {
                 "unit" => Type::Tuple(vec![]),
                 "real" => Type::Real,
+                "char" => Type::Char,
+                "string" => Type::String,
                 "int" => Type::Int,
+                "word" | "Word32.word" => Type::Word(WordSize::W32),
+                "Word8.word" => Type::Word(WordSize::W8),
+                "Word64.word" => Type::Word(WordSize::W64),
+                "IntInf.int" | "LargeInt.int" => Type::IntInf,
                 _ => Type::Datatype(name),
             })(i)
         }
     }
 
-    fn symbol_eq(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
-        move |i| alt((self.symbol_alphanumeric(), self.symbol_symbolic_eq()))(i)
+    // identifiers including `=`, which is reserved but can be bound by infix declarations
+    fn symbol_eq(&self) -> impl Fn(Tokens) -> IResult<Tokens, Symbol> + '_ {
+        move |i| label("an identifier", alt((self.symbol(), self.symbol_equal())))(i)
     }
 
-    fn symbol(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
-        move |i| alt((self.symbol_alphanumeric(), self.symbol_symbolic()))(i)
-    }
-
-    fn op_symbol_eq(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
-        move |i| alt((self.op_symbol_alphanumeric(), self.op_symbol_symbolic_eq()))(i)
-    }
-
-    fn op_symbol_alphanumeric(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
+    fn symbol_equal(&self) -> impl Fn(Tokens) -> IResult<Tokens, Symbol> + '_ {
         move |i| {
-            let (i, _) = opt(tuple((tag("op"), multispace1)))(i)?;
-            self.symbol_alphanumeric()(i)
+            let span = i[0].span;
+            value(Symbol::new("=").with_span(span), reserved("="))(i)
         }
     }
 
-    fn op_symbol_symbolic_eq(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
+    fn symbol(&self) -> impl Fn(Tokens) -> IResult<Tokens, Symbol> + '_ {
         move |i| {
-            let (i, _) = opt(tuple((tag("op"), multispace0)))(i)?;
-            alt((self.symbol_symbolic(), value(Symbol::new("="), tag("="))))(i)
+            let span = i[0].span;
+            token(Expected::Label("an identifier"), move |kind| match kind {
+                TokenKind::Ident(name) => Some(Symbol::new(*name).with_span(span)),
+                _ => None,
+            })(i)
         }
     }
 
-    fn symbol_alphanumeric(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
+    // qualified names like `Math.sqrt` are read as one symbol until structures are supported.
+    // they only refer to the names, so bindings take `symbol`
+    fn longid(&self) -> impl Fn(Tokens) -> IResult<Tokens, Symbol> + '_ {
         move |i| {
-            // FIXME: collect syntax is [a-zA-Z'_][a-zA-Z'_0-9]*
-            let (i, sym) = verify(alphanumeric1, |s: &str| !KEYWORDS.contains(&s))(i)?;
-            Ok((i, Symbol::new(sym.to_string())))
+            let span = i[0].span;
+            token(Expected::Label("an identifier"), move |kind| match kind {
+                TokenKind::Ident(name) | TokenKind::LongIdent(name) => {
+                    Some(Symbol::new(*name).with_span(span))
+                }
+                _ => None,
+            })(i)
         }
     }
 
-    fn symbol_symbolic_eq(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
-        move |i| alt((self.symbol_symbolic(), value(Symbol::new("="), tag("="))))(i)
-    }
-
-    fn symbol_symbolic(&self) -> impl Fn(&str) -> IResult<&str, Symbol> + '_ {
+    fn op_symbol_eq(&self) -> impl Fn(Tokens) -> IResult<Tokens, Symbol> + '_ {
         move |i| {
-            let symbolic1 = recognize(many1(nom::character::complete::one_of(
-                "!%&$#+-/:<=>?@\\~'^|*",
-            )));
-
-            let (i, sym) = verify(symbolic1, |s: &str| {
-                !KEYWORDS.contains(&s) && !RESERVED.contains(&s)
-            })(i)?;
-            Ok((i, Symbol::new(sym.to_string())))
+            let (i, _) = opt(reserved("op"))(i)?;
+            self.symbol_eq()(i)
         }
     }
 
-    fn pattern(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
-        move |i| alt((self.pattern_constructor(), self.pattern_atmic()))(i)
+    fn pattern(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
+        move |i| {
+            label(
+                "a pattern",
+                alt((self.pattern_constructor(), self.pattern_atmic())),
+            )(i)
+        }
     }
 
-    fn pattern_atmic(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_atmic(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
-            alt((
-                self.pattern_bool(),
-                self.pattern_char(),
-                self.pattern_int(),
-                self.pattern_tuple(),
-                self.pattern_var(),
-                self.pattern_wildcard(),
-                self.pattern_unit(),
-                self.pattern_paren(),
-            ))(i)
+            label(
+                "a pattern",
+                alt((
+                    self.pattern_bool(),
+                    self.pattern_char(),
+                    self.pattern_word(),
+                    self.pattern_real(),
+                    self.pattern_int(),
+                    self.pattern_tuple(),
+                    self.pattern_var(),
+                    self.pattern_wildcard(),
+                    self.pattern_unit(),
+                    self.pattern_paren(),
+                )),
+            )(i)
         }
     }
 
-    fn pattern_bool(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_bool(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
             alt((
-                map(tag("true"), |_| Pattern {
+                map(ident("true"), |span| Pattern {
                     ty: (),
                     inner: PatternKind::Constructor {
-                        name: Symbol::new("true"),
+                        name: Symbol::new("true").with_span(span),
                         arg: None,
                     },
                 }),
-                map(tag("false"), |_| Pattern {
+                map(ident("false"), |span| Pattern {
                     ty: (),
                     inner: PatternKind::Constructor {
-                        name: Symbol::new("false"),
+                        name: Symbol::new("false").with_span(span),
                         arg: None,
                     },
                 }),
@@ -803,42 +1171,49 @@ impl Parser {
         }
     }
 
-    fn pattern_int(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_int(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
-            map(digit1, |s: &str| Pattern {
+            map(self.int_literal(), |value| Pattern {
                 ty: (),
-                inner: PatternKind::Constant {
-                    value: s.parse().unwrap(),
-                },
+                inner: PatternKind::Constant { value },
             })(i)
         }
     }
 
-    fn pattern_char(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_real(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
-            let (i, _) = tag("#")(i)?;
-            let (i, s) = self.string_literal()(i)?;
-            assert_eq!(s.iter().count(), 1);
-            let c = s.into_iter().next().unwrap();
-            Ok((
-                i,
-                Pattern {
-                    ty: (),
-                    inner: PatternKind::Char { value: c },
-                },
-            ))
+            map(self.real_literal(), |value| Pattern {
+                ty: (),
+                inner: PatternKind::Real { value },
+            })(i)
         }
     }
 
-    fn pattern_tuple(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_word(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
-            let (i, _) = tag("(")(i)?;
-            let (i, _) = multispace0(i)?;
-            let sep = tuple((multispace0, tag(","), multispace0));
-            let (i, es) = many1(map(tuple((self.pattern(), sep)), |(e, _)| e))(i)?;
+            map(self.word_literal(), |value| Pattern {
+                ty: (),
+                inner: PatternKind::Word { value },
+            })(i)
+        }
+    }
+
+    fn pattern_char(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
+        move |i| {
+            map(self.char_literal(), |value| Pattern {
+                ty: (),
+                inner: PatternKind::Char { value },
+            })(i)
+        }
+    }
+
+    fn pattern_tuple(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
+        move |i| {
+            let start = i;
+            let (i, _) = reserved("(")(i)?;
+            let (i, es) = many1(terminated(self.pattern(), reserved(",")))(i)?;
             let (i, e) = self.pattern()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = close("(", start, ")")(i)?;
 
             let mut es = es;
             es.push(e);
@@ -852,14 +1227,14 @@ impl Parser {
         }
     }
 
-    fn pattern_unit(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_unit(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
             value(
                 Pattern {
                     ty: (),
                     inner: PatternKind::Tuple { tuple: vec![] },
                 },
-                tuple((tag("("), multispace0, tag(")"))),
+                tuple((reserved("("), reserved(")"))),
             )(i)
         }
     }
@@ -867,10 +1242,9 @@ impl Parser {
     // require constructor to have arg for now.
     // constructor withouth arg is parsed as variable and
     //  will be converted in later phases
-    fn pattern_constructor(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_constructor(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
             let (i, name) = self.symbol()(i)?;
-            let (i, _) = multispace0(i)?;
             let (i, arg) = self.pattern_atmic()(i)?;
             Ok((
                 i,
@@ -885,7 +1259,7 @@ impl Parser {
         }
     }
 
-    fn pattern_var(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_var(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
             map(self.symbol(), |name| Pattern {
                 ty: (),
@@ -894,31 +1268,58 @@ impl Parser {
         }
     }
 
-    fn pattern_wildcard(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_wildcard(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
             value(
                 Pattern {
                     ty: (),
                     inner: PatternKind::Wildcard {},
                 },
-                tag("_"),
+                reserved("_"),
             )(i)
         }
     }
 
-    fn pattern_paren(&self) -> impl Fn(&str) -> IResult<&str, Pattern<()>> + '_ {
+    fn pattern_paren(&self) -> impl Fn(Tokens) -> IResult<Tokens, Pattern<()>> + '_ {
         move |i| {
-            let (i, _) = tag("(")(i)?;
-            let (i, _) = multispace0(i)?;
+            let start = i;
+            let (i, _) = reserved("(")(i)?;
             let (i, e) = self.pattern()(i)?;
-            let (i, _) = multispace0(i)?;
-            let (i, _) = tag(")")(i)?;
+            let (i, _) = close("(", start, ")")(i)?;
 
             Ok((i, e))
         }
     }
 }
 
+// the first declaration keyword beginning a line after the failed declaration `failed`
+// and not before the point the error is found `at`
+fn resume_point<'a>(input: &str, failed: Tokens<'a>, at: Tokens<'a>) -> Tokens<'a> {
+    let begins_line = |token: &Token| {
+        let start = token.span.start;
+        start == 0 || input[..start].ends_with('\n')
+    };
+    (0..at.len())
+        .map(|n| &at[n..])
+        .find(|rest| match &rest[0].kind {
+            TokenKind::Reserved(kw) => {
+                rest.len() < failed.len() && DECL_KEYWORDS.contains(kw) && begins_line(&rest[0])
+            }
+            TokenKind::Eof => true,
+            _ => false,
+        })
+        .expect("internal error: the input does not end with Eof")
+}
+
+// a group of declarations is represented as `And` only when it has more than one declaration
+fn and_group(mut decls: Vec<Declaration<()>>) -> Declaration<()> {
+    if decls.len() == 1 {
+        decls.remove(0)
+    } else {
+        Declaration::And(decls)
+    }
+}
+
 fn map_window2<I>(
     iter: I,
     mut f: impl FnMut(I::Item, I::Item) -> (I::Item, Option<I::Item>),
@@ -997,58 +1398,63 @@ where
 
 #[test]
 fn test_expr_infix_and_app() {
-    let input = "true";
-    let ret = Parser::new().expr_infix_and_app()(input).unwrap();
+    let tokens = lex("true").unwrap();
+    let (_, ret) = Parser::new().expr_infix_and_app()(&tokens).unwrap();
     assert_eq!(
         ret,
-        (
-            "",
-            Expr {
-                ty: (),
-                inner: ExprKind::Constructor {
-                    arg: None,
-                    name: Symbol::new("true")
-                }
+        Expr {
+            ty: (),
+            inner: ExprKind::Constructor {
+                arg: None,
+                name: Symbol::new("true")
             }
-        )
+        }
     )
 }
 
 #[test]
 fn test_expr_infix_and_app2() {
-    let input = "f arg";
-    let ret = Parser::new().expr_infix_and_app()(input).unwrap();
+    let tokens = lex("f arg").unwrap();
+    let (_, ret) = Parser::new().expr_infix_and_app()(&tokens).unwrap();
     assert_eq!(
         ret,
-        (
-            "",
-            Expr {
-                ty: (),
-                inner: ExprKind::App {
-                    fun: Expr {
-                        ty: (),
-                        inner: ExprKind::Symbol {
-                            name: Symbol::new("f"),
-                        }
+        Expr {
+            ty: (),
+            inner: ExprKind::App {
+                fun: Expr {
+                    ty: (),
+                    inner: ExprKind::Symbol {
+                        name: Symbol::new("f"),
                     }
-                    .boxed(),
-                    arg: Expr {
-                        ty: (),
-                        inner: ExprKind::Symbol {
-                            name: Symbol::new("arg"),
-                        }
+                }
+                .boxed(),
+                arg: Expr {
+                    ty: (),
+                    inner: ExprKind::Symbol {
+                        name: Symbol::new("arg"),
                     }
-                    .boxed()
                 }
+                .boxed()
             }
-        )
+        }
     )
 }
 
-pub fn parse(
+pub fn parse(input: &str) -> ::std::result::Result<UntypedAst, Vec<ParseError<'_>>> {
+    parse_with_fixities(input).map(|(ast, _)| ast)
+}
+
+/// parses the program and gives the infix operators declared at its top level, by priority
+pub fn parse_with_fixities(
     input: &str,
-) -> ::std::result::Result<UntypedAst, nom::Err<(&str, nom::error::ErrorKind)>> {
+) -> ::std::result::Result<(UntypedAst, Fixities), Vec<ParseError<'_>>> {
+    let tokens = lex(input)?;
     let parser = Parser::new();
-    let (_, iresult) = all_consuming(parser.top())(input)?;
-    Ok(iresult)
+    let ast = parser.top(input, &tokens).map_err(|errors| {
+        errors
+            .into_iter()
+            .map(|e| ParseError::new(input, e))
+            .collect::<Vec<_>>()
+    })?;
+    Ok((ast, parser.get_table()))
 }
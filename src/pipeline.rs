@@ -0,0 +1,387 @@
+//! the passes of the compiler as a pipeline to run in part and to extend.
+//!
+//! `run` stops after any of `STAGES` and gives the IR there, and `resume` goes on from an IR.
+//! passes transforming an IR into the same IR, like optimizations or instrumentations,
+//! can be inserted after the stages giving the IR.
+use crate::ast::{self, TypeError, Warning};
+use crate::config::{Config, OptLevel};
+use crate::id::Id;
+use crate::parser::{parse_with_fixities, Fixities};
+use crate::pass::Pass;
+use crate::prim::Symbol;
+use crate::util::PP;
+use crate::{backend, hir, lir, mir};
+use log::info;
+use std::convert::Infallible;
+use std::io;
+
+/// the names of the stages in the order they run. `--print-ir` takes these
+pub const STAGES: &[&str] = &[
+    "parse",
+    "desugar",
+    "rename",
+    "var_to_constructor",
+    "typing",
+    "case_simplify",
+    "ast_to_hir",
+    "flattening_expression",
+    "flattening_let",
+    "unnest_functions",
+    "closure_conversion",
+    "hir_to_mir",
+    "unalias",
+    "block_arrange",
+    "mir_to_lir",
+    "backend",
+];
+
+/// the program in one of the IRs
+pub enum IR<'a> {
+    Source(&'a str),
+    Parsed(ast::UntypedAst),
+    Desugared(ast::UntypedCore),
+    Renamed((ast::SymbolTable, ast::UntypedCore)),
+    Typed((ast::SymbolTable, ast::TypedCore)),
+    Hir((hir::SymbolTable, hir::HIR)),
+    Mir((mir::SymbolTable, mir::MIR)),
+    Lir((lir::ExternTypes, lir::LIR)),
+    Wasm(wasm::Module),
+}
+
+impl<'a> IR<'a> {
+    pub fn name(&self) -> &'static str {
+        match self {
+            IR::Source(_) => "source",
+            IR::Parsed(_) => "parsed AST",
+            IR::Desugared(_) => "desugared AST",
+            IR::Renamed(_) => "renamed AST",
+            IR::Typed(_) => "typed AST",
+            IR::Hir(_) => "HIR",
+            IR::Mir(_) => "MIR",
+            IR::Lir(_) => "LIR",
+            IR::Wasm(_) => "wasm",
+        }
+    }
+}
+
+impl<'a> PP for IR<'a> {
+    fn pp<W: io::Write>(&self, w: &mut W, indent: usize) -> io::Result<()> {
+        match self {
+            IR::Source(input) => write!(w, "{}", input),
+            IR::Parsed(ast) => ast.pp(w, indent),
+            IR::Desugared(core) => core.pp(w, indent),
+            IR::Renamed(core) => core.pp(w, indent),
+            IR::Typed(core) => core.pp(w, indent),
+            IR::Hir(hir) => hir.pp(w, indent),
+            IR::Mir(mir) => mir.pp(w, indent),
+            IR::Lir(lir) => lir.pp(w, indent),
+            IR::Wasm(module) => module.pp(w, indent),
+        }
+    }
+}
+
+/// the IRs the passes can be inserted on
+pub trait Level: Sized {
+    /// the stages giving the IR
+    const STAGES: &'static [&'static str];
+    fn from_ir(ir: IR<'_>) -> Option<Self>;
+    fn into_ir<'a>(self) -> IR<'a>;
+}
+
+macro_rules! level {
+    ($ty: ty, $variant: ident, [$($stage: expr),*]) => {
+        impl Level for $ty {
+            const STAGES: &'static [&'static str] = &[$($stage),*];
+
+            fn from_ir(ir: IR<'_>) -> Option<Self> {
+                match ir {
+                    IR::$variant(ir) => Some(ir),
+                    _ => None,
+                }
+            }
+
+            fn into_ir<'a>(self) -> IR<'a> {
+                IR::$variant(self)
+            }
+        }
+    };
+}
+
+level!(
+    (ast::SymbolTable, ast::TypedCore),
+    Typed,
+    ["typing", "case_simplify"]
+);
+level!(
+    (hir::SymbolTable, hir::HIR),
+    Hir,
+    [
+        "ast_to_hir",
+        "flattening_expression",
+        "flattening_let",
+        "unnest_functions",
+        "closure_conversion"
+    ]
+);
+level!(
+    (mir::SymbolTable, mir::MIR),
+    Mir,
+    ["hir_to_mir", "unalias", "block_arrange"]
+);
+level!((lir::ExternTypes, lir::LIR), Lir, ["mir_to_lir"]);
+
+type Hook = Box<dyn for<'a> FnMut(IR<'a>, &Config) -> IR<'a>>;
+
+struct Inserted {
+    after: &'static str,
+    name: &'static str,
+    enabled: fn(&Config) -> bool,
+    pass: Hook,
+}
+
+pub struct Pipeline {
+    id: Id,
+    inserted: Vec<Inserted>,
+    fixities: Fixities,
+    exports: Vec<(String, Symbol)>,
+    warnings: Vec<Warning>,
+}
+
+fn trans<'a, P, In>(mut pass: P, input: In, config: &Config) -> Result<P::Target, TypeError<'a>>
+where
+    P: Pass<In, TypeError<'a>>,
+{
+    pass.trans(input, config)
+}
+
+fn always(_: &Config) -> bool {
+    true
+}
+
+fn o1(config: &Config) -> bool {
+    config.opt_level >= OptLevel::O1
+}
+
+fn o2(config: &Config) -> bool {
+    config.opt_level >= OptLevel::O2
+}
+
+impl Default for Pipeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pipeline {
+    /// the passes of the compiler with the optimizations `Config::opt_level` enables
+    pub fn new() -> Self {
+        let mut pipeline = Self::bare();
+        let id = pipeline.id();
+        pipeline
+            .insert_after_if(
+                "unnest_functions",
+                "inlining",
+                o2,
+                hir::Inline::new(id.clone()),
+            )
+            .insert_after("unalias", "known_calls", mir::KnownCall::new())
+            .insert_after_if("unalias", "constant_folding", o1, mir::ConstFold::new())
+            .insert_after_if("unalias", "copy_propagation", o1, mir::CopyProp::new())
+            .insert_after_if("unalias", "uncurrying", o2, mir::Uncurry::new(id))
+            .insert_after_if("unalias", "branch_folding", o2, mir::BranchFold::new())
+            .insert_after_if(
+                "unalias",
+                "unreachable_ebb",
+                o2,
+                mir::RemoveUnreachable::new(),
+            )
+            .insert_after_if("unalias", "dead_code", o1, mir::DeadCode::new());
+        pipeline
+    }
+
+    /// the passes of the compiler with no passes inserted
+    pub fn bare() -> Self {
+        Pipeline {
+            id: Id::new(),
+            inserted: Vec::new(),
+            fixities: Fixities::new(),
+            exports: Vec::new(),
+            warnings: Vec::new(),
+        }
+    }
+
+    /// the generator of the fresh names the passes share
+    pub fn id(&self) -> Id {
+        self.id.clone()
+    }
+
+    /// the infix operators the last program parsed declares at the top level
+    pub fn fixities(&self) -> &Fixities {
+        &self.fixities
+    }
+
+    /// the warnings found in the programs compiled so far
+    pub fn take_warnings(&mut self) -> Vec<Warning> {
+        std::mem::take(&mut self.warnings)
+    }
+
+    /// makes the backend export the functions by the names
+    pub fn export(&mut self, exports: Vec<(String, Symbol)>) -> &mut Self {
+        self.exports = exports;
+        self
+    }
+
+    /// inserts `pass`, printed as `name`, after the stage `after`.
+    /// the passes inserted after the same stage run in the order inserted
+    pub fn insert_after<T: Level + 'static>(
+        &mut self,
+        after: &'static str,
+        name: &'static str,
+        pass: impl Pass<T, Infallible, Target = T> + 'static,
+    ) -> &mut Self {
+        self.insert_after_if(after, name, always, pass)
+    }
+
+    /// same as `insert_after` but the pass runs only when `enabled` holds for the config,
+    /// to choose the passes by the optimization level for example
+    pub fn insert_after_if<T: Level + 'static>(
+        &mut self,
+        after: &'static str,
+        name: &'static str,
+        enabled: fn(&Config) -> bool,
+        mut pass: impl Pass<T, Infallible, Target = T> + 'static,
+    ) -> &mut Self {
+        assert!(
+            T::STAGES.contains(&after),
+            "the stage `{}` does not give the IR of the pass `{}`",
+            after,
+            name
+        );
+        let pass: Hook = Box::new(move |ir, config| {
+            let ir = T::from_ir(ir).expect("the stage gives the IR");
+            match pass.trans(ir, config) {
+                Ok(ir) => ir.into_ir(),
+                Err(e) => match e {},
+            }
+        });
+        self.inserted.push(Inserted {
+            after,
+            name,
+            enabled,
+            pass,
+        });
+        self
+    }
+
+    /// compiles the program until the stage `stop`, inclusive
+    pub fn run<'a>(
+        &mut self,
+        input: &'a str,
+        stop: &str,
+        config: &Config,
+    ) -> Result<IR<'a>, TypeError<'a>> {
+        self.stages(IR::Source(input), 0, stop, config)
+    }
+
+    /// compiles the program in the IR the stage `after` gives until the stage `stop`
+    pub fn resume<'a>(
+        &mut self,
+        ir: IR<'a>,
+        after: &str,
+        stop: &str,
+        config: &Config,
+    ) -> Result<IR<'a>, TypeError<'a>> {
+        self.stages(ir, position(after) + 1, stop, config)
+    }
+
+    /// `run` until the last stage giving the IR `T`
+    pub fn run_to<'a, T: Level>(
+        &mut self,
+        input: &'a str,
+        config: &Config,
+    ) -> Result<T, TypeError<'a>> {
+        let stop = T::STAGES[T::STAGES.len() - 1];
+        let ir = self.run(input, stop, config)?;
+        Ok(T::from_ir(ir).expect("the stage gives the IR"))
+    }
+
+    fn stages<'a>(
+        &mut self,
+        mut ir: IR<'a>,
+        start: usize,
+        stop: &str,
+        config: &Config,
+    ) -> Result<IR<'a>, TypeError<'a>> {
+        let stop = position(stop);
+        for &stage in &STAGES[start..=stop] {
+            ir = self.stage(stage, ir, config)?;
+            info!("pass: {}", stage);
+            print(stage, &ir, config);
+            for inserted in self.inserted.iter_mut().filter(|i| i.after == stage) {
+                if (inserted.enabled)(config) {
+                    ir = (inserted.pass)(ir, config);
+                    info!("pass: {}", inserted.name);
+                    print(inserted.name, &ir, config);
+                }
+            }
+        }
+        Ok(ir)
+    }
+
+    fn stage<'a>(
+        &mut self,
+        stage: &str,
+        ir: IR<'a>,
+        config: &Config,
+    ) -> Result<IR<'a>, TypeError<'a>> {
+        use self::IR::*;
+        let id = self.id.clone();
+        let ir = match (stage, ir) {
+            ("parse", Source(input)) => {
+                let (ast, fixities) = parse_with_fixities(input)?;
+                self.fixities = fixities;
+                Parsed(ast)
+            }
+            ("desugar", Parsed(ast)) => Desugared(trans(ast::Desugar::new(id), ast, config)?),
+            ("rename", Desugared(core)) => Renamed(trans(ast::Rename::new(id), core, config)?),
+            ("var_to_constructor", Renamed(core)) => {
+                Renamed(trans(ast::VarToConstructor::new(id), core, config)?)
+            }
+            ("typing", Renamed(core)) => Typed(trans(ast::Typer::new(), core, config)?),
+            ("case_simplify", Typed(core)) => {
+                let mut case_simplify = ast::CaseSimplify::new(id);
+                let core = Pass::<_, TypeError>::trans(&mut case_simplify, core, config)?;
+                self.warnings.extend(case_simplify.take_warnings());
+                Typed(core)
+            }
+            ("ast_to_hir", Typed(core)) => Hir(trans(hir::AST2HIR::new(id), core, config)?),
+            ("flattening_expression", Hir(hir)) => Hir(trans(hir::FlatExpr::new(id), hir, config)?),
+            ("flattening_let", Hir(hir)) => Hir(trans(hir::FlatLet::new(), hir, config)?),
+            ("unnest_functions", Hir(hir)) => Hir(trans(hir::UnnestFunc::new(id), hir, config)?),
+            ("closure_conversion", Hir(hir)) => Hir(trans(hir::ForceClosure::new(), hir, config)?),
+            ("hir_to_mir", Hir(hir)) => Mir(trans(mir::HIR2MIR::new(id), hir, config)?),
+            ("unalias", Mir(mir)) => Mir(trans(mir::UnAlias::new(), mir, config)?),
+            ("block_arrange", Mir(mir)) => Mir(trans(mir::BlockArrange::new(), mir, config)?),
+            ("mir_to_lir", Mir(mir)) => Lir(trans(lir::MIR2LIR::new(), mir, config)?),
+            ("backend", Lir(lir)) => {
+                let backend = backend::LIR2WASM::new().with_exports(self.exports.clone());
+                Wasm(trans(backend, lir, config)?)
+            }
+            (stage, ir) => panic!("the stage `{}` does not take {}", stage, ir.name()),
+        };
+        Ok(ir)
+    }
+}
+
+fn position(stage: &str) -> usize {
+    STAGES
+        .iter()
+        .position(|s| *s == stage)
+        .unwrap_or_else(|| panic!("no stage `{}`", stage))
+}
+
+fn print(name: &str, ir: &IR<'_>, config: &Config) {
+    if config.pretty_print_ir.contains(name) {
+        ir.pp(&mut io::stdout(), 0).unwrap();
+    }
+}
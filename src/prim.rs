@@ -1,12 +1,47 @@
 use crate::util::PP;
+use std::hash::{Hash, Hasher};
 use std::io;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-pub struct Symbol(pub String, pub u64);
+/// a range of the source in bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn contains(&self, offset: usize) -> bool {
+        self.start <= offset && offset < self.end
+    }
+}
+
+/// a name and its id. the span, where the name is written in the source,
+/// is not a part of the identity and is ignored by the comparison
+#[derive(Debug, Clone)]
+pub struct Symbol(pub String, pub u64, pub Option<Span>);
 
 impl Symbol {
     pub fn new<S: Into<String>>(s: S) -> Self {
-        Symbol(s.into(), 0)
+        Symbol(s.into(), 0, None)
+    }
+
+    pub fn with_span(self, span: Span) -> Self {
+        Symbol(self.0, self.1, Some(span))
+    }
+}
+
+impl PartialEq for Symbol {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0 && self.1 == other.1
+    }
+}
+
+impl Eq for Symbol {}
+
+impl Hash for Symbol {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.hash(state);
+        self.1.hash(state);
     }
 }
 
@@ -17,9 +52,44 @@ impl PP for Symbol {
     }
 }
 
+/// bit width of the unsigned integer types `Word8.word`, `Word32.word` and `Word64.word`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WordSize {
+    W8,
+    W32,
+    W64,
+}
+
+impl WordSize {
+    pub fn bits(&self) -> u32 {
+        use self::WordSize::*;
+        match self {
+            W8 => 8,
+            W32 => 32,
+            W64 => 64,
+        }
+    }
+}
+
+impl PP for WordSize {
+    fn pp<W: io::Write>(&self, w: &mut W, _indent: usize) -> io::Result<()> {
+        use self::WordSize::*;
+        match self {
+            W8 => write!(w, "Word8.word"),
+            W32 => write!(w, "word"),
+            W64 => write!(w, "Word64.word"),
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum Literal {
     Int(i64),
+    /// an integer constant too large for i64, in decimal with an optional `-`
+    LargeInt(String),
+    Word(u64),
+    /// a word constant beyond 64 bits as written. rejected by typing
+    LargeWord(String),
     Real(f64),
     Char(u32),
 }
@@ -31,9 +101,18 @@ impl PP for Literal {
             Int(v) => {
                 write!(w, "{}", v)?;
             }
-            Real(v) => {
+            LargeInt(v) => {
+                write!(w, "{}", v)?;
+            }
+            Word(v) => {
+                write!(w, "0w{}", v)?;
+            }
+            LargeWord(v) => {
                 write!(w, "{}", v)?;
             }
+            Real(v) => {
+                write!(w, "{:?}", v)?;
+            }
             Char(c) => {
                 write!(w, r##"#"{}""##, c)?;
             }
@@ -42,6 +121,16 @@ impl PP for Literal {
     }
 }
 
+/// exceptions raised by the primitive operations and the compiled pattern matches.
+/// the discriminant is passed to the host to tell which one is raised
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuiltinExn {
+    Overflow = 0,
+    Div = 1,
+    Match = 2,
+    Bind = 3,
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum BIF {
     Add,
@@ -56,6 +145,36 @@ pub enum BIF {
     Ge,
     Lt,
     Le,
+    Andb,
+    Orb,
+    Xorb,
+    /// `<<`. the shift amount is a `word`, and shifting by the width or more gives 0
+    Lshift,
+    /// `>>`, logical right shift
+    Rshift,
+    /// `~>>`, arithmetic right shift. shifting by the width or more gives the sign fill
+    Arshift,
+    /// `~`
+    Neg,
+    Abs,
+    /// `real`, int to real
+    Real,
+    /// real to int, rounding toward negative infinity
+    Floor,
+    /// real to int, rounding toward positive infinity
+    Ceil,
+    /// real to int, rounding to the nearest, ties to even
+    Round,
+    /// real to int, rounding toward zero
+    Trunc,
+    /// `Math.sqrt`
+    Sqrt,
+    /// `IntInf.fromInt`
+    IntInfFromInt,
+    /// `IntInf.toInt`
+    IntInfToInt,
+    /// raise the exception. only generated by the compiler
+    Raise(BuiltinExn),
 }
 
 impl PP for BIF {
@@ -98,6 +217,57 @@ impl PP for BIF {
             Le => {
                 write!(w, "le")?;
             }
+            Andb => {
+                write!(w, "andb")?;
+            }
+            Orb => {
+                write!(w, "orb")?;
+            }
+            Xorb => {
+                write!(w, "xorb")?;
+            }
+            Lshift => {
+                write!(w, "lshift")?;
+            }
+            Rshift => {
+                write!(w, "rshift")?;
+            }
+            Arshift => {
+                write!(w, "arshift")?;
+            }
+            Neg => {
+                write!(w, "neg")?;
+            }
+            Abs => {
+                write!(w, "abs")?;
+            }
+            Real => {
+                write!(w, "real")?;
+            }
+            Floor => {
+                write!(w, "floor")?;
+            }
+            Ceil => {
+                write!(w, "ceil")?;
+            }
+            Round => {
+                write!(w, "round")?;
+            }
+            Trunc => {
+                write!(w, "trunc")?;
+            }
+            Sqrt => {
+                write!(w, "sqrt")?;
+            }
+            IntInfFromInt => {
+                write!(w, "intinf_from_int")?;
+            }
+            IntInfToInt => {
+                write!(w, "intinf_to_int")?;
+            }
+            Raise(exn) => {
+                write!(w, "raise_{:?}", exn)?;
+            }
         }
         Ok(())
     }
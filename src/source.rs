@@ -0,0 +1,87 @@
+//! the files a program is made of. they are compiled as one input, the files put in order
+use crate::parser::Position;
+use crate::prim::Span;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SourceFile {
+    pub name: String,
+    /// the range of the file in the input
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Sources {
+    input: String,
+    files: Vec<SourceFile>,
+}
+
+impl Sources {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_file(&mut self, name: impl Into<String>, text: &str) {
+        let start = self.input.len();
+        self.input.push_str(text);
+        self.files.push(SourceFile {
+            name: name.into(),
+            span: Span {
+                start,
+                end: self.input.len(),
+            },
+        });
+        // a token does not continue to the next file
+        self.input.push('\n');
+    }
+
+    /// adds the text of no file, like the keywords basis files are put together with
+    pub fn add_text(&mut self, text: &str) {
+        self.input.push_str(text);
+        self.input.push('\n');
+    }
+
+    pub fn input(&self) -> &str {
+        &self.input
+    }
+
+    pub fn files(&self) -> &[SourceFile] {
+        &self.files
+    }
+
+    pub fn text(&self, file: &SourceFile) -> &str {
+        &self.input[file.span.start..file.span.end]
+    }
+
+    /// the file the byte `offset` of the input is in, and the byte in the file
+    pub fn locate(&self, offset: usize) -> Option<(&SourceFile, usize)> {
+        self.files
+            .iter()
+            .find(|file| file.span.start <= offset && offset <= file.span.end)
+            .map(|file| (file, offset - file.span.start))
+    }
+
+    /// the file and the position in it of the byte `offset` of the input
+    pub fn position(&self, offset: usize) -> Option<(&SourceFile, Position)> {
+        self.locate(offset)
+            .map(|(file, offset)| (file, Position::at(self.text(file), offset)))
+    }
+}
+
+#[test]
+fn test_locate() {
+    let mut sources = Sources::new();
+    sources.add_file("a.sml", "val a = 1");
+    sources.add_text("local");
+    sources.add_file("b.sml", "val b = 2\nval c = a");
+    assert_eq!(sources.input(), "val a = 1\nlocal\nval b = 2\nval c = a\n");
+    assert_eq!(
+        sources.locate(4).map(|(f, o)| (f.name.as_str(), o)),
+        Some(("a.sml", 4))
+    );
+    assert_eq!(sources.locate(12), None);
+    let (file, position) = sources.position(34).unwrap();
+    assert_eq!(
+        (file.name.as_str(), position.line, position.column),
+        ("b.sml", 2, 9)
+    );
+}
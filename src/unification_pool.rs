@@ -4,6 +4,8 @@ pub struct NodeId(usize);
 #[derive(Debug, Clone)]
 enum Node<T> {
     Value(T),
+    // the value is being unified. kept readable for error reports
+    Pending(T),
     Refer(NodeId),
 }
 
@@ -12,15 +14,26 @@ impl<T> Node<T> {
         Node::Value(t)
     }
 
-    fn take(&mut self) -> Option<T> {
+    fn settle(&mut self) {
         let node = std::mem::replace(self, Node::Refer(NodeId(std::usize::MAX)));
-        match node {
-            Node::Value(t) => Some(t),
-            Node::Refer(_) => None,
+        *self = match node {
+            Node::Pending(t) => Node::Value(t),
+            node => node,
         }
     }
 }
 
+impl<T: Clone> Node<T> {
+    fn pend(&mut self) -> Option<T> {
+        let t = match self {
+            Node::Value(t) | Node::Pending(t) => t.clone(),
+            Node::Refer(_) => return None,
+        };
+        *self = Node::Pending(t.clone());
+        Some(t)
+    }
+}
+
 #[derive(Debug)]
 pub struct UnificationPool<T> {
     pool: Vec<Node<T>>,
@@ -51,7 +64,18 @@ impl<T> UnificationPool<T> {
     fn value_id(&self, mut id: NodeId) -> NodeId {
         loop {
             match self.at(id) {
-                Node::Value(_) => return id,
+                Node::Value(_) | Node::Pending(_) => return id,
+                Node::Refer(new_id) => id = *new_id,
+            }
+        }
+    }
+
+    /// whether the node is being unified, that is, it encloses the nodes being unified now
+    pub fn is_pending(&self, mut id: NodeId) -> bool {
+        loop {
+            match self.at(id) {
+                Node::Value(_) => return false,
+                Node::Pending(_) => return true,
                 Node::Refer(new_id) => id = *new_id,
             }
         }
@@ -60,7 +84,7 @@ impl<T> UnificationPool<T> {
     pub fn value_of(&self, mut id: NodeId) -> &T {
         loop {
             match self.at(id) {
-                Node::Value(t) => return t,
+                Node::Value(t) | Node::Pending(t) => return t,
                 Node::Refer(new_id) => id = *new_id,
             }
         }
@@ -70,7 +94,7 @@ impl<T> UnificationPool<T> {
         let value_id = self.value_id(start);
         loop {
             match self.at_mut(start) {
-                Node::Value(_) => {
+                Node::Value(_) | Node::Pending(_) => {
                     return;
                 }
                 Node::Refer(ref mut id) => {
@@ -80,7 +104,9 @@ impl<T> UnificationPool<T> {
             }
         }
     }
+}
 
+impl<T: Clone> UnificationPool<T> {
     pub fn try_unify_with<E>(
         &mut self,
         id1: NodeId,
@@ -92,9 +118,16 @@ impl<T> UnificationPool<T> {
         if lid == rid {
             return Ok(lid);
         }
-        let l = self.at_mut(lid).take().unwrap();
-        let r = self.at_mut(rid).take().unwrap();
-        let new = try_unify(self, l, r)?;
+        let l = self.at_mut(lid).pend().unwrap();
+        let r = self.at_mut(rid).pend().unwrap();
+        let new = match try_unify(self, l, r) {
+            Ok(new) => new,
+            Err(e) => {
+                self.at_mut(lid).settle();
+                self.at_mut(rid).settle();
+                return Err(e);
+            }
+        };
         *self.at_mut(lid) = Node::Value(new);
         *self.at_mut(rid) = Node::Refer(lid);
 
@@ -0,0 +1,301 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// a directory of its own for each test, as the tests run in parallel
+fn workdir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("webml-cli-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// webml in `dir`, finding the runtime by itself
+fn command(dir: &PathBuf) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_webml"));
+    command.current_dir(dir).env_remove("WEBML_RUNTIME");
+    command
+}
+
+fn webml(dir: &PathBuf, args: &[&str]) -> Output {
+    command(dir)
+        .args(args)
+        .output()
+        .expect("failed to run webml")
+}
+
+/// whether the programs can be run: node is available and the runtime is built in the tree
+fn can_run() -> bool {
+    let runtime = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("webml-rt/target/wasm32-unknown-unknown/release/webml_rt.wasm");
+    let node = Command::new("node")
+        .arg("--version")
+        .output()
+        .map(|output| output.status.success())
+        .unwrap_or(false);
+    node && runtime.is_file()
+}
+
+#[test]
+fn cli_check_exit_codes() {
+    let dir = workdir("check");
+    fs::write(dir.join("ok.sml"), "val x = 1 + 2\n").unwrap();
+    fs::write(dir.join("bad.sml"), "val x = 1 + true\n").unwrap();
+
+    assert_eq!(webml(&dir, &["check", "ok.sml"]).status.code(), Some(0));
+    let bad = webml(&dir, &["check", "bad.sml"]);
+    assert_eq!(bad.status.code(), Some(1));
+    let stderr = String::from_utf8(bad.stderr).unwrap();
+    assert!(stderr.starts_with("error[type-mismatch]: type mismatch\n --> bad.sml:1:13\n"));
+    assert_eq!(
+        webml(&dir, &["check", "missing.sml"]).status.code(),
+        Some(2)
+    );
+    fs::write(dir.join("version.sml"), "val _ = print version\n").unwrap();
+    fs::write(
+        dir.join("prelude.sml"),
+        "val version = 1\nfun print x = x\n",
+    )
+    .unwrap();
+    assert_eq!(
+        webml(&dir, &["check", "version.sml"]).status.code(),
+        Some(0)
+    );
+    let no_prelude = webml(&dir, &["check", "--no-prelude", "version.sml"]);
+    assert_eq!(no_prelude.status.code(), Some(1));
+    let prelude = webml(&dir, &["check", "--prelude", "prelude.sml", "version.sml"]);
+    assert_eq!(prelude.status.code(), Some(0));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_build_outputs() {
+    let dir = workdir("build");
+    fs::write(dir.join("prog.sml"), "fun f x = x + 1\nval y = f 2\n").unwrap();
+
+    assert!(webml(&dir, &["build", "prog.sml"]).status.success());
+    assert!(dir.join("prog.wasm").is_file());
+
+    let emit = webml(
+        &dir,
+        &["build", "prog.sml", "-o", "out/a.wasm", "--emit", "ir,js"],
+    );
+    assert_eq!(
+        emit.status.code(),
+        Some(2),
+        "the directory out does not exist"
+    );
+    fs::create_dir(dir.join("out")).unwrap();
+    let emit = webml(
+        &dir,
+        &["build", "prog.sml", "-o", "out/a.wasm", "--emit", "ir,js"],
+    );
+    assert!(emit.status.success());
+    assert!(fs::read_to_string(dir.join("out/a.ir"))
+        .unwrap()
+        .contains("fun f"));
+    let js = fs::read_to_string(dir.join("out/a.js")).unwrap();
+    assert!(js.contains(r#"await load("a.wasm")"#));
+    assert!(!dir.join("out/a.wasm").exists());
+
+    let single = webml(
+        &dir,
+        &["build", "prog.sml", "-o", "prog.lir", "--emit", "ir"],
+    );
+    assert!(single.status.success());
+    assert!(dir.join("prog.lir").is_file());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_run() {
+    let dir = workdir("run");
+    fs::write(dir.join("prog.sml"), "val () = print (~7 div 2)\n").unwrap();
+
+    let missing = command(&dir)
+        .env("WEBML_RUNTIME", "missing.wasm")
+        .args(["run", "prog.sml"])
+        .output()
+        .unwrap();
+    assert_eq!(missing.status.code(), Some(2));
+    assert!(String::from_utf8(missing.stderr)
+        .unwrap()
+        .contains("not found at missing.wasm"));
+    // --runtime overrides WEBML_RUNTIME
+    let other = command(&dir)
+        .env("WEBML_RUNTIME", "missing.wasm")
+        .args(["run", "--runtime", "other.wasm", "prog.sml"])
+        .output()
+        .unwrap();
+    assert!(String::from_utf8(other.stderr)
+        .unwrap()
+        .contains("not found at other.wasm"));
+
+    if can_run() {
+        let run = webml(&dir, &["run", "prog.sml"]);
+        assert!(run.status.success());
+        assert_eq!(String::from_utf8(run.stdout).unwrap(), "-4\n");
+    }
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_run_overflow() {
+    if !can_run() {
+        return;
+    }
+    let dir = workdir("overflow");
+    fs::write(
+        dir.join("fact.sml"),
+        "fun fact n = if n = 0 then 1 else n * fact (n - 1)\nval () = print (fact 12)\nval () = print (fact 13)\n",
+    )
+    .unwrap();
+
+    let run = webml(&dir, &["run", "fact.sml"]);
+    assert_eq!(run.status.code(), Some(1));
+    assert_eq!(String::from_utf8(run.stdout).unwrap(), "479001600\n");
+    let stderr = String::from_utf8(run.stderr).unwrap();
+    assert!(stderr.contains("uncaught exception Overflow"));
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_mlb_project() {
+    let dir = workdir("mlb");
+    fs::create_dir(dir.join("lib")).unwrap();
+    fs::write(dir.join("lib/helper.sml"), "fun double x = x + x\n").unwrap();
+    fs::write(
+        dir.join("lib/api.sml"),
+        "fun quadruple x = double (double x)\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("lib/lib.mlb"),
+        "(* the helper is not exported *)\nlocal\n  helper.sml\nin\n  api.sml\nend\n",
+    )
+    .unwrap();
+    fs::write(dir.join("main.sml"), "val _ = print (quadruple 3)\n").unwrap();
+    fs::write(dir.join("bad.sml"), "val _ = print (double 3)\n").unwrap();
+    fs::write(
+        dir.join("project.mlb"),
+        "$(SML_LIB)/basis/basis.mlb\nbasis Lib = bas lib/lib.mlb end\nopen Lib\nmain.sml\n",
+    )
+    .unwrap();
+    fs::write(dir.join("bad.mlb"), "lib/lib.mlb\nbad.sml\n").unwrap();
+    fs::write(dir.join("cycle.mlb"), "main.sml cycle.mlb\n").unwrap();
+
+    assert_eq!(
+        webml(&dir, &["check", "project.mlb"]).status.code(),
+        Some(0)
+    );
+    let files = ["check", "lib/helper.sml", "lib/api.sml", "bad.sml"];
+    assert_eq!(webml(&dir, &files).status.code(), Some(0));
+
+    let bad = webml(&dir, &["check", "bad.mlb"]);
+    assert_eq!(bad.status.code(), Some(1));
+    let stderr = String::from_utf8(bad.stderr).unwrap();
+    assert!(
+        stderr.starts_with("error[unbound-name]: unbound variable `double`\n --> bad.sml:1:16\n")
+    );
+
+    let cycle = webml(&dir, &["check", "cycle.mlb"]);
+    assert_eq!(cycle.status.code(), Some(1));
+    let stderr = String::from_utf8(cycle.stderr).unwrap();
+    assert_eq!(stderr, "error: cycle.mlb:1:10: cycle.mlb includes itself\n");
+
+    assert!(webml(&dir, &["build", "project.mlb"]).status.success());
+    assert!(dir.join("project.wasm").is_file());
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn cli_units() {
+    use webml::interface::Interface;
+
+    let dir = workdir("units");
+    fs::write(dir.join("prelude.sml"), webml::PRELUDE).unwrap();
+    fs::write(
+        dir.join("shapes.sml"),
+        "datatype shape = Circle of real | Rect of real * real\n\
+         fun area (Circle r) = 3.0 * r * r\n  | area (Rect (w, h)) = w * h\n\
+         fun add x y = x + y\n\
+         val unit = Circle 1.0\n\
+         infix 6 ++\n\
+         fun op ++ (a, b) = a + b + 1\n",
+    )
+    .unwrap();
+    fs::write(
+        dir.join("main.sml"),
+        "val _ = print (1 ++ version)\nval a = area (Rect (2.0, 3.0))\nval b = area unit\n\
+         val _ = print (add 1 2)\n",
+    )
+    .unwrap();
+    fs::write(dir.join("pair.sml"), "val pair = (fn x => x + 1, 2)\n").unwrap();
+
+    let prelude = webml(
+        &dir,
+        &["build", "--no-prelude", "--emit", "wasm,wmi", "prelude.sml"],
+    );
+    assert!(prelude.status.success());
+    assert!(dir.join("prelude.wasm").is_file());
+    let shapes = webml(
+        &dir,
+        &[
+            "build",
+            "--no-prelude",
+            "--import",
+            "prelude.wmi",
+            "--emit",
+            "wasm,wmi",
+            "shapes.sml",
+        ],
+    );
+    assert!(shapes.status.success());
+    // a function value cannot be passed to the other units
+    let pair = webml(&dir, &["build", "--emit", "wasm,wmi", "pair.sml"]);
+    assert_eq!(pair.status.code(), Some(1));
+    let stderr = String::from_utf8(pair.stderr).unwrap();
+    assert!(stderr.starts_with(
+        "error[not-exported]: `pair: (int -> int) * int` cannot be exported: functions cannot be passed between units\n"
+    ));
+
+    let interface = fs::read_to_string(dir.join("shapes.wmi")).unwrap();
+    let interface = Interface::parse(&interface).unwrap();
+    assert_eq!(interface.unit, "shapes");
+    assert_eq!(interface.imports.len(), 1);
+    assert_eq!(interface.imports[0].unit, "prelude");
+    assert_eq!(interface.fixities[&6], vec!["++".to_string()]);
+    assert_eq!(interface.datatypes.len(), 1);
+    let values = interface
+        .values
+        .iter()
+        .map(|v| (v.name.as_str(), v.function))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        values,
+        vec![("area", true), ("add", true), ("unit", false), ("++", true)]
+    );
+
+    let imports = ["--import", "prelude.wmi", "--import", "shapes.wmi"];
+    let check = |args: &[&str]| {
+        let mut all = vec!["check", "--no-prelude"];
+        all.extend(args);
+        webml(&dir, &all)
+    };
+    assert!(check(&[&imports[..], &["main.sml"]].concat())
+        .status
+        .success());
+    // the imported names are not seen without the import
+    assert_eq!(
+        check(&["--import", "prelude.wmi", "main.sml"])
+            .status
+            .code(),
+        Some(1)
+    );
+    let width = check(&[&["--int-width", "64"], &imports[..], &["main.sml"]].concat());
+    assert_eq!(width.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(width.stderr).unwrap(),
+        "error: unit `prelude` is compiled with the other width of int\n"
+    );
+    fs::remove_dir_all(&dir).unwrap();
+}
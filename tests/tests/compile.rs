@@ -1,8 +1,10 @@
 use std::fs;
 use std::io::{self, prelude::*};
 use std::path::{Path, PathBuf};
-use webml::TypeError;
-use webml::{compile_str, Config};
+use webml::lir::{Op, LIR, RAISE_FUNCTION};
+use webml::prim::BuiltinExn;
+use webml::{compile_str, compile_to_lir, Config, OptLevel};
+use webml::{TypeError, Warning};
 
 fn read_and_append_to_string(path: impl AsRef<Path>, buf: &mut String) -> io::Result<usize> {
     let file = fs::File::open(path)?;
@@ -12,30 +14,60 @@ fn read_and_append_to_string(path: impl AsRef<Path>, buf: &mut String) -> io::Re
 
 fn with_compile_result(
     path: impl AsRef<Path>,
-    callback: impl for<'a> FnOnce(Result<Vec<u8>, TypeError<'a>>),
+    config: &Config,
+    callback: impl for<'a> FnOnce(Result<(Vec<u8>, Vec<Warning>), TypeError<'a>>),
 ) {
     let path = path.as_ref();
-    let mut input = include_str!("../../ml_src/prelude.sml").to_string();
-    let config = Config::default();
+    let mut input = webml::PRELUDE.to_string();
     read_and_append_to_string(&path, &mut input).expect("failed to load file");
-    let result = compile_str(&input, &config);
+    let result = compile_str(&input, config);
     println!("{}", path.to_str().unwrap());
     callback(result)
 }
 
 fn assert_compile_pass(path: impl AsRef<Path>) {
+    assert_compile_pass_with(path, &Config::default())
+}
+
+fn assert_compile_pass_with(path: impl AsRef<Path>, config: &Config) {
     let path = path.as_ref();
-    with_compile_result(path, |res| match res {
+    with_compile_result(path, config, |res| match res {
         Ok(_) => (),
         Err(e) => panic!("failed to compile {}: {}", path.display(), e),
     })
 }
 
+// the fixtures to fail start with `(* error: <code> *)` naming the error they should fail with
+fn error_header(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("(* error:")
+        .and_then(|rest| rest.strip_suffix("*)"))
+        .map(str::trim)
+}
+
+fn expected_error(path: &Path) -> String {
+    let mut input = String::new();
+    read_and_append_to_string(path, &mut input).expect("failed to load file");
+    input
+        .lines()
+        .next()
+        .and_then(error_header)
+        .map(str::to_string)
+        .unwrap_or_else(|| panic!("{} does not declare the expected error", path.display()))
+}
+
 fn assert_compile_fail(path: impl AsRef<Path>) {
     let path = path.as_ref();
-    with_compile_result(path, |res| match res {
+    let expected = expected_error(path);
+    with_compile_result(path, &Config::default(), |res| match res {
         Ok(_) => panic!("succeded to compile {}, which should fail", path.display(),),
-        Err(_) => (),
+        Err(e) => assert_eq!(
+            e.code(),
+            expected,
+            "{} failed with an unexpected error: {}",
+            path.display(),
+            e
+        ),
     })
 }
 
@@ -56,12 +88,97 @@ fn examples_compile_pass() {
     walk_dir("ml_example", assert_compile_pass)
 }
 
+#[test]
+fn examples_compile_pass_optimized() {
+    let config = Config {
+        opt_level: OptLevel::O2,
+        ..Config::default()
+    };
+    walk_dir("ml_example", |path| assert_compile_pass_with(path, &config))
+}
+
 #[test]
 fn test_compile_pass() {
-    walk_dir("tests/compile_pass", assert_compile_pass)
+    walk_dir("tests/tests/compile_pass", assert_compile_pass)
+}
+
+#[test]
+fn test_compile_pass_optimized() {
+    for &opt_level in &[OptLevel::O1, OptLevel::O2] {
+        let config = Config {
+            opt_level,
+            ..Config::default()
+        };
+        walk_dir("tests/tests/compile_pass", |path| {
+            assert_compile_pass_with(path, &config)
+        })
+    }
 }
 
 #[test]
 fn test_compile_fail() {
-    walk_dir("tests/compile_fail", assert_compile_fail)
+    walk_dir("tests/tests/compile_fail", assert_compile_fail)
+}
+
+fn raises(lir: &LIR, exn: BuiltinExn) -> bool {
+    lir.0
+        .iter()
+        .flat_map(|f| &f.body)
+        .flat_map(|b| &b.body)
+        .any(|op| matches!(op, Op::RaiseIfI32(_, e) if *e == exn))
+}
+
+#[test]
+fn test_arith_raises() {
+    let mut input = include_str!("../../ml_src/prelude.sml").to_string();
+    input.push_str("fun f x = x * 3 fun g x = 10 div x");
+    let input = input.as_str();
+    let raise = (RAISE_FUNCTION.0.to_string(), RAISE_FUNCTION.1.to_string());
+
+    let ((externs, lir), _) = compile_to_lir(input, &Config::default()).expect("failed to compile");
+    assert!(externs.contains_key(&raise));
+    assert!(raises(&lir, BuiltinExn::Overflow));
+    assert!(raises(&lir, BuiltinExn::Div));
+
+    let config = Config {
+        unchecked_arith: true,
+        ..Config::default()
+    };
+    let ((externs, lir), _) = compile_to_lir(input, &config).expect("failed to compile");
+    assert!(!externs.contains_key(&raise));
+    assert!(!raises(&lir, BuiltinExn::Overflow));
+    assert!(!raises(&lir, BuiltinExn::Div));
+}
+
+#[test]
+fn test_match_warnings() {
+    let mut input = include_str!("../../ml_src/prelude.sml").to_string();
+    input.push_str(include_str!("../../ml_example/match_warnings.sml"));
+    let (_, warnings) = compile_str(&input, &Config::default()).expect("failed to compile");
+    let warnings = warnings
+        .iter()
+        .map(|w| (w.code(), w.to_string()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        warnings,
+        vec![
+            (
+                "non-exhaustive-match",
+                "match nonexhaustive, missing: Node (Node (_, _), Node _)".to_string()
+            ),
+            (
+                "redundant-clause",
+                "redundant clause 2 of the match".to_string()
+            ),
+            (
+                "non-exhaustive-binding",
+                "binding not exhaustive, missing: Leaf".to_string()
+            ),
+        ]
+    );
+
+    let mut input = include_str!("../../ml_src/prelude.sml").to_string();
+    input.push_str("fun isZero n = case n of 0 => true | _ => false");
+    let (_, warnings) = compile_str(&input, &Config::default()).expect("failed to compile");
+    assert_eq!(warnings, vec![]);
 }
@@ -0,0 +1,84 @@
+use webml::{compile_str, Config, Diagnostic, Severity, Sources, PRELUDE};
+
+fn with_prelude(src: &str) -> Sources {
+    let mut sources = Sources::new();
+    sources.add_file("<prelude>", PRELUDE);
+    sources.add_file("a.sml", src);
+    sources
+}
+
+#[test]
+fn json_parse_errors() {
+    let sources = with_prelude("val x = let val y = 1 in y\nval z = 2\n");
+    let error = compile_str(sources.input(), &Config::default()).unwrap_err();
+    let diagnostics = Diagnostic::from_error(sources.input(), &error);
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(
+        diagnostics[0].to_json(&sources).to_string(),
+        concat!(
+            r#"{"severity":"error","code":"syntax-error","#,
+            r#""message":"expected `end` to close `let`, found `val`","#,
+            r#""span":{"file":"a.sml","byte_start":27,"byte_end":30,"line_start":2,"column_start":1,"line_end":2,"column_end":4},"#,
+            r#""labels":[{"span":{"file":"a.sml","byte_start":8,"byte_end":11,"line_start":1,"column_start":9,"line_end":1,"column_end":12},"#,
+            r#""message":"`let` begins here"}]}"#
+        )
+    );
+}
+
+#[test]
+fn json_type_errors() {
+    let sources = with_prelude("val x = 1\nval y = x + true\n");
+    let error = compile_str(sources.input(), &Config::default()).unwrap_err();
+    let diagnostics = Diagnostic::from_error(sources.input(), &error);
+    let json = diagnostics[0].to_json(&sources);
+    assert_eq!(json["code"].as_str(), Some("type-mismatch"));
+    assert_eq!(json["span"]["line_start"].as_u64(), Some(2));
+    assert_eq!(json["span"]["column_start"].as_u64(), Some(9));
+}
+
+#[test]
+fn json_match_warnings() {
+    let sources = with_prelude(
+        "datatype t = A of int | B\nval y = case A 1 of A x => x\nfun g (A x) = x | g (A y) = y | g B = 0\n",
+    );
+    let (_, warnings) = compile_str(sources.input(), &Config::default()).unwrap();
+    let diagnostics = warnings
+        .iter()
+        .map(Diagnostic::from_warning)
+        .collect::<Vec<_>>();
+    let summary = diagnostics
+        .iter()
+        .map(|d| {
+            let json = d.to_json(&sources);
+            (d.severity, d.code, json["span"]["line_start"].as_u64())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        summary,
+        vec![
+            (Severity::Warning, "non-exhaustive-match", Some(2)),
+            (Severity::Warning, "redundant-clause", Some(3)),
+        ]
+    );
+}
+
+#[test]
+fn render_type_errors() {
+    let sources = with_prelude("val x = 1\nval y = x + true\n");
+    let error = compile_str(sources.input(), &Config::default()).unwrap_err();
+    let diagnostics = Diagnostic::from_error(sources.input(), &error);
+    assert_eq!(
+        diagnostics[0].render(&sources),
+        concat!(
+            "error[type-mismatch]: type mismatch\n",
+            " --> a.sml:2:9\n",
+            "  |\n",
+            "2 | val y = x + true\n",
+            "  |         ^^^^^^^^\n",
+            "  = in:       x + true\n",
+            "  = expected: int * int -> int\n",
+            "  = found:    int * bool -> 'a\n",
+            "  = because int conflicts with bool\n",
+        )
+    );
+}
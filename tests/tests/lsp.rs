@@ -0,0 +1,177 @@
+use serde_json::{json, Value};
+use std::io::{prelude::*, BufReader};
+use std::process::{Command, Stdio};
+
+const URI: &str = "file:///test.sml";
+
+/// the id of the shutdown request, too large for a double
+const SHUTDOWN: u64 = (1 << 53) + 1;
+
+fn request(id: u64, method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+}
+
+fn notification(method: &str, params: Value) -> Value {
+    json!({ "jsonrpc": "2.0", "method": method, "params": params })
+}
+
+fn position(line: usize, character: usize) -> Value {
+    json!({
+        "textDocument": { "uri": URI },
+        "position": { "line": line, "character": character },
+    })
+}
+
+/// opens `text` on the server, sends `messages` and returns all the messages the server sent
+fn session(text: &str, messages: Vec<Value>) -> Vec<Value> {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_webml-lsp"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .expect("failed to start the server");
+    let open = notification(
+        "textDocument/didOpen",
+        json!({
+            "textDocument": { "uri": URI, "languageId": "sml", "version": 1, "text": text },
+        }),
+    );
+    let mut input = vec![
+        request(0, "initialize", json!({})),
+        notification("initialized", json!({})),
+        open,
+    ];
+    input.extend(messages);
+    input.push(request(SHUTDOWN, "shutdown", Value::Null));
+    input.push(notification("exit", Value::Null));
+
+    let mut stdin = server.stdin.take().unwrap();
+    for message in input {
+        let body = message.to_string();
+        write!(stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body).unwrap();
+    }
+    drop(stdin);
+
+    let mut output = BufReader::new(server.stdout.take().unwrap());
+    let mut messages = Vec::new();
+    loop {
+        let mut header = String::new();
+        if output.read_line(&mut header).unwrap() == 0 {
+            break;
+        }
+        let length = header
+            .trim()
+            .strip_prefix("Content-Length: ")
+            .expect("a header")
+            .parse::<usize>()
+            .unwrap();
+        output.read_line(&mut header).unwrap();
+        let mut body = vec![0; length];
+        output.read_exact(&mut body).unwrap();
+        messages.push(serde_json::from_slice(&body).unwrap());
+    }
+    assert!(server.wait().unwrap().success());
+    assert!(response(&messages, SHUTDOWN).is_null());
+    messages
+}
+
+fn response(messages: &[Value], id: u64) -> &Value {
+    messages
+        .iter()
+        .find(|m| m["id"].as_u64() == Some(id))
+        .map(|m| &m["result"])
+        .expect("no response")
+}
+
+fn diagnostics(messages: &[Value]) -> &[Value] {
+    messages
+        .iter()
+        .find(|m| m["method"].as_str() == Some("textDocument/publishDiagnostics"))
+        .and_then(|m| m["params"]["diagnostics"].as_array())
+        .expect("no diagnostics")
+}
+
+fn start_line(range: &Value) -> Option<u64> {
+    range["start"]["line"].as_u64()
+}
+
+#[test]
+fn lsp_diagnostics() {
+    let messages = session("val x = 1\nval y = if x then 1 else 2\n", vec![]);
+    let capabilities = &response(&messages, 0)["capabilities"];
+    assert_eq!(capabilities["hoverProvider"], Value::Bool(true));
+    let errors = diagnostics(&messages);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(start_line(&errors[0]["range"]), Some(1));
+    assert!(errors[0]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("type mismatch"));
+
+    let messages = session("val x = let val y = 1 in y\nval z = 2\n", vec![]);
+    let errors = diagnostics(&messages);
+    assert_eq!(start_line(&errors[0]["range"]), Some(1));
+    assert_eq!(errors[0]["code"].as_str(), Some("syntax-error"));
+    let related = errors[0]["relatedInformation"].as_array().unwrap();
+    assert_eq!(start_line(&related[0]["location"]["range"]), Some(0));
+
+    let messages = session("datatype t = A | B\nval x = case A of A => 1\n", vec![]);
+    let warnings = diagnostics(&messages);
+    assert_eq!(warnings[0]["severity"].as_u64(), Some(2));
+    assert_eq!(warnings[0]["code"].as_str(), Some("non-exhaustive-match"));
+}
+
+#[test]
+fn lsp_hover_and_definition() {
+    let text = "datatype t = A | B of int\nfun f x = B x\nval y = f 2\n";
+    let messages = session(
+        text,
+        vec![
+            request(1, "textDocument/hover", position(2, 8)),
+            request(2, "textDocument/definition", position(2, 8)),
+            request(3, "textDocument/hover", position(1, 10)),
+            request(4, "textDocument/definition", position(1, 12)),
+        ],
+    );
+    assert!(diagnostics(&messages).is_empty());
+    assert_eq!(
+        response(&messages, 1)["contents"]["value"].as_str(),
+        Some("```sml\nf : int -> t\n```")
+    );
+    let definition = response(&messages, 2);
+    assert_eq!(definition["uri"].as_str(), Some(URI));
+    assert_eq!(
+        definition["range"].to_string(),
+        r#"{"start":{"line":1,"character":4},"end":{"line":1,"character":5}}"#
+    );
+    assert_eq!(
+        response(&messages, 3)["contents"]["value"].as_str(),
+        Some("```sml\nB : int -> t\n```")
+    );
+    let definition = response(&messages, 4);
+    assert_eq!(
+        definition["range"]["start"].to_string(),
+        r#"{"line":1,"character":6}"#
+    );
+}
+
+#[test]
+fn lsp_document_symbols() {
+    let text = "datatype t = A | B of int\nfun f x = B x\nval y = f 2\nval g = fn x => x\n";
+    let messages = session(
+        text,
+        vec![request(
+            1,
+            "textDocument/documentSymbol",
+            json!({ "textDocument": { "uri": URI } }),
+        )],
+    );
+    let symbols = response(&messages, 1).as_array().unwrap();
+    let names = symbols
+        .iter()
+        .map(|s| (s["name"].as_str().unwrap(), s["kind"].as_u64().unwrap()))
+        .collect::<Vec<_>>();
+    assert_eq!(names, vec![("t", 10), ("f", 12), ("y", 13), ("g", 12)]);
+    let constructors = symbols[0]["children"].as_array().unwrap();
+    assert_eq!(constructors.len(), 2);
+    assert_eq!(constructors[1]["name"].as_str(), Some("B"));
+}
@@ -0,0 +1,101 @@
+use webml::ast::{Printer, Type};
+use webml::prim::Symbol;
+use webml::{compile_str, Config};
+
+fn fun(param: Type, body: Type) -> Type {
+    Type::Fun(Box::new(param), Box::new(body))
+}
+
+fn error(program: &str) -> (&'static str, String) {
+    let mut input = include_str!("../../ml_src/prelude.sml").to_string();
+    input.push_str(program);
+    match compile_str(&input, &Config::default()) {
+        Ok(_) => panic!("succeded to compile {}, which should fail", program),
+        Err(e) => (e.code(), e.to_string()),
+    }
+}
+
+#[test]
+fn print_types() {
+    let mut printer = Printer::new();
+    let ty = fun(
+        Type::Tuple(vec![Type::Int, Type::Datatype(Symbol::new("bool"))]),
+        Type::Variable(7),
+    );
+    assert_eq!(printer.ty(&ty), "int * bool -> 'a");
+    assert_eq!(printer.ty(&Type::Tuple(vec![])), "unit");
+    assert_eq!(printer.ty(&Type::Real), "real");
+}
+
+#[test]
+fn print_type_variables_in_order_of_appearance() {
+    let mut printer = Printer::new();
+    let ty = fun(Type::Variable(3), fun(Type::Variable(1), Type::Variable(3)));
+    assert_eq!(printer.ty(&ty), "'a -> 'b -> 'a");
+    // the names are kept across the types printed by one printer
+    assert_eq!(printer.ty(&Type::Variable(1)), "'b");
+    assert_eq!(printer.ty(&Type::Variable(5)), "'c");
+}
+
+#[test]
+fn print_nested_types() {
+    let mut printer = Printer::new();
+    let ty = fun(fun(Type::Int, Type::Int), Type::Int);
+    assert_eq!(printer.ty(&ty), "(int -> int) -> int");
+    let ty = Type::Tuple(vec![
+        Type::Tuple(vec![Type::Int, Type::Real]),
+        fun(Type::Int, Type::Int),
+    ]);
+    assert_eq!(printer.ty(&ty), "(int * real) * (int -> int)");
+    let ty = fun(Type::Int, Type::Tuple(vec![Type::Int, Type::Int]));
+    assert_eq!(printer.ty(&ty), "int -> int * int");
+}
+
+#[test]
+fn print_mismatch() {
+    let (code, message) = error("fun app f x = f x fun isPos n = n > 0 val b = app isPos true");
+    assert_eq!(code, "type-mismatch");
+    assert_eq!(
+        message,
+        "type mismatch
+  in:       app isPos true
+  expected: int -> bool
+  found:    bool -> 'a
+  because int conflicts with bool"
+    );
+}
+
+#[test]
+fn print_circularity() {
+    let (code, message) = error("fun f x = f");
+    assert_eq!(code, "circular-type");
+    assert_eq!(
+        message.lines().next(),
+        Some("circular type: 'a occurs in 'b -> 'a")
+    );
+
+    let (code, message) = error("fun f x = x x");
+    assert_eq!(code, "circular-type");
+    assert_eq!(
+        message,
+        "circular type: 'a occurs in 'a -> 'b\n  in:       x x"
+    );
+}
+
+#[test]
+fn print_unbound() {
+    let (code, message) = error("fun length xs = 0 fun main () = lenght 1");
+    assert_eq!(code, "unbound-name");
+    assert_eq!(
+        message,
+        "unbound variable `lenght` in `main`\n  did you mean `length`?"
+    );
+
+    // `printInt` is hidden by `local` in the prelude
+    let (code, message) = error("val x = printIn 1");
+    assert_eq!(code, "unbound-name");
+    assert_eq!(
+        message,
+        "unbound variable `printIn` in `x`\n  did you mean `print`?"
+    );
+}
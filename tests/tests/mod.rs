@@ -1,2 +1,7 @@
+pub mod cli;
 pub mod compile;
+pub mod diagnostic;
+pub mod lsp;
+pub mod messages;
 pub mod parser;
+pub mod pipeline;
@@ -4,6 +4,7 @@ use webml::ast::{
 };
 use webml::parse;
 use webml::prim::*;
+use webml::{Expected, Position};
 
 #[test]
 fn parse_char() {
@@ -53,6 +54,83 @@ fn parse_int() {
     )
 }
 
+#[test]
+fn parse_word() {
+    let input = r#"val x = 0w42 val y = 0wx2A"#;
+    let ast = parse(input).unwrap();
+    let val = |name: &str, w| Declaration::Val {
+        rec: false,
+        pattern: Pattern {
+            ty: (),
+            inner: PatternKind::Variable {
+                name: Symbol::new(name),
+            },
+        },
+        expr: Expr {
+            ty: (),
+            inner: ExprKind::Literal {
+                value: Literal::Word(w),
+            },
+        },
+    };
+    assert_eq!(ast, AST(vec![val("x", 42), val("y", 42)]))
+}
+
+#[test]
+fn parse_word_pattern() {
+    let input = r#"val x = case 0w1 of 0w1 => 1 | _ => 0"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                inner: PatternKind::Variable {
+                    name: Symbol::new("x"),
+                }
+            },
+            expr: Expr {
+                ty: (),
+                inner: ExprKind::Case {
+                    cond: Box::new(Expr {
+                        ty: (),
+                        inner: ExprKind::Literal {
+                            value: Literal::Word(1),
+                        }
+                    }),
+                    clauses: vec![
+                        (
+                            Pattern {
+                                ty: (),
+                                inner: PatternKind::Word { value: 1 },
+                            },
+                            Expr {
+                                ty: (),
+                                inner: ExprKind::Literal {
+                                    value: Literal::Int(1),
+                                }
+                            }
+                        ),
+                        (
+                            Pattern {
+                                ty: (),
+                                inner: PatternKind::Wildcard {},
+                            },
+                            Expr {
+                                ty: (),
+                                inner: ExprKind::Literal {
+                                    value: Literal::Int(0),
+                                }
+                            }
+                        ),
+                    ],
+                }
+            },
+        },])
+    )
+}
+
 #[test]
 fn parse_float() {
     let input = r#"val x = 1.0"#;
@@ -77,6 +155,110 @@ fn parse_float() {
     )
 }
 
+#[test]
+fn parse_float_exponent() {
+    let input = r#"val x = 1.5e2 val y = 25E~2 val z = ~1.0"#;
+    let ast = parse(input).unwrap();
+    let val = |name: &str, f| Declaration::Val {
+        rec: false,
+        pattern: Pattern {
+            ty: (),
+            inner: PatternKind::Variable {
+                name: Symbol::new(name),
+            },
+        },
+        expr: Expr {
+            ty: (),
+            inner: ExprKind::Literal {
+                value: Literal::Real(f),
+            },
+        },
+    };
+    assert_eq!(
+        ast,
+        AST(vec![val("x", 150.0), val("y", 0.25), val("z", -1.0)])
+    )
+}
+
+#[test]
+fn parse_negative_int() {
+    let input = r#"val x = ~1"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                inner: PatternKind::Variable {
+                    name: Symbol::new("x"),
+                }
+            },
+            expr: Expr {
+                ty: (),
+                inner: ExprKind::Literal {
+                    value: Literal::Int(-1),
+                }
+            },
+        },])
+    )
+}
+
+#[test]
+fn parse_large_int() {
+    let input = r#"val x = ~123456789012345678901234567890"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                inner: PatternKind::Variable {
+                    name: Symbol::new("x"),
+                }
+            },
+            expr: Expr {
+                ty: (),
+                inner: ExprKind::Literal {
+                    value: Literal::LargeInt("-123456789012345678901234567890".into()),
+                }
+            },
+        },])
+    )
+}
+
+#[test]
+fn parse_qualified_name() {
+    let input = r#"val x = Math.sqrt"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                inner: PatternKind::Variable {
+                    name: Symbol::new("x"),
+                }
+            },
+            expr: Expr {
+                ty: (),
+                inner: ExprKind::Symbol {
+                    name: Symbol::new("Math.sqrt"),
+                }
+            },
+        },])
+    )
+}
+
+#[test]
+fn parse_qualified_name_binding() {
+    // qualified names only refer to bindings
+    assert!(parse(r#"fun Math.sin x = x"#).is_err());
+    assert!(parse(r#"val Math.pi = 3.14"#).is_err());
+}
+
 #[test]
 fn parse_bool_true() {
     let input = r#"val x = true"#;
@@ -671,6 +853,23 @@ fn parse_datatype_arg1() {
     )
 }
 
+#[test]
+fn parse_datatype_word() {
+    let input = r#"datatype hoge = Hoge of word | Fuga of Word8.word | Piyo of Word64.word"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Datatype {
+            name: Symbol::new("hoge"),
+            constructors: vec![
+                (Symbol::new("Hoge"), Some(Type::Word(WordSize::W32))),
+                (Symbol::new("Fuga"), Some(Type::Word(WordSize::W8))),
+                (Symbol::new("Piyo"), Some(Type::Word(WordSize::W64)))
+            ]
+        },])
+    )
+}
+
 #[test]
 fn parse_datatype_arg2() {
     let input = r#"datatype hoge = Hoge of int | Fuga of real | Piyo of bool -> unit -> int"#;
@@ -1640,3 +1839,375 @@ fn parse_multistatement_val_datatype() {
         ])
     )
 }
+
+#[test]
+fn parse_fun_and() {
+    let input = r#"fun f x = g x and g x = x"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::And(vec![
+            Declaration::D(DerivedDeclaration::Fun {
+                name: Symbol::new("f"),
+                clauses: vec![(
+                    vec![Pattern {
+                        ty: (),
+                        inner: PatternKind::Variable {
+                            name: Symbol::new("x"),
+                        }
+                    }],
+                    Expr {
+                        ty: (),
+                        inner: ExprKind::App {
+                            fun: Expr {
+                                ty: (),
+                                inner: ExprKind::Symbol {
+                                    name: Symbol::new("g"),
+                                }
+                            }
+                            .boxed(),
+                            arg: Expr {
+                                ty: (),
+                                inner: ExprKind::Symbol {
+                                    name: Symbol::new("x"),
+                                }
+                            }
+                            .boxed(),
+                        }
+                    }
+                )]
+            }),
+            Declaration::D(DerivedDeclaration::Fun {
+                name: Symbol::new("g"),
+                clauses: vec![(
+                    vec![Pattern {
+                        ty: (),
+                        inner: PatternKind::Variable {
+                            name: Symbol::new("x"),
+                        }
+                    }],
+                    Expr {
+                        ty: (),
+                        inner: ExprKind::Symbol {
+                            name: Symbol::new("x"),
+                        }
+                    }
+                )]
+            }),
+        ])])
+    )
+}
+
+#[test]
+fn parse_val_rec_and() {
+    let input = r#"val rec f = fn x => g x and g = fn y => y"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::And(vec![
+            Declaration::Val {
+                rec: true,
+                pattern: Pattern {
+                    ty: (),
+                    inner: PatternKind::Variable {
+                        name: Symbol::new("f")
+                    }
+                },
+                expr: Expr {
+                    ty: (),
+                    inner: ExprKind::Fn {
+                        param: Symbol::new("x"),
+                        body: Expr {
+                            ty: (),
+                            inner: ExprKind::App {
+                                fun: Expr {
+                                    ty: (),
+                                    inner: ExprKind::Symbol {
+                                        name: Symbol::new("g"),
+                                    }
+                                }
+                                .boxed(),
+                                arg: Expr {
+                                    ty: (),
+                                    inner: ExprKind::Symbol {
+                                        name: Symbol::new("x"),
+                                    }
+                                }
+                                .boxed(),
+                            }
+                        }
+                        .boxed()
+                    }
+                }
+            },
+            Declaration::Val {
+                rec: true,
+                pattern: Pattern {
+                    ty: (),
+                    inner: PatternKind::Variable {
+                        name: Symbol::new("g")
+                    }
+                },
+                expr: Expr {
+                    ty: (),
+                    inner: ExprKind::Fn {
+                        param: Symbol::new("y"),
+                        body: Expr {
+                            ty: (),
+                            inner: ExprKind::Symbol {
+                                name: Symbol::new("y"),
+                            }
+                        }
+                        .boxed()
+                    }
+                }
+            },
+        ])])
+    )
+}
+
+#[test]
+fn parse_datatype_and() {
+    let input = r#"datatype tree = Leaf | Node of forest and forest = Nil | Cons of tree"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::And(vec![
+            Declaration::Datatype {
+                name: Symbol::new("tree"),
+                constructors: vec![
+                    (Symbol::new("Leaf"), None),
+                    (
+                        Symbol::new("Node"),
+                        Some(Type::Datatype(Symbol::new("forest")))
+                    ),
+                ]
+            },
+            Declaration::Datatype {
+                name: Symbol::new("forest"),
+                constructors: vec![
+                    (Symbol::new("Nil"), None),
+                    (
+                        Symbol::new("Cons"),
+                        Some(Type::Datatype(Symbol::new("tree")))
+                    ),
+                ]
+            },
+        ])])
+    )
+}
+
+#[test]
+fn parse_type() {
+    let input = r#"type point = int * int"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Type {
+            name: Symbol::new("point"),
+            ty: Type::Tuple(vec![Type::Int, Type::Int]),
+        }])
+    )
+}
+
+#[test]
+fn parse_datatype_withtype() {
+    let input = r#"datatype tree = Leaf | Node of forest withtype forest = tree * tree"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::And(vec![
+            Declaration::Datatype {
+                name: Symbol::new("tree"),
+                constructors: vec![
+                    (Symbol::new("Leaf"), None),
+                    (
+                        Symbol::new("Node"),
+                        Some(Type::Datatype(Symbol::new("forest")))
+                    ),
+                ]
+            },
+            Declaration::Type {
+                name: Symbol::new("forest"),
+                ty: Type::Tuple(vec![
+                    Type::Datatype(Symbol::new("tree")),
+                    Type::Datatype(Symbol::new("tree"))
+                ]),
+            },
+        ])])
+    )
+}
+
+#[test]
+fn parse_datatype_replication() {
+    let input = r#"datatype t = datatype u"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Type {
+            name: Symbol::new("t"),
+            ty: Type::Datatype(Symbol::new("u")),
+        }])
+    )
+}
+
+#[test]
+fn parse_abstype() {
+    let input = r#"abstype t = T of int with val x = 1 end"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Abstype {
+            datatype: Box::new(Declaration::Datatype {
+                name: Symbol::new("t"),
+                constructors: vec![(Symbol::new("T"), Some(Type::Int))]
+            }),
+            decls: vec![Declaration::Val {
+                rec: false,
+                pattern: Pattern {
+                    ty: (),
+                    inner: PatternKind::Variable {
+                        name: Symbol::new("x")
+                    },
+                },
+                expr: Expr {
+                    ty: (),
+                    inner: ExprKind::Literal {
+                        value: Literal::Int(1)
+                    }
+                },
+            }],
+        }])
+    )
+}
+
+#[test]
+fn parse_local() {
+    let input = r#"local val x = 1 in val y = x end"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Local {
+            binds: vec![Declaration::Val {
+                rec: false,
+                pattern: Pattern {
+                    ty: (),
+                    inner: PatternKind::Variable {
+                        name: Symbol::new("x")
+                    },
+                },
+                expr: Expr {
+                    ty: (),
+                    inner: ExprKind::Literal {
+                        value: Literal::Int(1)
+                    }
+                },
+            }],
+            body: vec![Declaration::Val {
+                rec: false,
+                pattern: Pattern {
+                    ty: (),
+                    inner: PatternKind::Variable {
+                        name: Symbol::new("y")
+                    },
+                },
+                expr: Expr {
+                    ty: (),
+                    inner: ExprKind::Symbol {
+                        name: Symbol::new("x")
+                    }
+                },
+            }],
+        }])
+    )
+}
+
+#[test]
+fn parse_local_fixity() {
+    let infix = parse("infix 6 add val y = a add b").unwrap();
+    let nonfix = parse("val y = a add b").unwrap();
+    let last = |input: &str| parse(input).unwrap().0.pop().unwrap();
+    // the fixities of binds are seen by the body but end with the declaration
+    match last("local infix 6 add in val y = a add b end") {
+        Declaration::Local { body, .. } => assert_eq!(body, infix.0[1..]),
+        decl => panic!("not a local declaration: {:?}", decl),
+    }
+    assert_eq!(
+        last("local infix 6 add in end val y = a add b"),
+        nonfix.0[0]
+    );
+    // the ones of the body outlive it
+    assert_eq!(last("local in infix 6 add end val y = a add b"), infix.0[1]);
+}
+
+#[test]
+fn parse_comments() {
+    let input = "(* the answer (* nested *) *)\nval x = (* inline *) 42";
+    assert_eq!(parse(input).unwrap(), parse("val x = 42").unwrap())
+}
+
+#[test]
+fn parse_keyword_prefixed_identifier() {
+    let input = r#"val iffy = fnord lets"#;
+    let ast = parse(input).unwrap();
+    assert_eq!(
+        ast,
+        AST(vec![Declaration::Val {
+            rec: false,
+            pattern: Pattern {
+                ty: (),
+                inner: PatternKind::Variable {
+                    name: Symbol::new("iffy"),
+                }
+            },
+            expr: Expr {
+                ty: (),
+                inner: ExprKind::App {
+                    fun: Expr {
+                        ty: (),
+                        inner: ExprKind::Symbol {
+                            name: Symbol::new("fnord")
+                        }
+                    }
+                    .boxed(),
+                    arg: Expr {
+                        ty: (),
+                        inner: ExprKind::Symbol {
+                            name: Symbol::new("lets")
+                        }
+                    }
+                    .boxed()
+                }
+            },
+        }])
+    )
+}
+
+#[test]
+fn parse_unclosed_let() {
+    let input = "val x = let val y = 1 in y\nval z = 2";
+    let errors = parse(input).unwrap_err();
+    assert_eq!(errors.len(), 1);
+    let error = &errors[0];
+    assert_eq!(error.position, Position { line: 2, column: 1 });
+    assert_eq!(error.found, "val");
+    assert_eq!(error.expected, vec![Expected::Token("end")]);
+    assert_eq!(
+        error.context,
+        Some(("let", Position { line: 1, column: 9 }))
+    );
+    assert_eq!(
+        error.to_string(),
+        "syntax error at 2:1: expected `end` to close `let` at 1:9, found `val`"
+    );
+}
+
+#[test]
+fn parse_recover_at_declarations() {
+    let input = "val = 1\nval x = (1, 2\nfun f x = if x then 1\nval y = 3";
+    let errors = parse(input).unwrap_err();
+    let positions = errors.iter().map(|e| e.position.line).collect::<Vec<_>>();
+    assert_eq!(positions, vec![1, 3, 4]);
+    assert_eq!(errors[0].expected, vec![Expected::Label("a pattern")]);
+    assert_eq!(errors[2].expected, vec![Expected::Token("else")]);
+}
@@ -0,0 +1,282 @@
+use std::cell::Cell;
+use std::convert::Infallible;
+use std::rc::Rc;
+use webml::hir::util::Traverse;
+use webml::pipeline::Level;
+use webml::{hir, lir, mir, Config, OptLevel, Pass, Pipeline, IR};
+
+type Mir = (mir::SymbolTable, mir::MIR);
+type Lir = (lir::ExternTypes, lir::LIR);
+
+struct Count(Rc<Cell<usize>>);
+
+impl<T> Pass<T, Infallible> for Count {
+    type Target = T;
+
+    fn trans(&mut self, t: T, _: &Config) -> Result<Self::Target, Infallible> {
+        self.0.set(self.0.get() + 1);
+        Ok(t)
+    }
+}
+
+fn input() -> String {
+    format!("{}\nval x = 1 + 2", webml::PRELUDE)
+}
+
+#[test]
+fn pipeline_stop() {
+    let input = input();
+    let config = Config::default();
+    let mut pipeline = Pipeline::new();
+    match pipeline.run(&input, "flattening_let", &config) {
+        Ok(IR::Hir(_)) => (),
+        Ok(ir) => panic!("stopped at {}", ir.name()),
+        Err(e) => panic!("failed to compile: {}", e),
+    }
+    let mir = pipeline.run_to::<Mir>(&input, &config);
+    assert!(mir.is_ok());
+}
+
+#[test]
+fn pipeline_resume() {
+    let input = input();
+    let config = Config::default();
+    let mut pipeline = Pipeline::new();
+    let ir = pipeline.run(&input, "typing", &config).ok().unwrap();
+    match pipeline.resume(ir, "typing", "mir_to_lir", &config) {
+        Ok(IR::Lir(_)) => (),
+        Ok(ir) => panic!("stopped at {}", ir.name()),
+        Err(e) => panic!("failed to compile: {}", e),
+    }
+}
+
+#[test]
+fn pipeline_insert() {
+    let input = input();
+    let config = Config::default();
+    let hir_count = Rc::new(Cell::new(0));
+    let mir_count = Rc::new(Cell::new(0));
+    let mut pipeline = Pipeline::new();
+    pipeline
+        .insert_after::<(hir::SymbolTable, hir::HIR)>(
+            "unnest_functions",
+            "count_hir",
+            Count(hir_count.clone()),
+        )
+        .insert_after_if::<Mir>(
+            "unalias",
+            "count_mir",
+            |config| config.pretty_print_ir.contains("count_mir"),
+            Count(mir_count.clone()),
+        );
+    assert!(pipeline.run(&input, "backend", &config).is_ok());
+    assert_eq!(hir_count.get(), 1);
+    assert_eq!(mir_count.get(), 0);
+
+    // stopping before the stage does not run the passes inserted after it
+    assert!(pipeline.run(&input, "flattening_let", &config).is_ok());
+    assert_eq!(hir_count.get(), 1);
+}
+
+#[test]
+#[should_panic]
+fn pipeline_insert_mismatch() {
+    let hir_count = Rc::new(Cell::new(0));
+    Pipeline::new().insert_after::<(hir::SymbolTable, hir::HIR)>(
+        "unalias",
+        "count_hir",
+        Count(hir_count),
+    );
+}
+
+fn config(opt_level: OptLevel) -> Config {
+    Config {
+        opt_level,
+        ..Config::default()
+    }
+}
+
+/// the IRs made of functions of operations
+trait Ops: Level {
+    type Op;
+    /// the operations of the function `name`, or of all the functions
+    fn ops(&self, name: Option<&str>) -> Vec<&Self::Op>;
+}
+
+impl Ops for Mir {
+    type Op = mir::Op;
+    fn ops(&self, name: Option<&str>) -> Vec<&mir::Op> {
+        (self.1)
+            .0
+            .iter()
+            .filter(|f| name.is_none_or(|name| f.name.0 == name))
+            .flat_map(|f| f.body.iter())
+            .flat_map(|ebb| ebb.body.iter())
+            .collect()
+    }
+}
+
+impl Ops for Lir {
+    type Op = lir::Op;
+    fn ops(&self, name: Option<&str>) -> Vec<&lir::Op> {
+        (self.1)
+            .0
+            .iter()
+            .filter(|f| name.is_none_or(|name| f.name.0 == name))
+            .flat_map(|f| f.body.iter())
+            .flat_map(|block| block.body.iter())
+            .collect()
+    }
+}
+
+/// the operations `pred` accepts in the function `name`, or in all of them,
+/// of `input` compiled after the prelude down to `T`
+fn count_ops<T: Ops>(
+    mut pipeline: Pipeline,
+    input: &str,
+    config: &Config,
+    name: Option<&str>,
+    pred: impl Fn(&T::Op) -> bool,
+) -> usize {
+    let input = format!("{}\n{}", webml::PRELUDE, input);
+    let ir = pipeline.run_to::<T>(&input, config).ok().unwrap();
+    ir.ops(name).into_iter().filter(|op| pred(op)).count()
+}
+
+#[test]
+fn pipeline_run_to() {
+    // `+` of the prelude is called through its closure in MIR,
+    // and in LIR unless the known calls are made direct
+    let input = "fun f x = x + 1\nval y = f 2";
+    let config = Config::default();
+    let calls = count_ops::<Mir>(Pipeline::new(), input, &config, Some("f"), |op| {
+        matches!(op, mir::Op::Call { .. })
+    });
+    assert_eq!(calls, 1);
+    let calls = count_ops::<Lir>(Pipeline::bare(), input, &config, Some("f"), |op| {
+        matches!(op, lir::Op::ClosureCall(..))
+    });
+    assert_eq!(calls, 1);
+}
+
+struct Apps(usize);
+
+impl Traverse for Apps {
+    fn traverse_app(
+        &mut self,
+        _: &mut hir::HTy,
+        fun: &mut Box<hir::Expr>,
+        arg: &mut Box<hir::Expr>,
+    ) {
+        self.0 += 1;
+        self.traverse_expr(fun);
+        self.traverse_expr(arg);
+    }
+}
+
+/// the calls out of the functions
+fn toplevel_apps(opt_level: OptLevel) -> usize {
+    let input = "fun id x = case x of 0 => x | _ => x\nfun twice x = id (id x)\nval y = twice 1";
+    let (_, mut hir) = Pipeline::new()
+        .run_to::<(hir::SymbolTable, hir::HIR)>(input, &config(opt_level))
+        .ok()
+        .unwrap();
+    let mut apps = Apps(0);
+    for val in hir.0.iter_mut() {
+        if let hir::Expr::Binds { .. } = val.expr {
+            apps.traverse_val(val)
+        }
+    }
+    apps.0
+}
+
+#[test]
+fn pipeline_inline() {
+    assert_eq!(toplevel_apps(OptLevel::O0), 1);
+    assert_eq!(toplevel_apps(OptLevel::O2), 0);
+}
+
+/// the calls through the function table
+fn closure_calls(pipeline: Pipeline) -> usize {
+    let input = "fun double x = x * 2\nfun f y = let val add = fn z => z + y in add (double y) end\nval z = f 1";
+    count_ops::<Lir>(pipeline, input, &Config::default(), None, |op| {
+        matches!(op, lir::Op::ClosureCall(..))
+    })
+}
+
+#[test]
+fn pipeline_known_calls() {
+    assert_ne!(closure_calls(Pipeline::bare()), 0);
+    assert_eq!(closure_calls(Pipeline::new()), 0);
+}
+
+/// the closures and the tuples the toplevel makes
+fn toplevel_allocs(opt_level: OptLevel) -> usize {
+    // too large to inline and used twice
+    let input = "fun add3 a b c = a * b + b * c + c * a + a * b * c + a - b - c\nfun addp (x, y) = x * y + x * x + y * y + x * y * x - x - y\nval r = addp (add3 1 2 3, 4) + addp (add3 4 5 6, 7)";
+    count_ops::<Mir>(
+        Pipeline::new(),
+        input,
+        &config(opt_level),
+        Some("sml-main"),
+        |op| matches!(op, mir::Op::Closure { .. } | mir::Op::Tuple { .. }),
+    )
+}
+
+#[test]
+fn pipeline_uncurry() {
+    assert_ne!(toplevel_allocs(OptLevel::O1), 0);
+    assert_eq!(toplevel_allocs(OptLevel::O2), 0);
+}
+
+/// the types of the parameter and the result of the function `name`
+fn signature(input: &str, name: &str) -> (lir::LTy, lir::LTy) {
+    let input = format!("{}\n{}", webml::PRELUDE, input);
+    let (_, lir) = Pipeline::bare()
+        .run_to::<Lir>(&input, &Config::default())
+        .ok()
+        .unwrap();
+    let f = lir.0.iter().find(|f| f.name.0 == name).unwrap();
+    (f.regs[0].clone(), f.ret_ty.clone())
+}
+
+#[test]
+fn datatype_repr() {
+    use webml::lir::LTy;
+    let color = "datatype color = Red | Green | Blue\n";
+    let input = format!("{}fun f c = case c of Red => Green | _ => Blue", color);
+    assert_eq!(signature(&input, "f"), (LTy::I32, LTy::I32));
+    let input = "datatype meter = Meter of real\nfun f m = case m of Meter x => Meter (x * 2.0)";
+    assert_eq!(signature(input, "f"), (LTy::F64, LTy::F64));
+    let input = "datatype opt = None | Some of int\nfun f x = if x > 0 then Some x else None";
+    assert_eq!(signature(input, "f"), (LTy::I32, LTy::Ptr));
+    assert_eq!(signature("fun f x = x > 0", "f"), (LTy::I32, LTy::I32));
+}
+
+/// the allocations on the heap and on the stack in the function `name`
+fn allocs(opt_level: OptLevel, name: &str) -> (usize, usize) {
+    let input = "fun add p = case p of (x, y) => x + y\nfun f n = add (n, n)\nfun g n = (n, n)\nval x = f 1 + (case g 2 of (a, b) => a)";
+    let count = |pred: fn(&lir::Op) -> bool| {
+        count_ops::<Lir>(
+            Pipeline::bare(),
+            input,
+            &config(opt_level),
+            Some(name),
+            pred,
+        )
+    };
+    (
+        count(|op| matches!(op, lir::Op::HeapAlloc(..))),
+        count(|op| matches!(op, lir::Op::StackAlloc(..))),
+    )
+}
+
+#[test]
+fn pipeline_stack_alloc() {
+    assert_eq!(allocs(OptLevel::O0, "f").1, 0);
+    let (heap, stack) = allocs(OptLevel::O1, "f");
+    assert_eq!(heap, 0);
+    assert_ne!(stack, 0);
+    // the tuple `g` returns escapes
+    assert_eq!(allocs(OptLevel::O1, "g").0, 1);
+}
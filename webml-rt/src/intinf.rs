@@ -0,0 +1,326 @@
+//! arbitrary-precision integers (`IntInf.int`).
+//! a value is an immutable heap object holding its sign and magnitude:
+//! `size` is the number of 32 bit limbs negated if the value is negative
+//! and the limbs follow it in little endian order. zero has no limbs.
+use crate::alloc;
+use core::slice;
+
+#[repr(C)]
+pub struct IntInf {
+    size: i32,
+    limbs: [u32; 0],
+}
+
+const LIMB_BITS: u32 = 32;
+// the largest power of 10 which fits in a limb
+const DECIMAL_BASE: u32 = 1_000_000_000;
+const DECIMAL_DIGITS: usize = 9;
+
+unsafe fn new(negative: bool, mag: &[u32]) -> *mut IntInf {
+    let mut len = mag.len();
+    while len > 0 && mag[len - 1] == 0 {
+        len -= 1;
+    }
+    let ret = alloc(4 + 4 * len) as *mut IntInf;
+    (*ret).size = if negative { -(len as i32) } else { len as i32 };
+    let limbs = (*ret).limbs.as_mut_ptr();
+    for (i, limb) in mag[..len].iter().enumerate() {
+        *limbs.add(i) = *limb;
+    }
+    ret
+}
+
+unsafe fn with_capacity(len: usize) -> *mut IntInf {
+    let ret = alloc(4 + 4 * len) as *mut IntInf;
+    (*ret).size = len as i32;
+    ret
+}
+
+unsafe fn limbs<'a>(x: *const IntInf) -> &'a [u32] {
+    slice::from_raw_parts((*x).limbs.as_ptr(), (*x).size.abs() as usize)
+}
+
+unsafe fn limbs_mut<'a>(x: *mut IntInf) -> &'a mut [u32] {
+    slice::from_raw_parts_mut((*x).limbs.as_mut_ptr(), (*x).size.abs() as usize)
+}
+
+unsafe fn is_negative(x: *const IntInf) -> bool {
+    (*x).size < 0
+}
+
+/// strip the leading zeros and set the sign
+unsafe fn normalize(x: *mut IntInf, negative: bool) -> *mut IntInf {
+    let mut len = limbs(x).len();
+    while len > 0 && limbs(x)[len - 1] == 0 {
+        len -= 1;
+    }
+    (*x).size = if negative { -(len as i32) } else { len as i32 };
+    x
+}
+
+fn cmp_mag(a: &[u32], b: &[u32]) -> i32 {
+    if a.len() != b.len() {
+        return if a.len() < b.len() { -1 } else { 1 };
+    }
+    for (x, y) in a.iter().rev().zip(b.iter().rev()) {
+        if x != y {
+            return if x < y { -1 } else { 1 };
+        }
+    }
+    0
+}
+
+unsafe fn add_mag(a: &[u32], b: &[u32], negative: bool) -> *mut IntInf {
+    let (a, b) = if a.len() < b.len() { (b, a) } else { (a, b) };
+    let ret = with_capacity(a.len() + 1);
+    let r = limbs_mut(ret);
+    let mut carry = 0u64;
+    for i in 0..a.len() {
+        let sum = a[i] as u64 + *b.get(i).unwrap_or(&0) as u64 + carry;
+        r[i] = sum as u32;
+        carry = sum >> LIMB_BITS;
+    }
+    r[a.len()] = carry as u32;
+    normalize(ret, negative)
+}
+
+/// requires |a| >= |b|
+unsafe fn sub_mag(a: &[u32], b: &[u32], negative: bool) -> *mut IntInf {
+    let ret = with_capacity(a.len());
+    let r = limbs_mut(ret);
+    let mut borrow = 0i64;
+    for i in 0..a.len() {
+        let mut diff = a[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+        borrow = 0;
+        if diff < 0 {
+            diff += 1 << LIMB_BITS;
+            borrow = 1;
+        }
+        r[i] = diff as u32;
+    }
+    normalize(ret, negative)
+}
+
+/// a + b where b is negated if `negate_b`
+unsafe fn add_signed(a: *const IntInf, b: *const IntInf, negate_b: bool) -> *mut IntInf {
+    let a_neg = is_negative(a);
+    let b_neg = is_negative(b) != negate_b;
+    let (am, bm) = (limbs(a), limbs(b));
+    if a_neg == b_neg {
+        add_mag(am, bm, a_neg)
+    } else if cmp_mag(am, bm) >= 0 {
+        sub_mag(am, bm, a_neg)
+    } else {
+        sub_mag(bm, am, b_neg)
+    }
+}
+
+/// divide the magnitude in place by a single limb and return the remainder
+fn divmod_limb(a: &mut [u32], d: u32) -> u32 {
+    let mut rem = 0u64;
+    for limb in a.iter_mut().rev() {
+        let cur = (rem << LIMB_BITS) | *limb as u64;
+        *limb = (cur / d as u64) as u32;
+        rem = cur % d as u64;
+    }
+    rem as u32
+}
+
+/// truncating division of the magnitudes by shift and subtract.
+/// returns (quotient, remainder) without signs
+unsafe fn divmod_mag(a: &[u32], b: &[u32]) -> (*mut IntInf, *mut IntInf) {
+    let q = with_capacity(a.len());
+    let r = with_capacity(b.len() + 1);
+    {
+        let qs = limbs_mut(q);
+        let rs = limbs_mut(r);
+        for limb in qs.iter_mut().chain(rs.iter_mut()) {
+            *limb = 0;
+        }
+        for bit in (0..a.len() * LIMB_BITS as usize).rev() {
+            // r = r << 1 | the bit of a
+            let mut carry = (a[bit / 32] >> (bit % 32)) & 1;
+            for limb in rs.iter_mut() {
+                let next = *limb >> (LIMB_BITS - 1);
+                *limb = (*limb << 1) | carry;
+                carry = next;
+            }
+            let rlen = rs.iter().rposition(|l| *l != 0).map(|i| i + 1).unwrap_or(0);
+            if cmp_mag(&rs[..rlen], b) >= 0 {
+                let mut borrow = 0i64;
+                for i in 0..rs.len() {
+                    let mut diff = rs[i] as i64 - *b.get(i).unwrap_or(&0) as i64 - borrow;
+                    borrow = 0;
+                    if diff < 0 {
+                        diff += 1 << LIMB_BITS;
+                        borrow = 1;
+                    }
+                    rs[i] = diff as u32;
+                }
+                qs[bit / 32] |= 1 << (bit % 32);
+            }
+        }
+    }
+    (normalize(q, false), normalize(r, false))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_of_i64(n: i64) -> *mut IntInf {
+    let mag = n.unsigned_abs();
+    new(n < 0, &[mag as u32, (mag >> LIMB_BITS) as u32])
+}
+
+/// the lower 64 bits of the two's complement representation
+#[no_mangle]
+pub unsafe extern "C" fn intinf_to_i64(x: *const IntInf) -> i64 {
+    let l = limbs(x);
+    let mag = *l.get(0).unwrap_or(&0) as u64 | (*l.get(1).unwrap_or(&0) as u64) << LIMB_BITS;
+    if is_negative(x) {
+        (mag as i64).wrapping_neg()
+    } else {
+        mag as i64
+    }
+}
+
+/// 1 if `x` is representable in a signed integer of `bits` bits, 0 otherwise
+#[no_mangle]
+pub unsafe extern "C" fn intinf_fits(x: *const IntInf, bits: i32) -> i32 {
+    let l = limbs(x);
+    if l.len() > 2 {
+        return 0;
+    }
+    let mag = *l.get(0).unwrap_or(&0) as u64 | (*l.get(1).unwrap_or(&0) as u64) << LIMB_BITS;
+    let limit = 1u64 << (bits - 1);
+    let fits = if is_negative(x) {
+        mag <= limit
+    } else {
+        mag < limit
+    };
+    fits as i32
+}
+
+/// -1, 0 or 1 by the sign of `x`
+#[no_mangle]
+pub unsafe extern "C" fn intinf_sign(x: *const IntInf) -> i32 {
+    (*x).size.signum()
+}
+
+/// -1, 0 or 1 if `a` is less than, equal to or greater than `b`
+#[no_mangle]
+pub unsafe extern "C" fn intinf_compare(a: *const IntInf, b: *const IntInf) -> i32 {
+    match (is_negative(a), is_negative(b)) {
+        (false, true) => 1,
+        (true, false) => -1,
+        (false, false) => cmp_mag(limbs(a), limbs(b)),
+        (true, true) => cmp_mag(limbs(b), limbs(a)),
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_add(a: *const IntInf, b: *const IntInf) -> *mut IntInf {
+    add_signed(a, b, false)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_sub(a: *const IntInf, b: *const IntInf) -> *mut IntInf {
+    add_signed(a, b, true)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_mul(a: *const IntInf, b: *const IntInf) -> *mut IntInf {
+    let (am, bm) = (limbs(a), limbs(b));
+    let ret = with_capacity(am.len() + bm.len());
+    let r = limbs_mut(ret);
+    for limb in r.iter_mut() {
+        *limb = 0;
+    }
+    for (i, x) in am.iter().enumerate() {
+        let mut carry = 0u64;
+        for (j, y) in bm.iter().enumerate() {
+            let cur = r[i + j] as u64 + *x as u64 * *y as u64 + carry;
+            r[i + j] = cur as u32;
+            carry = cur >> LIMB_BITS;
+        }
+        r[i + bm.len()] = carry as u32;
+    }
+    normalize(ret, is_negative(a) != is_negative(b))
+}
+
+/// rounds toward negative infinity as `div` does. `b` must not be 0
+#[no_mangle]
+pub unsafe extern "C" fn intinf_div(a: *const IntInf, b: *const IntInf) -> *mut IntInf {
+    let (q, r) = divmod_mag(limbs(a), limbs(b));
+    let negative = is_negative(a) != is_negative(b);
+    if negative && (*r).size != 0 {
+        add_mag(limbs(q), &[1], true)
+    } else {
+        normalize(q, negative)
+    }
+}
+
+/// takes the sign of the divisor as `mod` does. `b` must not be 0
+#[no_mangle]
+pub unsafe extern "C" fn intinf_mod(a: *const IntInf, b: *const IntInf) -> *mut IntInf {
+    let (_, r) = divmod_mag(limbs(a), limbs(b));
+    if (*r).size == 0 {
+        r
+    } else if is_negative(a) != is_negative(b) {
+        sub_mag(limbs(b), limbs(r), is_negative(b))
+    } else {
+        normalize(r, is_negative(b))
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_neg(x: *const IntInf) -> *mut IntInf {
+    new(!is_negative(x), limbs(x))
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_abs(x: *const IntInf) -> *mut IntInf {
+    new(false, limbs(x))
+}
+
+/// the decimal representation of `x` as a string, `~` for the minus sign
+#[no_mangle]
+pub unsafe extern "C" fn intinf_to_string(x: *const IntInf) -> *mut u8 {
+    let l = limbs(x);
+    // each limb takes at most 10 digits. one more for the sign
+    let cap = l.len() * 10 + 1;
+    let ret = alloc(4 + cap);
+    let buf = slice::from_raw_parts_mut(ret.add(4), cap);
+    let work = limbs_mut(new(false, l));
+    let mut work_len = work.len();
+    // fill the buffer from the end
+    let mut pos = cap;
+    loop {
+        let mut chunk = divmod_limb(&mut work[..work_len], DECIMAL_BASE);
+        while work_len > 0 && work[work_len - 1] == 0 {
+            work_len -= 1;
+        }
+        for _ in 0..DECIMAL_DIGITS {
+            pos -= 1;
+            buf[pos] = b'0' + (chunk % 10) as u8;
+            chunk /= 10;
+            if work_len == 0 && chunk == 0 {
+                break;
+            }
+        }
+        if work_len == 0 {
+            break;
+        }
+    }
+    if is_negative(x) {
+        pos -= 1;
+        buf[pos] = b'~';
+    }
+    let len = cap - pos;
+    buf.copy_within(pos.., 0);
+    *(ret as *mut u32) = len as u32;
+    ret
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn intinf_print(x: *const IntInf) {
+    crate::string::string_print(intinf_to_string(x));
+}
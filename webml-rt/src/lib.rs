@@ -4,6 +4,10 @@ use core::arch::wasm32::{memory_grow, memory_size};
 use core::mem;
 use core::panic::PanicInfo;
 
+mod intinf;
+mod real;
+mod string;
+
 #[repr(C)]
 struct Page {
     next: *mut Page,
@@ -53,10 +57,21 @@ pub unsafe extern "C" fn alloc(size: usize) -> *mut u8 {
         add_new_page();
     }
     let ret = (*HEAD).data.offset((*HEAD).top as isize);
-    (*GC).top += size;
+    (*HEAD).top += size;
     ret
 }
 
+/// the fresh pages of at least `size` bytes for the stack frames of a module
+#[no_mangle]
+pub unsafe extern "C" fn alloc_stack(size: usize) -> *mut u8 {
+    let pages = (size + WASM_PAGE_SIZE - 1) / WASM_PAGE_SIZE;
+    let ret = memory_grow(MEMORY, pages);
+    if ret == usize::max_value() {
+        panic!("memory exhausted")
+    }
+    (ret * WASM_PAGE_SIZE) as *mut u8
+}
+
 #[no_mangle]
 pub unsafe extern "C" fn memory_used() -> usize {
     WASM_PAGE_SIZE * memory_size(MEMORY)
@@ -0,0 +1,115 @@
+//! `Real.toString`: 12 significant digits, `~` for the minus sign and
+//! the exponent notation for the magnitudes below 1E~4 or from 1E12
+use crate::string;
+use core::fmt::{self, Write};
+use core::str;
+
+const DIGITS: usize = 12;
+
+/// a fixed buffer to format into
+struct Buf {
+    bytes: [u8; 32],
+    len: usize,
+}
+
+impl Buf {
+    fn new() -> Self {
+        Buf {
+            bytes: [0; 32],
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, b: u8) {
+        self.bytes[self.len] = b;
+        self.len += 1;
+    }
+
+    fn as_bytes(&self) -> &[u8] {
+        &self.bytes[..self.len]
+    }
+}
+
+impl Write for Buf {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let end = self.len + s.len();
+        if self.bytes.len() < end {
+            return Err(fmt::Error);
+        }
+        self.bytes[self.len..end].copy_from_slice(s.as_bytes());
+        self.len = end;
+        Ok(())
+    }
+}
+
+fn format(x: f64, out: &mut Buf) {
+    if x.is_nan() {
+        let _ = out.write_str("nan");
+        return;
+    }
+    if x.is_sign_negative() {
+        out.push(b'~');
+    }
+    let x = x.abs();
+    if x.is_infinite() {
+        let _ = out.write_str("inf");
+        return;
+    }
+    // d.ddddddddddde<exp>, rounded by core
+    let mut sci = Buf::new();
+    let _ = write!(sci, "{:.*e}", DIGITS - 1, x);
+    let sci = sci.as_bytes();
+    let e = sci.iter().position(|&b| b == b'e').unwrap_or(sci.len());
+    let exp = str::from_utf8(&sci[e + 1..])
+        .ok()
+        .and_then(|exp| exp.parse::<i32>().ok())
+        .unwrap_or(0);
+    let mut digits = [b'0'; DIGITS];
+    let mut len = 0;
+    for &b in sci[..e].iter().filter(|b| b.is_ascii_digit()) {
+        digits[len] = b;
+        len += 1;
+    }
+    while len > 1 && digits[len - 1] == b'0' {
+        len -= 1;
+    }
+    let digits = &digits[..len];
+    if -4 <= exp && exp < DIGITS as i32 {
+        if exp < 0 {
+            let _ = out.write_str("0.");
+            for _ in 0..(-exp - 1) {
+                out.push(b'0');
+            }
+            digits.iter().for_each(|&d| out.push(d));
+        } else {
+            let point = exp as usize + 1;
+            for i in 0..point {
+                out.push(*digits.get(i).unwrap_or(&b'0'));
+            }
+            out.push(b'.');
+            if point < len {
+                digits[point..].iter().for_each(|&d| out.push(d));
+            } else {
+                out.push(b'0');
+            }
+        }
+    } else {
+        out.push(digits[0]);
+        if len > 1 {
+            out.push(b'.');
+            digits[1..].iter().for_each(|&d| out.push(d));
+        }
+        out.push(b'E');
+        if exp < 0 {
+            out.push(b'~');
+        }
+        let _ = write!(out, "{}", exp.abs());
+    }
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn real_to_string(x: f64) -> *mut u8 {
+    let mut out = Buf::new();
+    format(x, &mut out);
+    string::new(out.as_bytes())
+}
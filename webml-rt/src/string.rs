@@ -0,0 +1,27 @@
+//! strings. a value is an immutable heap object holding its byte length
+//! followed by the bytes in UTF-8
+use crate::alloc;
+use core::slice;
+
+#[link(wasm_import_module = "imports")]
+extern "C" {
+    fn print_string(ptr: *const u8, len: usize);
+}
+
+/// a string on the heap with a copy of `bytes`
+pub unsafe fn new(bytes: &[u8]) -> *mut u8 {
+    let ret = alloc(4 + bytes.len());
+    *(ret as *mut u32) = bytes.len() as u32;
+    slice::from_raw_parts_mut(ret.add(4), bytes.len()).copy_from_slice(bytes);
+    ret
+}
+
+unsafe fn bytes<'a>(s: *const u8) -> &'a [u8] {
+    slice::from_raw_parts(s.add(4), *(s as *const u32) as usize)
+}
+
+#[no_mangle]
+pub unsafe extern "C" fn string_print(s: *const u8) {
+    let bytes = bytes(s);
+    print_string(bytes.as_ptr(), bytes.len());
+}